@@ -1,9 +1,44 @@
-use clap::{Parser, Subcommand};
+use clap::{ArgAction, Args, Parser, Subcommand};
 use std::path::PathBuf;
 
+use crate::core::analytics::parse_utc_offset;
+use crate::core::db_browser::OutputFormat;
+use crate::core::export::listing::ListFormat;
+use crate::core::export::favorite::FavoriteFormat;
+use crate::core::export::graph::GraphFormat;
+use crate::core::export::sns::SnsFormat;
+use crate::core::export::stats::StatsFormat;
+use crate::core::export::table::{Dataset, TableFormat};
+
 #[derive(Parser)]
 #[command(author, version, about, long_about = None)]
 pub struct Cli {
+    /// (可选)数据库密钥。给出时直接通过 SQLCipher 读取加密的数据库, 不在磁盘上写出解密文件
+    /// (Bias/Decrypt 也使用该密钥)
+    #[arg(short, long, global = true)]
+    pub key: Option<String>,
+
+    /// 输出更详细的日志到 stderr (-v: debug, -vv: trace)
+    #[arg(short, long, action = ArgAction::Count, global = true)]
+    pub verbose: u8,
+
+    /// 减少日志输出 (-q: 只显示警告, -qq: 只显示错误)
+    #[arg(short, long, action = ArgAction::Count, global = true, conflicts_with = "verbose")]
+    pub quiet: u8,
+
+    /// (可选)同时把日志追加写入该文件
+    #[arg(long, global = true)]
+    pub log_file: Option<PathBuf>,
+
+    /// 在日志中显示完整密钥 (默认只显示前 4 个字符)
+    #[arg(long, global = true)]
+    pub log_keys: bool,
+
+    /// 命令结果的输出格式: text(对齐的彩色表格), json, jsonl, table(不带颜色的表格)。
+    /// 日志始终写到 stderr, 因此 json/jsonl 可以直接交给 jq 处理
+    #[arg(long, global = true, default_value = "text", ignore_case = true)]
+    pub output: ListFormat,
+
     #[command(subcommand)]
     pub command: Commands,
 }
@@ -11,6 +46,7 @@ pub struct Cli {
 #[derive(Subcommand)]
 pub enum Commands {
     /// 获取微信基址偏移
+    #[cfg(windows)]
     Bias {
         /// 手机号
         #[arg(long, required = true)]
@@ -24,10 +60,6 @@ pub enum Commands {
         #[arg(long, required = true)]
         account: String,
         
-        /// (可选)密钥
-        #[arg(long)]
-        key: Option<String>,
-        
         /// (可选)已登录账号的微信文件夹路径
         #[arg(long)]
         db_path: Option<PathBuf>,
@@ -38,6 +70,7 @@ pub enum Commands {
     },
     
     /// 获取微信信息
+    #[cfg(windows)]
     Info {
         /// (可选)微信版本偏移文件路径
         #[arg(short, long)]
@@ -63,12 +96,8 @@ pub enum Commands {
         wxid: Option<String>,
     },
     
-    /// 解密微信数据库
+    /// 解密微信数据库(微信运行中也可使用: 先做快照复制, 并合并 WAL 中尚未写回的最新消息)
     Decrypt {
-        /// 密钥
-        #[arg(short, long, required = true)]
-        key: String,
-        
         /// 数据库路径(目录or文件)
         #[arg(short, long, required = true)]
         db_path: PathBuf,
@@ -106,9 +135,13 @@ pub enum Commands {
         /// (可选)是否在线查看(局域网查看)
         #[arg(long, default_value_t = false)]
         online: bool,
+
+        /// (可选)端口号
+        #[arg(long, default_value_t = 5000)]
+        port: u16,
     },
 
-    /// 转储数据库表的内容
+    /// 转储数据库表的前 5 行 (同 `db dump --limit 5`)
     TableDump {
         /// 要查询的 SQLite 数据库文件的路径
         #[arg(long, required = true)]
@@ -119,6 +152,12 @@ pub enum Commands {
         table_name: String,
     },
 
+    /// 浏览数据库: 表, 表结构, 表数据, 只读 SQL 查询
+    Db {
+        #[command(subcommand)]
+        command: DbCommands,
+    },
+
     /// 显示联系人信息
     ShowContacts {
         /// MicroMsg.db 数据库文件的路径
@@ -136,6 +175,10 @@ pub enum Commands {
         /// 用于按标签 ID 列表过滤 (可多次出现)
         #[arg(long)]
         label_ids: Option<Vec<i64>>,
+
+        /// (可选)解密后的 OpenIMContact.db 的路径(企业微信联系人/群聊)
+        #[arg(long)]
+        openim_db_path: Option<PathBuf>,
     },
 
     /// 显示群聊信息
@@ -147,6 +190,10 @@ pub enum Commands {
         /// 用于按群聊 wxid 列表过滤 (可多次出现)
         #[arg(long)]
         room_wxids: Option<Vec<String>>,
+
+        /// (可选)解密后的 OpenIMContact.db 的路径(企业微信联系人/群聊)
+        #[arg(long)]
+        openim_db_path: Option<PathBuf>,
     },
 
     /// 显示会话列表
@@ -158,6 +205,10 @@ pub enum Commands {
         /// 限制显示的会话数量
         #[arg(long)]
         limit: Option<usize>,
+
+        /// (可选)解密后的 OpenIMContact.db 的路径(企业微信联系人/群聊)
+        #[arg(long)]
+        openim_db_path: Option<PathBuf>,
     },
 
     /// 显示最近聊天的 wxid
@@ -171,6 +222,348 @@ pub enum Commands {
         limit: usize,
     },
     
+    /// 导出语音消息(SILK 解码为 WAV)
+    ExportVoice {
+        /// MSG.db (或合并后的 merge_all.db) 数据库文件的路径
+        #[arg(long, required = true)]
+        db_path: PathBuf,
+
+        /// (可选)MediaMSG.db 数据库文件的路径 (可多次出现)[默认使用 db_path]
+        #[arg(long)]
+        media_db_path: Option<Vec<PathBuf>>,
+
+        /// 聊天对象的 wxid (或 xxx@chatroom)
+        #[arg(long, required = true)]
+        talker: String,
+
+        /// 输出目录
+        #[arg(long, required = true)]
+        out: PathBuf,
+
+        /// (可选)只导出原始 .silk 文件, 不解码为 WAV
+        #[arg(long, default_value_t = false)]
+        raw: bool,
+    },
+    
+    /// 导出单个会话为 HTML
+    ExportHtml {
+        /// MSG.db (或合并后的 merge_all.db) 数据库文件的路径
+        #[arg(long, required = true)]
+        db_path: PathBuf,
+
+        /// (可选)MicroMsg.db 数据库文件的路径(联系人/群成员)[默认使用 db_path]
+        #[arg(long)]
+        micro_db_path: Option<PathBuf>,
+
+        /// (可选)MediaMSG.db 数据库文件的路径 (可多次出现)[默认使用 db_path]
+        #[arg(long)]
+        media_db_path: Option<Vec<PathBuf>>,
+
+        /// (可选)微信账号文件夹的路径(用于图片/视频/文件/表情)
+        #[arg(long)]
+        wx_path: Option<PathBuf>,
+
+        /// 聊天对象的 wxid (或 xxx@chatroom)
+        #[arg(long, required = true)]
+        talker: String,
+
+        /// (可选)微信账号(本人微信id)
+        #[arg(long, default_value = "")]
+        my_wxid: String,
+
+        /// 输出路径: 以 .html 结尾时生成单个文件, 否则生成目录(index.html + assets)
+        #[arg(long, required = true)]
+        out: PathBuf,
+    },
+
+    /// 导出聊天记录为纯文本(.txt)
+    ExportText {
+        #[command(flatten)]
+        args: TranscriptArgs,
+    },
+
+    /// 导出聊天记录为 Markdown(.md)
+    ExportMd {
+        #[command(flatten)]
+        args: TranscriptArgs,
+    },
+
+    /// 全文搜索聊天记录 (优先使用 FTSMSG.db, 否则使用本地 FTS5 索引)
+    Search {
+        /// 要搜索的关键词 (空格分隔的多个词需同时出现)
+        query: String,
+
+        /// MSG.db (或合并后的 merge_all.db) 数据库文件的路径
+        #[arg(long, required = true)]
+        db_path: PathBuf,
+
+        /// (可选)解密后的 FTSMSG.db 的路径
+        #[arg(long)]
+        fts_db_path: Option<PathBuf>,
+
+        /// (可选)本地索引文件的路径 [默认: db_path 同目录下的 <文件名>.search.db; 指定 --key 时只在内存中建立]
+        #[arg(long)]
+        index_path: Option<PathBuf>,
+
+        /// (可选)重建本地索引
+        #[arg(long, default_value_t = false)]
+        reindex: bool,
+
+        /// (可选)只搜索该聊天对象(wxid 或 xxx@chatroom)
+        #[arg(long)]
+        talker: Option<String>,
+
+        /// (可选)起始时间(eg: 2023-01-01 或 "2023-01-01 08:00:00" 或 unix 时间戳)
+        #[arg(long)]
+        since: Option<String>,
+
+        /// (可选)最多返回的条数
+        #[arg(long, default_value_t = 50)]
+        limit: usize,
+    },
+
+    /// 导出朋友圈 (Sns.db) 为 JSON 或 HTML
+    Sns {
+        /// 解密后的 Sns.db 的路径
+        #[arg(long, required = true)]
+        db_path: PathBuf,
+
+        /// (可选)MicroMsg.db 数据库文件的路径(显示联系人名称)
+        #[arg(long)]
+        micro_db_path: Option<PathBuf>,
+
+        /// (可选)微信账号文件夹的路径(查找本地缓存的图片)
+        #[arg(long)]
+        wx_path: Option<PathBuf>,
+
+        /// (可选)只导出该 wxid 发布的朋友圈
+        #[arg(long)]
+        author: Option<String>,
+
+        /// (可选)开始时间(eg: 2023-01-01 或 "2023-01-01 08:00:00" 或 unix 时间戳)
+        #[arg(long)]
+        start: Option<String>,
+
+        /// (可选)结束时间(格式同 start, 只有日期时包含当天)
+        #[arg(long)]
+        end: Option<String>,
+
+        /// (可选)输出格式: json 或 html
+        #[arg(long, default_value = "json", ignore_case = true)]
+        format: SnsFormat,
+
+        /// 输出文件路径
+        #[arg(long, required = true)]
+        out: PathBuf,
+    },
+
+    /// 聊天统计报告: 按发送者/消息类型/小时/星期/月份统计, 首次/最近聊天, 最长连续聊天天数,
+    /// 平均回复时间和高频词, 输出为 JSON 或带 SVG 图表的 HTML
+    Stats {
+        /// MSG.db (或合并后的 merge_all.db) 数据库文件的路径
+        #[arg(long, required = true)]
+        db_path: PathBuf,
+
+        /// (可选)MicroMsg.db 数据库文件的路径(会话列表/联系人名称)[默认使用 db_path]
+        #[arg(long)]
+        micro_db_path: Option<PathBuf>,
+
+        /// (可选)聊天对象的 wxid 或群 id (可多次出现)[默认统计会话列表中的全部会话]
+        #[arg(long)]
+        talker: Vec<String>,
+
+        /// (可选)微信账号(本人微信id)
+        #[arg(long, default_value = "")]
+        my_wxid: String,
+
+        /// (可选)开始时间(eg: 2023-01-01 或 "2023-01-01 08:00:00" 或 unix 时间戳)
+        #[arg(long)]
+        start: Option<String>,
+
+        /// (可选)结束时间(格式同 start, 只有日期时包含当天)
+        #[arg(long)]
+        end: Option<String>,
+
+        /// (可选)统计小时/星期/日期以及解析 start/end 时使用的时区, 相对 UTC 的小时数或时:分(eg: 8 为北京时间, +05:30 为印度时间)[默认使用本机时区]
+        #[arg(long, allow_hyphen_values = true, value_parser = parse_utc_offset)]
+        utc_offset: Option<i32>,
+
+        /// (可选)每个会话列出的高频词数量
+        #[arg(long, default_value_t = 50)]
+        top_words: usize,
+
+        /// (可选)输出格式: json 或 html
+        #[arg(long, default_value = "html", ignore_case = true)]
+        format: StatsFormat,
+
+        /// 输出文件路径
+        #[arg(long, required = true)]
+        out: PathBuf,
+    },
+
+    /// 群聊报告: 成员的发言数/最后发言时间, 潜水成员, 以及系统消息中的进群/退群/改群名记录
+    RoomReport {
+        /// MSG.db (或合并后的 merge_all.db) 数据库文件的路径
+        #[arg(long, required = true)]
+        db_path: PathBuf,
+
+        /// (可选)MicroMsg.db 数据库文件的路径(群成员/联系人名称)[默认使用 db_path]
+        #[arg(long)]
+        micro_db_path: Option<PathBuf>,
+
+        /// 群聊 id (xxx@chatroom)
+        #[arg(long, required = true)]
+        room: String,
+
+        /// (可选)微信账号(本人微信id)
+        #[arg(long, default_value = "")]
+        my_wxid: String,
+
+        /// (可选)开始时间(eg: 2023-01-01 或 "2023-01-01 08:00:00" 或 unix 时间戳)
+        #[arg(long)]
+        start: Option<String>,
+
+        /// (可选)结束时间(格式同 start, 只有日期时包含当天)
+        #[arg(long)]
+        end: Option<String>,
+
+        /// (可选)显示时间以及解析 start/end 时使用的时区, 相对 UTC 的小时数或时:分(eg: 8 为北京时间, +05:30 为印度时间)[默认使用本机时区]
+        #[arg(long, allow_hyphen_values = true, value_parser = parse_utc_offset)]
+        utc_offset: Option<i32>,
+    },
+
+    /// 导出联系人关系图: 联系人和群聊为节点, 群成员/共同群聊/私聊消息数为边 (GraphML, GEXF 或 JSON)
+    Graph {
+        /// MSG.db (或合并后的 merge_all.db) 数据库文件的路径
+        #[arg(long, required = true)]
+        db_path: PathBuf,
+
+        /// (可选)MicroMsg.db 数据库文件的路径(联系人/群聊)[默认使用 db_path]
+        #[arg(long)]
+        micro_db_path: Option<PathBuf>,
+
+        /// (可选)微信账号(本人微信id), 私聊消息数的边从该节点连出
+        #[arg(long, default_value = "")]
+        my_wxid: String,
+
+        /// (可选)只包含未删除的好友
+        #[arg(long)]
+        friends_only: bool,
+
+        /// (可选)不包含公众号
+        #[arg(long)]
+        exclude_official: bool,
+
+        /// (可选)成员数超过该值的群聊不生成成员之间的共同群聊边
+        #[arg(long, default_value_t = 100)]
+        max_room_size: usize,
+
+        /// (可选)开始时间(eg: 2023-01-01 或 "2023-01-01 08:00:00" 或 unix 时间戳)
+        #[arg(long)]
+        start: Option<String>,
+
+        /// (可选)结束时间(格式同 start, 只有日期时包含当天)
+        #[arg(long)]
+        end: Option<String>,
+
+        /// (可选)输出格式: graphml, gexf 或 json
+        #[arg(long, default_value = "graphml", ignore_case = true)]
+        format: GraphFormat,
+
+        /// 输出文件路径
+        #[arg(long, required = true)]
+        out: PathBuf,
+    },
+
+    /// 导出收藏 (Favorite.db) 为 JSON 或 Markdown
+    Favorites {
+        /// 解密后的 Favorite.db 的路径
+        #[arg(long, required = true)]
+        db_path: PathBuf,
+
+        /// (可选)MicroMsg.db 数据库文件的路径(显示来源联系人名称)
+        #[arg(long)]
+        micro_db_path: Option<PathBuf>,
+
+        /// (可选)只导出带有该标签的收藏
+        #[arg(long)]
+        tag: Option<String>,
+
+        /// (可选)只导出该类型的收藏 (1 文字, 2 图片, 5 链接, 8 文件, 14 聊天记录, 18 笔记 ...)
+        #[arg(long = "type")]
+        fav_type: Option<i64>,
+
+        /// (可选)输出格式: json 或 md
+        #[arg(long, default_value = "json", ignore_case = true)]
+        format: FavoriteFormat,
+
+        /// 输出文件路径
+        #[arg(long, required = true)]
+        out: PathBuf,
+    },
+
+    /// 导出联系人/群成员/会话/消息/公众号文章为 CSV、JSON Lines 或 JSON
+    Export {
+        /// 数据库文件的路径(联系人/群聊/会话需要 MicroMsg.db, 消息需要 MSG.db, 或合并后的 merge_all.db; 公众号文章需要 PublicMsg.db)
+        #[arg(long, required = true)]
+        db_path: PathBuf,
+
+        /// 导出内容: contacts | chatrooms | sessions | messages | articles
+        #[arg(long, required = true, ignore_case = true)]
+        dataset: Dataset,
+
+        /// 输出格式: csv | jsonl | json
+        #[arg(long, default_value = "csv", ignore_case = true)]
+        format: TableFormat,
+
+        /// (可选)只导出这些列, 逗号分隔(eg: talker,sender,time_str,text)
+        #[arg(long, value_delimiter = ',')]
+        columns: Vec<String>,
+
+        /// (可选)只导出该会话的消息(articles 时为公众号 gh_ wxid)
+        #[arg(long)]
+        talker: Option<String>,
+
+        /// (可选)开始时间(eg: 2023-01-01 或 "2023-01-01 08:00:00" 或 unix 时间戳)
+        #[arg(long)]
+        start: Option<String>,
+
+        /// (可选)结束时间(格式同 start, 只有日期时包含当天)
+        #[arg(long)]
+        end: Option<String>,
+
+        /// (可选)微信账号(本人微信id)
+        #[arg(long, default_value = "")]
+        my_wxid: String,
+
+        /// (可选)微信账号文件夹的路径(消息 text 列中的图片/视频路径)
+        #[arg(long)]
+        wx_path: Option<PathBuf>,
+
+        /// 输出文件
+        #[arg(long, required = true)]
+        out: PathBuf,
+    },
+
+    /// 解密微信图片(.dat)
+    DecryptImages {
+        /// 微信账号文件夹的路径(eg: WeChat Files/wxid_xxx)
+        #[arg(long, required = true)]
+        wx_path: PathBuf,
+
+        /// 输出目录[默认为当前路径下decrypted_images文件夹]
+        #[arg(long, default_value = "decrypted_images")]
+        out_path: PathBuf,
+
+        /// (可选)V2 格式图片的 AES 密钥(16个字符或32位hex)
+        #[arg(long)]
+        aes_key: Option<String>,
+
+        /// (可选)V1/V2 格式图片尾部的 XOR 密钥(eg: 0x88), 缺省时自动推断
+        #[arg(long)]
+        xor_key: Option<String>,
+    },
+    
     // /// 启动UI界面
     // Ui {
     //     /// (可选)端口号
@@ -190,18 +583,135 @@ pub enum Commands {
     //     is_open_browser: bool,
     // },
     
-    // /// 启动api，不打开浏览器
-    // Api {
-    //     /// (可选)端口号
-    //     #[arg(short, long, default_value_t = 5000)]
-    //     port: u16,
-        
-    //     /// (可选)是否在线查看(局域网查看)
-    //     #[arg(long, default_value_t = false)]
-    //     online: bool,
-        
-    //     /// (可选)是否开启debug模式
-    //     #[arg(long, default_value_t = false)]
-    //     debug: bool,
-    // },
+    /// 启动api，不打开浏览器
+    Api {
+        /// 解密并合并后的 merge_all.db 的路径
+        #[arg(long, required = true)]
+        merge_path: PathBuf,
+
+        /// (可选)端口号
+        #[arg(short, long, default_value_t = 5000)]
+        port: u16,
+
+        /// (可选)是否在线查看(局域网查看)
+        #[arg(long, default_value_t = false)]
+        online: bool,
+
+        /// (可选)是否开启debug模式
+        #[arg(long, default_value_t = false)]
+        debug: bool,
+    },
+}
+
+/// db 子命令
+#[derive(Subcommand)]
+pub enum DbCommands {
+    /// 列出所有表及其行数
+    Tables {
+        /// SQLite 数据库文件的路径
+        #[arg(long, required = true)]
+        db_path: PathBuf,
+    },
+
+    /// 显示表结构(建表语句, 列, 索引)
+    Schema {
+        /// SQLite 数据库文件的路径
+        #[arg(long, required = true)]
+        db_path: PathBuf,
+
+        /// 表名
+        table: String,
+    },
+
+    /// 转储表数据
+    Dump {
+        /// SQLite 数据库文件的路径
+        #[arg(long, required = true)]
+        db_path: PathBuf,
+
+        /// 表名
+        table: String,
+
+        /// (可选)最多输出的行数, 0 表示全部
+        #[arg(long, default_value_t = 20)]
+        limit: usize,
+
+        /// (可选)跳过的行数
+        #[arg(long, default_value_t = 0)]
+        offset: usize,
+
+        /// (可选)过滤条件(SQL, eg: "Type = 1 AND CreateTime > 1700000000")
+        #[arg(long = "where")]
+        where_clause: Option<String>,
+
+        /// (可选)输出格式: text, csv, jsonl, json [默认跟随全局 --output]
+        #[arg(long, ignore_case = true)]
+        format: Option<OutputFormat>,
+
+        /// (可选)BLOB 列的解码方式, 格式为 列名=解码器 (hex, extrabuf, bytesextra, lz4), 可多次出现 [默认 hex]
+        #[arg(long)]
+        decode: Vec<String>,
+    },
+
+    /// 执行只读 SQL 查询
+    Query {
+        /// SQLite 数据库文件的路径
+        #[arg(long, required = true)]
+        db_path: PathBuf,
+
+        /// SQL 语句(只允许只读语句)
+        sql: String,
+
+        /// (可选)输出格式: text, csv, jsonl, json [默认跟随全局 --output]
+        #[arg(long, ignore_case = true)]
+        format: Option<OutputFormat>,
+
+        /// (可选)BLOB 列的解码方式, 格式为 列名=解码器 (hex, extrabuf, bytesextra, lz4), 可多次出现 [默认 hex]
+        #[arg(long)]
+        decode: Vec<String>,
+    },
+}
+
+/// export-text / export-md 共用参数
+#[derive(Args)]
+pub struct TranscriptArgs {
+    /// MSG.db (或合并后的 merge_all.db) 数据库文件的路径
+    #[arg(long, required = true)]
+    pub db_path: PathBuf,
+
+    /// (可选)MicroMsg.db 数据库文件的路径(联系人/群成员)[默认使用 db_path]
+    #[arg(long)]
+    pub micro_db_path: Option<PathBuf>,
+
+    /// (可选)微信账号文件夹的路径(图片/视频路径相对于该目录)
+    #[arg(long)]
+    pub wx_path: Option<PathBuf>,
+
+    /// (可选)聊天对象的 wxid (可多次出现)[默认导出全部会话]
+    #[arg(long)]
+    pub talker: Vec<String>,
+
+    /// (可选)微信账号(本人微信id)
+    #[arg(long, default_value = "")]
+    pub my_wxid: String,
+
+    /// (可选)开始时间(eg: 2023-01-01 或 "2023-01-01 08:00:00" 或 unix 时间戳)
+    #[arg(long)]
+    pub start: Option<String>,
+
+    /// (可选)结束时间(格式同 start, 只有日期时包含当天)
+    #[arg(long)]
+    pub end: Option<String>,
+
+    /// (可选)只导出这些发送者的消息 (wxid, 可多次出现)
+    #[arg(long)]
+    pub sender: Vec<String>,
+
+    /// (可选)所有会话写入同一个文件(out 为文件路径), 默认每个会话一个文件(out 为目录)
+    #[arg(long, default_value_t = false)]
+    pub combined: bool,
+
+    /// 输出目录(或 --combined 时的输出文件)
+    #[arg(long, required = true)]
+    pub out: PathBuf,
 }
@@ -1,432 +1,1019 @@
+use anyhow::Context;
 use clap::Parser;
+use log::{debug, info, warn};
+use serde_json::json;
 // Assuming cli.rs is in src/cli.rs and lib.rs has `pub mod cli;`
-use wxdump_rs::cli::{Cli, Commands};
-use wxdump_rs::core::db_parser::micro_msg_parser::{Contact, get_contacts, get_chat_rooms, ChatRoomInfo, get_sessions, SessionInfo, get_recent_chat_wxids};
-use wxdump_rs::core::db_parser::connect_sqlite_db;
-
-fn main() -> anyhow::Result<()> {
-    let cli_args = Cli::parse();
-
-    match cli_args.command {
-        Commands::Bias { mobile, name, account, key, db_path, wx_offs_path } => {
-            println!("Command: Bias");
-            println!("CLI Args Received:");
-            println!("  Mobile: {}", mobile);
-            println!("  Name: {}", name);
-            println!("  Account: {}", account);
+use wxdump_rs::cli::{Cli, Commands, DbCommands, TranscriptArgs};
+use wxdump_rs::core::db_parser::micro_msg_parser::{get_contacts, get_chat_rooms, ChatRoomInfo, get_sessions, SessionInfo, get_recent_chat_wxids};
+use wxdump_rs::core::db_parser::{attach_openim_db, get_favorites, open_encrypted_database, get_sns_posts, is_chat_room_wxid, FavoriteQuery, SnsQuery};
+use wxdump_rs::core::db_parser::{format_timestamp_to_string, get_messages, Message, find_voice_blob, list_talkers, strip_wechat_silk_prefix, MSG_TYPE_VOICE};
+use wxdump_rs::core::db_browser::{
+    dump_table_sql, list_tables_with_counts, parse_decoder_arg, table_schema, write_query,
+    DumpOptions, OutputFormat,
+};
+use wxdump_rs::core::search::{search_messages, SearchIndex, SearchOptions, SearchSource};
+use wxdump_rs::core::silk;
+use wxdump_rs::core::export::message_text;
+use wxdump_rs::core::export::listing::{write_listing, write_summary, ListFormat};
+use wxdump_rs::core::export::favorite::{write_favorites_json, write_favorites_markdown, FavoriteFormat};
+use wxdump_rs::core::export::graph::{write_gexf, write_graph_json, write_graphml, GraphFormat};
+use wxdump_rs::core::export::html::{export_html, HtmlExportOptions};
+use wxdump_rs::core::export::sns::{export_sns_html, write_sns_json, SnsFormat};
+use wxdump_rs::core::export::stats::{export_stats_html, write_stats_json, StatsFormat};
+use wxdump_rs::core::export::table::{export_dataset, Dataset, DatasetOptions, TableFormat};
+use wxdump_rs::core::export::text::{export_transcripts, TranscriptFormat, TranscriptOptions, TranscriptReport};
+use wxdump_rs::core::account::discover_databases;
+use wxdump_rs::core::analytics::{collect_stats, local_utc_offset_minutes, parse_time_arg, ConversationStats, StatsOptions};
+use wxdump_rs::core::analytics::graph::{contact_graph, ContactGraph, GraphOptions};
+use wxdump_rs::core::analytics::room::{room_report, RoomReport};
+use wxdump_rs::core::decryption::decrypt_live_database_file;
+use wxdump_rs::core::error::WxDumpError;
+use wxdump_rs::core::logging::{self, redact_key};
+use wxdump_rs::core::image_decode::{decrypt_images_in_dir, DatKeys};
+use wxdump_rs::server::{self, api::ApiServer, viewer::Viewer, RequestLog};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::sync::OnceLock;
+
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+    logging::set_reveal_keys(cli.log_keys);
+    if let Err(e) = logging::init(logging::level_from_verbosity(cli.verbose, cli.quiet), cli.log_file.as_deref()) {
+        eprintln!("Error: {:#}", e);
+        return ExitCode::from(WxDumpError::exit_code_of(&e));
+    }
+
+    match run(cli) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("Error: {:#}", e);
+            ExitCode::from(WxDumpError::exit_code_of(&e))
+        }
+    }
+}
+
+/// Runs one command. Failures are returned (and turned into the exit code by `main`);
+/// an empty result is not a failure.
+fn run(Cli { key, output, command, .. }: Cli) -> anyhow::Result<()> {
+    let _ = DB_KEY.set(key.clone());
+
+    match command {
+        #[cfg(windows)]
+        Commands::Bias { mobile, name, account, db_path, wx_offs_path } => {
+            debug!("Command: Bias");
+            debug!("CLI Args Received:");
+            debug!("  Mobile: {}", mobile);
+            debug!("  Name: {}", name);
+            debug!("  Account: {}", account);
             if let Some(k) = &key { // Borrowing key
-                println!("  Key: {}", k);
+                debug!("  Key: {}", redact_key(k));
             }
             if let Some(p) = &db_path { // Borrowing db_path
-                println!("  DB Path: {:?}", p);
+                debug!("  DB Path: {:?}", p);
             }
             if let Some(p) = &wx_offs_path { // Borrowing wx_offs_path
-                println!("  WX Offsets Path: {:?}", p);
+                debug!("  WX Offsets Path: {:?}", p);
             }
-            println!("\nAttempting to extract WeChat info (simulating full bias logic)...");
+            debug!("Attempting to extract WeChat info (simulating full bias logic)...");
             
             // 1. Load offsets (similar to main.rs, but wx_offs_path from CLI can override)
             // For now, we'll just use the default loading mechanism from core::offsets
             // In a real implementation, you'd check if wx_offs_path is Some and load from there.
             let loaded_offsets_map = match wxdump_rs::core::offsets::load_wx_offsets() {
                 Ok(offsets) => {
-                    println!("[Bias Command] Successfully loaded {} offset entries.", offsets.len());
+                    debug!("Successfully loaded {} offset entries.", offsets.len());
                     offsets
                 }
-                Err(e) => {
-                    eprintln!("[Bias Command] Error loading WX_OFFS.json: {}. Cannot proceed with info extraction.", e);
-                    return Ok(()); // Or handle error differently
-                }
+                Err(e) => return Err(e.context("Error loading WX_OFFS.json, cannot proceed with info extraction")),
             };
 
             // 2. Extract info
-            match wxdump_rs::core::info_extractor::extract_all_wechat_info(&loaded_offsets_map) {
-                Ok(user_infos) => {
-                    if user_infos.is_empty() {
-                        println!("[Bias Command] No WeChat user info extracted.");
-                    }
-                    for user_info_extracted in user_infos {
-                        println!("[Bias Command] ---- Extracted Info for PID: {} ----", user_info_extracted.pid);
-                        println!("  Version: {}", user_info_extracted.version);
-                        println!("  Account (extracted): {}", user_info_extracted.account.as_deref().unwrap_or("N/A"));
-                        println!("  Nickname (extracted): {}", user_info_extracted.nickname.as_deref().unwrap_or("N/A"));
-                        println!("  Mobile (extracted): {}", user_info_extracted.mobile.as_deref().unwrap_or("N/A"));
-                        println!("  Mail (extracted): {}", user_info_extracted.mail.as_deref().unwrap_or("N/A"));
-                        println!("  Key (extracted): {}", user_info_extracted.key.as_deref().unwrap_or("N/A"));
-                        println!("  WxID (extracted): {}", user_info_extracted.wxid.as_deref().unwrap_or("N/A"));
-                        println!("  WeChat Files Path (extracted): {}", user_info_extracted.wx_files_path.as_ref().map_or_else(|| String::from("N/A"), |p_buf| p_buf.to_string_lossy().to_string()));
-                        println!("  User DB Path (extracted): {}", user_info_extracted.wx_user_db_path.as_ref().map_or_else(|| String::from("N/A"), |p_buf| p_buf.to_string_lossy().to_string()));
-                        // Here, you would compare/use the CLI args (mobile, name, account, key, db_path)
-                        // with the extracted user_info_extracted.
-                        // For now, we just print both.
-                    }
-                }
-                Err(e) => {
-                    eprintln!("[Bias Command] Error extracting WeChat info: {}", e);
-                }
-            }
+            let user_infos = wxdump_rs::core::info_extractor::extract_all_wechat_info(&loaded_offsets_map)
+                .map_err(|e| anyhow::Error::from(e).context("Error extracting WeChat info"))?;
+            // Here, you would compare/use the CLI args (mobile, name, account, key, db_path)
+            // with the extracted info. For now, we just print it.
+            let columns = ["pid", "version", "wxid", "nickname", "account", "mobile", "key", "user_db_path"];
+            write_listing(&mut std::io::stdout().lock(), output, &user_infos, &columns, |u| {
+                vec![
+                    u.pid.to_string(),
+                    u.version.clone(),
+                    u.wxid.clone().unwrap_or_default(),
+                    u.nickname.clone().unwrap_or_default(),
+                    u.account.clone().unwrap_or_default(),
+                    u.mobile.clone().unwrap_or_default(),
+                    u.key.clone().unwrap_or_default(),
+                    u.wx_user_db_path.as_ref().map(|p| p.to_string_lossy().into_owned()).unwrap_or_default(),
+                ]
+            })?;
         }
+        #[cfg(windows)]
         Commands::Info { wx_offs_path, save_path } => {
-            println!("Command: Info");
+            debug!("Command: Info");
             if let Some(p) = wx_offs_path {
-                println!("  WX Offsets Path: {:?}", p);
+                debug!("  WX Offsets Path: {:?}", p);
             }
             if let Some(p) = save_path {
-                println!("  Save Path: {:?}", p);
+                debug!("  Save Path: {:?}", p);
             }
+            warn!("Info is not implemented yet");
         }
         Commands::WxPath { db_types, wx_files, wxid } => {
-            println!("Command: WxPath");
+            debug!("Command: WxPath");
             if let Some(types) = db_types {
-                println!("  DB Types: {}", types);
+                debug!("  DB Types: {}", types);
             }
             if let Some(p) = wx_files {
-                println!("  WX Files Path: {:?}", p);
+                debug!("  WX Files Path: {:?}", p);
             }
             if let Some(id) = wxid {
-                println!("  WxID: {}", id);
+                debug!("  WxID: {}", id);
             }
+            warn!("WxPath is not implemented yet");
         }
-        Commands::Decrypt { key, db_path, out_path } => {
-            println!("Command: Decrypt");
-            println!("  Key: {}", key);
-            println!("  DB Path: {:?}", db_path);
-            println!("  Out Path: {:?}", out_path);
+        Commands::Decrypt { db_path, out_path } => {
+            debug!("Command: Decrypt");
+            debug!("  Key: {}", key.as_deref().map_or_else(|| "N/A (required)".to_string(), redact_key));
+            debug!("  DB Path: {:?}", db_path);
+            debug!("  Out Path: {:?}", out_path);
+
+            let Some(key) = key.as_deref() else {
+                return Err(WxDumpError::InvalidArgument("Decrypt requires --key".to_string()).into());
+            };
+            let written = run_decrypt(key, &db_path, &out_path).with_context(|| format!("Failed to decrypt {:?}", db_path))?;
+            let text = format!("Decrypted {} database(s) to {:?}.", written.len(), out_path);
+            write_summary(&mut std::io::stdout().lock(), output, &text, &json!({ "decrypted": written }))?;
         }
         Commands::Merge { db_path, out_path } => {
-            println!("Command: Merge");
-            println!("  DB Path: {}", db_path); // This is a String of comma-separated paths
-            println!("  Out Path: {:?}", out_path);
+            debug!("Command: Merge");
+            debug!("  DB Path: {}", db_path); // This is a String of comma-separated paths
+            debug!("  Out Path: {:?}", out_path);
+            warn!("Merge is not implemented yet");
         }
-        Commands::DbShow { merge_path, wx_path, my_wxid, online } => {
-            println!("Command: DbShow");
-            println!("  Merge Path: {:?}", merge_path);
-            if let Some(p) = wx_path {
-                println!("  WX Path: {:?}", p);
+        Commands::DbShow { merge_path, wx_path, my_wxid, online, port } => {
+            debug!("Command: DbShow");
+            debug!("  Merge Path: {:?}", merge_path);
+            if let Some(p) = &wx_path {
+                debug!("  WX Path: {:?}", p);
             }
-            println!("  My WxID: {}", my_wxid);
-            println!("  Online: {}", online);
+            debug!("  My WxID: {}", my_wxid);
+            debug!("  Online: {}", online);
+            debug!("  Port: {}", port);
+
+            let viewer = open_db(&merge_path)
+                .and_then(|conn| Viewer::from_connection(conn, wx_path.clone(), &my_wxid))
+                .with_context(|| format!("Failed to open {:?}", merge_path))?;
+            let http_server = server::bind(server::bind_host(online), port)?;
+            info!("Serving chat history at {} (Ctrl+C to stop)", server::listen_url(&http_server));
+            server::serve(&http_server, &viewer);
         }
-        Commands::TableDump { db_path, table_name } => {
-            println!("Command: TableDump");
-            println!("  DB Path: {:?}", db_path);
-            println!("  Table Name: {}", table_name);
-
-            let mut absolute_db_path = db_path.clone();
-            if !absolute_db_path.is_absolute() {
-                match std::env::current_dir() {
-                    Ok(cwd) => {
-                        absolute_db_path = cwd.join(absolute_db_path);
-                        println!("Resolved relative DB path to: {:?}", absolute_db_path);
-                    }
-                    Err(e) => {
-                        eprintln!("Failed to get current working directory: {}. Please use an absolute path for --db-path.", e);
-                        return Ok(()); // Or handle error appropriately
-                    }
-                }
-            }
+        Commands::Api { merge_path, port, online, debug } => {
+            debug!("Command: Api");
+            debug!("  Merge Path: {:?}", merge_path);
+            debug!("  Port: {}", port);
+            debug!("  Online: {}", online);
+            debug!("  Debug: {}", debug);
 
-            match wxdump_rs::core::db_parser::connect_sqlite_db(&absolute_db_path) {
-                Ok(conn) => {
-                    println!("Successfully connected to database: {:?}", absolute_db_path);
-                    // 可选：列出所有表
-                    // match wxdump_rust_core::core::db_parser::get_table_names(&conn) {
-                    //     Ok(tables) => {
-                    //         println!("Available tables: {:?}", tables);
-                    //     }
-                    //     Err(e) => {
-                    //         eprintln!("Error listing tables: {}", e);
-                    //     }
-                    // }
-
-                    match wxdump_rs::core::db_parser::get_all_rows_from_table(&conn, &table_name) {
-                        Ok(rows) => {
-                            if rows.is_empty() {
-                                println!("Table '{}' is empty or does not exist.", table_name);
-                            } else {
-                                println!("First {} rows from table '{}':", std::cmp::min(5, rows.len()), table_name);
-                                for (i, row_map) in rows.iter().take(5).enumerate() {
-                                    print!("  Row {}: ", i + 1);
-                                    let mut first_col = true;
-                                    for (col_name, value) in row_map.iter().take(3) { // 只打印前3列以保持简洁
-                                        if !first_col {
-                                            print!(", ");
-                                        }
-                                        print!("{}: {:?}", col_name, value);
-                                        first_col = false;
-                                    }
-                                    if row_map.len() > 3 {
-                                        print!(", ..."); // 表示还有更多列
-                                    }
-                                    println!();
-                                }
-                                if rows.len() > 5 {
-                                    println!("  ... and {} more rows.", rows.len() - 5);
-                                }
-                            }
-                        }
-                        Err(e) => {
-                            eprintln!("Error getting rows from table '{}': {}", table_name, e);
-                        }
-                    }
-                }
-                Err(e) => {
-                    eprintln!("Error connecting to database '{:?}': {}", absolute_db_path, e);
-                }
+            let api = open_db(&merge_path)
+                .map(ApiServer::from_connection)
+                .with_context(|| format!("Failed to open {:?}", merge_path))?;
+            let http_server = server::bind(server::bind_host(online), port)?;
+            info!("API listening on {} (Ctrl+C to stop)", server::listen_url(&http_server));
+            if debug {
+                server::serve(&http_server, &RequestLog(api));
+            } else {
+                server::serve(&http_server, &api);
             }
         }
-        Commands::ShowContacts { db_path, word, wxids, label_ids } => {
-            println!("Command: ShowContacts");
-            println!("  DB Path: {:?}", db_path);
+        Commands::TableDump { db_path, table_name } => {
+            debug!("Command: TableDump");
+            debug!("  DB Path: {:?}", db_path);
+            debug!("  Table Name: {}", table_name);
+
+            let options = DumpOptions { limit: Some(5), ..Default::default() };
+            run_db_dump(&db_path, &table_name, &options, OutputFormat::Text, &[])
+                .with_context(|| format!("Error dumping table '{}'", table_name))?;
+        }
+        Commands::Db { command } => {
+            run_db_command(command, output)?;
+        }
+        Commands::ShowContacts { db_path, word, wxids, label_ids, openim_db_path } => {
+            debug!("Command: ShowContacts");
+            debug!("  DB Path: {:?}", db_path);
             if let Some(w) = &word {
-                println!("  Word: {}", w);
+                debug!("  Word: {}", w);
             }
             if let Some(ids) = &wxids {
-                println!("  WxIDs: {:?}", ids);
+                debug!("  WxIDs: {:?}", ids);
             }
             if let Some(l_ids) = &label_ids {
-                println!("  Label IDs: {:?}", l_ids);
-            }
-
-            let mut absolute_db_path = db_path.clone();
-            if !absolute_db_path.is_absolute() {
-                match std::env::current_dir() {
-                    Ok(cwd) => {
-                        absolute_db_path = cwd.join(absolute_db_path);
-                        println!("Resolved relative DB path to: {:?}", absolute_db_path);
-                    }
-                    Err(e) => {
-                        eprintln!("Failed to get current working directory: {}. Please use an absolute path for --db-path.", e);
-                        return Ok(());
-                    }
-                }
+                debug!("  Label IDs: {:?}", l_ids);
             }
 
-            match connect_sqlite_db(&absolute_db_path) {
-                Ok(conn) => {
-                    println!("Successfully connected to database: {:?}", absolute_db_path);
-                    
-                    // Convert Option<String> to Option<&str> and Option<Vec<String>> to Option<&[String]>
-                    let word_ref = word.as_deref();
-                    let wxids_ref = wxids.as_ref().map(|v| v.as_slice());
-                    // label_ids is Option<Vec<i64>>, get_contacts expects Option<&[i64]>
-                    let label_ids_ref = label_ids.as_ref().map(|v| v.as_slice());
-
-                    match get_contacts(&conn, word_ref, wxids_ref, label_ids_ref) {
-                        Ok(contacts) => {
-                            if contacts.is_empty() {
-                                println!("No contacts found matching the criteria.");
-                            } else {
-                                println!("Found {} contacts:", contacts.len());
-                                for (i, contact) in contacts.iter().enumerate() {
-                                    println!("--- Contact {} ---", i + 1);
-                                    println!("  WxID: {}", contact.wxid); // wxid is String, not Option<String>
-                                    println!("  Nickname: {}", contact.nickname.as_deref().unwrap_or("N/A"));
-                                    println!("  Remark: {}", contact.remark.as_deref().unwrap_or("N/A"));
-                                    println!("  Account: {}", contact.account.as_deref().unwrap_or("N/A"));
-                                    if !contact.label_list.is_empty() { // label_list is Vec<String>, not Option
-                                        println!("  Labels: {}", contact.label_list.join(", "));
-                                    }
-                                    if let Some(extra_info) = &contact.extra_buf_info {
-                                        if let Some(gender) = extra_info.gender {
-                                            println!("  Gender: {}", gender);
-                                        }
-                                        if let Some(country) = &extra_info.country {
-                                            print!("  Region: {}", country);
-                                            if let Some(province) = &extra_info.province {
-                                                print!(", {}", province);
-                                            }
-                                            if let Some(city) = &extra_info.city {
-                                                print!(", {}", city);
-                                            }
-                                            println!();
-                                        }
-                                    }
-                                }
-                                if contacts.len() > 10 {
-                                     println!("... (output truncated, showing first 10 contacts)");
-                                }
-                            }
-                        }
-                        Err(e) => {
-                            eprintln!("Error getting contacts: {}", e);
-                        }
-                    }
-                }
-                Err(e) => {
-                    eprintln!("Error connecting to database '{:?}': {}", absolute_db_path, e);
-                }
-            }
+            let conn = open_micro_msg(&db_path, openim_db_path.as_deref())?;
+            let contacts = get_contacts(&conn, word.as_deref(), wxids.as_deref(), label_ids.as_deref())
+                .context("Error getting contacts")?;
+            let columns = ["wxid", "remark", "nickname", "account", "labels", "corp", "gender", "region"];
+            write_listing(&mut std::io::stdout().lock(), output, &contacts, &columns, |c| {
+                let extra = c.extra_buf_info.as_ref();
+                let region = extra
+                    .map(|e| [&e.country, &e.province, &e.city].into_iter().flatten().cloned().collect::<Vec<_>>().join(", "))
+                    .unwrap_or_default();
+                vec![
+                    c.wxid.clone(),
+                    c.remark.clone().unwrap_or_default(),
+                    c.nickname.clone().unwrap_or_default(),
+                    c.account.clone().unwrap_or_default(),
+                    c.label_list.join(", "),
+                    c.corp_name.clone().unwrap_or_default(),
+                    extra.and_then(|e| e.gender).map(|g| g.to_string()).unwrap_or_default(),
+                    region,
+                ]
+            })?;
         }
-        Commands::ShowChatrooms { db_path, room_wxids } => {
-            println!("Command: ShowChatrooms");
-            println!("  DB Path: {:?}", db_path);
+        Commands::ShowChatrooms { db_path, room_wxids, openim_db_path } => {
+            debug!("Command: ShowChatrooms");
+            debug!("  DB Path: {:?}", db_path);
             if let Some(ids) = &room_wxids {
-                println!("  Room WxIDs: {:?}", ids);
-            }
-
-            let mut absolute_db_path = db_path.clone();
-            if !absolute_db_path.is_absolute() {
-                match std::env::current_dir() {
-                    Ok(cwd) => {
-                        absolute_db_path = cwd.join(absolute_db_path);
-                        println!("Resolved relative DB path to: {:?}", absolute_db_path);
-                    }
-                    Err(e) => {
-                        eprintln!("Failed to get current working directory: {}. Please use an absolute path for --db-path.", e);
-                        return Ok(());
-                    }
-                }
+                debug!("  Room WxIDs: {:?}", ids);
             }
 
-            match connect_sqlite_db(&absolute_db_path) {
-                Ok(conn) => {
-                    println!("Successfully connected to database: {:?}", absolute_db_path);
-                    match get_chat_rooms(&conn, room_wxids.as_ref().map(|v| v.as_slice())) {
-                        Ok(chat_rooms) => {
-                            if chat_rooms.is_empty() {
-                                println!("No chat rooms found matching the criteria.");
-                            } else {
-                                println!("Found {} chat room(s):", chat_rooms.len());
-                                for (wxid, room_info) in chat_rooms {
-                                    println!("--- Chat Room: {} ---", wxid);
-                                    println!("  Announcement: {}", room_info.announcement.as_deref().unwrap_or("N/A"));
-                                    println!("  Owner WxID: {}", room_info.owner_wxid.as_deref().unwrap_or("N/A"));
-                                    println!("  Member Count: {}", room_info.members.len());
-                                    if !room_info.members.is_empty() {
-                                        println!("  Members (showing up to 5):");
-                                        for (i, member) in room_info.members.iter().take(5).enumerate() {
-                                            println!("    {}. WxID: {}, Nickname: {}",
-                                                     i + 1,
-                                                     member.wxid,
-                                                     member.room_nickname.as_deref().unwrap_or("N/A (parsing pending)"));
-                                        }
-                                        if room_info.members.len() > 5 {
-                                            println!("    ... and {} more members.", room_info.members.len() - 5);
-                                        }
-                                    }
-                                }
-                            }
-                        }
-                        Err(e) => {
-                            eprintln!("Error getting chat rooms: {}", e);
-                        }
-                    }
-                }
-                Err(e) => {
-                    eprintln!("Error connecting to database '{:?}': {}", absolute_db_path, e);
-                }
-            }
+            let conn = open_micro_msg(&db_path, openim_db_path.as_deref())?;
+            let mut chat_rooms: Vec<ChatRoomInfo> = get_chat_rooms(&conn, room_wxids.as_deref())
+                .context("Error getting chat rooms")?
+                .into_values()
+                .collect();
+            chat_rooms.sort_by(|a, b| a.wxid.cmp(&b.wxid));
+            let columns = ["wxid", "owner", "members", "announcement"];
+            write_listing(&mut std::io::stdout().lock(), output, &chat_rooms, &columns, |room| {
+                vec![
+                    room.wxid.clone(),
+                    room.owner_wxid.clone().unwrap_or_default(),
+                    room.member_wxids.len().max(room.members.len()).to_string(),
+                    room.announcement.clone().unwrap_or_default(),
+                ]
+            })?;
         }
-        Commands::ShowSessions { db_path, limit } => {
-            println!("Command: ShowSessions");
-            println!("  DB Path: {:?}", db_path);
+        Commands::ShowSessions { db_path, limit, openim_db_path } => {
+            debug!("Command: ShowSessions");
+            debug!("  DB Path: {:?}", db_path);
             if let Some(l) = limit {
-                println!("  Limit: {}", l);
-            }
-
-            let mut absolute_db_path = db_path.clone();
-            if !absolute_db_path.is_absolute() {
-                match std::env::current_dir() {
-                    Ok(cwd) => {
-                        absolute_db_path = cwd.join(absolute_db_path);
-                        println!("Resolved relative DB path to: {:?}", absolute_db_path);
-                    }
-                    Err(e) => {
-                        eprintln!("Failed to get current working directory: {}. Please use an absolute path for --db-path.", e);
-                        return Ok(());
-                    }
-                }
+                debug!("  Limit: {}", l);
             }
 
-            match connect_sqlite_db(&absolute_db_path) {
-                Ok(conn) => {
-                    println!("Successfully connected to database: {:?}", absolute_db_path);
-                    match get_sessions(&conn) {
-                        Ok(mut sessions) => {
-                            if let Some(l) = limit {
-                                sessions.truncate(l);
-                            }
-
-                            if sessions.is_empty() {
-                                println!("No sessions found.");
-                            } else {
-                                println!("Found {} session(s):", sessions.len());
-                                for (i, session) in sessions.iter().enumerate() {
-                                    println!("--- Session {} ---", i + 1);
-                                    println!("  WxID: {}", session.wxid);
-                                    let display_name = session.session_nickname
-                                        .as_deref()
-                                        .or(session.contact_remark.as_deref())
-                                        .or(session.contact_nickname.as_deref())
-                                        .unwrap_or("N/A");
-                                    println!("  Nickname: {}", display_name);
-                                    println!("  Latest Message: {}", session.content.as_deref().unwrap_or("N/A"));
-                                    println!("  Time: {}", session.time_str.as_deref().unwrap_or("N/A"));
-                                    println!("  Unread Count: {}", session.unread_count.map_or_else(|| 0.to_string(), |c| c.to_string()));
-                                }
-                            }
-                        }
-                        Err(e) => {
-                            eprintln!("Error getting sessions: {}", e);
-                        }
-                    }
-                }
-                Err(e) => {
-                    eprintln!("Error connecting to database '{:?}': {}", absolute_db_path, e);
-                }
+            let conn = open_micro_msg(&db_path, openim_db_path.as_deref())?;
+            let mut sessions: Vec<SessionInfo> = get_sessions(&conn).context("Error getting sessions")?;
+            if let Some(l) = limit {
+                sessions.truncate(l);
             }
+            let columns = ["wxid", "name", "time", "unread", "latest message"];
+            write_listing(&mut std::io::stdout().lock(), output, &sessions, &columns, |session| {
+                let display_name = session
+                    .session_nickname
+                    .as_deref()
+                    .or(session.contact_remark.as_deref())
+                    .or(session.contact_nickname.as_deref())
+                    .unwrap_or_default();
+                vec![
+                    session.wxid.clone(),
+                    display_name.to_string(),
+                    session.time_str.clone().unwrap_or_default(),
+                    session.unread_count.unwrap_or(0).to_string(),
+                    session.content.clone().unwrap_or_default(),
+                ]
+            })?;
         }
         Commands::ShowRecentWxids { db_path, limit } => {
-            println!("Command: ShowRecentWxids");
-            println!("  DB Path: {:?}", db_path);
-            println!("  Limit: {}", limit);
-
-            let mut absolute_db_path = db_path.clone();
-            if !absolute_db_path.is_absolute() {
-                match std::env::current_dir() {
-                    Ok(cwd) => {
-                        absolute_db_path = cwd.join(absolute_db_path);
-                        println!("Resolved relative DB path to: {:?}", absolute_db_path);
-                    }
-                    Err(e) => {
-                        eprintln!("Failed to get current working directory: {}. Please use an absolute path for --db-path.", e);
-                        return Ok(());
-                    }
+            debug!("Command: ShowRecentWxids");
+            debug!("  DB Path: {:?}", db_path);
+            debug!("  Limit: {}", limit);
+
+            let conn = open_micro_msg(&db_path, None)?;
+            let wxids = get_recent_chat_wxids(&conn, limit).context("Error getting recent chat wxids")?;
+            write_listing(&mut std::io::stdout().lock(), output, &wxids, &["wxid"], |wxid| vec![wxid.clone()])?;
+        }
+        Commands::ExportVoice { db_path, media_db_path, talker, out, raw } => {
+            debug!("Command: ExportVoice");
+            debug!("  DB Path: {:?}", db_path);
+            if let Some(paths) = &media_db_path {
+                debug!("  Media DB Paths: {:?}", paths);
+            }
+            debug!("  Talker: {}", talker);
+            debug!("  Out: {:?}", out);
+
+            // A merged database carries the Media table alongside MSG.
+            let media_db_paths = media_db_path.unwrap_or_else(|| vec![db_path.clone()]);
+            let (exported, problems) =
+                export_voice(&db_path, &media_db_paths, &talker, &out, raw).context("Error exporting voice messages")?;
+            let mut text = format!("Exported {} voice file(s) to {:?}.", exported, out);
+            if !problems.is_empty() {
+                text.push_str(&format!("\n{} problem(s):", problems.len()));
+                for problem in &problems {
+                    text.push_str(&format!("\n  {}", problem));
                 }
             }
+            let summary = json!({ "exported": exported, "out": out, "problems": problems });
+            write_summary(&mut std::io::stdout().lock(), output, &text, &summary)?;
+        }
+        Commands::ExportHtml { db_path, micro_db_path, media_db_path, wx_path, talker, my_wxid, out } => {
+            debug!("Command: ExportHtml");
+            debug!("  DB Path: {:?}", db_path);
+            if let Some(p) = &micro_db_path {
+                debug!("  MicroMsg DB Path: {:?}", p);
+            }
+            if let Some(p) = &wx_path {
+                debug!("  WX Path: {:?}", p);
+            }
+            debug!("  Talker: {}", talker);
+            debug!("  Out: {:?}", out);
+
+            let micro_db_path = micro_db_path.unwrap_or_else(|| db_path.clone());
+            let media_db_paths = media_db_path.unwrap_or_else(|| vec![db_path.clone()]);
+            let options = HtmlExportOptions { my_wxid, wx_path, ..Default::default() };
+            let report = run_export_html(&db_path, &micro_db_path, &media_db_paths, &talker, &options, &out)
+                .context("Failed to export HTML")?;
+            let mut text = format!("Exported {} message(s) to {:?}.", report.messages, report.html_path);
+            if report.assets > 0 {
+                text.push_str(&format!("\n  {} embedded/copied media file(s).", report.assets));
+            }
+            if report.missing_media > 0 {
+                text.push_str(&format!("\n  {} media file(s) not found, shown as placeholders.", report.missing_media));
+            }
+            write_summary(&mut std::io::stdout().lock(), output, &text, &json!(report))?;
+        }
+        Commands::ExportText { args } => {
+            debug!("Command: ExportText");
+            export_transcripts_command(&args, TranscriptFormat::Text, output)?;
+        }
+        Commands::ExportMd { args } => {
+            debug!("Command: ExportMd");
+            export_transcripts_command(&args, TranscriptFormat::Markdown, output)?;
+        }
+        Commands::Export { db_path, dataset, format, columns, talker, start, end, my_wxid, wx_path, out } => {
+            debug!("Command: Export");
+            debug!("  DB Path: {:?}", db_path);
+            debug!("  Dataset: {:?}", dataset);
+            debug!("  Format: {:?}", format);
+            if !columns.is_empty() {
+                debug!("  Columns: {}", columns.join(","));
+            }
+            debug!("  Out: {:?}", out);
 
-            match connect_sqlite_db(&absolute_db_path) {
-                Ok(conn) => {
-                    println!("Successfully connected to database: {:?}", absolute_db_path);
-                    match get_recent_chat_wxids(&conn, limit) {
-                        Ok(wxids) => {
-                            if wxids.is_empty() {
-                                println!("No recent chat wxids found.");
-                            } else {
-                                println!("Found {} recent chat wxid(s):", wxids.len());
-                                for (i, wxid) in wxids.iter().enumerate() {
-                                    println!("  {}. {}", i + 1, wxid);
-                                }
-                            }
-                        }
-                        Err(e) => {
-                            eprintln!("Error getting recent chat wxids: {}", e);
-                        }
-                    }
+            let options = DatasetOptions { columns, talker, start_time: None, end_time: None, my_wxid, wx_path };
+            let result = run_export_dataset(&db_path, dataset, format, options, start.as_deref(), end.as_deref(), &out);
+            let rows = result.context("Failed to export")?;
+            let text = format!("Exported {} row(s) to {:?}.", rows, out);
+            write_summary(&mut std::io::stdout().lock(), output, &text, &json!({ "rows": rows, "out": out }))?;
+        }
+        Commands::Search { query, db_path, fts_db_path, index_path, reindex, talker, since, limit } => {
+            debug!("Command: Search");
+            debug!("  Query: {}", query);
+            debug!("  DB Path: {:?}", db_path);
+            if let Some(p) = &fts_db_path {
+                debug!("  FTS DB Path: {:?}", p);
+            }
+
+            let options = SearchOptions { talker, since: None, limit };
+            let messages = run_search(&query, &db_path, fts_db_path.as_deref(), index_path, reindex, since.as_deref(), options)
+                .context("Search failed")?;
+            let columns = ["time", "dir", "talker", "message"];
+            write_listing(&mut std::io::stdout().lock(), output, &messages, &columns, |msg| {
+                vec![
+                    msg.time_str.clone(),
+                    if msg.is_sender { "->" } else { "<-" }.to_string(),
+                    msg.talker.clone(),
+                    message_text(msg, None),
+                ]
+            })?;
+        }
+        Commands::Sns { db_path, micro_db_path, wx_path, author, start, end, format, out } => {
+            debug!("Command: Sns");
+            debug!("  DB Path: {:?}", db_path);
+            if let Some(p) = &micro_db_path {
+                debug!("  MicroMsg DB Path: {:?}", p);
+            }
+            debug!("  Format: {:?}", format);
+            debug!("  Out: {:?}", out);
+
+            let query = SnsQuery { author, wx_path, ..Default::default() };
+            let posts = run_export_sns(&db_path, micro_db_path.as_deref(), query, start.as_deref(), end.as_deref(), format, &out)
+                .context("Failed to export Moments")?;
+            let text = format!("Exported {} post(s) to {:?}.", posts, out);
+            write_summary(&mut std::io::stdout().lock(), output, &text, &json!({ "posts": posts, "out": out }))?;
+        }
+        Commands::Favorites { db_path, micro_db_path, tag, fav_type, format, out } => {
+            debug!("Command: Favorites");
+            debug!("  DB Path: {:?}", db_path);
+            if let Some(p) = &micro_db_path {
+                debug!("  MicroMsg DB Path: {:?}", p);
+            }
+            debug!("  Format: {:?}", format);
+            debug!("  Out: {:?}", out);
+
+            let query = FavoriteQuery { fav_type, tag };
+            let items = run_export_favorites(&db_path, micro_db_path.as_deref(), &query, format, &out)
+                .context("Failed to export favorites")?;
+            let text = format!("Exported {} favorite(s) to {:?}.", items, out);
+            write_summary(&mut std::io::stdout().lock(), output, &text, &json!({ "favorites": items, "out": out }))?;
+        }
+        Commands::Stats { db_path, micro_db_path, talker, my_wxid, start, end, utc_offset, top_words, format, out } => {
+            debug!("Command: Stats");
+            debug!("  DB Path: {:?}", db_path);
+            if let Some(p) = &micro_db_path {
+                debug!("  MicroMsg DB Path: {:?}", p);
+            }
+            if !talker.is_empty() {
+                debug!("  Talkers: {}", talker.join(", "));
+            }
+            debug!("  Format: {:?}", format);
+            debug!("  Out: {:?}", out);
+
+            let utc_offset_minutes = utc_offset.unwrap_or_else(local_utc_offset_minutes);
+            let options = StatsOptions { my_wxid, start_time: None, end_time: None, utc_offset_minutes, top_words };
+            let micro_db_path = micro_db_path.unwrap_or_else(|| db_path.clone());
+            let stats = run_stats(&db_path, &micro_db_path, talker, options, start.as_deref(), end.as_deref(), format, &out)
+                .context("Failed to build statistics")?;
+            let messages: usize = stats.iter().map(|s| s.total_messages).sum();
+            let text = format!("Wrote statistics of {} conversation(s), {} message(s) to {:?}.", stats.len(), messages, out);
+            let summary = json!({ "conversations": stats.len(), "messages": messages, "out": out });
+            write_summary(&mut std::io::stdout().lock(), output, &text, &summary)?;
+        }
+        Commands::RoomReport { db_path, micro_db_path, room, my_wxid, start, end, utc_offset } => {
+            debug!("Command: RoomReport");
+            debug!("  DB Path: {:?}", db_path);
+            if let Some(p) = &micro_db_path {
+                debug!("  MicroMsg DB Path: {:?}", p);
+            }
+            debug!("  Room: {}", room);
+
+            let utc_offset_minutes = utc_offset.unwrap_or_else(local_utc_offset_minutes);
+            let options = StatsOptions { my_wxid, utc_offset_minutes, ..Default::default() };
+            let micro_db_path = micro_db_path.unwrap_or_else(|| db_path.clone());
+            let report = run_room_report(&db_path, &micro_db_path, &room, options, start.as_deref(), end.as_deref())
+                .with_context(|| format!("Failed to build the report of {}", room))?;
+            write_room_report(&mut std::io::stdout().lock(), output, &report)?;
+        }
+        Commands::Graph {
+            db_path,
+            micro_db_path,
+            my_wxid,
+            friends_only,
+            exclude_official,
+            max_room_size,
+            start,
+            end,
+            format,
+            out,
+        } => {
+            debug!("Command: Graph");
+            debug!("  DB Path: {:?}", db_path);
+            if let Some(p) = &micro_db_path {
+                debug!("  MicroMsg DB Path: {:?}", p);
+            }
+            debug!("  Friends only: {}, exclude official: {}", friends_only, exclude_official);
+            debug!("  Format: {:?}", format);
+            debug!("  Out: {:?}", out);
+
+            let options = GraphOptions { my_wxid, friends_only, exclude_official, max_room_size, ..Default::default() };
+            let micro_db_path = micro_db_path.unwrap_or_else(|| db_path.clone());
+            let graph = run_graph(&db_path, &micro_db_path, options, start.as_deref(), end.as_deref(), format, &out)
+                .context("Failed to export the contact graph")?;
+            let text = format!("Wrote {} node(s) and {} edge(s) to {:?}.", graph.nodes.len(), graph.edges.len(), out);
+            let summary = json!({ "nodes": graph.nodes.len(), "edges": graph.edges.len(), "out": out });
+            write_summary(&mut std::io::stdout().lock(), output, &text, &summary)?;
+        }
+        Commands::DecryptImages { wx_path, out_path, aes_key, xor_key } => {
+            debug!("Command: DecryptImages");
+            debug!("  WX Path: {:?}", wx_path);
+            debug!("  Out Path: {:?}", out_path);
+
+            let keys = parse_dat_keys(aes_key.as_deref(), xor_key.as_deref())
+                .map_err(|e| WxDumpError::KeyInvalid(e.to_string()))?;
+
+            let report = decrypt_images_in_dir(&wx_path, &out_path, &keys)
+                .with_context(|| format!("Error decrypting images in {:?}", wx_path))?;
+            let mut text = format!("Decoded {} image(s) to {:?}.", report.decoded.len(), out_path);
+            if !report.failed.is_empty() {
+                text.push_str(&format!("\nCould not decode {} file(s):", report.failed.len()));
+                for (path, reason) in &report.failed {
+                    text.push_str(&format!("\n  {:?}: {}", path, reason));
+                }
+            }
+            write_summary(&mut std::io::stdout().lock(), output, &text, &json!(report))?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Opens the MicroMsg database of the `Show*` commands (relative paths are taken from the
+/// working directory), with OpenIMContact.db attached when given.
+fn open_micro_msg(db_path: &Path, openim_db_path: Option<&Path>) -> anyhow::Result<rusqlite::Connection> {
+    let absolute_db_path = if db_path.is_absolute() {
+        db_path.to_path_buf()
+    } else {
+        let cwd = std::env::current_dir()
+            .context("Failed to get current working directory. Please use an absolute path for --db-path")?;
+        cwd.join(db_path)
+    };
+    debug!("Resolved DB path: {:?}", absolute_db_path);
+    let conn = open_db(&absolute_db_path).with_context(|| format!("Error connecting to database '{:?}'", absolute_db_path))?;
+    if let Some(openim_path) = openim_db_path {
+        attach_openim_db(&conn, openim_path, db_key())
+            .with_context(|| format!("Failed to attach OpenIM database {:?}", openim_path))?;
+    }
+    Ok(conn)
+}
+
+/// Writes every voice message of `talker` to `out_dir`, as WAV when SILK decoding is
+/// available (and `raw` is not set), otherwise as the raw .silk stream. Returns the number of
+/// files written and the messages that could not be exported as intended.
+fn export_voice(
+    db_path: &Path,
+    media_db_paths: &[PathBuf],
+    talker: &str,
+    out_dir: &Path,
+    raw: bool,
+) -> anyhow::Result<(usize, Vec<String>)> {
+    for p in std::iter::once(&db_path.to_path_buf()).chain(media_db_paths) {
+        if !p.exists() {
+            return Err(WxDumpError::DatabaseNotFound(p.to_path_buf()).into());
+        }
+    }
+    let msg_conn = open_db(db_path)?;
+    let media_conns = media_db_paths
+        .iter()
+        .map(|p| open_db(p))
+        .collect::<Result<Vec<_>, _>>()?;
+    std::fs::create_dir_all(out_dir)?;
+
+    let messages = get_messages(&msg_conn, Some(talker), Some(&[MSG_TYPE_VOICE]), None, None, None, None)?;
+    info!("Found {} voice message(s) for {}.", messages.len(), talker);
+
+    let decode = !raw && silk::silk_decoding_available();
+    if !raw && !decode {
+        warn!("SILK decoding is not available in this build (enable the `silk` feature), writing .silk files instead.");
+    }
+
+    let mut exported = 0;
+    let mut failed: Vec<String> = Vec::new();
+    for msg in &messages {
+        let Some(svr_id) = msg.msg_svr_id else {
+            failed.push(format!("localId {}: no MsgSvrID", msg.local_id));
+            continue;
+        };
+        let blob = match find_voice_blob(&media_conns, svr_id)? {
+            Some(b) => b,
+            None => {
+                failed.push(format!("MsgSvrID {}: not found in MediaMSG", svr_id));
+                continue;
+            }
+        };
+        let stem = format!("{}_{}", format_timestamp_to_string(msg.create_time, "%Y%m%d_%H%M%S"), svr_id);
+        let silk_data = strip_wechat_silk_prefix(&blob);
+
+        if decode {
+            match silk::decode_silk_to_pcm(silk_data, silk::DEFAULT_SAMPLE_RATE) {
+                Ok(pcm) => {
+                    silk::write_wav(&out_dir.join(format!("{}.wav", stem)), &pcm, silk::DEFAULT_SAMPLE_RATE)?;
+                    exported += 1;
+                    continue;
                 }
                 Err(e) => {
-                    eprintln!("Error connecting to database '{:?}': {}", absolute_db_path, e);
+                    failed.push(format!("MsgSvrID {}: SILK decode failed ({}), wrote .silk", svr_id, e));
                 }
             }
         }
-        // The Ui and Api commands are commented out in cli.rs, so no need to handle them here
-        // unless they are uncommented.
-        // _ => {
-        //     // This should not be reached if all commands are handled
-        //     eprintln!("Unhandled command variant.");
-        // }
+        std::fs::write(out_dir.join(format!("{}.silk", stem)), silk_data)?;
+        exported += 1;
+    }
+
+    Ok((exported, failed))
+}
+
+/// Parses `--aes-key` (16 characters, or 32 hex digits) and `--xor-key` (e.g. `0x88` or `88`).
+fn parse_dat_keys(aes_key: Option<&str>, xor_key: Option<&str>) -> anyhow::Result<DatKeys> {
+    let aes_key = match aes_key {
+        None => None,
+        Some(k) if k.len() == 16 => Some(k.as_bytes().try_into()?),
+        Some(k) if k.len() == 32 => Some(hex::decode(k)?.as_slice().try_into()?),
+        Some(k) => return Err(anyhow::anyhow!("AES key must be 16 characters or 32 hex digits, got {} characters", k.len())),
+    };
+    let xor_key = match xor_key {
+        None => None,
+        Some(k) => Some(u8::from_str_radix(k.trim_start_matches("0x").trim_start_matches("0X"), 16)?),
+    };
+    Ok(DatKeys { aes_key, xor_key })
+}
+
+fn run_export_html(
+    db_path: &Path,
+    micro_db_path: &Path,
+    media_db_paths: &[PathBuf],
+    talker: &str,
+    options: &HtmlExportOptions,
+    out: &Path,
+) -> anyhow::Result<wxdump_rs::core::export::html::HtmlExportReport> {
+    for p in [db_path, micro_db_path].into_iter().chain(media_db_paths.iter().map(PathBuf::as_path)) {
+        if !p.exists() {
+            return Err(WxDumpError::DatabaseNotFound(p.to_path_buf()).into());
+        }
+    }
+    let msg_conn = open_db(db_path)?;
+    let contact_conn = open_db(micro_db_path)?;
+    let media_conns = media_db_paths
+        .iter()
+        .map(|p| open_db(p))
+        .collect::<Result<Vec<_>, _>>()?;
+    export_html(&msg_conn, &contact_conn, &media_conns, talker, options, out)
+}
+
+fn export_transcripts_command(args: &TranscriptArgs, format: TranscriptFormat, output: ListFormat) -> anyhow::Result<()> {
+    debug!("  DB Path: {:?}", args.db_path);
+    if !args.talker.is_empty() {
+        debug!("  Talkers: {}", args.talker.join(", "));
+    }
+    debug!("  Out: {:?}", args.out);
+
+    let report = run_export_transcripts(args, format).context("Failed to export transcripts")?;
+    let text = format!(
+        "Exported {} message(s) from {} conversation(s) into {} file(s).",
+        report.messages,
+        report.conversations,
+        report.files.len()
+    );
+    write_summary(&mut std::io::stdout().lock(), output, &text, &json!(report))
+}
+
+fn run_export_transcripts(args: &TranscriptArgs, format: TranscriptFormat) -> anyhow::Result<TranscriptReport> {
+    let micro_db_path = args.micro_db_path.clone().unwrap_or_else(|| args.db_path.clone());
+    for p in [&args.db_path, &micro_db_path] {
+        if !p.exists() {
+            return Err(WxDumpError::DatabaseNotFound(p.to_path_buf()).into());
+        }
+    }
+    let options = TranscriptOptions {
+        format,
+        my_wxid: args.my_wxid.clone(),
+        wx_path: args.wx_path.clone(),
+        start_time: args.start.as_deref().map(|s| parse_time_arg(s, false, 0)).transpose()?,
+        end_time: args.end.as_deref().map(|s| parse_time_arg(s, true, 0)).transpose()?,
+        senders: args.sender.clone(),
+        combined: args.combined,
+    };
+    let msg_conn = open_db(&args.db_path)?;
+    let contact_conn = open_db(&micro_db_path)?;
+    let talkers = if args.talker.is_empty() { list_talkers(&msg_conn)? } else { args.talker.clone() };
+    export_transcripts(&msg_conn, &contact_conn, &talkers, &options, &args.out)
+}
+
+fn run_export_dataset(
+    db_path: &Path,
+    dataset: Dataset,
+    format: TableFormat,
+    mut options: DatasetOptions,
+    start: Option<&str>,
+    end: Option<&str>,
+    out: &Path,
+) -> anyhow::Result<usize> {
+    if !db_path.exists() {
+        return Err(WxDumpError::DatabaseNotFound(db_path.to_path_buf()).into());
+    }
+    options.start_time = start.map(|s| parse_time_arg(s, false, 0)).transpose()?;
+    options.end_time = end.map(|s| parse_time_arg(s, true, 0)).transpose()?;
+    let conn = open_db(db_path)?;
+    let file = std::fs::File::create(out).map_err(|e| anyhow::anyhow!("Failed to create {:?}: {}", out, e))?;
+    export_dataset(&conn, dataset, format, &options, std::io::BufWriter::new(file))
+}
+
+/// The global `--key`. When given, every database is read encrypted in place.
+static DB_KEY: OnceLock<Option<String>> = OnceLock::new();
+
+fn db_key() -> Option<&'static str> {
+    DB_KEY.get().and_then(|key| key.as_deref())
+}
+
+/// Opens `db_path` read-only; the `db` commands never write.
+fn open_db(db_path: &Path) -> anyhow::Result<rusqlite::Connection> {
+    open_encrypted_database(db_path, db_key())
+}
+
+/// `db` subcommands. `tables`/`schema` follow the global `--output`; `dump`/`query` have their
+/// own `--format`, falling back to `--output`.
+fn run_db_command(command: DbCommands, output: ListFormat) -> anyhow::Result<()> {
+    match command {
+        DbCommands::Tables { db_path } => {
+            let conn = open_db(&db_path)?;
+            let tables = list_tables_with_counts(&conn)?;
+            write_listing(&mut std::io::stdout().lock(), output, &tables, &["table", "rows"], |t| {
+                vec![t.name.clone(), t.row_count.to_string()]
+            })?;
+        }
+        DbCommands::Schema { db_path, table } => {
+            let conn = open_db(&db_path)?;
+            let schema = table_schema(&conn, &table)?;
+            if output.is_json() {
+                return write_summary(&mut std::io::stdout().lock(), output, "", &json!(schema));
+            }
+            if let Some(sql) = &schema.sql {
+                println!("{};\n", sql);
+            }
+            let columns = ["column", "type", "null", "default", "pk"];
+            write_listing(&mut std::io::stdout().lock(), output, &schema.columns, &columns, |c| {
+                vec![
+                    c.name.clone(),
+                    c.decl_type.clone(),
+                    if c.not_null { "NOT NULL".to_string() } else { String::new() },
+                    c.default_value.clone().unwrap_or_default(),
+                    if c.primary_key > 0 { c.primary_key.to_string() } else { String::new() },
+                ]
+            })?;
+            for index in &schema.indexes {
+                println!("{};", index);
+            }
+        }
+        DbCommands::Dump { db_path, table, limit, offset, where_clause, format, decode } => {
+            let options = DumpOptions { limit: (limit > 0).then_some(limit), offset, where_clause };
+            run_db_dump(&db_path, &table, &options, format.unwrap_or_else(|| query_format(output)), &decode)?;
+        }
+        DbCommands::Query { db_path, sql, format, decode } => {
+            let conn = open_db(&db_path)?;
+            let decoders = decode.iter().map(|arg| parse_decoder_arg(arg)).collect::<anyhow::Result<_>>()?;
+            let format = format.unwrap_or_else(|| query_format(output));
+            write_query(&conn, &sql, &decoders, format, std::io::stdout().lock())?;
+        }
+    }
+    Ok(())
+}
+
+/// The rows of `db dump`/`query` for a global `--output`: the JSON formats as such, the tables as text.
+fn query_format(output: ListFormat) -> OutputFormat {
+    match output {
+        ListFormat::Text | ListFormat::Table => OutputFormat::Text,
+        ListFormat::Json => OutputFormat::Table(TableFormat::Json),
+        ListFormat::Jsonl => OutputFormat::Table(TableFormat::Jsonl),
+    }
+}
+
+fn run_db_dump(
+    db_path: &Path,
+    table: &str,
+    options: &DumpOptions,
+    format: OutputFormat,
+    decode: &[String],
+) -> anyhow::Result<usize> {
+    let conn = open_db(db_path)?;
+    let decoders = decode.iter().map(|arg| parse_decoder_arg(arg)).collect::<anyhow::Result<_>>()?;
+    let sql = dump_table_sql(&conn, table, options)?;
+    write_query(&conn, &sql, &decoders, format, std::io::stdout().lock())
+}
+
+fn run_search(
+    query: &str,
+    db_path: &Path,
+    fts_db_path: Option<&Path>,
+    index_path: Option<PathBuf>,
+    reindex: bool,
+    since: Option<&str>,
+    mut options: SearchOptions,
+) -> anyhow::Result<Vec<Message>> {
+    options.since = since.map(|s| parse_time_arg(s, false, 0)).transpose()?;
+    let msg_conn = open_db(db_path)?;
+    let fts_conn = fts_db_path.map(open_db).transpose()?;
+
+    let has_fts = match &fts_conn {
+        Some(conn) => !wxdump_rs::core::db_parser::fts_index_names(conn)?.is_empty(),
+        None => false,
+    };
+    let index = if has_fts {
+        None
+    } else {
+        // A database read with --key gets no plaintext index next to it unless one is asked for.
+        let index_path = index_path.or_else(|| db_key().is_none().then(|| db_path.with_extension("search.db")));
+        let mut index = match &index_path {
+            Some(path) => SearchIndex::open(path)?,
+            None => SearchIndex::open_in_memory()?,
+        };
+        if reindex || index.is_stale(&msg_conn)? {
+            match &index_path {
+                Some(path) => info!("Building search index {:?} ...", path),
+                None => info!("Building search index in memory ..."),
+            }
+            let count = index.rebuild(&msg_conn)?;
+            info!("Indexed {} message(s).", count);
+        }
+        Some(index)
+    };
+
+    let (source, messages) = search_messages(&msg_conn, fts_conn.as_ref(), index.as_ref(), query, &options)?;
+    let source = match source {
+        SearchSource::WeChatFts => "FTSMSG.db",
+        SearchSource::LocalIndex => "local index",
+    };
+    info!("Found {} message(s) ({}).", messages.len(), source);
+    Ok(messages)
+}
+
+/// Decrypts one database or every WeChat database under a directory into `out_dir` as
+/// `de_<name>`. Each file is snapshot-copied first, so this also works while WeChat is running,
+/// and its WAL is folded into the output.
+fn run_decrypt(key: &str, db_path: &Path, out_dir: &Path) -> anyhow::Result<Vec<PathBuf>> {
+    let sources = if db_path.is_dir() {
+        let mut paths: Vec<PathBuf> = discover_databases(db_path)?.into_values().flatten().collect();
+        paths.sort();
+        paths
+    } else {
+        vec![db_path.to_path_buf()]
+    };
+    std::fs::create_dir_all(out_dir).map_err(|e| anyhow::anyhow!("Failed to create {:?}: {}", out_dir, e))?;
+
+    let mut written = Vec::new();
+    let mut first_error = None;
+    for source in sources {
+        let name = source.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
+        let out = out_dir.join(format!("de_{}", name));
+        match decrypt_live_database_file(&source, &out, key) {
+            Ok(()) => written.push(out),
+            Err(e) => {
+                log::warn!("{:?}: {}", source, e);
+                first_error.get_or_insert(e);
+            }
+        }
+    }
+    match first_error {
+        Some(e) if written.is_empty() => Err(e.into()),
+        _ => Ok(written),
+    }
+}
+
+fn run_export_sns(
+    db_path: &Path,
+    micro_db_path: Option<&Path>,
+    mut query: SnsQuery,
+    start: Option<&str>,
+    end: Option<&str>,
+    format: SnsFormat,
+    out: &Path,
+) -> anyhow::Result<usize> {
+    query.start_time = start.map(|s| parse_time_arg(s, false, 0)).transpose()?;
+    query.end_time = end.map(|s| parse_time_arg(s, true, 0)).transpose()?;
+    let sns_conn = open_db(db_path)?;
+    let contact_conn = micro_db_path.map(open_db).transpose()?;
+    let posts = get_sns_posts(&sns_conn, contact_conn.as_ref(), &query)?;
+
+    match format {
+        SnsFormat::Json => {
+            let file = std::fs::File::create(out).map_err(|e| anyhow::anyhow!("Failed to create {:?}: {}", out, e))?;
+            write_sns_json(&posts, std::io::BufWriter::new(file))?;
+        }
+        SnsFormat::Html => export_sns_html(&posts, "朋友圈", out)?,
     }
+    Ok(posts.len())
+}
 
+fn run_export_favorites(
+    db_path: &Path,
+    micro_db_path: Option<&Path>,
+    query: &FavoriteQuery,
+    format: FavoriteFormat,
+    out: &Path,
+) -> anyhow::Result<usize> {
+    let conn = open_db(db_path)?;
+    let contact_conn = micro_db_path.map(open_db).transpose()?;
+    let items = get_favorites(&conn, contact_conn.as_ref(), query)?;
+
+    let file = std::fs::File::create(out).map_err(|e| anyhow::anyhow!("Failed to create {:?}: {}", out, e))?;
+    let writer = std::io::BufWriter::new(file);
+    match format {
+        FavoriteFormat::Json => write_favorites_json(&items, writer)?,
+        FavoriteFormat::Md => write_favorites_markdown(&items, writer)?,
+    }
+    Ok(items.len())
+}
+
+fn run_room_report(
+    db_path: &Path,
+    micro_db_path: &Path,
+    room: &str,
+    mut options: StatsOptions,
+    start: Option<&str>,
+    end: Option<&str>,
+) -> anyhow::Result<RoomReport> {
+    if !is_chat_room_wxid(room) {
+        return Err(WxDumpError::InvalidArgument(format!("{:?} is not a chat room id (xxx@chatroom)", room)).into());
+    }
+    for p in [db_path, micro_db_path] {
+        if !p.exists() {
+            return Err(WxDumpError::DatabaseNotFound(p.to_path_buf()).into());
+        }
+    }
+    options.start_time = start.map(|s| parse_time_arg(s, false, options.utc_offset_minutes)).transpose()?;
+    options.end_time = end.map(|s| parse_time_arg(s, true, options.utc_offset_minutes)).transpose()?;
+    room_report(&open_db(db_path)?, &open_db(micro_db_path)?, room, &options)
+}
+
+/// The whole report as JSON, otherwise a summary line, the member table and the events.
+fn write_room_report(out: &mut impl std::io::Write, output: ListFormat, report: &RoomReport) -> anyhow::Result<()> {
+    if output.is_json() {
+        return write_summary(out, output, "", &json!(report));
+    }
+    writeln!(
+        out,
+        "{} ({}): {} member(s), {} message(s), {} lurker(s), owner {}\n",
+        report.name,
+        report.room,
+        report.member_count,
+        report.total_messages,
+        report.lurkers.len(),
+        report.owner.as_deref().unwrap_or("unknown")
+    )?;
+    let columns = ["name", "wxid", "messages", "last active", "joined", "left", "member"];
+    write_listing(out, output, &report.members, &columns, |m| {
+        vec![
+            if m.is_owner { format!("{} (owner)", m.name) } else { m.name.clone() },
+            m.wxid.clone(),
+            m.messages.to_string(),
+            m.last_active.clone().unwrap_or_default(),
+            m.joined.clone().unwrap_or_default(),
+            m.left.clone().unwrap_or_default(),
+            if m.is_member { "yes" } else { "no" }.to_string(),
+        ]
+    })?;
+    if !report.events.is_empty() {
+        writeln!(out)?;
+        write_listing(out, output, &report.events, &["time", "event", "notice"], |e| {
+            vec![e.time_str.clone(), e.kind.as_str().to_string(), e.text.clone()]
+        })?;
+    }
     Ok(())
+}
+
+/// The contact graph of the account, written to `out` as GraphML, GEXF or node-link JSON.
+fn run_graph(
+    db_path: &Path,
+    micro_db_path: &Path,
+    mut options: GraphOptions,
+    start: Option<&str>,
+    end: Option<&str>,
+    format: GraphFormat,
+    out: &Path,
+) -> anyhow::Result<ContactGraph> {
+    for p in [db_path, micro_db_path] {
+        if !p.exists() {
+            return Err(WxDumpError::DatabaseNotFound(p.to_path_buf()).into());
+        }
+    }
+    options.start_time = start.map(|s| parse_time_arg(s, false, 0)).transpose()?;
+    options.end_time = end.map(|s| parse_time_arg(s, true, 0)).transpose()?;
+    let writer: fn(&ContactGraph, std::io::BufWriter<std::fs::File>) -> anyhow::Result<()> = match format {
+        GraphFormat::Graphml => write_graphml,
+        GraphFormat::Gexf => write_gexf,
+        GraphFormat::Json => write_graph_json,
+    };
+    let graph = contact_graph(&open_db(db_path)?, &open_db(micro_db_path)?, &options)?;
+    if let Some(parent) = out.parent().filter(|p| !p.as_os_str().is_empty()) {
+        std::fs::create_dir_all(parent)?;
+    }
+    let file = std::fs::File::create(out).map_err(|e| anyhow::anyhow!("Failed to create {:?}: {}", out, e))?;
+    writer(&graph, std::io::BufWriter::new(file))?;
+    Ok(graph)
+}
+
+/// Statistics of `talkers`, by default of every conversation in the session list (or of
+/// every talker in MSG when there is none), written to `out` as JSON or HTML.
+#[allow(clippy::too_many_arguments)]
+fn run_stats(
+    db_path: &Path,
+    micro_db_path: &Path,
+    talkers: Vec<String>,
+    mut options: StatsOptions,
+    start: Option<&str>,
+    end: Option<&str>,
+    format: StatsFormat,
+    out: &Path,
+) -> anyhow::Result<Vec<ConversationStats>> {
+    for p in [db_path, micro_db_path] {
+        if !p.exists() {
+            return Err(WxDumpError::DatabaseNotFound(p.to_path_buf()).into());
+        }
+    }
+    options.start_time = start.map(|s| parse_time_arg(s, false, options.utc_offset_minutes)).transpose()?;
+    options.end_time = end.map(|s| parse_time_arg(s, true, options.utc_offset_minutes)).transpose()?;
+    let msg_conn = open_db(db_path)?;
+    let contact_conn = open_db(micro_db_path)?;
+    let talkers = if !talkers.is_empty() {
+        talkers
+    } else {
+        let sessions: Vec<String> = get_sessions(&contact_conn)?.into_iter().map(|s| s.wxid).collect();
+        if sessions.is_empty() { list_talkers(&msg_conn)? } else { sessions }
+    };
+    let stats = collect_stats(&msg_conn, &contact_conn, &talkers, &options)?;
+
+    match format {
+        StatsFormat::Json => {
+            let file = std::fs::File::create(out).map_err(|e| anyhow::anyhow!("Failed to create {:?}: {}", out, e))?;
+            write_stats_json(&stats, std::io::BufWriter::new(file))?;
+        }
+        StatsFormat::Html => export_stats_html(&stats, "聊天统计", out)?,
+    }
+    Ok(stats)
 }
\ No newline at end of file
@@ -0,0 +1,382 @@
+// src/core/account.rs
+
+use anyhow::{anyhow, Result};
+use rusqlite::Connection;
+use std::cell::OnceCell;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use super::db_parser::{
+    attach_openim_db, find_voice_blob, open_encrypted_database, get_chat_rooms, get_contacts, get_display_names, get_messages, get_sessions,
+    ChatRoomInfo, Contact, Message, SessionInfo, MSG_TYPE_VOICE,
+};
+use super::media_resolver::{MediaRef, MediaResolver};
+
+/// The databases of an account folder (`WeChat Files/<wxid>/Msg`) this crate knows how to read.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DbKind {
+    MicroMsg,
+    /// `Multi/MSG0.db`, `MSG1.db`, ...
+    Msg,
+    /// `Multi/MediaMSG0.db`, ...
+    MediaMsg,
+    /// `Multi/FTSMSG0.db`, ... (or a single `FTSMSG.db`)
+    FtsMsg,
+    Sns,
+    Emotion,
+    OpenImContact,
+    Favorite,
+    PublicMsg,
+}
+
+impl DbKind {
+    pub const ALL: [DbKind; 9] = [
+        DbKind::MicroMsg,
+        DbKind::Msg,
+        DbKind::MediaMsg,
+        DbKind::FtsMsg,
+        DbKind::Sns,
+        DbKind::Emotion,
+        DbKind::OpenImContact,
+        DbKind::Favorite,
+        DbKind::PublicMsg,
+    ];
+
+    /// Kind and shard number of a database file name. Accepts both `MicroMsg.db` and the
+    /// `de_MicroMsg.db` names of decrypted copies.
+    pub fn from_file_name(name: &str) -> Option<(DbKind, u32)> {
+        let stem = name.strip_suffix(".db")?;
+        let stem = stem.strip_prefix("de_").unwrap_or(stem);
+        let sharded = |prefix: &str| -> Option<u32> {
+            let digits = stem.strip_prefix(prefix)?;
+            if digits.is_empty() {
+                Some(0)
+            } else if digits.bytes().all(|b| b.is_ascii_digit()) {
+                digits.parse().ok()
+            } else {
+                None
+            }
+        };
+        let single = |kind: DbKind, expected: &str| (stem == expected).then_some((kind, 0));
+
+        single(DbKind::MicroMsg, "MicroMsg")
+            .or_else(|| single(DbKind::Sns, "Sns"))
+            .or_else(|| single(DbKind::Emotion, "Emotion"))
+            .or_else(|| single(DbKind::OpenImContact, "OpenIMContact"))
+            .or_else(|| single(DbKind::Favorite, "Favorite"))
+            .or_else(|| single(DbKind::PublicMsg, "PublicMsg"))
+            .or_else(|| sharded("MediaMSG").map(|n| (DbKind::MediaMsg, n)))
+            .or_else(|| sharded("FTSMSG").map(|n| (DbKind::FtsMsg, n)))
+            // `MSG.db` alone is not a message shard.
+            .or_else(|| sharded("MSG").filter(|_| stem != "MSG").map(|n| (DbKind::Msg, n)))
+    }
+
+    fn index(self) -> usize {
+        DbKind::ALL.iter().position(|k| *k == self).unwrap_or_default()
+    }
+}
+
+/// Finds the known databases under `dir` (the account's `Msg` folder or a folder of decrypted
+/// copies), descending into subfolders such as `Multi`. Shards are sorted by number.
+pub fn discover_databases(dir: &Path) -> Result<HashMap<DbKind, Vec<PathBuf>>> {
+    fn walk(dir: &Path, depth: usize, found: &mut Vec<(DbKind, u32, PathBuf)>) -> Result<()> {
+        for entry in std::fs::read_dir(dir).map_err(|e| anyhow!("Failed to read {:?}: {}", dir, e))? {
+            let path = entry?.path();
+            if path.is_dir() {
+                if depth > 0 {
+                    walk(&path, depth - 1, found)?;
+                }
+            } else if let Some((kind, shard)) =
+                path.file_name().and_then(|n| n.to_str()).and_then(DbKind::from_file_name)
+            {
+                found.push((kind, shard, path));
+            }
+        }
+        Ok(())
+    }
+
+    let mut found = Vec::new();
+    walk(dir, 2, &mut found)?;
+    found.sort_by(|a, b| (a.1, &a.2).cmp(&(b.1, &b.2)));
+
+    let mut databases: HashMap<DbKind, Vec<PathBuf>> = HashMap::new();
+    for (kind, _, path) in found {
+        databases.entry(kind).or_default().push(path);
+    }
+    Ok(databases)
+}
+
+/// What a message's media resolved to.
+#[derive(Debug, Clone)]
+pub enum MessageMedia {
+    /// Raw voice blob from MediaMSG (WeChat SILK, see `strip_wechat_silk_prefix`).
+    Voice(Vec<u8>),
+    /// Image, video or file under the account folder.
+    File(MediaRef),
+}
+
+/// All databases of one WeChat account behind a single handle.
+///
+/// Databases are discovered up front and opened (read-only) on first use, so a tool that only
+/// lists contacts never touches the MSG shards. Encrypted accounts are read in place through
+/// SQLCipher; nothing is decrypted to disk. OpenIMContact is attached to MicroMsg, which
+/// makes Enterprise WeChat contacts part of every contact, room and session lookup. Display
+/// names are loaded once and shared by all calls.
+pub struct WeChatAccount {
+    root: PathBuf,
+    key: Option<String>,
+    databases: HashMap<DbKind, Vec<PathBuf>>,
+    resolver: Option<MediaResolver>,
+    connections: [OnceCell<Vec<Connection>>; DbKind::ALL.len()],
+    names: OnceCell<HashMap<String, String>>,
+}
+
+impl WeChatAccount {
+    /// Opens a folder of decrypted databases (the output of `decrypt`).
+    pub fn open(db_dir: impl Into<PathBuf>) -> Result<Self> {
+        Self::new(db_dir.into(), None, None)
+    }
+
+    /// Opens an encrypted account folder (`WeChat Files/<wxid>`) with its 64 hex char key.
+    /// Media resolves under `wx_path`.
+    pub fn open_encrypted(wx_path: impl Into<PathBuf>, key: &str) -> Result<Self> {
+        let wx_path = wx_path.into();
+        let msg_dir = wx_path.join("Msg");
+        let root = if msg_dir.is_dir() { msg_dir } else { wx_path.clone() };
+        Self::new(root, Some(key.to_string()), Some(MediaResolver::new(wx_path)))
+    }
+
+    fn new(root: PathBuf, key: Option<String>, resolver: Option<MediaResolver>) -> Result<Self> {
+        let databases = discover_databases(&root)?;
+        if !databases.contains_key(&DbKind::MicroMsg) && !databases.contains_key(&DbKind::Msg) {
+            return Err(anyhow!("No MicroMsg or MSG database found under {:?}", root));
+        }
+        Ok(WeChatAccount {
+            root,
+            key,
+            databases,
+            resolver,
+            connections: Default::default(),
+            names: OnceCell::new(),
+        })
+    }
+
+    /// Account folder (`WeChat Files/<wxid>`) used to resolve images, videos and files.
+    pub fn with_wx_path(mut self, wx_path: impl Into<PathBuf>) -> Self {
+        self.resolver = Some(MediaResolver::new(wx_path));
+        self
+    }
+
+    /// Folder the databases were discovered in.
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Paths of the discovered databases of `kind`.
+    pub fn database_paths(&self, kind: DbKind) -> &[PathBuf] {
+        self.databases.get(&kind).map(Vec::as_slice).unwrap_or_default()
+    }
+
+    /// Connections to every shard of `kind`, opened on first call. Empty when there is none.
+    pub fn connections(&self, kind: DbKind) -> Result<&[Connection]> {
+        let cell = &self.connections[kind.index()];
+        if let Some(conns) = cell.get() {
+            return Ok(conns);
+        }
+        let mut conns = Vec::new();
+        for path in self.database_paths(kind) {
+            conns.push(open_encrypted_database(path, self.key.as_deref())?);
+        }
+        if kind == DbKind::MicroMsg {
+            if let (Some(conn), Some(openim)) = (conns.first(), self.database_paths(DbKind::OpenImContact).first()) {
+                attach_openim_db(conn, openim, self.key.as_deref())?;
+            }
+        }
+        Ok(cell.get_or_init(|| conns))
+    }
+
+    /// The first (usually only) database of `kind`, if the account has one.
+    pub fn connection(&self, kind: DbKind) -> Result<Option<&Connection>> {
+        Ok(self.connections(kind)?.first())
+    }
+
+    /// MicroMsg, with OpenIMContact attached when present.
+    pub fn micro_msg(&self) -> Result<&Connection> {
+        self.connection(DbKind::MicroMsg)?.ok_or_else(|| anyhow!("MicroMsg.db not found"))
+    }
+
+    pub fn contacts(&self) -> Result<Vec<Contact>> {
+        get_contacts(self.micro_msg()?, None, None, None)
+    }
+
+    pub fn rooms(&self) -> Result<HashMap<String, ChatRoomInfo>> {
+        get_chat_rooms(self.micro_msg()?, None)
+    }
+
+    pub fn sessions(&self) -> Result<Vec<SessionInfo>> {
+        get_sessions(self.micro_msg()?)
+    }
+
+    /// All messages with `talker` across the MSG shards, oldest first.
+    pub fn messages(&self, talker: &str) -> Result<Vec<Message>> {
+        let mut messages = Vec::new();
+        for conn in self.connections(DbKind::Msg)? {
+            messages.extend(get_messages(conn, Some(talker), None, None, None, None, None)?);
+        }
+        messages.sort_by_key(|m| m.create_time);
+        Ok(messages)
+    }
+
+    /// Voice from the MediaMSG shards, images/videos/files under the account folder
+    /// (when one is known). `None` for messages without media or when it cannot be found.
+    pub fn media(&self, msg: &Message) -> Result<Option<MessageMedia>> {
+        if msg.msg_type == MSG_TYPE_VOICE {
+            let Some(svr_id) = msg.msg_svr_id else { return Ok(None) };
+            return Ok(find_voice_blob(self.connections(DbKind::MediaMsg)?, svr_id)?.map(MessageMedia::Voice));
+        }
+        Ok(self.resolver.as_ref().and_then(|r| r.resolve(msg)).map(MessageMedia::File))
+    }
+
+    /// Display names of every contact, room and OpenIM contact, loaded once.
+    pub fn display_names(&self) -> Result<&HashMap<String, String>> {
+        if let Some(names) = self.names.get() {
+            return Ok(names);
+        }
+        let names = match self.connection(DbKind::MicroMsg)? {
+            Some(conn) => get_display_names(conn)?,
+            None => HashMap::new(),
+        };
+        Ok(self.names.get_or_init(|| names))
+    }
+
+    /// The name WeChat shows for `wxid`, the wxid itself when unknown.
+    pub fn display_name(&self, wxid: &str) -> Result<String> {
+        Ok(self.display_names()?.get(wxid).cloned().unwrap_or_else(|| wxid.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::error::WxDumpError;
+    use crate::core::testutil::{
+        merged_db_with_sample_data, write_encrypted_copy, write_sample_account, MEDIA_MSG_SCHEMA, MSG_SCHEMA, OPENIM_CONTACT_SCHEMA, TEST_KEY,
+    };
+
+    #[test]
+    fn test_from_file_name() {
+        assert_eq!(DbKind::from_file_name("MicroMsg.db"), Some((DbKind::MicroMsg, 0)));
+        assert_eq!(DbKind::from_file_name("de_MSG3.db"), Some((DbKind::Msg, 3)));
+        assert_eq!(DbKind::from_file_name("MediaMSG12.db"), Some((DbKind::MediaMsg, 12)));
+        assert_eq!(DbKind::from_file_name("FTSMSG.db"), Some((DbKind::FtsMsg, 0)));
+        assert_eq!(DbKind::from_file_name("OpenIMContact.db"), Some((DbKind::OpenImContact, 0)));
+        assert_eq!(DbKind::from_file_name("MSG.db"), None);
+        assert_eq!(DbKind::from_file_name("MSG0.db-wal"), None);
+        assert_eq!(DbKind::from_file_name("FTSContact.db"), None);
+    }
+
+    #[test]
+    fn test_account() {
+        let root = std::env::temp_dir().join(format!("wxdump_rs_account_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        std::fs::create_dir_all(root.join("Multi")).unwrap();
+
+        // MicroMsg.db also carries MSG/Media here; only its MicroMsg tables are read.
+        let micro = merged_db_with_sample_data();
+        micro.execute("VACUUM INTO ?", [root.join("MicroMsg.db").to_string_lossy()]).unwrap();
+        for (shard, svr_id, time) in [(0, 1, 1_700_000_300), (1, 2, 1_700_000_200)] {
+            let conn = Connection::open(root.join(format!("Multi/MSG{}.db", shard))).unwrap();
+            conn.execute_batch(MSG_SCHEMA).unwrap();
+            conn.execute(
+                "INSERT INTO MSG (MsgSvrID, Type, SubType, IsSender, CreateTime, StrTalker, StrContent) \
+                 VALUES (?, 34, 0, 0, ?, 'carol@openim', '<msg><voicemsg voicelength=\"1000\" /></msg>')",
+                rusqlite::params![svr_id, time],
+            )
+            .unwrap();
+        }
+        let media = Connection::open(root.join("Multi/MediaMSG0.db")).unwrap();
+        media.execute_batch(MEDIA_MSG_SCHEMA).unwrap();
+        media.execute("INSERT INTO Media (Key, Reserved0, Buf) VALUES ('k', 2, x'0223')", []).unwrap();
+        let openim = Connection::open(root.join("OpenIMContact.db")).unwrap();
+        openim.execute_batch(OPENIM_CONTACT_SCHEMA).unwrap();
+        openim.execute("INSERT INTO OpenIMContact (UserName, NickName) VALUES ('carol@openim', 'Carol')", []).unwrap();
+        drop((micro, media, openim));
+
+        let account = WeChatAccount::open(&root).unwrap();
+        assert_eq!(account.database_paths(DbKind::Msg).len(), 2);
+        assert!(account.database_paths(DbKind::Sns).is_empty());
+        assert!(account.connection(DbKind::Sns).unwrap().is_none());
+
+        let wxids: Vec<_> = account.contacts().unwrap().into_iter().map(|c| c.wxid).collect();
+        assert_eq!(wxids, vec!["wxid_alice", "wxid_bob", "carol@openim"]);
+        assert_eq!(account.rooms().unwrap().len(), 1);
+        assert_eq!(account.sessions().unwrap().len(), 2);
+        assert_eq!(account.display_name("carol@openim").unwrap(), "Carol");
+        assert_eq!(account.display_name("wxid_unknown").unwrap(), "wxid_unknown");
+
+        let messages = account.messages("carol@openim").unwrap();
+        assert_eq!(messages.iter().map(|m| m.msg_svr_id).collect::<Vec<_>>(), vec![Some(2), Some(1)]);
+        assert!(matches!(account.media(&messages[0]).unwrap(), Some(MessageMedia::Voice(buf)) if buf == [2, 0x23]));
+        assert!(account.media(&messages[1]).unwrap().is_none());
+
+        std::fs::remove_dir_all(&root).unwrap();
+        assert!(WeChatAccount::open(std::env::temp_dir().join("wxdump_rs_account_missing")).is_err());
+    }
+
+    #[test]
+    fn test_encrypted_account() {
+        let wx_path = std::env::temp_dir().join(format!("wxdump_rs_account_enc_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&wx_path);
+        std::fs::create_dir_all(wx_path.join("Msg/Multi")).unwrap();
+
+        let merged = merged_db_with_sample_data();
+        write_encrypted_copy(&merged, &wx_path.join("Msg/MicroMsg.db"), [1; 16]);
+        write_encrypted_copy(&merged, &wx_path.join("Msg/Multi/MSG0.db"), [2; 16]);
+        let openim = Connection::open_in_memory().unwrap();
+        openim.execute_batch(OPENIM_CONTACT_SCHEMA).unwrap();
+        openim.execute("INSERT INTO OpenIMContact (UserName, NickName) VALUES ('carol@openim', 'Carol')", []).unwrap();
+        write_encrypted_copy(&openim, &wx_path.join("Msg/OpenIMContact.db"), [3; 16]);
+        assert_ne!(&std::fs::read(wx_path.join("Msg/MicroMsg.db")).unwrap()[..6], b"SQLite");
+
+        let account = WeChatAccount::open_encrypted(&wx_path, TEST_KEY).unwrap();
+        assert_eq!(account.root(), wx_path.join("Msg"));
+        assert_eq!(account.contacts().unwrap().len(), 3);
+        assert_eq!(account.display_name("carol@openim").unwrap(), "Carol");
+        assert_eq!(account.messages("wxid_alice").unwrap().len(), 3);
+
+        let wrong = WeChatAccount::open_encrypted(&wx_path, &"ab".repeat(32)).unwrap();
+        let err = wrong.contacts().unwrap_err();
+        assert!(matches!(err.downcast_ref::<WxDumpError>(), Some(WxDumpError::KeyInvalid(_))));
+        // Without a key the same files cannot be read.
+        let err = WeChatAccount::open(wx_path.join("Msg")).unwrap().contacts().unwrap_err();
+        assert!(matches!(err.downcast_ref::<WxDumpError>(), Some(WxDumpError::InvalidArgument(m)) if m.contains("key required")));
+
+        // The file-based decryption agrees with SQLCipher on the format.
+        let plain = wx_path.join("plain.db");
+        crate::core::decryption::decrypt_database_file(&wx_path.join("Msg/Multi/MSG0.db"), &plain, TEST_KEY).unwrap();
+        let count: i64 = Connection::open(&plain).unwrap().query_row("SELECT COUNT(*) FROM MSG", [], |r| r.get(0)).unwrap();
+        assert_eq!(count, 6);
+
+        std::fs::remove_dir_all(&wx_path).unwrap();
+    }
+
+    #[test]
+    fn test_sample_account() {
+        let wx_path = std::env::temp_dir().join(format!("wxdump_rs_sample_account_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&wx_path);
+        for encrypted in [false, true] {
+            let msg_dir = write_sample_account(&wx_path, encrypted);
+            let account = if encrypted {
+                WeChatAccount::open_encrypted(&wx_path, TEST_KEY).unwrap()
+            } else {
+                WeChatAccount::open(&msg_dir).unwrap()
+            };
+            assert_eq!(account.contacts().unwrap().len(), 2);
+            assert_eq!(account.display_name("wxid_carol").unwrap(), "Carol (cousin)");
+            let messages = account.messages("wxid_carol").unwrap();
+            let voice = messages.iter().find(|m| m.msg_svr_id == Some(3004)).unwrap();
+            assert!(matches!(account.media(voice).unwrap(), Some(MessageMedia::Voice(buf)) if buf.starts_with(&[2, 0x23])));
+        }
+        std::fs::remove_dir_all(&wx_path).unwrap();
+    }
+}
@@ -0,0 +1,290 @@
+// src/core/analytics/graph.rs
+
+use anyhow::Result;
+use rusqlite::Connection;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use crate::core::db_parser::{
+    count_messages, for_each_message, get_chat_rooms, get_contacts, get_display_names, is_chat_room_wxid, list_talkers,
+    message_sender_wxid, Contact, MSG_TYPE_SYSMSG, MSG_TYPE_SYSTEM,
+};
+
+#[derive(Debug, Clone)]
+pub struct GraphOptions {
+    /// wxid of the account owner: the other end of the direct message edges.
+    pub my_wxid: String,
+    /// Only contacts that are friends and not deleted (`DelFlag` 0); the account owner stays.
+    pub friends_only: bool,
+    /// Leaves out official accounts (`gh_` wxids or a `VerifyFlag`).
+    pub exclude_official: bool,
+    /// Rooms with more participants than this get no pairwise `shared_rooms` edges, which
+    /// grow with the square of the room size.
+    pub max_room_size: usize,
+    /// Inclusive unix timestamps of the counted messages.
+    pub start_time: Option<i64>,
+    pub end_time: Option<i64>,
+}
+
+impl Default for GraphOptions {
+    fn default() -> Self {
+        GraphOptions {
+            my_wxid: String::new(),
+            friends_only: false,
+            exclude_official: false,
+            max_room_size: 100,
+            start_time: None,
+            end_time: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum NodeKind {
+    Contact,
+    Room,
+}
+
+impl NodeKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            NodeKind::Contact => "contact",
+            NodeKind::Room => "room",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct GraphNode {
+    /// The wxid or room id.
+    pub id: String,
+    pub label: String,
+    pub kind: NodeKind,
+    pub is_friend: bool,
+    pub deleted: bool,
+    pub official: bool,
+    /// Messages of the direct conversation with the account owner; all messages for rooms.
+    pub messages: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EdgeKind {
+    /// Contact to room: 1, plus the messages the contact sent in the room.
+    Member,
+    /// Contact to contact: the number of rooms both take part in.
+    SharedRooms,
+    /// Account owner to contact: the messages of their direct conversation.
+    Messages,
+}
+
+impl EdgeKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            EdgeKind::Member => "member",
+            EdgeKind::SharedRooms => "shared_rooms",
+            EdgeKind::Messages => "messages",
+        }
+    }
+}
+
+/// An undirected edge; `source` sorts before `target` except for `member` (contact, room)
+/// and `messages` (account owner, contact).
+#[derive(Debug, Clone, Serialize)]
+pub struct GraphEdge {
+    pub source: String,
+    pub target: String,
+    pub kind: EdgeKind,
+    pub weight: usize,
+}
+
+/// Contacts and rooms of an account with their relationships.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ContactGraph {
+    pub nodes: Vec<GraphNode>,
+    pub edges: Vec<GraphEdge>,
+}
+
+/// Official accounts: `gh_` wxids, or a VerifyFlag (set for verified public and service accounts).
+pub fn is_official(contact: &Contact) -> bool {
+    contact.wxid.starts_with("gh_") || contact.verify_flag.unwrap_or(0) != 0
+}
+
+/// Friends have the lowest bit of Contact.Type set (3); room members met only in chat rooms
+/// have 4 and the account owner 0.
+pub fn is_friend(contact: &Contact) -> bool {
+    contact.user_type.unwrap_or(0) & 1 != 0 && contact.del_flag.unwrap_or(0) == 0
+}
+
+/// Builds the graph from the MicroMsg tables of `contact_conn` (contacts, chat rooms and
+/// their members) and the MSG table of `msg_conn` (message counts).
+///
+/// The participants of a room are its current members plus everyone who sent a message
+/// there, so people who left still share the room with the others.
+pub fn contact_graph(msg_conn: &Connection, contact_conn: &Connection, options: &GraphOptions) -> Result<ContactGraph> {
+    let names = get_display_names(contact_conn)?;
+    let keep_wxid = |wxid: &str| !(options.exclude_official && wxid.starts_with("gh_"));
+
+    let mut nodes: BTreeMap<String, GraphNode> = BTreeMap::new();
+    for contact in get_contacts(contact_conn, None, None, None)? {
+        if contact.is_chatroom_contact {
+            continue;
+        }
+        let is_me = contact.wxid == options.my_wxid;
+        if !is_me && ((options.friends_only && !is_friend(&contact)) || (options.exclude_official && is_official(&contact))) {
+            continue;
+        }
+        let label = names.get(&contact.wxid).cloned().unwrap_or_else(|| contact.wxid.clone());
+        nodes.insert(
+            contact.wxid.clone(),
+            GraphNode {
+                id: contact.wxid.clone(),
+                label,
+                kind: NodeKind::Contact,
+                is_friend: is_friend(&contact),
+                deleted: contact.del_flag.unwrap_or(0) != 0,
+                official: is_official(&contact),
+                messages: 0,
+            },
+        );
+    }
+    // Room members and senders missing from Contact are strangers: kept unless only friends are wanted.
+    let add_stranger = |nodes: &mut BTreeMap<String, GraphNode>, wxid: &str| {
+        let is_me = wxid == options.my_wxid;
+        if nodes.contains_key(wxid) || wxid.is_empty() || (!is_me && (options.friends_only || !keep_wxid(wxid))) {
+            return nodes.contains_key(wxid);
+        }
+        nodes.insert(
+            wxid.to_string(),
+            GraphNode {
+                id: wxid.to_string(),
+                label: names.get(wxid).cloned().unwrap_or_else(|| wxid.to_string()),
+                kind: NodeKind::Contact,
+                is_friend: false,
+                deleted: false,
+                official: wxid.starts_with("gh_"),
+                messages: 0,
+            },
+        );
+        true
+    };
+    if !options.my_wxid.is_empty() {
+        add_stranger(&mut nodes, &options.my_wxid);
+    }
+
+    let talkers: HashSet<String> = list_talkers(msg_conn)?.into_iter().collect();
+    let mut edges: Vec<GraphEdge> = Vec::new();
+
+    let rooms = get_chat_rooms(contact_conn, None)?;
+    let mut room_ids: Vec<&String> = rooms.keys().collect();
+    room_ids.sort();
+    let mut shared: HashMap<(String, String), usize> = HashMap::new();
+    for room_id in room_ids {
+        let room = &rooms[room_id];
+        let mut sent: BTreeMap<String, usize> = room.member_wxids.iter().map(|w| (w.clone(), 0)).collect();
+        let mut total = 0;
+        if talkers.contains(room_id) {
+            for_each_message(msg_conn, Some(room_id), None, options.start_time, options.end_time, None, None, |msg| {
+                total += 1;
+                if !matches!(msg.msg_type, MSG_TYPE_SYSTEM | MSG_TYPE_SYSMSG) {
+                    *sent.entry(message_sender_wxid(&msg, &options.my_wxid)).or_default() += 1;
+                }
+                Ok(())
+            })?;
+        }
+        nodes.insert(
+            room_id.clone(),
+            GraphNode {
+                id: room_id.clone(),
+                label: names.get(room_id).cloned().unwrap_or_else(|| room_id.clone()),
+                kind: NodeKind::Room,
+                is_friend: false,
+                deleted: false,
+                official: false,
+                messages: total,
+            },
+        );
+
+        let mut participants = Vec::new();
+        for (wxid, messages) in sent {
+            if is_chat_room_wxid(&wxid) || !add_stranger(&mut nodes, &wxid) {
+                continue;
+            }
+            edges.push(GraphEdge { source: wxid.clone(), target: room_id.clone(), kind: EdgeKind::Member, weight: 1 + messages });
+            participants.push(wxid);
+        }
+        if participants.len() > options.max_room_size {
+            continue;
+        }
+        for (i, a) in participants.iter().enumerate() {
+            for b in &participants[i + 1..] {
+                *shared.entry((a.clone(), b.clone())).or_default() += 1;
+            }
+        }
+    }
+    let mut shared: Vec<((String, String), usize)> = shared.into_iter().collect();
+    shared.sort();
+    edges.extend(shared.into_iter().map(|((source, target), weight)| GraphEdge { source, target, kind: EdgeKind::SharedRooms, weight }));
+
+    let direct: Vec<String> = nodes
+        .keys()
+        .filter(|wxid| talkers.contains(*wxid) && !is_chat_room_wxid(wxid) && **wxid != options.my_wxid)
+        .cloned()
+        .collect();
+    for wxid in direct {
+        let count = count_messages(msg_conn, Some(&wxid), options.start_time, options.end_time)? as usize;
+        if count == 0 {
+            continue;
+        }
+        if let Some(node) = nodes.get_mut(&wxid) {
+            node.messages = count;
+        }
+        if !options.my_wxid.is_empty() {
+            edges.push(GraphEdge { source: options.my_wxid.clone(), target: wxid, kind: EdgeKind::Messages, weight: count });
+        }
+    }
+
+    Ok(ContactGraph { nodes: nodes.into_values().collect(), edges })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::testutil::{micro_msg_db, msg_db};
+
+    fn edge<'a>(graph: &'a ContactGraph, kind: EdgeKind, source: &str, target: &str) -> Option<&'a GraphEdge> {
+        graph.edges.iter().find(|e| e.kind == kind && e.source == source && e.target == target)
+    }
+
+    #[test]
+    fn test_contact_graph() {
+        let contacts = micro_msg_db();
+        contacts
+            .execute_batch(
+                "INSERT INTO Contact (UserName, Type, NickName, VerifyFlag) VALUES ('gh_news', 3, 'News', 8);
+                 INSERT INTO Contact (UserName, Type, NickName, DelFlag) VALUES ('wxid_gone', 3, 'Gone', 1);",
+            )
+            .unwrap();
+        let options = GraphOptions { my_wxid: "wxid_me".into(), ..Default::default() };
+        let graph = contact_graph(&msg_db(), &contacts, &options).unwrap();
+
+        let ids: Vec<&str> = graph.nodes.iter().map(|n| n.id.as_str()).collect();
+        assert_eq!(ids, ["456@chatroom", "gh_news", "wxid_carol", "wxid_dave", "wxid_gone", "wxid_me"]);
+        let room = &graph.nodes[0];
+        assert_eq!((room.kind, room.label.as_str(), room.messages), (NodeKind::Room, "周末爬山", 3));
+        assert!(graph.nodes[1].official && graph.nodes[4].deleted && !graph.nodes[4].is_friend);
+
+        assert_eq!(edge(&graph, EdgeKind::Member, "wxid_carol", "456@chatroom").unwrap().weight, 2);
+        assert_eq!(edge(&graph, EdgeKind::Member, "wxid_me", "456@chatroom").unwrap().weight, 2);
+        assert_eq!(edge(&graph, EdgeKind::SharedRooms, "wxid_carol", "wxid_dave").unwrap().weight, 1);
+        assert_eq!(edge(&graph, EdgeKind::Messages, "wxid_me", "wxid_carol").unwrap().weight, 4);
+        assert_eq!(graph.edges.len(), 3 + 3 + 1);
+
+        let options = GraphOptions { friends_only: true, exclude_official: true, max_room_size: 2, ..options };
+        let graph = contact_graph(&msg_db(), &contacts, &options).unwrap();
+        let ids: Vec<&str> = graph.nodes.iter().map(|n| n.id.as_str()).collect();
+        assert_eq!(ids, ["456@chatroom", "wxid_carol", "wxid_dave", "wxid_me"]);
+        assert!(graph.edges.iter().all(|e| e.kind != EdgeKind::SharedRooms));
+    }
+}
@@ -0,0 +1,470 @@
+// src/core/analytics/mod.rs
+
+pub mod graph;
+pub mod room;
+
+use anyhow::{Result, anyhow};
+use chrono::{DateTime, Datelike, FixedOffset, NaiveDate, NaiveDateTime, NaiveTime, Timelike};
+use jieba_rs::Jieba;
+use rusqlite::Connection;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::OnceLock;
+
+use crate::core::db_parser::{
+    for_each_message, is_chat_room_wxid, message_sender_wxid, Message, APP_MSG_TYPE_QUOTE, MSG_TYPE_APP,
+    MSG_TYPE_EMOJI, MSG_TYPE_IMAGE, MSG_TYPE_LOCATION, MSG_TYPE_SYSMSG, MSG_TYPE_SYSTEM, MSG_TYPE_TEXT,
+    MSG_TYPE_VIDEO, MSG_TYPE_VOICE,
+};
+use crate::core::export::Participants;
+
+/// A reply more than this long after the previous message starts a new conversation and does
+/// not count towards the response time.
+const RESPONSE_WINDOW_SECS: i64 = 6 * 3600;
+
+/// Frequent words that say nothing about a conversation. Single characters are dropped anyway.
+const STOP_WORDS: &[&str] = &[
+    "一个", "一下", "不是", "什么", "今天", "他们", "你们", "可以", "我们", "所以", "还是", "这个", "这样", "那个",
+    "然后", "没有", "现在", "知道", "自己", "就是", "已经", "怎么", "因为", "但是", "如果", "时候", "is", "it", "to",
+    "of", "in", "on", "at", "be", "do", "the", "and", "you", "for", "that", "this", "are", "was", "with", "have", "not",
+];
+
+#[derive(Debug, Clone)]
+pub struct StatsOptions {
+    /// wxid of the account owner: the sender of messages with IsSender set.
+    pub my_wxid: String,
+    /// Inclusive unix timestamps.
+    pub start_time: Option<i64>,
+    pub end_time: Option<i64>,
+    /// Time zone of hours, weekdays, months and dates in minutes east of UTC (480 for China);
+    /// stored times are UTC.
+    pub utc_offset_minutes: i32,
+    /// Number of words in `top_words`.
+    pub top_words: usize,
+}
+
+/// Offset of the local time zone in minutes, the default of `--utc-offset`.
+pub fn local_utc_offset_minutes() -> i32 {
+    chrono::Local::now().offset().local_minus_utc() / 60
+}
+
+/// Parses a `--utc-offset` of hours (`8`, `-3`) or hours and minutes (`+05:30`) into minutes.
+pub fn parse_utc_offset(value: &str) -> Result<i32, String> {
+    let value = value.trim();
+    let (sign, rest) = match value.strip_prefix('-') {
+        Some(rest) => (-1, rest),
+        None => (1, value.strip_prefix('+').unwrap_or(value)),
+    };
+    let (hours, minutes) = rest.split_once(':').unwrap_or((rest, "0"));
+    let invalid = || format!("invalid UTC offset {:?} (expected hours like 8 or -3, or +05:30)", value);
+    let hours: i32 = hours.parse().map_err(|_| invalid())?;
+    let minutes: i32 = minutes.parse().map_err(|_| invalid())?;
+    if !(0..=14).contains(&hours) || !(0..60).contains(&minutes) {
+        return Err(invalid());
+    }
+    Ok(sign * (hours * 60 + minutes))
+}
+
+/// Parses a `--start`/`--end`: a unix timestamp, `YYYY-MM-DD` or `YYYY-MM-DD HH:MM:SS` in the
+/// time zone `utc_offset_minutes` east of UTC. A bare date used as `end` covers the whole day.
+pub fn parse_time_arg(value: &str, end_of_range: bool, utc_offset_minutes: i32) -> Result<i64> {
+    let value = value.trim();
+    if let Ok(ts) = value.parse::<i64>() {
+        return Ok(ts);
+    }
+    let local = match NaiveDateTime::parse_from_str(value, "%Y-%m-%d %H:%M:%S") {
+        Ok(dt) => dt,
+        Err(_) => {
+            let date = NaiveDate::parse_from_str(value, "%Y-%m-%d").map_err(|_| {
+                anyhow!("Invalid time {:?}, expected YYYY-MM-DD, \"YYYY-MM-DD HH:MM:SS\" or a unix timestamp", value)
+            })?;
+            let time = if end_of_range { NaiveTime::from_hms_opt(23, 59, 59) } else { NaiveTime::from_hms_opt(0, 0, 0) };
+            date.and_time(time.expect("valid time of day"))
+        }
+    };
+    Ok(local.and_utc().timestamp() - i64::from(utc_offset_minutes) * 60)
+}
+
+impl Default for StatsOptions {
+    fn default() -> Self {
+        StatsOptions { my_wxid: String::new(), start_time: None, end_time: None, utc_offset_minutes: 0, top_words: 50 }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SenderCount {
+    pub wxid: String,
+    pub name: String,
+    pub messages: usize,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct LabelCount {
+    pub label: String,
+    pub count: usize,
+}
+
+/// Consecutive days with at least one message.
+#[derive(Debug, Clone, Serialize)]
+pub struct Streak {
+    pub days: usize,
+    pub start: String,
+    pub end: String,
+}
+
+/// How quickly `wxid` answers a message from someone else.
+#[derive(Debug, Clone, Serialize)]
+pub struct ResponseTime {
+    pub wxid: String,
+    pub name: String,
+    pub replies: usize,
+    pub average_seconds: i64,
+}
+
+/// Statistics of one conversation (a contact or a chat room).
+///
+/// System messages (join notices, recalls, ...) only count in `total_messages` and `by_kind`.
+#[derive(Debug, Clone, Serialize)]
+pub struct ConversationStats {
+    pub talker: String,
+    pub name: String,
+    pub is_chat_room: bool,
+    pub total_messages: usize,
+    /// Dates (`YYYY-MM-DD`) of the first and last message.
+    pub first_date: String,
+    pub last_date: String,
+    pub active_days: usize,
+    pub longest_streak: Streak,
+    /// Most active first.
+    pub by_sender: Vec<SenderCount>,
+    /// Kinds as named by [`message_kind`], most frequent first.
+    pub by_kind: Vec<LabelCount>,
+    /// Index 0 is 00:00-00:59.
+    pub by_hour: [usize; 24],
+    /// Index 0 is Monday.
+    pub by_weekday: [usize; 7],
+    /// `YYYY-MM` labels in order, months without messages included.
+    pub by_month: Vec<LabelCount>,
+    pub response_times: Vec<ResponseTime>,
+    /// Words of text messages, most frequent first.
+    pub top_words: Vec<LabelCount>,
+}
+
+/// Short name of the kind of a message for statistics: text, image, voice, video, sticker,
+/// location, link, file, quote, system or other.
+pub fn message_kind(msg: &Message) -> &'static str {
+    match msg.msg_type {
+        MSG_TYPE_TEXT => "text",
+        MSG_TYPE_IMAGE => "image",
+        MSG_TYPE_VOICE => "voice",
+        MSG_TYPE_VIDEO => "video",
+        MSG_TYPE_EMOJI => "sticker",
+        MSG_TYPE_LOCATION => "location",
+        MSG_TYPE_APP => match msg.sub_type {
+            APP_MSG_TYPE_QUOTE => "quote",
+            6 => "file",
+            _ => "link",
+        },
+        MSG_TYPE_SYSTEM | MSG_TYPE_SYSMSG => "system",
+        _ => "other",
+    }
+}
+
+fn jieba() -> &'static Jieba {
+    static JIEBA: OnceLock<Jieba> = OnceLock::new();
+    JIEBA.get_or_init(Jieba::new)
+}
+
+/// Words of `text` counted by `top_words`: jieba segments, at least two characters long and
+/// containing a letter (so no numbers, punctuation or emoji), minus [`STOP_WORDS`].
+pub fn words(text: &str) -> Vec<String> {
+    jieba()
+        .cut(text, true)
+        .into_iter()
+        .map(|w| w.trim().to_lowercase())
+        .filter(|w| w.chars().count() >= 2 && w.chars().any(char::is_alphabetic) && !STOP_WORDS.contains(&w.as_str()))
+        .collect()
+}
+
+/// Accumulates the statistics of one conversation from its messages in time order.
+struct StatsBuilder<'a> {
+    options: &'a StatsOptions,
+    offset: FixedOffset,
+    total: usize,
+    first_time: Option<i64>,
+    last_time: Option<i64>,
+    days: Vec<NaiveDate>,
+    senders: HashMap<String, usize>,
+    kinds: HashMap<&'static str, usize>,
+    by_hour: [usize; 24],
+    by_weekday: [usize; 7],
+    by_month: BTreeMap<(i32, u32), usize>,
+    // sender -> (replies, total seconds)
+    responses: HashMap<String, (usize, i64)>,
+    previous: Option<(String, i64)>,
+    words: HashMap<String, usize>,
+}
+
+impl<'a> StatsBuilder<'a> {
+    fn new(options: &'a StatsOptions) -> Result<Self> {
+        let offset = FixedOffset::east_opt(options.utc_offset_minutes * 60)
+            .ok_or_else(|| anyhow!("Invalid UTC offset {} minutes", options.utc_offset_minutes))?;
+        Ok(StatsBuilder {
+            options,
+            offset,
+            total: 0,
+            first_time: None,
+            last_time: None,
+            days: Vec::new(),
+            senders: HashMap::new(),
+            kinds: HashMap::new(),
+            by_hour: [0; 24],
+            by_weekday: [0; 7],
+            by_month: BTreeMap::new(),
+            responses: HashMap::new(),
+            previous: None,
+            words: HashMap::new(),
+        })
+    }
+
+    fn add(&mut self, msg: &Message) {
+        let Some(time) = DateTime::from_timestamp(msg.create_time, 0).map(|t| t.with_timezone(&self.offset)) else {
+            return;
+        };
+        self.total += 1;
+        self.first_time.get_or_insert(msg.create_time);
+        self.last_time = Some(msg.create_time);
+        if self.days.last() != Some(&time.date_naive()) {
+            self.days.push(time.date_naive());
+        }
+        *self.kinds.entry(message_kind(msg)).or_default() += 1;
+        self.by_hour[time.hour() as usize] += 1;
+        self.by_weekday[time.weekday().num_days_from_monday() as usize] += 1;
+        *self.by_month.entry((time.year(), time.month())).or_default() += 1;
+
+        if matches!(msg.msg_type, MSG_TYPE_SYSTEM | MSG_TYPE_SYSMSG) {
+            return;
+        }
+        let sender = message_sender_wxid(msg, &self.options.my_wxid);
+        *self.senders.entry(sender.clone()).or_default() += 1;
+        if let Some((previous_sender, previous_time)) = &self.previous {
+            let gap = msg.create_time - previous_time;
+            if *previous_sender != sender && (0..=RESPONSE_WINDOW_SECS).contains(&gap) {
+                let entry = self.responses.entry(sender.clone()).or_default();
+                entry.0 += 1;
+                entry.1 += gap;
+            }
+        }
+        self.previous = Some((sender, msg.create_time));
+
+        if msg.msg_type == MSG_TYPE_TEXT {
+            for word in words(msg.content.as_deref().unwrap_or("")) {
+                *self.words.entry(word).or_default() += 1;
+            }
+        }
+    }
+
+    fn finish(self, talker: &str, participants: &Participants) -> Option<ConversationStats> {
+        let (first_time, last_time) = (self.first_time?, self.last_time?);
+        let date = |ts: i64| {
+            DateTime::from_timestamp(ts, 0)
+                .map(|t| t.with_timezone(&self.offset).format("%Y-%m-%d").to_string())
+                .unwrap_or_default()
+        };
+
+        let mut by_sender: Vec<SenderCount> = self
+            .senders
+            .into_iter()
+            .map(|(wxid, messages)| SenderCount { name: participants.name(&wxid), wxid, messages })
+            .collect();
+        by_sender.sort_by(|a, b| b.messages.cmp(&a.messages).then_with(|| a.wxid.cmp(&b.wxid)));
+
+        let mut response_times: Vec<ResponseTime> = self
+            .responses
+            .into_iter()
+            .map(|(wxid, (replies, total))| ResponseTime {
+                name: participants.name(&wxid),
+                wxid,
+                replies,
+                average_seconds: total / replies as i64,
+            })
+            .collect();
+        response_times.sort_by(|a, b| b.replies.cmp(&a.replies).then_with(|| a.wxid.cmp(&b.wxid)));
+
+        Some(ConversationStats {
+            talker: talker.to_string(),
+            name: participants.name(talker),
+            is_chat_room: is_chat_room_wxid(talker),
+            total_messages: self.total,
+            first_date: date(first_time),
+            last_date: date(last_time),
+            active_days: self.days.len(),
+            longest_streak: longest_streak(&self.days),
+            by_sender,
+            by_kind: sorted_counts(self.kinds.into_iter().map(|(k, v)| (k.to_string(), v)), usize::MAX),
+            by_hour: self.by_hour,
+            by_weekday: self.by_weekday,
+            by_month: months(&self.by_month),
+            response_times,
+            top_words: sorted_counts(self.words, self.options.top_words),
+        })
+    }
+}
+
+/// Most frequent first (ties by label), at most `limit`.
+fn sorted_counts(counts: impl IntoIterator<Item = (String, usize)>, limit: usize) -> Vec<LabelCount> {
+    let mut counts: Vec<LabelCount> = counts.into_iter().map(|(label, count)| LabelCount { label, count }).collect();
+    counts.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.label.cmp(&b.label)));
+    counts.truncate(limit);
+    counts
+}
+
+/// Every month from the first to the last one in `counts`, zero when missing.
+fn months(counts: &BTreeMap<(i32, u32), usize>) -> Vec<LabelCount> {
+    let (Some(&first), Some(&last)) = (counts.keys().next(), counts.keys().next_back()) else { return Vec::new() };
+    let mut months = Vec::new();
+    let (mut year, mut month) = first;
+    while (year, month) <= last {
+        let count = counts.get(&(year, month)).copied().unwrap_or(0);
+        months.push(LabelCount { label: format!("{:04}-{:02}", year, month), count });
+        (year, month) = if month == 12 { (year + 1, 1) } else { (year, month + 1) };
+    }
+    months
+}
+
+/// Longest run of consecutive dates in `days` (sorted, distinct); the earliest one on ties.
+fn longest_streak(days: &[NaiveDate]) -> Streak {
+    let mut best: Option<(usize, usize)> = None;
+    let mut start = 0;
+    for i in 0..days.len() {
+        if i > 0 && days[i - 1].succ_opt() != Some(days[i]) {
+            start = i;
+        }
+        if best.is_none_or(|(s, e)| i - start > e - s) {
+            best = Some((start, i));
+        }
+    }
+    match best {
+        Some((s, e)) => Streak { days: e - s + 1, start: days[s].to_string(), end: days[e].to_string() },
+        None => Streak { days: 0, start: String::new(), end: String::new() },
+    }
+}
+
+/// Statistics of the conversation with `talker`, None when it has no messages in the range.
+///
+/// `msg_conn` holds MSG and `contact_conn` the MicroMsg tables (the same connection for a merged db).
+pub fn conversation_stats(
+    msg_conn: &Connection,
+    contact_conn: &Connection,
+    talker: &str,
+    options: &StatsOptions,
+) -> Result<Option<ConversationStats>> {
+    let mut builder = StatsBuilder::new(options)?;
+    for_each_message(msg_conn, Some(talker), None, options.start_time, options.end_time, None, None, |msg| {
+        builder.add(&msg);
+        Ok(())
+    })?;
+    let senders: Vec<String> = builder.senders.keys().cloned().collect();
+    let participants = Participants::load(contact_conn, talker, &senders)?;
+    Ok(builder.finish(talker, &participants))
+}
+
+/// [`conversation_stats`] of every talker with messages, in the order given.
+pub fn collect_stats(
+    msg_conn: &Connection,
+    contact_conn: &Connection,
+    talkers: &[String],
+    options: &StatsOptions,
+) -> Result<Vec<ConversationStats>> {
+    let mut stats = Vec::new();
+    for talker in talkers {
+        if let Some(s) = conversation_stats(msg_conn, contact_conn, talker, options)? {
+            stats.push(s);
+        }
+    }
+    Ok(stats)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::testutil::{micro_msg_db, msg_db};
+
+    #[test]
+    fn test_conversation_stats() {
+        let (msgs, contacts) = (msg_db(), micro_msg_db());
+        let options = StatsOptions { my_wxid: "wxid_me".into(), utc_offset_minutes: 480, ..Default::default() };
+        let room = conversation_stats(&msgs, &contacts, "456@chatroom", &options).unwrap().unwrap();
+        assert_eq!((room.name.as_str(), room.is_chat_room, room.total_messages), ("周末爬山", true, 3));
+        let senders: Vec<_> = room.by_sender.iter().map(|s| (s.name.as_str(), s.messages)).collect();
+        assert_eq!(senders, vec![("Carol 🏔", 1), ("Dave", 1), ("领队", 1)]);
+        assert_eq!(room.by_kind.iter().map(|k| k.label.as_str()).collect::<Vec<_>>(), vec!["text", "quote"]);
+        // 1700100240 is 2023-11-16 10:04 in UTC+8, a Thursday.
+        assert_eq!((room.by_hour[10], room.by_weekday[3]), (3, 3));
+        assert_eq!(room.first_date, "2023-11-16");
+        assert_eq!(room.response_times.iter().map(|r| (r.wxid.as_str(), r.average_seconds)).collect::<Vec<_>>(),
+            vec![("wxid_carol", 60), ("wxid_me", 60)]);
+        assert!(room.top_words.iter().any(|w| w.label == "集合"));
+
+        let carol = conversation_stats(&msgs, &contacts, "wxid_carol", &options).unwrap().unwrap();
+        assert_eq!(carol.name, "Carol (cousin)");
+        assert_eq!(carol.by_month.iter().map(|m| (m.label.as_str(), m.count)).collect::<Vec<_>>(), vec![("2023-11", 4)]);
+        let none = StatsOptions { start_time: Some(1800000000), ..options };
+        assert!(conversation_stats(&msgs, &contacts, "wxid_carol", &none).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_streaks_and_months() {
+        let day = |d: u32| NaiveDate::from_ymd_opt(2024, 1, d).unwrap();
+        let streak = longest_streak(&[day(1), day(3), day(4), day(5), day(7), day(8)]);
+        assert_eq!((streak.days, streak.start.as_str(), streak.end.as_str()), (3, "2024-01-03", "2024-01-05"));
+        assert_eq!(longest_streak(&[day(9)]).days, 1);
+        assert_eq!(longest_streak(&[]).days, 0);
+
+        let counts = BTreeMap::from([((2023, 11), 2), ((2024, 2), 1)]);
+        let labels: Vec<_> = months(&counts).into_iter().map(|m| format!("{}:{}", m.label, m.count)).collect();
+        assert_eq!(labels, vec!["2023-11:2", "2023-12:0", "2024-01:0", "2024-02:1"]);
+        assert_eq!(words("我们明天去爬山吧, hiking is fun!"), vec!["明天", "爬山", "hiking", "fun"]);
+    }
+
+    #[test]
+    fn test_parse_utc_offset() {
+        assert_eq!(parse_utc_offset("8"), Ok(480));
+        assert_eq!(parse_utc_offset("+05:30"), Ok(330));
+        assert_eq!(parse_utc_offset("-3:30"), Ok(-210));
+        assert_eq!(parse_utc_offset("-0"), Ok(0));
+        assert!(parse_utc_offset("5.5").is_err());
+        assert!(parse_utc_offset("+05:60").is_err());
+        assert!(parse_utc_offset("15").is_err());
+    }
+
+    #[test]
+    fn test_date_range_matches_local_days() {
+        assert_eq!(parse_time_arg("1700100000", false, 480).unwrap(), 1700100000);
+        assert_eq!(parse_time_arg("2023-11-16 10:00:00", false, 480).unwrap(), 1700100000);
+        assert_eq!(parse_time_arg("2023-11-16 02:00:00", false, 0).unwrap(), 1700100000);
+        assert!(parse_time_arg("16/11/2023", false, 0).is_err());
+
+        // Midnight at both ends of 2023-11-16 in UTC+8.
+        let (msgs, contacts) = (msg_db(), micro_msg_db());
+        for (svr_id, time) in [(3101, 1700064000), (3102, 1700150400)] {
+            msgs.execute(
+                "INSERT INTO MSG (MsgSvrID, Type, SubType, IsSender, CreateTime, StrTalker, StrContent)
+                 VALUES (?, 1, 0, 0, ?, 'wxid_carol', 'hi')",
+                rusqlite::params![svr_id, time],
+            )
+            .unwrap();
+        }
+        let options = StatsOptions {
+            my_wxid: "wxid_me".into(),
+            start_time: Some(parse_time_arg("2023-11-16", false, 480).unwrap()),
+            end_time: Some(parse_time_arg("2023-11-16", true, 480).unwrap()),
+            utc_offset_minutes: 480,
+            ..Default::default()
+        };
+        let carol = conversation_stats(&msgs, &contacts, "wxid_carol", &options).unwrap().unwrap();
+        assert_eq!(carol.total_messages, 5);
+        assert_eq!((carol.first_date.as_str(), carol.last_date.as_str(), carol.active_days), ("2023-11-16", "2023-11-16", 1));
+        assert_eq!(carol.by_hour[0], 1);
+    }
+}
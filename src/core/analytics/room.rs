@@ -0,0 +1,384 @@
+// src/core/analytics/room.rs
+
+use anyhow::{Result, anyhow};
+use chrono::{DateTime, FixedOffset};
+use rusqlite::Connection;
+use serde::Serialize;
+use std::collections::HashMap;
+
+use super::StatsOptions;
+use crate::core::db_parser::{for_each_message, get_chat_rooms, message_sender_wxid, Message, MSG_TYPE_SYSMSG, MSG_TYPE_SYSTEM};
+use crate::core::export::Participants;
+
+/// What a room system message records.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RoomEventKind {
+    Joined,
+    Left,
+    Renamed,
+}
+
+impl RoomEventKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RoomEventKind::Joined => "joined",
+            RoomEventKind::Left => "left",
+            RoomEventKind::Renamed => "renamed",
+        }
+    }
+}
+
+/// Someone named in a system message. Plain-text notices only carry display names, so `wxid`
+/// is None when the name matches no current member.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct EventMember {
+    pub wxid: Option<String>,
+    pub name: String,
+}
+
+/// A join, leave or rename of the room, in time order.
+#[derive(Debug, Clone, Serialize)]
+pub struct RoomEvent {
+    pub time: i64,
+    pub time_str: String,
+    pub kind: RoomEventKind,
+    /// Who invited, removed or renamed, when the notice says.
+    pub actor: Option<EventMember>,
+    /// Who joined or left; empty for renames.
+    pub members: Vec<EventMember>,
+    /// The room name after a rename.
+    pub new_name: Option<String>,
+    pub text: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct MemberActivity {
+    pub wxid: String,
+    pub name: String,
+    pub messages: usize,
+    /// Times of the first and last message.
+    pub first_active: Option<String>,
+    pub last_active: Option<String>,
+    /// Time of the latest join or leave notice naming this member.
+    pub joined: Option<String>,
+    pub left: Option<String>,
+    /// In the room's current member list.
+    pub is_member: bool,
+    pub is_owner: bool,
+}
+
+/// Membership and activity of one chat room.
+#[derive(Debug, Clone, Serialize)]
+pub struct RoomReport {
+    pub room: String,
+    pub name: String,
+    pub owner: Option<String>,
+    pub member_count: usize,
+    pub total_messages: usize,
+    /// Current members and everyone who spoke or was named in a notice, most active first.
+    pub members: Vec<MemberActivity>,
+    /// Current members without a single message.
+    pub lurkers: Vec<String>,
+    pub events: Vec<RoomEvent>,
+}
+
+/// Text between ASCII double quotes, in order: `"A"邀请"B"、"C"加入了群聊` gives A, B, C.
+fn quoted(text: &str) -> Vec<String> {
+    text.split('"').skip(1).step_by(2).map(str::to_string).collect()
+}
+
+/// Names of a plain-text notice part, where WeChat writes the account owner as "你"/"You".
+fn names_in(part: &str) -> Vec<String> {
+    let mut names = quoted(part);
+    let trimmed = part.trim_start();
+    if trimmed.starts_with('你') || trimmed.split_whitespace().next() == Some("You") {
+        names.insert(0, "你".to_string());
+    }
+    names
+}
+
+struct ParsedEvent {
+    kind: RoomEventKind,
+    actor: Option<String>,
+    members: Vec<String>,
+    new_name: Option<String>,
+}
+
+/// Parses a plain-text group notice (Chinese or English client).
+fn parse_notice_text(text: &str) -> Option<ParsedEvent> {
+    let event = |kind, actor: Option<String>, members, new_name| Some(ParsedEvent { kind, actor, members, new_name });
+    if let Some((left, right)) = text.split_once("修改群名为") {
+        let name = right.trim().trim_matches(|c| matches!(c, '“' | '”' | '"')).to_string();
+        return event(RoomEventKind::Renamed, names_in(left).into_iter().next(), Vec::new(), Some(name));
+    }
+    if let Some((left, right)) = text.split_once(" changed the group name to ") {
+        let name = right.trim().trim_matches(|c| matches!(c, '“' | '”' | '"')).to_string();
+        return event(RoomEventKind::Renamed, names_in(left).into_iter().next(), Vec::new(), Some(name));
+    }
+    if text.contains("二维码加入群聊") || text.contains("via the QR Code") {
+        // "B"通过扫描"A"分享的二维码加入群聊: B joined, A shared the code.
+        let names = names_in(text);
+        return event(RoomEventKind::Joined, names.get(1).cloned(), names.into_iter().take(1).collect(), None);
+    }
+    if text.contains("加入了群聊") || text.contains("to the group chat") || text.contains("joined the group chat") {
+        let (left, right) = text.split_once("邀请").or_else(|| text.split_once(" invited ")).unwrap_or(("", text));
+        return event(RoomEventKind::Joined, names_in(left).into_iter().next(), names_in(right), None);
+    }
+    if let Some((left, right)) = text.split_once('将').filter(|_| text.contains("移出了群聊")) {
+        return event(RoomEventKind::Left, names_in(left).into_iter().next(), names_in(right), None);
+    }
+    if let Some((left, right)) = text.split_once(" removed ") {
+        return event(RoomEventKind::Left, names_in(left).into_iter().next(), names_in(right), None);
+    }
+    if text.contains("退出了群聊") || text.contains("left the group chat") {
+        return event(RoomEventKind::Left, None, names_in(text), None);
+    }
+    None
+}
+
+/// Parses a `<sysmsg type="sysmsgtemplate">` notice, whose links carry the wxids:
+/// `"$username$"邀请"$names$"加入了群聊`, `"$adder$"通过扫描"$from$"分享的二维码加入群聊`,
+/// `你将"$kickoutname$"移出了群聊`.
+fn parse_notice_template(xml: &str) -> Option<(ParsedEvent, HashMap<String, String>, String)> {
+    let doc = roxmltree::Document::parse(xml.trim()).ok()?;
+    let template = doc.descendants().find(|n| n.has_tag_name("template"))?.text()?.trim().to_string();
+    let mut links: HashMap<String, Vec<(String, String)>> = HashMap::new();
+    for link in doc.descendants().filter(|n| n.has_tag_name("link")) {
+        let Some(name) = link.attribute("name") else { continue };
+        let members = link
+            .descendants()
+            .filter(|n| n.has_tag_name("member"))
+            .filter_map(|m| {
+                let child = |tag: &str| m.children().find(|c| c.has_tag_name(tag)).and_then(|c| c.text()).map(|t| t.trim().to_string());
+                Some((child("username")?, child("nickname").unwrap_or_default()))
+            })
+            .collect();
+        links.insert(name.to_string(), members);
+    }
+
+    // Fill in the template with names to get the text and reuse the plain-text rules on it.
+    let mut text = template.clone();
+    let mut wxids_by_name = HashMap::new();
+    for (link, members) in &links {
+        let names: Vec<&str> = members.iter().map(|(_, nickname)| nickname.as_str()).collect();
+        text = text.replace(&format!("${}$", link), &names.join("\"、\""));
+        for (wxid, nickname) in members {
+            wxids_by_name.insert(nickname.clone(), wxid.clone());
+        }
+    }
+    let parsed = parse_notice_text(&text)?;
+    Some((parsed, wxids_by_name, text))
+}
+
+/// Builds the report of `room` from its messages (within the time range of `options`) and its
+/// current member list. `msg_conn` holds MSG and `contact_conn` the MicroMsg tables.
+pub fn room_report(msg_conn: &Connection, contact_conn: &Connection, room: &str, options: &StatsOptions) -> Result<RoomReport> {
+    let offset = FixedOffset::east_opt(options.utc_offset_minutes * 60)
+        .ok_or_else(|| anyhow!("Invalid UTC offset {} minutes", options.utc_offset_minutes))?;
+    let local_time = |ts: i64| {
+        DateTime::from_timestamp(ts, 0).map(|t| t.with_timezone(&offset).format("%Y-%m-%d %H:%M:%S").to_string()).unwrap_or_default()
+    };
+    let info = get_chat_rooms(contact_conn, Some(&[room.to_string()]))?.remove(room);
+    let current: Vec<String> = info.as_ref().map(|i| i.member_wxids.clone()).unwrap_or_default();
+    let owner = info.as_ref().and_then(|i| i.owner_wxid.clone()).filter(|o| !o.is_empty());
+
+    let mut messages: Vec<Message> = Vec::new();
+    let mut notices: Vec<Message> = Vec::new();
+    for_each_message(msg_conn, Some(room), None, options.start_time, options.end_time, None, None, |msg| {
+        if matches!(msg.msg_type, MSG_TYPE_SYSTEM | MSG_TYPE_SYSMSG) {
+            notices.push(msg);
+        } else {
+            messages.push(msg);
+        }
+        Ok(())
+    })?;
+
+    let mut senders: Vec<String> = messages.iter().map(|m| message_sender_wxid(m, &options.my_wxid)).collect();
+    senders.extend(current.iter().cloned());
+    senders.sort();
+    senders.dedup();
+    let participants = Participants::load(contact_conn, room, &senders)?;
+
+    // Display names of notices back to wxids, from everything we know about the members. A
+    // name used by more than one member maps to None: guessing would credit the wrong person.
+    let mut wxids_by_name: HashMap<String, Option<String>> = HashMap::new();
+    let mut add_name = |name: String, wxid: &String| {
+        let entry = wxids_by_name.entry(name).or_insert_with(|| Some(wxid.clone()));
+        if entry.as_ref() != Some(wxid) {
+            *entry = None;
+        }
+    };
+    for member in info.iter().flat_map(|i| &i.members) {
+        for name in [&member.nickname, &member.remark, &member.room_nickname].into_iter().flatten() {
+            add_name(name.clone(), &member.wxid);
+        }
+    }
+    for wxid in &senders {
+        add_name(participants.name(wxid), wxid);
+    }
+    if !options.my_wxid.is_empty() {
+        wxids_by_name.insert("你".to_string(), Some(options.my_wxid.clone()));
+    }
+
+    let mut events = Vec::new();
+    for notice in &notices {
+        let content = notice.content.as_deref().unwrap_or("").trim();
+        let (parsed, template_wxids, text) = match parse_notice_template(content) {
+            Some(parsed) => parsed,
+            None => match parse_notice_text(content) {
+                Some(parsed) => (parsed, HashMap::new(), content.to_string()),
+                None => continue,
+            },
+        };
+        let member = |name: String| EventMember {
+            wxid: template_wxids.get(&name).cloned().or_else(|| wxids_by_name.get(&name).cloned().flatten()),
+            name,
+        };
+        events.push(RoomEvent {
+            time: notice.create_time,
+            time_str: local_time(notice.create_time),
+            kind: parsed.kind,
+            actor: parsed.actor.map(member),
+            members: parsed.members.into_iter().map(member).collect(),
+            new_name: parsed.new_name,
+            text,
+        });
+    }
+
+    let mut activity: HashMap<String, MemberActivity> = HashMap::new();
+    for wxid in &current {
+        member_entry(&mut activity, wxid, &participants, &current, owner.as_deref());
+    }
+    for msg in &messages {
+        let wxid = message_sender_wxid(msg, &options.my_wxid);
+        let member = member_entry(&mut activity, &wxid, &participants, &current, owner.as_deref());
+        member.messages += 1;
+        member.first_active.get_or_insert_with(|| local_time(msg.create_time));
+        member.last_active = Some(local_time(msg.create_time));
+    }
+    for event in &events {
+        for wxid in event.members.iter().filter_map(|m| m.wxid.as_deref()) {
+            let member = member_entry(&mut activity, wxid, &participants, &current, owner.as_deref());
+            match event.kind {
+                RoomEventKind::Joined => member.joined = Some(event.time_str.clone()),
+                RoomEventKind::Left => member.left = Some(event.time_str.clone()),
+                RoomEventKind::Renamed => {}
+            }
+        }
+    }
+
+    let mut members: Vec<MemberActivity> = activity.into_values().collect();
+    members.sort_by(|a, b| b.messages.cmp(&a.messages).then_with(|| a.wxid.cmp(&b.wxid)));
+    let lurkers = members.iter().filter(|m| m.is_member && m.messages == 0).map(|m| m.wxid.clone()).collect();
+    Ok(RoomReport {
+        room: room.to_string(),
+        name: participants.name(room),
+        owner,
+        member_count: current.len(),
+        total_messages: messages.len(),
+        members,
+        lurkers,
+        events,
+    })
+}
+
+fn member_entry<'a>(
+    activity: &'a mut HashMap<String, MemberActivity>,
+    wxid: &str,
+    participants: &Participants,
+    current: &[String],
+    owner: Option<&str>,
+) -> &'a mut MemberActivity {
+    activity.entry(wxid.to_string()).or_insert_with(|| MemberActivity {
+        wxid: wxid.to_string(),
+        name: participants.name(wxid),
+        messages: 0,
+        first_active: None,
+        last_active: None,
+        joined: None,
+        left: None,
+        is_member: current.iter().any(|w| w == wxid),
+        is_owner: owner == Some(wxid),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::testutil::{micro_msg_db, msg_db};
+
+    fn parse(text: &str) -> (RoomEventKind, Option<String>, Vec<String>, Option<String>) {
+        let e = parse_notice_text(text).unwrap();
+        (e.kind, e.actor, e.members, e.new_name)
+    }
+
+    #[test]
+    fn test_parse_notices() {
+        let s = |v: &[&str]| v.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+        assert_eq!(parse("\"A\"邀请\"B\"、\"C\"加入了群聊"), (RoomEventKind::Joined, Some("A".into()), s(&["B", "C"]), None));
+        assert_eq!(parse("\"A\"邀请你和\"B\"加入了群聊").2, s(&["你", "B"]));
+        assert_eq!(parse("\"B\"通过扫描\"A\"分享的二维码加入群聊").1, Some("A".into()));
+        assert_eq!(parse("你将\"B\"移出了群聊"), (RoomEventKind::Left, Some("你".into()), s(&["B"]), None));
+        assert_eq!(parse("You removed \"B\" from the group chat").1, Some("你".into()));
+        assert_eq!(parse("\"A\"修改群名为“周末爬山”").3.as_deref(), Some("周末爬山"));
+        assert_eq!(parse("\"A\" invited \"B\" to the group chat").2, s(&["B"]));
+        assert!(parse_notice_text("\"A\" 撤回了一条消息").is_none());
+
+        let xml = "<sysmsg type=\"sysmsgtemplate\"><sysmsgtemplate><content_template type=\"tmpl_type_profile\">\
+                   <template><![CDATA[\"$username$\"邀请\"$names$\"加入了群聊]]></template><link_list>\
+                   <link name=\"username\"><memberlist><member><username>wxid_a</username><nickname>A</nickname></member></memberlist></link>\
+                   <link name=\"names\"><memberlist><member><username>wxid_b</username><nickname>B</nickname></member>\
+                   <member><username>wxid_c</username><nickname>C</nickname></member></memberlist></link>\
+                   </link_list></content_template></sysmsgtemplate></sysmsg>";
+        let (event, wxids, text) = parse_notice_template(xml).unwrap();
+        assert_eq!(text, "\"A\"邀请\"B\"、\"C\"加入了群聊");
+        assert_eq!(event.members, s(&["B", "C"]));
+        assert_eq!(wxids["C"], "wxid_c");
+    }
+
+    #[test]
+    fn test_room_report() {
+        let (msgs, contacts) = (msg_db(), micro_msg_db());
+        msgs.execute_batch(
+            "INSERT INTO MSG (MsgSvrID, Type, IsSender, CreateTime, StrTalker, StrContent)
+                 VALUES (5001, 10000, 0, 1700090000, '456@chatroom', '你邀请\"Carol 🏔\"、\"Erin\"加入了群聊');
+             INSERT INTO MSG (MsgSvrID, Type, IsSender, CreateTime, StrTalker, StrContent)
+                 VALUES (5002, 10000, 0, 1700095000, '456@chatroom', '你将\"Erin\"移出了群聊');
+             INSERT INTO MSG (MsgSvrID, Type, IsSender, CreateTime, StrTalker, StrContent)
+                 VALUES (5003, 10000, 0, 1700096000, '456@chatroom', '你修改群名为“周末爬山”');
+             INSERT INTO MSG (MsgSvrID, Type, IsSender, CreateTime, StrTalker, StrContent)
+                 VALUES (5004, 10000, 0, 1700097000, '456@chatroom', '你将\"Dave\"移出了群聊');",
+        )
+        .unwrap();
+        contacts.execute("UPDATE ChatRoom SET UserNameList = UserNameList || char(7) || 'wxid_frank'", []).unwrap();
+        // Frank goes by the same name as wxid_dave.
+        contacts.execute("INSERT INTO Contact (UserName, Type, NickName) VALUES ('wxid_frank', 4, 'Dave')", []).unwrap();
+        let options = StatsOptions { my_wxid: "wxid_me".into(), utc_offset_minutes: 480, ..Default::default() };
+        let report = room_report(&msgs, &contacts, "456@chatroom", &options).unwrap();
+
+        assert_eq!((report.name.as_str(), report.owner.as_deref()), ("周末爬山", Some("wxid_me")));
+        assert_eq!((report.member_count, report.total_messages), (4, 3));
+        assert_eq!(report.lurkers, vec!["wxid_frank"]);
+        let kinds: Vec<_> = report.events.iter().map(|e| e.kind).collect();
+        assert_eq!(kinds, vec![RoomEventKind::Joined, RoomEventKind::Left, RoomEventKind::Renamed, RoomEventKind::Left]);
+        let joined = &report.events[0];
+        assert_eq!(joined.actor.as_ref().unwrap().wxid.as_deref(), Some("wxid_me"));
+        assert_eq!(joined.members[0].wxid.as_deref(), Some("wxid_carol"));
+        assert_eq!(joined.members[1], EventMember { wxid: None, name: "Erin".into() });
+        assert_eq!(report.events[2].new_name.as_deref(), Some("周末爬山"));
+        assert_eq!(report.events[3].members[0], EventMember { wxid: None, name: "Dave".into() });
+
+        let carol = report.members.iter().find(|m| m.wxid == "wxid_carol").unwrap();
+        assert_eq!((carol.messages, carol.is_member, carol.is_owner), (1, true, false));
+        assert_eq!(carol.joined.as_deref(), Some("2023-11-16 07:13:20"));
+        assert_eq!(carol.last_active.as_deref(), Some("2023-11-16 10:05:00"));
+        assert!(report.members.iter().find(|m| m.wxid == "wxid_me").unwrap().is_owner);
+
+        // The invitation at 07:13 on 2023-11-16 in UTC+8 is still 2023-11-15 in UTC.
+        let day = |end| crate::core::analytics::parse_time_arg("2023-11-16", end, 480).unwrap();
+        let options = StatsOptions { start_time: Some(day(false)), end_time: Some(day(true)), ..options };
+        let report = room_report(&msgs, &contacts, "456@chatroom", &options).unwrap();
+        assert_eq!((report.events.len(), report.total_messages), (4, 3));
+    }
+}
@@ -0,0 +1,382 @@
+// src/core/db_browser.rs
+
+use anyhow::{Result, anyhow};
+use rusqlite::types::Value;
+use rusqlite::Connection;
+use serde::Serialize;
+use serde_json::Map;
+use std::collections::HashMap;
+use std::io::Write;
+use std::str::FromStr;
+
+use crate::core::db_parser::{decompress_content, list_tables, parse_bytes_extra, parse_extra_buf};
+use crate::core::export::table::{RowWriter, TableFormat};
+
+/// Cells longer than this are cut in `OutputFormat::Text`.
+const MAX_TEXT_CELL_CHARS: usize = 60;
+
+/// `"name"` with embedded quotes doubled, for splicing a table or column name into SQL.
+pub fn quote_identifier(name: &str) -> String {
+    format!("\"{}\"", name.replace('"', "\"\""))
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TableSummary {
+    pub name: String,
+    pub row_count: i64,
+}
+
+/// `list_tables` with the number of rows of each table.
+pub fn list_tables_with_counts(conn: &Connection) -> Result<Vec<TableSummary>> {
+    list_tables(conn)?
+        .into_iter()
+        .map(|name| {
+            let row_count =
+                conn.query_row(&format!("SELECT count(*) FROM {}", quote_identifier(&name)), [], |row| row.get(0))?;
+            Ok(TableSummary { name, row_count })
+        })
+        .collect()
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ColumnSchema {
+    pub name: String,
+    pub decl_type: String,
+    pub not_null: bool,
+    pub default_value: Option<String>,
+    /// Position in the primary key (1-based), 0 when not part of it.
+    pub primary_key: i64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TableSchema {
+    pub name: String,
+    /// `CREATE TABLE` statement as stored in sqlite_master.
+    pub sql: Option<String>,
+    pub columns: Vec<ColumnSchema>,
+    /// `CREATE INDEX` statements (automatic indexes have none and are left out).
+    pub indexes: Vec<String>,
+}
+
+pub fn table_schema(conn: &Connection, table: &str) -> Result<TableSchema> {
+    ensure_table_exists(conn, table)?;
+    let sql = conn.query_row("SELECT sql FROM sqlite_master WHERE type='table' AND name=?", [table], |row| row.get(0))?;
+
+    let mut stmt = conn.prepare(&format!("PRAGMA table_info({})", quote_identifier(table)))?;
+    let columns = stmt
+        .query_map([], |row| {
+            Ok(ColumnSchema {
+                name: row.get("name")?,
+                decl_type: row.get("type")?,
+                not_null: row.get::<_, i64>("notnull")? != 0,
+                default_value: row.get("dflt_value")?,
+                primary_key: row.get("pk")?,
+            })
+        })?
+        .collect::<std::result::Result<Vec<_>, _>>()?;
+
+    let mut stmt = conn.prepare("SELECT sql FROM sqlite_master WHERE type='index' AND tbl_name=? AND sql IS NOT NULL")?;
+    let indexes = stmt.query_map([table], |row| row.get(0))?.collect::<std::result::Result<Vec<String>, _>>()?;
+
+    Ok(TableSchema { name: table.to_string(), sql, columns, indexes })
+}
+
+/// How a BLOB cell is rendered.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BlobDecoder {
+    #[default]
+    Hex,
+    /// Contact.ExtraBuf (see `parse_extra_buf`).
+    ExtraBuf,
+    /// MSG.BytesExtra (see `parse_bytes_extra`).
+    BytesExtra,
+    /// LZ4 block compressed text such as MSG.CompressContent.
+    Lz4,
+}
+
+impl FromStr for BlobDecoder {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "hex" => Ok(BlobDecoder::Hex),
+            "extrabuf" => Ok(BlobDecoder::ExtraBuf),
+            "bytesextra" => Ok(BlobDecoder::BytesExtra),
+            "lz4" => Ok(BlobDecoder::Lz4),
+            other => Err(format!("unknown decoder {:?} (expected hex, extrabuf, bytesextra or lz4)", other)),
+        }
+    }
+}
+
+impl BlobDecoder {
+    /// Decoded value of `bytes`; falls back to hex when the blob does not decode.
+    pub fn decode(&self, bytes: &[u8]) -> serde_json::Value {
+        let decoded = match self {
+            BlobDecoder::Hex => None,
+            BlobDecoder::ExtraBuf => {
+                parse_extra_buf(Some(bytes)).ok().flatten().and_then(|info| serde_json::to_value(info).ok())
+            }
+            BlobDecoder::BytesExtra => {
+                parse_bytes_extra(Some(bytes)).ok().flatten().and_then(|info| serde_json::to_value(info).ok())
+            }
+            BlobDecoder::Lz4 => decompress_content(bytes).ok().map(serde_json::Value::from),
+        };
+        decoded.unwrap_or_else(|| hex::encode(bytes).into())
+    }
+}
+
+/// Parses a `COLUMN=DECODER` argument, e.g. `ExtraBuf=extrabuf`.
+pub fn parse_decoder_arg(arg: &str) -> Result<(String, BlobDecoder)> {
+    let (column, decoder) =
+        arg.split_once('=').ok_or_else(|| anyhow!("Expected COLUMN=DECODER, got {:?}", arg))?;
+    Ok((column.trim().to_string(), decoder.trim().parse().map_err(|e: String| anyhow!(e))?))
+}
+
+/// SQLite value as JSON; BLOBs become hex strings.
+pub fn sql_value_to_json(value: Value) -> serde_json::Value {
+    match value {
+        Value::Null => serde_json::Value::Null,
+        Value::Integer(i) => i.into(),
+        Value::Real(f) => f.into(),
+        Value::Text(s) => s.into(),
+        Value::Blob(b) => hex::encode(b).into(),
+    }
+}
+
+/// Output of `write_query`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputFormat {
+    /// Aligned columns for the terminal (rows are buffered to size them).
+    Text,
+    Table(TableFormat),
+}
+
+impl clap::ValueEnum for OutputFormat {
+    fn value_variants<'a>() -> &'a [Self] {
+        &[
+            OutputFormat::Text,
+            OutputFormat::Table(TableFormat::Csv),
+            OutputFormat::Table(TableFormat::Jsonl),
+            OutputFormat::Table(TableFormat::Json),
+        ]
+    }
+
+    fn to_possible_value(&self) -> Option<clap::builder::PossibleValue> {
+        match self {
+            OutputFormat::Text => Some(clap::builder::PossibleValue::new("text").alias("table")),
+            OutputFormat::Table(format) => clap::ValueEnum::to_possible_value(format),
+        }
+    }
+}
+
+/// Options of `dump_table_sql`.
+#[derive(Debug, Clone, Default)]
+pub struct DumpOptions {
+    /// All rows when `None`.
+    pub limit: Option<usize>,
+    pub offset: usize,
+    /// Raw SQL condition, e.g. `Type = 1 AND CreateTime > 1700000000`.
+    pub where_clause: Option<String>,
+}
+
+/// `SELECT` statement dumping `table`, which must exist.
+pub fn dump_table_sql(conn: &Connection, table: &str, options: &DumpOptions) -> Result<String> {
+    ensure_table_exists(conn, table)?;
+    let mut sql = format!("SELECT * FROM {}", quote_identifier(table));
+    if let Some(condition) = options.where_clause.as_deref().filter(|c| !c.trim().is_empty()) {
+        sql.push_str(&format!(" WHERE ({})", condition));
+    }
+    match options.limit {
+        Some(limit) => sql.push_str(&format!(" LIMIT {} OFFSET {}", limit, options.offset)),
+        None if options.offset > 0 => sql.push_str(&format!(" LIMIT -1 OFFSET {}", options.offset)),
+        None => {}
+    }
+    Ok(sql)
+}
+
+/// Runs a single read-only statement and writes its rows to `out`. Returns the number of rows.
+///
+/// Statements that could modify the database are refused before they run. BLOB cells go
+/// through the decoder registered for their column in `decoders`, hex otherwise.
+pub fn write_query(
+    conn: &Connection,
+    sql: &str,
+    decoders: &HashMap<String, BlobDecoder>,
+    format: OutputFormat,
+    mut out: impl Write,
+) -> Result<usize> {
+    let mut stmt = conn.prepare(sql)?;
+    if !stmt.readonly() {
+        return Err(anyhow!("Only read-only statements are allowed"));
+    }
+    let columns: Vec<String> = stmt.column_names().into_iter().map(String::from).collect();
+
+    let mut rows = stmt.query([])?;
+    let mut next_row = move || -> Result<Option<Map<String, serde_json::Value>>> {
+        let Some(row) = rows.next()? else { return Ok(None) };
+        let mut map = Map::new();
+        for (i, column) in row.as_ref().column_names().iter().enumerate() {
+            let value = match row.get::<_, Value>(i)? {
+                Value::Blob(bytes) => decoders.get(*column).copied().unwrap_or_default().decode(&bytes),
+                other => sql_value_to_json(other),
+            };
+            map.insert(column.to_string(), value);
+        }
+        Ok(Some(map))
+    };
+
+    match format {
+        OutputFormat::Table(format) => {
+            let mut writer = RowWriter::new(out, format, columns)?;
+            while let Some(row) = next_row()? {
+                writer.write_row(&row)?;
+            }
+            writer.finish()
+        }
+        OutputFormat::Text => {
+            let mut cells: Vec<Vec<String>> = Vec::new();
+            while let Some(row) = next_row()? {
+                cells.push(columns.iter().map(|c| text_cell(&row[c])).collect());
+            }
+            write_text_table(&mut out, &columns, &cells)?;
+            writeln!(out, "({} rows)", cells.len())?;
+            out.flush()?;
+            Ok(cells.len())
+        }
+    }
+}
+
+/// Writes `rows` as space aligned columns under a header line.
+pub fn write_text_table(out: &mut impl Write, columns: &[String], rows: &[Vec<String>]) -> Result<()> {
+    let mut widths: Vec<usize> = columns.iter().map(|c| c.chars().count()).collect();
+    for row in rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+    let line = |cells: &[String]| -> String {
+        let padded: Vec<String> =
+            cells.iter().zip(&widths).map(|(cell, width)| format!("{:<width$}", cell, width = *width)).collect();
+        padded.join("  ").trim_end().to_string()
+    };
+    writeln!(out, "{}", line(columns))?;
+    writeln!(out, "{}", widths.iter().map(|w| "-".repeat(*w)).collect::<Vec<_>>().join("  "))?;
+    for row in rows {
+        writeln!(out, "{}", line(row))?;
+    }
+    Ok(())
+}
+
+/// One line, at most `MAX_TEXT_CELL_CHARS` characters.
+fn text_cell(value: &serde_json::Value) -> String {
+    let text = match value {
+        serde_json::Value::Null => "NULL".to_string(),
+        serde_json::Value::String(s) => s.clone(),
+        other => other.to_string(),
+    };
+    clip_cell(&text, MAX_TEXT_CELL_CHARS)
+}
+
+/// `text` on one line, at most `max_chars` characters.
+pub(crate) fn clip_cell(text: &str, max_chars: usize) -> String {
+    let text = text.replace('\r', "\\r").replace('\n', "\\n").replace('\t', " ");
+    if text.chars().count() > max_chars {
+        format!("{}…", text.chars().take(max_chars - 1).collect::<String>())
+    } else {
+        text
+    }
+}
+
+fn ensure_table_exists(conn: &Connection, table: &str) -> Result<()> {
+    if list_tables(conn)?.iter().any(|t| t == table) {
+        Ok(())
+    } else {
+        Err(anyhow!("No such table: {}", table))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::testutil::merged_db_with_sample_data;
+
+    fn run(conn: &Connection, sql: &str, decoders: &HashMap<String, BlobDecoder>, format: OutputFormat) -> String {
+        let mut out = Vec::new();
+        write_query(conn, sql, decoders, format, &mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn test_tables_and_schema() {
+        let conn = merged_db_with_sample_data();
+        let tables = list_tables_with_counts(&conn).unwrap();
+        let msg = tables.iter().find(|t| t.name == "MSG").unwrap();
+        assert_eq!(msg.row_count, 6);
+
+        let schema = table_schema(&conn, "Media").unwrap();
+        assert!(schema.sql.unwrap().starts_with("CREATE TABLE Media"));
+        let names: Vec<_> = schema.columns.iter().map(|c| c.name.as_str()).collect();
+        assert_eq!(names, vec!["Key", "Reserved0", "Buf", "Reserved1", "Reserved2"]);
+        assert_eq!(schema.columns[2].decl_type, "BLOB");
+        assert!(table_schema(&conn, "Nope").is_err());
+    }
+
+    #[test]
+    fn test_dump_quotes_and_filters() {
+        let conn = merged_db_with_sample_data();
+        conn.execute_batch("CREATE TABLE \"odd \"\"name\" (a INT); INSERT INTO \"odd \"\"name\" VALUES (1), (2), (3);")
+            .unwrap();
+        let options = DumpOptions { limit: Some(1), offset: 1, where_clause: Some("a > 1".to_string()) };
+        let sql = dump_table_sql(&conn, "odd \"name", &options).unwrap();
+        assert_eq!(sql, "SELECT * FROM \"odd \"\"name\" WHERE (a > 1) LIMIT 1 OFFSET 1");
+        let out = run(&conn, &sql, &HashMap::new(), OutputFormat::Table(TableFormat::Csv));
+        assert_eq!(out, "a\n3\n");
+
+        assert!(dump_table_sql(&conn, "MSG; DROP TABLE MSG", &DumpOptions::default()).is_err());
+    }
+
+    #[test]
+    fn test_blob_decoders() {
+        let conn = merged_db_with_sample_data();
+        let sql = "SELECT MsgSvrID, BytesExtra FROM MSG WHERE MsgSvrID = 2001";
+        let hex = run(&conn, sql, &HashMap::new(), OutputFormat::Table(TableFormat::Jsonl));
+        assert!(hex.contains(&hex::encode(crate::core::testutil::sender_bytes_extra("wxid_stranger"))));
+
+        let decoders = HashMap::from([parse_decoder_arg("BytesExtra=bytesextra").unwrap()]);
+        let decoded = run(&conn, sql, &decoders, OutputFormat::Table(TableFormat::Jsonl));
+        let row: serde_json::Value = serde_json::from_str(decoded.trim()).unwrap();
+        assert!(row["BytesExtra"].is_object());
+        assert!(row["BytesExtra"].to_string().contains("wxid_stranger"));
+
+        let compressed = lz4_flex::block::compress(b"<msg>compressed</msg>");
+        assert_eq!(BlobDecoder::Lz4.decode(&compressed), "<msg>compressed</msg>");
+        assert_eq!(BlobDecoder::ExtraBuf.decode(&[]), "");
+        assert!(parse_decoder_arg("Buf").is_err());
+        assert!(parse_decoder_arg("Buf=gzip").is_err());
+    }
+
+    #[test]
+    fn test_text_output_and_read_only() {
+        let conn = merged_db_with_sample_data();
+        let out = run(
+            &conn,
+            "SELECT MsgSvrID, StrContent FROM MSG WHERE StrTalker = 'wxid_alice' AND Type = 1 ORDER BY MsgSvrID",
+            &HashMap::new(),
+            OutputFormat::Text,
+        );
+        assert_eq!(
+            out,
+            "MsgSvrID  StrContent\n\
+             --------  ------------------\n\
+             1001      hi there\n\
+             1002      hello <b>Alice</b>\n\
+             (2 rows)\n"
+        );
+
+        let mut sink = Vec::new();
+        let err = write_query(&conn, "DELETE FROM MSG", &HashMap::new(), OutputFormat::Text, &mut sink).unwrap_err();
+        assert!(err.to_string().contains("read-only"));
+        let count: i64 = conn.query_row("SELECT count(*) FROM MSG", [], |row| row.get(0)).unwrap();
+        assert_eq!(count, 6);
+    }
+}
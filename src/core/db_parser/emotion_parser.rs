@@ -0,0 +1,246 @@
+use anyhow::{Result, anyhow};
+use rusqlite::{Connection, OptionalExtension, Row};
+use serde::Serialize;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+use aes::Aes128;
+use cbc::cipher::{BlockDecryptMut, KeyIvInit};
+use cipher::block_padding::Pkcs7;
+
+use crate::core::image_decode::detect_image_format;
+
+/// The `<emoji .../>` element of a type 47 message.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct EmojiInfo {
+    pub md5: String,
+    pub cdn_url: Option<String>,    // cdnurl, plain image
+    pub thumb_url: Option<String>,
+    pub aes_key: Option<String>,    // hex, for cached stickers that are encrypted
+    pub product_id: Option<String>, // sticker pack, empty for custom stickers
+    pub width: Option<i64>,
+    pub height: Option<i64>,
+}
+
+/// Where a sticker's image can be obtained.
+#[derive(Debug, Clone)]
+pub enum EmojiSource {
+    /// Cached under `FileStorage/CustomEmotion`, to be read with [`read_cached_emoji`].
+    /// `encrypted` files are not a plain image and need the sticker's `aes_key`.
+    LocalFile { path: PathBuf, encrypted: bool },
+    /// Image bytes stored directly in Emotion.db (EmotionItem.Data, store packs).
+    Embedded(Vec<u8>),
+    /// Not cached; the plain image can be loaded from `cdn_url`. (The `encrypturl` copy is left
+    /// out: nothing here downloads, and a browser cannot show it.)
+    Remote { cdn_url: Option<String> },
+}
+
+/// Whether `md5` is a sticker md5 (32 hex digits). It comes from message XML and names a cache
+/// file, so nothing else may be used as a path component.
+pub fn is_emoji_md5(md5: &str) -> bool {
+    md5.len() == 32 && md5.bytes().all(|b| b.is_ascii_hexdigit())
+}
+
+/// Parses the StrContent of a type 47 message. Stickers without a valid md5 are rejected.
+pub fn parse_emoji_xml(xml: &str) -> Option<EmojiInfo> {
+    let doc = roxmltree::Document::parse(xml.trim()).ok()?;
+    let emoji = doc.descendants().find(|n| n.has_tag_name("emoji"))?;
+    let attr = |name: &str| emoji.attribute(name).map(|v| v.trim().to_string()).filter(|v| !v.is_empty());
+
+    Some(EmojiInfo {
+        md5: attr("md5").filter(|m| is_emoji_md5(m))?.to_lowercase(),
+        cdn_url: attr("cdnurl"),
+        thumb_url: attr("thumburl"),
+        aes_key: attr("aeskey"),
+        product_id: attr("productid"),
+        width: attr("width").and_then(|v| v.parse().ok()),
+        height: attr("height").and_then(|v| v.parse().ok()),
+    })
+}
+
+fn opt_column<T: rusqlite::types::FromSql>(row: &Row, name: &str) -> rusqlite::Result<Option<T>> {
+    // Column sets differ between WeChat versions, so tolerate missing ones.
+    match row.as_ref().column_index(name) {
+        Ok(idx) => row.get(idx),
+        Err(_) => Ok(None),
+    }
+}
+
+/// Looks up a custom sticker (or a favourited pack sticker) in Emotion.db's `CustomEmotion` table.
+pub fn get_custom_emotion(conn: &Connection, md5: &str) -> Result<Option<EmojiInfo>> {
+    let mut stmt = conn.prepare("SELECT * FROM CustomEmotion WHERE LOWER(MD5) = LOWER(?) LIMIT 1;")?;
+    let info = stmt
+        .query_row([md5], |row| {
+            Ok(EmojiInfo {
+                md5: md5.to_lowercase(),
+                cdn_url: opt_column(row, "CDNUrl")?,
+                thumb_url: opt_column(row, "ThumbUrl")?,
+                aes_key: opt_column(row, "AesKey")?,
+                product_id: opt_column(row, "ProductId")?,
+                width: None,
+                height: None,
+            })
+        })
+        .optional()?;
+    Ok(info)
+}
+
+/// Fetches the image bytes of a store-pack sticker from Emotion.db's `EmotionItem` table.
+pub fn get_emotion_item_data(conn: &Connection, md5: &str) -> Result<Option<Vec<u8>>> {
+    let mut stmt = conn.prepare("SELECT Data FROM EmotionItem WHERE LOWER(MD5) = LOWER(?) LIMIT 1;")?;
+    let data: Option<Option<Vec<u8>>> = stmt.query_row([md5], |row| row.get(0)).optional()?;
+    Ok(data.flatten().filter(|d| !d.is_empty()))
+}
+
+/// `FileStorage/CustomEmotion/<md5[0..2]>/<md5>` under the account folder, `None` if `md5` is
+/// not 32 hex digits.
+pub fn custom_emotion_cache_path(account_root: &Path, md5: &str) -> Option<PathBuf> {
+    if !is_emoji_md5(md5) {
+        return None;
+    }
+    let md5 = md5.to_lowercase();
+    Some(account_root.join("FileStorage").join("CustomEmotion").join(&md5[..2]).join(&md5))
+}
+
+/// Resolves a sticker: the local cache first, then Emotion.db, then the CDN urls.
+///
+/// Fields missing from the message XML are completed from `CustomEmotion`.
+pub fn resolve_emoji(emotion_conn: Option<&Connection>, account_root: Option<&Path>, emoji: &EmojiInfo) -> Result<EmojiSource> {
+    if let Some(path) = account_root.and_then(|root| custom_emotion_cache_path(root, &emoji.md5)) {
+        if path.is_file() {
+            // The image signature is all that tells plain files from encrypted ones.
+            let mut head = Vec::with_capacity(16);
+            std::fs::File::open(&path)?.take(16).read_to_end(&mut head)?;
+            let encrypted = detect_image_format(&head).is_none();
+            return Ok(EmojiSource::LocalFile { path, encrypted });
+        }
+    }
+
+    let mut cdn_url = emoji.cdn_url.clone();
+    if let Some(conn) = emotion_conn {
+        if let Some(data) = get_emotion_item_data(conn, &emoji.md5)? {
+            return Ok(EmojiSource::Embedded(data));
+        }
+        if let Some(db_info) = get_custom_emotion(conn, &emoji.md5)? {
+            cdn_url = cdn_url.or(db_info.cdn_url);
+        }
+    }
+
+    Ok(EmojiSource::Remote { cdn_url })
+}
+
+/// Decrypts an encrypted sticker of the local cache.
+/// AES-128-CBC with the hex `aeskey` used as both key and IV, PKCS#7 padded.
+pub fn decrypt_emoji_data(data: &[u8], aes_key_hex: &str) -> Result<Vec<u8>> {
+    let key = hex::decode(aes_key_hex.trim()).map_err(|e| anyhow!("Invalid emoji aeskey: {}", e))?;
+    if key.len() != 16 {
+        return Err(anyhow!("Emoji aeskey must be 16 bytes, got {}", key.len()));
+    }
+    let mut buf = data.to_vec();
+    let decryptor = cbc::Decryptor::<Aes128>::new_from_slices(&key, &key)
+        .map_err(|e| anyhow!("Failed to init AES decryptor: {}", e))?;
+    let plain_len = decryptor
+        .decrypt_padded_mut::<Pkcs7>(&mut buf)
+        .map_err(|_| anyhow!("Failed to decrypt sticker (wrong aeskey or not encrypted)"))?
+        .len();
+    buf.truncate(plain_len);
+    Ok(buf)
+}
+
+/// Reads a cached sticker file ([`EmojiSource::LocalFile`]), decrypting it with `aes_key_hex`
+/// when it is `encrypted`.
+pub fn read_cached_emoji(path: &Path, encrypted: bool, aes_key_hex: Option<&str>) -> Result<Vec<u8>> {
+    let data = std::fs::read(path).map_err(|e| anyhow!("Failed to read {:?}: {}", path, e))?;
+    if !encrypted {
+        return Ok(data);
+    }
+    let key = aes_key_hex.ok_or_else(|| anyhow!("Sticker {:?} is encrypted and no aeskey is known", path))?;
+    decrypt_emoji_data(&data, key)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cbc::cipher::BlockEncryptMut;
+
+    #[test]
+    fn test_parse_emoji_xml() {
+        let xml = r#"<msg><emoji fromusername="wxid_a" tousername="wxid_b" type="2" md5="ABCDEF0123456789ABCDEF0123456789" len="1024" productid="" cdnurl="http://emoji.qpic.cn/x" encrypturl="http://emoji.qpic.cn/y" aeskey="00112233445566778899aabbccddeeff" width="240" height="240"></emoji></msg>"#;
+        let info = parse_emoji_xml(xml).unwrap();
+        assert_eq!(info.md5, "abcdef0123456789abcdef0123456789");
+        assert_eq!(info.cdn_url.as_deref(), Some("http://emoji.qpic.cn/x"));
+        assert!(info.product_id.is_none());
+        assert_eq!(info.width, Some(240));
+        assert!(parse_emoji_xml("<msg><emoji/></msg>").is_none());
+        assert!(parse_emoji_xml(r#"<msg><emoji md5="../../../../etc/passwd"/></msg>"#).is_none());
+    }
+
+    #[test]
+    fn test_custom_emotion_cache_path_rejects_non_md5() {
+        let root = Path::new("account");
+        let path = custom_emotion_cache_path(root, "ABCDEF0123456789ABCDEF0123456789").unwrap();
+        assert_eq!(path, root.join("FileStorage/CustomEmotion/ab/abcdef0123456789abcdef0123456789"));
+        assert!(custom_emotion_cache_path(root, "../../secret.png").is_none());
+        assert!(custom_emotion_cache_path(root, "/home/u/secret.png").is_none());
+        assert!(custom_emotion_cache_path(root, "aa11").is_none());
+    }
+
+    #[test]
+    fn test_decrypt_emoji_data_roundtrip() {
+        let key_hex = "00112233445566778899aabbccddeeff";
+        let key = hex::decode(key_hex).unwrap();
+        let plain = b"GIF89a\x01\x00\x01\x00 sticker";
+        let mut buf = plain.to_vec();
+        buf.resize(plain.len() + 16, 0);
+        let ct_len = cbc::Encryptor::<Aes128>::new_from_slices(&key, &key)
+            .unwrap()
+            .encrypt_padded_mut::<Pkcs7>(&mut buf, plain.len())
+            .unwrap()
+            .len();
+        buf.truncate(ct_len);
+
+        assert_eq!(decrypt_emoji_data(&buf, key_hex).unwrap(), plain);
+        assert!(decrypt_emoji_data(&buf, "0011").is_err());
+    }
+
+    #[test]
+    fn test_resolve_cached_emoji() {
+        let root = std::env::temp_dir().join(format!("wxdump_rs_emoji_{}", std::process::id()));
+        let plain = EmojiInfo { md5: "aa".repeat(16), ..Default::default() };
+        let secret = EmojiInfo { md5: "bb".repeat(16), ..Default::default() };
+        for (emoji, data) in [(&plain, &b"GIF89a plain"[..]), (&secret, &[0x5a; 32][..])] {
+            let path = custom_emotion_cache_path(&root, &emoji.md5).unwrap();
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, data).unwrap();
+        }
+
+        let EmojiSource::LocalFile { path, encrypted } = resolve_emoji(None, Some(&root), &plain).unwrap() else { panic!() };
+        assert!(!encrypted);
+        assert_eq!(read_cached_emoji(&path, encrypted, None).unwrap(), b"GIF89a plain");
+        let EmojiSource::LocalFile { path, encrypted } = resolve_emoji(None, Some(&root), &secret).unwrap() else { panic!() };
+        assert!(encrypted);
+        assert!(read_cached_emoji(&path, encrypted, None).is_err());
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn test_resolve_emoji_from_db() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "CREATE TABLE CustomEmotion (MD5 TEXT, ProductId TEXT, CDNUrl TEXT, EncryptUrl TEXT, AesKey TEXT);\
+             CREATE TABLE EmotionItem (ProductId TEXT, MD5 TEXT, Data BLOB);\
+             INSERT INTO CustomEmotion VALUES ('aaaa', '', 'http://cdn/a', 'http://enc/a', 'ffff');\
+             INSERT INTO EmotionItem VALUES ('pack1', 'bbbb', x'47494638');",
+        )
+        .unwrap();
+
+        let a = EmojiInfo { md5: "aaaa".to_string(), ..Default::default() };
+        match resolve_emoji(Some(&conn), None, &a).unwrap() {
+            EmojiSource::Remote { cdn_url } => assert_eq!(cdn_url.as_deref(), Some("http://cdn/a")),
+            other => panic!("unexpected source {:?}", other),
+        }
+
+        let b = EmojiInfo { md5: "BBBB".to_lowercase(), ..Default::default() };
+        assert!(matches!(resolve_emoji(Some(&conn), None, &b).unwrap(), EmojiSource::Embedded(d) if d == b"GIF8"));
+    }
+}
@@ -0,0 +1,345 @@
+// src/core/db_parser/favorite_parser.rs
+
+use anyhow::Result;
+use rusqlite::Connection;
+use serde::Serialize;
+use std::collections::HashMap;
+
+use super::{format_timestamp_to_string, get_contacts, list_tables};
+
+pub const FAV_TYPE_TEXT: i64 = 1;
+pub const FAV_TYPE_IMAGE: i64 = 2;
+pub const FAV_TYPE_VOICE: i64 = 3;
+pub const FAV_TYPE_VIDEO: i64 = 4;
+pub const FAV_TYPE_LINK: i64 = 5;
+pub const FAV_TYPE_LOCATION: i64 = 6;
+pub const FAV_TYPE_FILE: i64 = 8;
+pub const FAV_TYPE_CHAT_RECORD: i64 = 14;
+pub const FAV_TYPE_NOTE: i64 = 18;
+
+/// A `<datalist><dataitem>` of a favorite: an attachment, or one message of a chat record.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct FavData {
+    /// `datatype` attribute, using the FAV_TYPE_* values.
+    pub data_type: Option<i64>,
+    pub data_id: Option<String>,
+    pub title: Option<String>,       // <datatitle>
+    pub desc: Option<String>,        // <datadesc>, the text of text items
+    pub format: Option<String>,      // <datafmt>, file extension
+    pub size: Option<i64>,           // <fullsize>
+    pub cdn_url: Option<String>,     // <cdn_dataurl>
+    pub source_name: Option<String>, // <dataitemsource><sourcename> or <sourcename>, sender in chat records
+    pub source_time: Option<String>, // <sourcetime>, as displayed ("2023-11-14 22:13")
+}
+
+/// The payload of a favorite, by `FavItems.Type`.
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum FavoriteContent {
+    Text { text: String },
+    Image { items: Vec<FavData> },
+    Voice { items: Vec<FavData> },
+    Video { items: Vec<FavData> },
+    Link { title: Option<String>, description: Option<String>, url: Option<String> },
+    Location { label: Option<String>, poi_name: Option<String>, latitude: Option<f64>, longitude: Option<f64> },
+    File { title: Option<String>, size: Option<i64>, items: Vec<FavData> },
+    /// Forwarded chat history; each record is one message.
+    ChatRecord { title: Option<String>, records: Vec<FavData> },
+    /// A note: its text plus the embedded items.
+    Note { text: String, items: Vec<FavData> },
+    Other { fav_type: i64, items: Vec<FavData> },
+}
+
+impl FavoriteContent {
+    /// Short name of the variant, as in the JSON `kind` field.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            FavoriteContent::Text { .. } => "text",
+            FavoriteContent::Image { .. } => "image",
+            FavoriteContent::Voice { .. } => "voice",
+            FavoriteContent::Video { .. } => "video",
+            FavoriteContent::Link { .. } => "link",
+            FavoriteContent::Location { .. } => "location",
+            FavoriteContent::File { .. } => "file",
+            FavoriteContent::ChatRecord { .. } => "chat_record",
+            FavoriteContent::Note { .. } => "note",
+            FavoriteContent::Other { .. } => "other",
+        }
+    }
+}
+
+/// A row of FavItems with its `XmlBuf` parsed.
+#[derive(Debug, Clone, Serialize)]
+pub struct FavoriteItem {
+    pub local_id: i64,          // FavLocalID
+    pub server_id: Option<i64>, // SvrID
+    pub fav_type: i64,          // Type
+    /// Who wrote the original message (`FromUser`).
+    pub from_wxid: Option<String>,
+    /// The conversation it was saved from: `RealChatName` for chat rooms, else `FromUser`.
+    pub source_talker: Option<String>,
+    /// Remark or nickname of `source_talker` when a contact db was given.
+    pub source_name: Option<String>,
+    pub update_time: i64,
+    pub time_str: String,
+    pub tags: Vec<String>,
+    pub content: FavoriteContent,
+}
+
+/// Filters of `get_favorites`.
+#[derive(Debug, Clone, Default)]
+pub struct FavoriteQuery {
+    /// Only items of this FavItems.Type.
+    pub fav_type: Option<i64>,
+    /// Only items carrying this tag name.
+    pub tag: Option<String>,
+}
+
+/// Parses the `XmlBuf` of a favorite of type `fav_type`. Returns `Other` when the XML is unreadable.
+pub fn parse_favorite_xml(fav_type: i64, xml: &str) -> FavoriteContent {
+    let Ok(doc) = roxmltree::Document::parse(xml.trim()) else {
+        return FavoriteContent::Other { fav_type, items: Vec::new() };
+    };
+    let root = doc.root_element();
+    let child_text = |parent: roxmltree::Node, name: &str| -> Option<String> {
+        parent
+            .children()
+            .find(|n| n.has_tag_name(name))
+            .and_then(|n| n.text())
+            .map(|t| t.trim().to_string())
+            .filter(|t| !t.is_empty())
+    };
+    let find = |name: &str| root.children().find(|n| n.has_tag_name(name));
+
+    let items: Vec<FavData> = find("datalist")
+        .map(|list| {
+            list.children()
+                .filter(|n| n.has_tag_name("dataitem"))
+                .map(|item| FavData {
+                    data_type: item.attribute("datatype").and_then(|t| t.trim().parse().ok()),
+                    data_id: item.attribute("dataid").map(str::to_string),
+                    title: child_text(item, "datatitle"),
+                    desc: child_text(item, "datadesc"),
+                    format: child_text(item, "datafmt"),
+                    size: child_text(item, "fullsize").and_then(|s| s.parse().ok()),
+                    cdn_url: child_text(item, "cdn_dataurl"),
+                    source_name: item
+                        .children()
+                        .find(|n| n.has_tag_name("dataitemsource"))
+                        .and_then(|s| child_text(s, "sourcename"))
+                        .or_else(|| child_text(item, "sourcename")),
+                    source_time: child_text(item, "sourcetime"),
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+    let desc = child_text(root, "desc");
+    let title = child_text(root, "title");
+
+    match fav_type {
+        FAV_TYPE_TEXT => FavoriteContent::Text { text: desc.unwrap_or_default() },
+        FAV_TYPE_IMAGE => FavoriteContent::Image { items },
+        FAV_TYPE_VOICE => FavoriteContent::Voice { items },
+        FAV_TYPE_VIDEO => FavoriteContent::Video { items },
+        FAV_TYPE_LINK => {
+            let web = find("weburlitem");
+            FavoriteContent::Link {
+                title: web.and_then(|w| child_text(w, "pagetitle")).or(title),
+                description: web.and_then(|w| child_text(w, "pagedesc")).or(desc),
+                url: web.and_then(|w| child_text(w, "clean_url").or_else(|| child_text(w, "link"))),
+            }
+        }
+        FAV_TYPE_LOCATION => {
+            let loc = find("locitem");
+            let text = |name: &str| loc.and_then(|l| child_text(l, name));
+            FavoriteContent::Location {
+                label: text("label"),
+                poi_name: text("poiname"),
+                latitude: text("lat").and_then(|v| v.parse().ok()),
+                longitude: text("lng").and_then(|v| v.parse().ok()),
+            }
+        }
+        FAV_TYPE_FILE => FavoriteContent::File {
+            title: items.first().and_then(|i| i.title.clone()).or(title),
+            size: items.first().and_then(|i| i.size),
+            items,
+        },
+        FAV_TYPE_CHAT_RECORD => FavoriteContent::ChatRecord { title: title.or(desc), records: items },
+        FAV_TYPE_NOTE => FavoriteContent::Note {
+            text: desc.unwrap_or_else(|| {
+                items.iter().filter_map(|i| i.desc.as_deref()).collect::<Vec<_>>().join("\n")
+            }),
+            items,
+        },
+        other => FavoriteContent::Other { fav_type: other, items },
+    }
+}
+
+/// Tag names by FavLocalID, from FavTags (`LocalID`, `TagName`) and the FavBindTagDatas
+/// link table (`TagLocalID`, `FavLocalID`). Empty when the db has no tags.
+pub fn get_favorite_tags(conn: &Connection) -> Result<HashMap<i64, Vec<String>>> {
+    let tables = list_tables(conn)?;
+    if !tables.iter().any(|t| t == "FavTags") || !tables.iter().any(|t| t == "FavBindTagDatas") {
+        return Ok(HashMap::new());
+    }
+    let mut stmt = conn.prepare(
+        "SELECT b.FavLocalID, t.TagName FROM FavBindTagDatas b JOIN FavTags t ON t.LocalID = b.TagLocalID \
+         WHERE t.TagName IS NOT NULL ORDER BY b.FavLocalID, t.TagName;",
+    )?;
+    let mut tags: HashMap<i64, Vec<String>> = HashMap::new();
+    let rows = stmt.query_map([], |row| Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?)))?;
+    for row in rows {
+        let (local_id, name) = row?;
+        tags.entry(local_id).or_default().push(name);
+    }
+    Ok(tags)
+}
+
+/// Favorites of Favorite.db, most recently updated first.
+///
+/// Attachments come from the `<datalist>` of `XmlBuf`, which FavDataItem mirrors row by row.
+/// `contact_conn` (MicroMsg tables) fills in `source_name`.
+pub fn get_favorites(
+    conn: &Connection,
+    contact_conn: Option<&Connection>,
+    query: &FavoriteQuery,
+) -> Result<Vec<FavoriteItem>> {
+    let tags = get_favorite_tags(conn)?;
+
+    let mut sql = String::from("SELECT FavLocalID, SvrID, Type, FromUser, RealChatName, UpdateTime, XmlBuf FROM FavItems");
+    let mut params_list: Vec<Box<dyn rusqlite::ToSql>> = Vec::new();
+    if let Some(fav_type) = query.fav_type {
+        sql.push_str(" WHERE Type = ?");
+        params_list.push(Box::new(fav_type));
+    }
+    sql.push_str(" ORDER BY UpdateTime DESC, FavLocalID DESC;");
+
+    let params_for_query: Vec<&dyn rusqlite::ToSql> = params_list.iter().map(|p| p.as_ref()).collect();
+    let mut stmt = conn.prepare(&sql)?;
+    let rows = stmt.query_map(&*params_for_query, |row| {
+        let local_id: i64 = row.get("FavLocalID")?;
+        let fav_type = row.get::<_, Option<i64>>("Type")?.unwrap_or(0);
+        let from_wxid = row.get::<_, Option<String>>("FromUser")?.filter(|s| !s.is_empty());
+        let real_chat = row.get::<_, Option<String>>("RealChatName")?.filter(|s| !s.is_empty());
+        let update_time = row.get::<_, Option<i64>>("UpdateTime")?.unwrap_or(0);
+        let xml = row.get::<_, Option<String>>("XmlBuf")?.unwrap_or_default();
+        Ok(FavoriteItem {
+            local_id,
+            server_id: row.get("SvrID")?,
+            fav_type,
+            source_talker: real_chat.or_else(|| from_wxid.clone()),
+            from_wxid,
+            source_name: None,
+            update_time,
+            time_str: format_timestamp_to_string(update_time, "%Y-%m-%d %H:%M:%S"),
+            tags: tags.get(&local_id).cloned().unwrap_or_default(),
+            content: parse_favorite_xml(fav_type, &xml),
+        })
+    })?;
+
+    let mut items = Vec::new();
+    for item in rows {
+        let item = item?;
+        if query.tag.as_ref().is_some_and(|tag| !item.tags.contains(tag)) {
+            continue;
+        }
+        items.push(item);
+    }
+
+    if let Some(contact_conn) = contact_conn {
+        let mut wxids: Vec<String> = items.iter().filter_map(|i| i.source_talker.clone()).collect();
+        wxids.sort();
+        wxids.dedup();
+        let names: HashMap<String, String> = get_contacts(contact_conn, None, Some(&wxids), None)?
+            .into_iter()
+            .filter_map(|c| Some((c.wxid, [c.remark, c.nickname].into_iter().flatten().find(|n| !n.is_empty())?)))
+            .collect();
+        for item in &mut items {
+            item.source_name = item.source_talker.as_ref().and_then(|t| names.get(t).cloned());
+        }
+    }
+    Ok(items)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::testutil::{merged_db_with_sample_data, FAVORITE_SCHEMA};
+
+    const LINK_XML: &str = "<favitem type=\"5\"><desc>fallback</desc><weburlitem><pagetitle>Rust book</pagetitle>\
+        <clean_url>https://doc.rust-lang.org/book/</clean_url></weburlitem></favitem>";
+    const RECORD_XML: &str = "<favitem type=\"14\"><title>Group chat history</title><datalist count=\"2\">\
+        <dataitem datatype=\"1\" dataid=\"a\"><datadesc>hello all</datadesc><sourcename>Stranger</sourcename>\
+        <sourcetime>2023-11-14 22:13</sourcetime></dataitem>\
+        <dataitem datatype=\"2\" dataid=\"b\"><datafmt>jpg</datafmt><fullsize>2048</fullsize>\
+        <dataitemsource><sourcename>Alice</sourcename></dataitemsource></dataitem></datalist></favitem>";
+
+    fn favorite_db() -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(FAVORITE_SCHEMA).unwrap();
+        let insert = "INSERT INTO FavItems (FavLocalID, SvrID, Type, FromUser, RealChatName, UpdateTime, XmlBuf) \
+                      VALUES (?, ?, ?, ?, ?, ?, ?)";
+        conn.execute(insert, rusqlite::params![1, 101, 1, "wxid_alice", "", 1700000000,
+            "<favitem type=\"1\"><desc>remember the milk</desc></favitem>"]).unwrap();
+        conn.execute(insert, rusqlite::params![2, 102, 5, "wxid_bob", "", 1700000100, LINK_XML]).unwrap();
+        conn.execute(insert, rusqlite::params![3, 103, 14, "wxid_stranger", "123@chatroom", 1700000200, RECORD_XML])
+            .unwrap();
+        conn.execute_batch(
+            "INSERT INTO FavTags (LocalID, TagName) VALUES (1, 'rust'), (2, 'todo');
+             INSERT INTO FavBindTagDatas (TagLocalID, FavLocalID) VALUES (1, 2), (2, 1), (1, 1);",
+        )
+        .unwrap();
+        conn
+    }
+
+    #[test]
+    fn test_parse_favorite_xml() {
+        match parse_favorite_xml(FAV_TYPE_LINK, LINK_XML) {
+            FavoriteContent::Link { title, description, url } => {
+                assert_eq!(title.as_deref(), Some("Rust book"));
+                assert_eq!(description.as_deref(), Some("fallback"));
+                assert_eq!(url.as_deref(), Some("https://doc.rust-lang.org/book/"));
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+        match parse_favorite_xml(FAV_TYPE_CHAT_RECORD, RECORD_XML) {
+            FavoriteContent::ChatRecord { title, records } => {
+                assert_eq!(title.as_deref(), Some("Group chat history"));
+                assert_eq!(records.len(), 2);
+                assert_eq!(records[0].desc.as_deref(), Some("hello all"));
+                assert_eq!(records[0].source_time.as_deref(), Some("2023-11-14 22:13"));
+                assert_eq!(records[1].source_name.as_deref(), Some("Alice"));
+                assert_eq!(records[1].size, Some(2048));
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+        assert!(matches!(parse_favorite_xml(FAV_TYPE_TEXT, "<<"), FavoriteContent::Other { fav_type: 1, .. }));
+        assert_eq!(parse_favorite_xml(99, "<favitem/>").kind(), "other");
+    }
+
+    #[test]
+    fn test_get_favorites() {
+        let conn = favorite_db();
+        let contacts = merged_db_with_sample_data();
+        let items = get_favorites(&conn, Some(&contacts), &FavoriteQuery::default()).unwrap();
+        let ids: Vec<_> = items.iter().map(|i| i.local_id).collect();
+        assert_eq!(ids, vec![3, 2, 1]);
+
+        assert_eq!(items[0].source_talker.as_deref(), Some("123@chatroom"));
+        assert_eq!(items[0].source_name.as_deref(), Some("Project <Team>"));
+        assert_eq!(items[0].from_wxid.as_deref(), Some("wxid_stranger"));
+        assert_eq!(items[2].tags, vec!["rust", "todo"]);
+        assert_eq!(items[2].source_name.as_deref(), Some("Alice (work)"));
+        assert!(matches!(&items[2].content, FavoriteContent::Text { text } if text == "remember the milk"));
+
+        let query = FavoriteQuery { tag: Some("rust".to_string()), ..Default::default() };
+        let ids: Vec<_> = get_favorites(&conn, None, &query).unwrap().iter().map(|i| i.local_id).collect();
+        assert_eq!(ids, vec![2, 1]);
+        let query = FavoriteQuery { fav_type: Some(FAV_TYPE_LINK), ..Default::default() };
+        assert_eq!(get_favorites(&conn, None, &query).unwrap().len(), 1);
+
+        let json = serde_json::to_value(&items[1]).unwrap();
+        assert_eq!(json["content"]["kind"], "link");
+        assert_eq!(json["content"]["title"], "Rust book");
+    }
+}
@@ -0,0 +1,159 @@
+// src/core/db_parser/fts_parser.rs
+
+use anyhow::Result;
+use rusqlite::Connection;
+
+use super::list_tables;
+
+/// A match in FTSMSG.db, pointing back to an MSG row.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FtsHit {
+    /// `msgId` of the MetaData table, the MsgSvrID of the message.
+    pub msg_svr_id: i64,
+    /// Resolved through NameToId; None when the id is unknown.
+    pub talker: Option<String>,
+    pub create_time: i64,
+    pub content: String,
+}
+
+/// Names of the FTSChatMsg<N> indexes that have both their `_content` and `_MetaData` tables.
+///
+/// The FTS4 tables use WeChat's own tokenizer, which SQLite does not know, so instead of
+/// `MATCH` on the virtual table we read its `_content` shadow table (`docid`, `c0content`)
+/// and join `docid` to the MetaData table (`docid`, `msgId`, `entityId`, `type`, `CreateTime`).
+pub fn fts_index_names(conn: &Connection) -> Result<Vec<String>> {
+    let tables = list_tables(conn)?;
+    let mut names: Vec<String> = tables
+        .iter()
+        .filter_map(|t| t.strip_suffix("_content"))
+        .filter(|name| name.starts_with("FTSChatMsg") && tables.iter().any(|t| *t == format!("{}_MetaData", name)))
+        .map(String::from)
+        .collect();
+    names.sort();
+    Ok(names)
+}
+
+/// Searches every FTSChatMsg index of an FTSMSG.db for messages containing all
+/// whitespace separated terms of `query`, newest first.
+///
+/// `talker` filters by wxid through the NameToId table (`usrName`, its rowid being the
+/// MetaData `entityId`); `since` is an inclusive unix timestamp.
+pub fn search_fts(
+    conn: &Connection,
+    query: &str,
+    talker: Option<&str>,
+    since: Option<i64>,
+    limit: usize,
+) -> Result<Vec<FtsHit>> {
+    let terms: Vec<&str> = query.split_whitespace().collect();
+    if terms.is_empty() {
+        return Ok(Vec::new());
+    }
+    let has_name_table = list_tables(conn)?.iter().any(|t| t == "NameToId");
+    if talker.is_some() && !has_name_table {
+        return Ok(Vec::new());
+    }
+
+    let mut hits = Vec::new();
+    for name in fts_index_names(conn)? {
+        let talker_column = if has_name_table { "n.usrName" } else { "NULL" };
+        let mut sql = format!(
+            "SELECT m.msgId, {talker}, m.CreateTime, c.c0content FROM \"{name}_content\" c \
+             JOIN \"{name}_MetaData\" m ON m.docid = c.docid",
+            talker = talker_column,
+            name = name,
+        );
+        if has_name_table {
+            sql.push_str(" LEFT JOIN NameToId n ON n.rowid = m.entityId");
+        }
+
+        let mut conditions: Vec<String> = Vec::new();
+        let mut params_list: Vec<Box<dyn rusqlite::ToSql>> = Vec::new();
+        for term in &terms {
+            conditions.push("c.c0content LIKE ? ESCAPE '\\'".to_string());
+            params_list.push(Box::new(like_pattern(term)));
+        }
+        if let Some(talker) = talker {
+            conditions.push("n.usrName = ?".to_string());
+            params_list.push(Box::new(talker.to_string()));
+        }
+        if let Some(since) = since {
+            conditions.push("m.CreateTime >= ?".to_string());
+            params_list.push(Box::new(since));
+        }
+        sql.push_str(" WHERE ");
+        sql.push_str(&conditions.join(" AND "));
+        sql.push_str(" ORDER BY m.CreateTime DESC LIMIT ?;");
+        params_list.push(Box::new(limit as i64));
+
+        let params_for_query: Vec<&dyn rusqlite::ToSql> = params_list.iter().map(|p| p.as_ref()).collect();
+        let mut stmt = conn.prepare(&sql)?;
+        let rows = stmt.query_map(&*params_for_query, |row| {
+            Ok(FtsHit {
+                msg_svr_id: row.get(0)?,
+                talker: row.get(1)?,
+                create_time: row.get::<_, Option<i64>>(2)?.unwrap_or(0),
+                content: row.get::<_, Option<String>>(3)?.unwrap_or_default(),
+            })
+        })?;
+        for hit in rows {
+            hits.push(hit?);
+        }
+    }
+
+    hits.sort_by_key(|hit| std::cmp::Reverse(hit.create_time));
+    hits.truncate(limit);
+    Ok(hits)
+}
+
+/// `%term%` with LIKE wildcards in `term` escaped by `\`.
+pub fn like_pattern(term: &str) -> String {
+    let escaped = term.replace('\\', "\\\\").replace('%', "\\%").replace('_', "\\_");
+    format!("%{}%", escaped)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fts_db() -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "CREATE TABLE NameToId (usrName TEXT PRIMARY KEY);
+             INSERT INTO NameToId (rowid, usrName) VALUES (1, 'wxid_alice'), (2, '123@chatroom');
+             CREATE TABLE FTSChatMsg2_content (docid INTEGER PRIMARY KEY, c0content TEXT, c1entityId TEXT);
+             CREATE TABLE FTSChatMsg2_MetaData (docid INTEGER PRIMARY KEY, msgId INTEGER, entityId INTEGER,
+                 type INTEGER, subType INTEGER, CreateTime INTEGER);
+             INSERT INTO FTSChatMsg2_content VALUES (1, 'hi there', '1'), (2, 'hello all', '2'), (3, '100% done', '1');
+             INSERT INTO FTSChatMsg2_MetaData VALUES (1, 1001, 1, 1, 0, 1700000000), (2, 2001, 2, 1, 0, 1700000000),
+                 (3, 1004, 1, 1, 0, 1700000500);
+             CREATE TABLE FTSChatMsg15_content (docid INTEGER PRIMARY KEY, c0content TEXT, c1entityId TEXT);",
+        )
+        .unwrap();
+        conn
+    }
+
+    #[test]
+    fn test_index_names() {
+        assert_eq!(fts_index_names(&fts_db()).unwrap(), vec!["FTSChatMsg2"]);
+    }
+
+    #[test]
+    fn test_search_fts() {
+        let conn = fts_db();
+        let hits = search_fts(&conn, "h", None, None, 10).unwrap();
+        let ids: Vec<_> = hits.iter().map(|h| h.msg_svr_id).collect();
+        assert_eq!(ids.len(), 2);
+        assert!(ids.contains(&1001) && ids.contains(&2001));
+
+        let hits = search_fts(&conn, "hello all", Some("123@chatroom"), None, 10).unwrap();
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].talker.as_deref(), Some("123@chatroom"));
+        assert!(search_fts(&conn, "hi", Some("123@chatroom"), None, 10).unwrap().is_empty());
+
+        assert!(search_fts(&conn, "hi", None, Some(1700000001), 10).unwrap().is_empty());
+        // `%` is literal, not a wildcard.
+        assert_eq!(search_fts(&conn, "0%", None, None, 10).unwrap()[0].msg_svr_id, 1004);
+        assert!(search_fts(&conn, "h%e", None, None, 10).unwrap().is_empty());
+    }
+}
@@ -0,0 +1,63 @@
+use anyhow::Result;
+use rusqlite::{Connection, OptionalExtension};
+
+/// Header of a standard SILK v3 stream.
+pub const SILK_V3_HEADER: &[u8] = b"#!SILK_V3";
+
+/// Looks up the raw voice blob for a message in a MediaMSG*.db.
+///
+/// The `Media` table is keyed by `Reserved0`, which holds the `MsgSvrID` of the MSG row.
+/// Returns `Ok(None)` if this shard does not contain the message.
+pub fn get_voice_blob(conn: &Connection, msg_svr_id: i64) -> Result<Option<Vec<u8>>> {
+    let mut stmt = conn.prepare("SELECT Buf FROM Media WHERE Reserved0 = ? LIMIT 1;")?;
+    let buf: Option<Option<Vec<u8>>> = stmt.query_row([msg_svr_id], |row| row.get(0)).optional()?;
+    Ok(buf.flatten())
+}
+
+/// Same as `get_voice_blob`, but searches several MediaMSG shards (MediaMSG0.db, MediaMSG1.db, ...)
+/// and returns the first hit.
+pub fn find_voice_blob(conns: &[Connection], msg_svr_id: i64) -> Result<Option<Vec<u8>>> {
+    for conn in conns {
+        if let Some(buf) = get_voice_blob(conn, msg_svr_id)? {
+            return Ok(Some(buf));
+        }
+    }
+    Ok(None)
+}
+
+/// WeChat prefixes its SILK streams with a single `0x02` byte ("\x02#!SILK_V3...").
+/// Standard decoders expect the stream to start at "#!SILK_V3", so strip it if present.
+pub fn strip_wechat_silk_prefix(data: &[u8]) -> &[u8] {
+    match data.first() {
+        Some(0x02) if data[1..].starts_with(SILK_V3_HEADER) => &data[1..],
+        _ => data,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_strip_wechat_silk_prefix() {
+        let wechat = b"\x02#!SILK_V3\x10\x00";
+        assert_eq!(strip_wechat_silk_prefix(wechat), b"#!SILK_V3\x10\x00");
+        let plain = b"#!SILK_V3\x10\x00";
+        assert_eq!(strip_wechat_silk_prefix(plain), plain);
+        assert_eq!(strip_wechat_silk_prefix(b"\x02"), b"\x02");
+        assert_eq!(strip_wechat_silk_prefix(b""), b"");
+    }
+
+    #[test]
+    fn test_get_voice_blob() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "CREATE TABLE Media (Key TEXT, Reserved0 INT, Buf BLOB, Reserved1 INT, Reserved2 TEXT);\
+             INSERT INTO Media (Key, Reserved0, Buf) VALUES ('k1', 1234567890123, x'02232153494C4B5F5633');",
+        )
+        .unwrap();
+        let blob = get_voice_blob(&conn, 1234567890123).unwrap().unwrap();
+        assert_eq!(strip_wechat_silk_prefix(&blob), SILK_V3_HEADER);
+        assert!(get_voice_blob(&conn, 42).unwrap().is_none());
+    }
+}
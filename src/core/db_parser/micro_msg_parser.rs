@@ -1,22 +1,18 @@
 use anyhow::Result;
-use std::collections::HashMap;
-use std::fmt; // Added for Display
+use std::collections::{HashMap, HashSet};
 
 use chrono::{DateTime, NaiveDateTime, Utc};
 use rusqlite::{Connection, Result as RusqliteResult};
+use serde::Serialize;
 
-// Custom error type to wrap anyhow::Error for std::error::Error compatibility
-#[derive(Debug)]
-struct AnyhowToStdError(String);
+use crate::core::error::WxDumpError;
+use crate::core::protobuf::parse_message;
 
-impl fmt::Display for AnyhowToStdError {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{}", self.0)
-    }
-}
+use super::openim_parser::{
+    get_openim_chat_rooms, get_openim_contacts, get_openim_display_names, get_openim_sessions, is_chat_room_wxid,
+};
 
-impl std::error::Error for AnyhowToStdError {}
-#[derive(Debug, Default, Clone)]
+#[derive(Debug, Default, Clone, Serialize)]
 pub struct ExtraBufInfo {
     pub gender: Option<i64>,
     pub signature: Option<String>,
@@ -31,7 +27,7 @@ pub struct ExtraBufInfo {
     pub remark_img_url2: Option<String>,
     // TODO: Add other fields from buf_dict if needed
 }
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, Serialize)]
 pub struct SessionInfo {
     pub wxid: String,
     pub order_num: Option<i64>,
@@ -58,6 +54,9 @@ pub struct SessionInfo {
     pub contact_verify_flag: Option<i64>,
     pub contact_chat_room_type: Option<i64>,
     pub contact_chat_room_notify: Option<i64>,
+    /// The talker is an Enterprise WeChat (OpenIM) contact or room.
+    pub contact_is_openim: bool,
+    pub contact_corp_name: Option<String>,
 }
 
 pub fn format_timestamp_to_string(timestamp_secs: i64, format_str: &str) -> String {
@@ -73,7 +72,7 @@ pub fn format_timestamp_to_string(timestamp_secs: i64, format_str: &str) -> Stri
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct Contact {
     pub wxid: String,
     pub account: Option<String>,
@@ -92,8 +91,12 @@ pub struct Contact {
     pub reserved5: Option<i64>,
     pub chat_room_notify: Option<i64>,
     pub is_chatroom_contact: bool,
+    /// Enterprise WeChat (企业微信) contact or room, read from OpenIMContact.
+    pub is_openim: bool,
+    /// Enterprise the OpenIM contact belongs to.
+    pub corp_name: Option<String>,
 }
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, Serialize)]
 pub struct ChatRoomMember {
     pub wxid: String,
     pub nickname: Option<String>,
@@ -103,7 +106,7 @@ pub struct ChatRoomMember {
     pub room_nickname: Option<String>, // From RoomData parsing
 }
 
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, Serialize)]
 pub struct ChatRoomInfo {
     pub wxid: String,                         // From ChatRoomName
     pub member_wxids: Vec<String>,            // From UserNameList, split
@@ -115,21 +118,40 @@ pub struct ChatRoomInfo {
     pub members: Vec<ChatRoomMember>,         // Populated via get_contacts and parse_chat_room_data
     pub is_show_name: Option<i64>,            // From IsShowName
     pub chat_room_flag: Option<i64>,          // From ChatRoomFlag
+    pub is_openim: bool,                      // An @im.chatroom room from OpenIMContact
+    pub corp_name: Option<String>,            // Corp of an OpenIM room
     // RoomData parsing result can be temporarily stored or used to populate members' room_nickname
 }
 
-/// Parses the RoomData field from the ChatRoom table.
+/// Parses the RoomData field from the ChatRoom table into wxid -> display name in the room.
 ///
-/// TODO: Implement proper protobuf parsing for RoomData.
-/// The Python code uses blackboxprotobuf.decode_message.
-/// For now, this is a placeholder.
-pub fn parse_chat_room_data(_room_data_bytes: Option<&[u8]>) -> Result<HashMap<String, String>, anyhow::Error> {
-    // Placeholder implementation
-    // In Python, this parses protobuf data to get wxid -> roomNickname mappings.
-    // e.g., i['1'] (wxid) and i['2'] (roomNickname)
-    Ok(HashMap::new())
-    // Or, to indicate it's not implemented:
-    // Err(anyhow::anyhow!("RoomData parsing not yet implemented"))
+/// RoomData is a protobuf whose repeated field 1 holds the members as `{1: wxid, 2: display
+/// name, ...}`; members without a display name are left out.
+/// Corresponds to Python's `get_room_list`, which decodes it with blackboxprotobuf.
+pub fn parse_chat_room_data(room_data_bytes: Option<&[u8]>) -> Result<HashMap<String, String>, anyhow::Error> {
+    let mut room_nicknames = HashMap::new();
+    let Some(bytes) = room_data_bytes else {
+        return Ok(room_nicknames);
+    };
+    for (field, value) in parse_message(bytes)? {
+        if field != 1 {
+            continue;
+        }
+        let Some(member_bytes) = value.as_bytes() else { continue };
+        let mut wxid = None;
+        let mut display_name = None;
+        for (member_field, member_value) in parse_message(member_bytes)? {
+            match member_field {
+                1 => wxid = member_value.as_string(),
+                2 => display_name = member_value.as_string(),
+                _ => {}
+            }
+        }
+        if let (Some(wxid), Some(name)) = (wxid, display_name.filter(|n| !n.is_empty())) {
+            room_nicknames.insert(wxid, name);
+        }
+    }
+    Ok(room_nicknames)
 }
 
 /// Retrieves information about chat rooms.
@@ -186,43 +208,7 @@ pub fn get_chat_rooms(
             })
             .unwrap_or_default();
 
-        // Parse RoomData (currently a placeholder)
-        let room_nicknames_map = parse_chat_room_data(room_data_bytes.as_deref())
-            .map_err(|e| rusqlite::Error::FromSqlConversionFailure(
-                0, rusqlite::types::Type::Blob, Box::new(AnyhowToStdError(e.to_string()))
-            ))?;
-
-        // Get contact details for members
-        let mut chat_room_members: Vec<ChatRoomMember> = Vec::new();
-        if !member_wxids.is_empty() {
-            // Convert Vec<String> to &[String] for get_contacts
-            let member_wxid_slices: Vec<String> = member_wxids.iter().map(|s| s.to_string()).collect();
-
-            match get_contacts(conn, None, Some(&member_wxid_slices), None) {
-                Ok(contacts) => {
-                    for contact in contacts {
-                        chat_room_members.push(ChatRoomMember {
-                            wxid: contact.wxid.clone(),
-                            nickname: contact.nickname.clone(),
-                            remark: contact.remark.clone(),
-                            account: contact.account.clone(),
-                            head_img_url: contact.head_img_url.clone(),
-                            room_nickname: room_nicknames_map.get(&contact.wxid).cloned(),
-                        });
-                    }
-                }
-                Err(e) => {
-                     // Log or handle error from get_contacts
-                    eprintln!("Error fetching members for room {}: {}", chat_room_name, e);
-                    // Convert anyhow::Error to rusqlite::Error to propagate
-                    return Err(rusqlite::Error::FromSqlConversionFailure(
-                        0, rusqlite::types::Type::Null, Box::new(AnyhowToStdError(e.to_string()))
-                    ));
-                }
-            }
-        }
-
-        Ok(ChatRoomInfo {
+        let room = ChatRoomInfo {
             wxid: chat_room_name,
             member_wxids,
             self_display_name: row.get("SelfDisplayName")?,
@@ -230,24 +216,42 @@ pub fn get_chat_rooms(
             announcement: row.get("Announcement")?,
             announcement_editor: row.get("AnnouncementEditor")?,
             announcement_publish_time: row.get("AnnouncementPublishTime")?,
-            members: chat_room_members,
+            members: Vec::new(),
             is_show_name: row.get("IsShowName")?,
             chat_room_flag: row.get("ChatRoomFlag")?,
-        })
+            is_openim: false,
+            corp_name: None,
+        };
+        Ok((room, room_data_bytes))
     })?;
+    let rows = rows.collect::<RusqliteResult<Vec<_>>>()?;
 
-    for row_result in rows {
-        match row_result {
-            Ok(chat_room_info) => {
-                chat_room_map.insert(chat_room_info.wxid.clone(), chat_room_info);
-            }
-            Err(e) => {
-                // Handle or propagate the error from row mapping
-                // For simplicity, we'll print and continue, but a robust app might return Err here.
-                eprintln!("Error processing chat room row: {}", e);
-                // Or, to propagate: return Err(anyhow::anyhow!("Failed to process row: {}", e));
+    for (mut chat_room_info, room_data_bytes) in rows {
+        // Display names set by members in this room; a RoomData we cannot read only costs those names.
+        let room_nicknames_map = parse_chat_room_data(room_data_bytes.as_deref()).unwrap_or_else(|e| {
+            log::warn!("Ignoring unreadable RoomData of {}: {}", chat_room_info.wxid, e);
+            HashMap::new()
+        });
+
+        // Get contact details for members
+        if !chat_room_info.member_wxids.is_empty() {
+            for contact in get_contacts(conn, None, Some(&chat_room_info.member_wxids), None)? {
+                chat_room_info.members.push(ChatRoomMember {
+                    room_nickname: room_nicknames_map.get(&contact.wxid).cloned(),
+                    wxid: contact.wxid,
+                    nickname: contact.nickname,
+                    remark: contact.remark,
+                    account: contact.account,
+                    head_img_url: contact.head_img_url,
+                });
             }
         }
+        chat_room_map.insert(chat_room_info.wxid.clone(), chat_room_info);
+    }
+
+    // Enterprise WeChat rooms missing from the ChatRoom table.
+    for (wxid, room) in get_openim_chat_rooms(conn, filter_room_wxids)? {
+        chat_room_map.entry(wxid).or_insert(room);
     }
 
     Ok(chat_room_map)
@@ -323,15 +327,15 @@ pub fn parse_extra_buf(extra_buf_bytes: Option<&[u8]>) -> Result<Option<ExtraBuf
                         Ok(s) => return Ok(Some(s)),
                         Err(e) => {
                             // Fallback or log error
-                            eprintln!("Failed to decode UTF-16 for {}: {}", field_name, e);
+                            log::debug!("Failed to decode UTF-16 for {}: {}", field_name, e);
                             // Try UTF-8 as a fallback, though less likely for type_id 2
                             match String::from_utf8(value_bytes.to_vec()) {
                                 Ok(s_utf8) => {
-                                    eprintln!("Successfully decoded as UTF-8 for {} (fallback)", field_name);
+                                    log::debug!("Successfully decoded as UTF-8 for {} (fallback)", field_name);
                                     return Ok(Some(s_utf8));
                                 }
                                 Err(e_utf8) => {
-                                    eprintln!("Failed to decode UTF-8 for {} (fallback): {}", field_name, e_utf8);
+                                    log::warn!("Failed to decode {} as UTF-16 or UTF-8: {}", field_name, e_utf8);
                                     return Ok(None); // Or handle error differently
                                 }
                             }
@@ -467,6 +471,7 @@ pub fn get_contacts(
     if filter_wxids.is_none() {
         conditions.push("A.UserName NOT LIKE '%@app'".to_string());
         conditions.push("A.UserName NOT LIKE '%@chatroom'".to_string()); // Assuming get_contacts is for individual users primarily
+        conditions.push("A.UserName NOT LIKE '%@im.chatroom'".to_string());
         conditions.push("A.Type != 4".to_string()); // Type 4 are often special/system contacts
         conditions.push("A.Type != 0".to_string()); // Type 0 can be current user or system accounts
     }
@@ -500,27 +505,9 @@ pub fn get_contacts(
         }
 
         let extra_buf_bytes: Option<Vec<u8>> = row.get("ExtraBuf")?;
-        let extra_buf_info = match parse_extra_buf(extra_buf_bytes.as_deref()) {
-            Ok(info) => info,
-            Err(e) => {
-                // Convert anyhow::Error to rusqlite::Error::FromSqlConversionFailure
-                let column_index = 0; // Placeholder, as this isn't a direct SQL column conversion
-                let source_type = rusqlite::types::Type::Blob; // ExtraBuf is likely a BLOB
-                
-                // Wrap the anyhow::Error's string representation in our custom error type
-                let std_error = Box::new(AnyhowToStdError(e.to_string()));
-
-                return Err(rusqlite::Error::FromSqlConversionFailure(
-                    column_index,
-                    source_type,
-                    std_error, // This now correctly implements std::error::Error
-                ));
-            }
-        };
-
-        let is_chatroom_contact = wxid.contains("@chatroom");
+        let is_chatroom_contact = is_chat_room_wxid(&wxid);
 
-        Ok(Contact {
+        let contact = Contact {
             wxid,
             account: row.get("Alias")?, // Python's 'Alias' seems to map to 'account'
             nickname: row.get("NickName")?,
@@ -528,7 +515,7 @@ pub fn get_contacts(
             head_img_url: row.get("bigHeadImgUrl")?,
             label_list: labels,
             description: row.get("description")?,
-            extra_buf_info,
+            extra_buf_info: None,
             user_type: row.get("Type")?,
             verify_flag: row.get("VerifyFlag")?,
             chat_room_type: row.get("ChatRoomType")?,
@@ -538,17 +525,58 @@ pub fn get_contacts(
             reserved5: row.get("Reserved5")?,
             chat_room_notify: row.get("ChatRoomNotify")?,
             is_chatroom_contact,
-        })
+            is_openim: false,
+            corp_name: None,
+        };
+        Ok((contact, extra_buf_bytes))
     })?;
 
     let mut contacts = Vec::new();
     for contact_result in contact_iter {
-        contacts.push(contact_result?);
+        let (mut contact, extra_buf_bytes) = contact_result?;
+        contact.extra_buf_info = parse_extra_buf(extra_buf_bytes.as_deref())
+            .map_err(|e| WxDumpError::parse(format!("ExtraBuf of {}", contact.wxid), e))?;
+        contacts.push(contact);
+    }
+
+    // Enterprise WeChat contacts live in OpenIMContact; they carry no labels. A wxid in both
+    // tables is kept once, from Contact.
+    if filter_label_ids.is_none() {
+        let known: HashSet<String> = contacts.iter().map(|c| c.wxid.clone()).collect();
+        let openim_contacts = get_openim_contacts(conn, filter_word, filter_wxids)?;
+        contacts.extend(openim_contacts.into_iter().filter(|c| !known.contains(&c.wxid)));
     }
 
     Ok(contacts)
 }
 
+/// Maps every UserName of the Contact table (friends, chat rooms, and strangers met in
+/// chat rooms) to the name WeChat displays for it: Remark, then NickName, then the wxid.
+/// Enterprise WeChat contacts are included as `Name@Corp` when OpenIMContact is available.
+pub fn get_display_names(conn: &Connection) -> Result<HashMap<String, String>> {
+    let mut stmt = conn.prepare("SELECT UserName, Remark, NickName FROM Contact;")?;
+    let rows = stmt.query_map([], |row| {
+        let wxid: String = row.get(0)?;
+        let remark: Option<String> = row.get(1)?;
+        let nickname: Option<String> = row.get(2)?;
+        let name = remark
+            .filter(|r| !r.is_empty())
+            .or(nickname.filter(|n| !n.is_empty()))
+            .unwrap_or_else(|| wxid.clone());
+        Ok((wxid, name))
+    })?;
+
+    let mut names = HashMap::new();
+    for row_result in rows {
+        let (wxid, name) = row_result?;
+        names.insert(wxid, name);
+    }
+    for (wxid, name) in get_openim_display_names(conn)? {
+        names.entry(wxid).or_insert(name);
+    }
+    Ok(names)
+}
+
 pub fn get_sessions(conn: &Connection) -> Result<Vec<SessionInfo>, anyhow::Error> {
     let label_map = get_contact_labels(conn)
         .map_err(|e| anyhow::anyhow!("Failed to get contact labels: {}", e))?;
@@ -605,20 +633,8 @@ ORDER BY
         }
 
         let contact_extra_buf_bytes: Option<Vec<u8>> = row.get("contact_extra_buf")?;
-        let contact_extra_buf_info = match parse_extra_buf(contact_extra_buf_bytes.as_deref()) {
-            Ok(info) => info,
-            Err(e) => {
-                eprintln!("Error parsing ExtraBuf for session with wxid {}: {}", wxid, e);
-                // Convert anyhow::Error to rusqlite::Error to satisfy query_map's error type
-                return Err(rusqlite::Error::FromSqlConversionFailure(
-                    0, // Replaced problematic column_index call with a fixed value
-                    rusqlite::types::Type::Blob,
-                    Box::new(AnyhowToStdError(format!("Failed to parse ExtraBuf for {}: {}", wxid, e)))
-                ));
-            }
-        };
 
-        Ok(SessionInfo {
+        let session = SessionInfo {
             wxid,
             order_num: row.get("nOrder")?,
             unread_count: row.get("nUnReadCount")?,
@@ -637,25 +653,35 @@ ORDER BY
             contact_account: row.get("contact_alias")?,
             contact_description: row.get("contact_reserved6_describe")?,
             contact_head_img_url: row.get("contact_big_head_img_url")?,
-            contact_extra_buf_info,
+            contact_extra_buf_info: None,
             contact_label_list,
             contact_del_flag: row.get("contact_del_flag")?,
             contact_type: row.get("contact_type")?,
             contact_verify_flag: row.get("contact_verify_flag")?,
             contact_chat_room_type: row.get("contact_chat_room_type")?,
             contact_chat_room_notify: row.get("contact_chat_room_notify")?,
-        })
+            contact_is_openim: false,
+            contact_corp_name: None,
+        };
+        Ok((session, contact_extra_buf_bytes))
     })?;
 
     let mut sessions = Vec::new();
     for row_result in mapped_rows {
-        match row_result {
-            Ok(session_info) => sessions.push(session_info),
-            Err(e) => {
-                // Log error and continue, to collect all successfully mapped ones
-                eprintln!("Error processing a session row, skipping: {}", e);
-            }
-        }
+        let (mut session, contact_extra_buf_bytes) = row_result?;
+        session.contact_extra_buf_info = parse_extra_buf(contact_extra_buf_bytes.as_deref())
+            .map_err(|e| WxDumpError::parse(format!("ExtraBuf of {}", session.wxid), e))?;
+        sessions.push(session);
+    }
+
+    // Enterprise WeChat sessions have no Contact row and are dropped by the join above; those
+    // that do have one are already there.
+    let known: HashSet<String> = sessions.iter().map(|s| s.wxid.clone()).collect();
+    let openim_sessions: Vec<SessionInfo> =
+        get_openim_sessions(conn)?.into_iter().filter(|s| !known.contains(&s.wxid)).collect();
+    if !openim_sessions.is_empty() {
+        sessions.extend(openim_sessions);
+        sessions.sort_by_key(|s| std::cmp::Reverse(s.timestamp));
     }
 
     Ok(sessions)
@@ -665,7 +691,7 @@ pub fn get_recent_chat_wxids(conn: &Connection, limit: usize) -> Result<Vec<Stri
         SELECT strUsrName
         FROM Session
         WHERE strUsrName NOT LIKE '%@chatroom'
-          AND strUsrName NOT LIKE '%@openim'
+          AND strUsrName NOT LIKE '%@im.chatroom'
           AND strUsrName NOT LIKE 'gh_%'
         ORDER BY nOrder DESC
         LIMIT ?;
@@ -737,13 +763,13 @@ mod tests {
     fn test_parse_real_world_example_shortened() {
         // This is a shortened and modified example based on typical ExtraBuf structure
         // Contains: Gender (Female=2), Signature ("Test Signature"), Country ("US")
-        let hex_data = "someprefixbytes\
+        let hex_data = "0A0B0C0D\
                         74752C06010102\
-                        somerandombytesbetween\
+                        DEADBEEF\
                         46CF10C4021C00540065007300740020005300690067006E0061007400750072006500\
-                        anotherbunchofrandombytes\
+                        CAFEBABE\
                         A4D9024A02040055005300\
-                        suffixbytes";
+                        0E0F";
         let bytes = hex::decode(hex_data).unwrap();
         let result = parse_extra_buf(Some(&bytes)).unwrap().unwrap();
 
@@ -769,4 +795,46 @@ mod tests {
         assert_eq!(result_partial_sig.gender, Some(1));
         assert!(result_partial_sig.signature.is_none());
     }
+
+    #[test]
+    fn test_micro_msg_fixture() {
+        use crate::core::testutil::{extra_buf, micro_msg_db, room_data};
+
+        let info = ExtraBufInfo { gender: Some(1), city: Some("深圳".into()), remark_img_url2: Some("u".into()), ..Default::default() };
+        let parsed = parse_extra_buf(Some(&extra_buf(&info))).unwrap().unwrap();
+        assert_eq!((parsed.gender, parsed.city.as_deref(), parsed.remark_img_url2.as_deref()), (Some(1), Some("深圳"), Some("u")));
+        assert!(parsed.signature.is_none());
+        let names = parse_chat_room_data(Some(&room_data(&[("wxid_a", "A"), ("wxid_b", "")]))).unwrap();
+        assert_eq!(names, HashMap::from([("wxid_a".to_string(), "A".to_string())]));
+        assert!(parse_chat_room_data(Some(&[0x0A, 0x05])).is_err());
+
+        let conn = micro_msg_db();
+        let contacts = get_contacts(&conn, None, None, None).unwrap();
+        let carol = contacts.iter().find(|c| c.wxid == "wxid_carol").unwrap();
+        assert_eq!(carol.label_list, vec!["Family", "Hiking"]);
+        assert_eq!(carol.head_img_url.as_deref(), Some("http://img/carol.jpg"));
+        let extra = carol.extra_buf_info.as_ref().unwrap();
+        assert_eq!((extra.gender, extra.signature.as_deref(), extra.city.as_deref()), (Some(2), Some("山不在高"), Some("Shenzhen")));
+        let dave = contacts.iter().find(|c| c.wxid == "wxid_dave").unwrap();
+        assert_eq!(dave.extra_buf_info.as_ref().unwrap().mobile_phone.as_deref(), Some("13800000000"));
+
+        let rooms = get_chat_rooms(&conn, None).unwrap();
+        let room = &rooms["456@chatroom"];
+        assert_eq!(room.member_wxids, vec!["wxid_me", "wxid_carol", "wxid_dave"]);
+        assert_eq!(room.owner_wxid.as_deref(), Some("wxid_me"));
+        assert_eq!(room.announcement.as_deref(), Some("Saturday 8:00"));
+        let member = |wxid: &str| room.members.iter().find(|m| m.wxid == wxid).unwrap().room_nickname.clone();
+        assert_eq!(member("wxid_carol").as_deref(), Some("Carol 🏔"));
+        assert_eq!(member("wxid_dave"), None);
+
+        conn.execute("UPDATE ChatRoom SET RoomData = x'0A05' WHERE ChatRoomName = '456@chatroom'", []).unwrap();
+        let rooms = get_chat_rooms(&conn, None).unwrap();
+        assert_eq!(rooms["456@chatroom"].members.len(), 3);
+        assert!(rooms["456@chatroom"].members.iter().all(|m| m.room_nickname.is_none()));
+
+        let sessions = get_sessions(&conn).unwrap();
+        assert_eq!(sessions.iter().map(|s| s.wxid.as_str()).collect::<Vec<_>>(), vec!["456@chatroom", "wxid_carol"]);
+        assert_eq!(sessions[0].unread_count, Some(3));
+        assert_eq!(sessions[1].contact_remark.as_deref(), Some("Carol (cousin)"));
+    }
 }
\ No newline at end of file
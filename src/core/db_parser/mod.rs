@@ -2,67 +2,154 @@
 
 pub mod micro_msg_parser; 
 pub use micro_msg_parser::*; 
+pub mod msg_parser;
+pub use msg_parser::*;
+pub mod media_parser;
+pub use media_parser::*;
+pub mod emotion_parser;
+pub use emotion_parser::*;
+pub mod fts_parser;
+pub use fts_parser::*;
+pub mod sns_parser;
+pub use sns_parser::*;
+pub mod favorite_parser;
+pub use favorite_parser::*;
+pub mod public_msg_parser;
+pub use public_msg_parser::*;
+pub mod openim_parser;
+pub use openim_parser::*;
 
 use anyhow::{Result, anyhow};
+use log::{debug, warn};
 use rusqlite::{Result as RusqliteResult, types::Value};
 use std::collections::HashMap;
 use rusqlite::{Connection, OpenFlags, Error};
-use std::path::PathBuf;
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::core::decryption::sqlcipher_raw_key;
+use crate::core::error::WxDumpError;
+use crate::core::logging::redact_key;
+
+/// PRAGMAs of the SQLCipher 3 format used by WeChat 3.x (the key itself is set first).
+const WECHAT_CIPHER_PRAGMAS: &str = "PRAGMA cipher_page_size = 4096;\
+                                     PRAGMA kdf_iter = 64000;\
+                                     PRAGMA cipher_hmac_algorithm = HMAC_SHA1;\
+                                     PRAGMA cipher_kdf_algorithm = PBKDF2_HMAC_SHA1;";
 
 /// Opens a potentially SQLCipher encrypted SQLite database and sets the key and PRAGMAs.
-/// This function now expects to operate on files that might still require SQLCipher PRAGMAs
-/// to be correctly interpreted, even if page content is decrypted (due to preserved page reserved areas).
-pub fn open_database(db_path: &PathBuf, key: &str) -> Result<Connection> {
+/// `key` is the raw SQLCipher key in hex (empty for none), used as `PRAGMA key = x'...'`;
+/// to read a WeChat database with the account key use [`open_encrypted_database`].
+pub fn open_database(db_path: &Path, key: &str) -> Result<Connection> {
     if !db_path.exists() {
-        return Err(anyhow!("Database file not found: {:?}", db_path));
+        return Err(WxDumpError::DatabaseNotFound(db_path.to_path_buf()).into());
     }
-    // Key can be empty if we expect SQLCipher to handle a db with decrypted content but SQLCipher structure.
-    // if key.is_empty() {
-    //     return Err(anyhow!("Database key is empty."));
-    // }
+    let conn = Connection::open_with_flags(
+        db_path,
+        OpenFlags::SQLITE_OPEN_READ_WRITE | OpenFlags::SQLITE_OPEN_CREATE | OpenFlags::SQLITE_OPEN_NO_MUTEX,
+    )?;
+    debug!("Opened database file: {:?}", db_path);
 
-    let conn = Connection::open_with_flags(db_path, OpenFlags::SQLITE_OPEN_READ_WRITE | OpenFlags::SQLITE_OPEN_CREATE | OpenFlags::SQLITE_OPEN_NO_MUTEX)?;
-    println!("[DBParser] Opened database file: {:?}", db_path);
+    let key_pragma_value = if key.is_empty() { "''".to_string() } else { format!("x'{}'", key) };
+    conn.pragma_update(None, "key", &key_pragma_value)
+        .map_err(|e| anyhow!("Failed to set the key of {:?}: {}", db_path, e))?;
+    // These might be necessary for SQLCipher to interpret the page structure even if the content is decrypted.
+    if let Err(e) = conn.execute_batch(&format!("{}PRAGMA cipher_compatibility = 1;", WECHAT_CIPHER_PRAGMAS)) {
+        warn!("Failed to set some SQLCipher PRAGMAs on {:?}: {}. Proceeding anyway.", db_path, e);
+    }
+    conn.query_row("SELECT count(*) FROM sqlite_master;", [], |row| row.get::<_, i64>(0)).map_err(|e| {
+        anyhow!("Failed to verify database {:?} (key: '{}') after PRAGMA settings: {}", db_path, redact_key(key), e)
+    })?;
+    Ok(conn)
+}
 
-    // Set the key. If key is empty, SQLCipher might use a default or no encryption.
-    let key_pragma_value = if key.is_empty() {
-        "''".to_string() // Empty key for SQLCipher
-    } else {
-        format!("x'{}'", key)
-    };
-    
-    match conn.pragma_update(None, "key", &key_pragma_value) {
-        Ok(_) => println!("[DBParser] PRAGMA key = '{}' executed successfully.", key_pragma_value),
-        Err(e) => {
-            eprintln!("[DBParser] Failed to execute PRAGMA key = '{}': {}. Database might not be accessible.", key_pragma_value, e);
-            return Err(anyhow!("Failed to set database key with PRAGMA key = '{}': {}", key_pragma_value, e));
-        }
+/// Opens a WeChat database read-only. With `account_key` (the 64 hex char key read from the
+/// WeChat process) the encrypted file is read in place through SQLCipher, the page key being
+/// derived from it and the file's salt, so no plaintext copy is ever written to disk; without
+/// it the file must already be decrypted.
+pub fn open_encrypted_database(db_path: &Path, account_key: Option<&str>) -> Result<Connection> {
+    let key = account_key.filter(|key| !key.is_empty());
+    if !db_path.exists() {
+        return Err(WxDumpError::DatabaseNotFound(db_path.to_path_buf()).into());
     }
-    
-    // Set other SQLCipher PRAGMAs that were found in the Python source or are common.
-    // These might be necessary for SQLCipher to correctly interpret page structure even if content is decrypted.
-    let pragma_batch = "PRAGMA cipher_page_size = 4096;\
-                        PRAGMA kdf_iter = 64000;\
-                        PRAGMA cipher_hmac_algorithm = HMAC_SHA1;\
-                        PRAGMA cipher_kdf_algorithm = PBKDF2_HMAC_SHA1;\
-                        PRAGMA cipher_compatibility = 1;"; // Changed to compatibility mode 1
-
-    match conn.execute_batch(pragma_batch) {
-        Ok(_) => println!("[DBParser] SQLCipher PRAGMAs (page_size, kdf_iter, compatibility, etc.) set successfully."),
-        Err(e) => println!("[DBParser] Warning: Failed to set some SQLCipher PRAGMAs: {}. Proceeding anyway.", e),
+    let conn = Connection::open_with_flags(db_path, OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_NO_MUTEX)?;
+    if let Some(key) = key {
+        let raw_key = sqlcipher_raw_key(db_path, key).map_err(|e| anyhow!("{:?}: {}", db_path, e))?;
+        conn.pragma_update(None, "key", &raw_key)?;
+        conn.execute_batch(WECHAT_CIPHER_PRAGMAS)?;
     }
+    verify_readable(&conn, "main", db_path, key.is_some())?;
+    Ok(conn)
+}
 
-    // Verify readability by a simple query.
-    match conn.query_row("SELECT count(*) FROM sqlite_master;", [], |row| row.get::<_, i32>(0)) {
-        Ok(count) => {
-            println!("[DBParser] Successfully queried sqlite_master, found {} entries. Key and PRAGMAs likely correct for file structure.", count);
-            Ok(conn)
-        }
-        Err(e) => {
-            eprintln!("[DBParser] Failed to query sqlite_master after setting key and PRAGMAs (key used: {}): {}", key_pragma_value, e);
-            eprintln!("[DBParser] This indicates an issue with the key, PRAGMAs, or the DB file structure/corruption.");
-            Err(anyhow!("Failed to verify database (key: '{}', path: {:?}) after PRAGMA settings: {}", key_pragma_value, db_path, e))
+/// Attaches another database of the account to `conn` as `schema`.
+///
+/// ATTACH ... KEY would take its SQLCipher settings from the process-wide defaults, so an
+/// encrypted file (`key` given) is instead decrypted by a connection of its own into a private
+/// in-memory database, which is attached. The plaintext copy never touches the disk and is
+/// freed once it is detached or `conn` is closed.
+pub fn attach_database(conn: &Connection, db_path: &Path, schema: &str, key: Option<&str>) -> Result<()> {
+    if !db_path.exists() {
+        return Err(WxDumpError::DatabaseNotFound(db_path.to_path_buf()).into());
+    }
+    let (source, uri) = match key {
+        Some(key) => {
+            let (source, uri) = decrypt_to_memory(db_path, key)?;
+            (Some(source), uri)
         }
+        None => (None, db_path.to_string_lossy().into_owned()),
+    };
+    // An explicit empty key keeps SQLCipher from reusing the key of the main database.
+    conn.execute(&format!("ATTACH DATABASE ? AS {} KEY ''", crate::core::db_browser::quote_identifier(schema)), [&uri])
+        .map_err(|e| anyhow!("Failed to attach {:?}: {}", db_path, e))?;
+    // The in-memory copy lives as long as a connection has it open, now `conn`.
+    drop(source);
+    verify_readable(conn, schema, db_path, false)
+}
+
+/// Decrypts `db_path` with `key` into a new shared in-memory database and returns the
+/// connection keeping it alive (it stays attached there) with the URI to attach it by.
+fn decrypt_to_memory(db_path: &Path, key: &str) -> Result<(Connection, String)> {
+    static NEXT_ID: AtomicUsize = AtomicUsize::new(0);
+    // The connection must be writable for the export into the attached memory database;
+    // `mode=ro` still opens the file itself read-only.
+    let source = Connection::open_with_flags(
+        format!("{}?mode=ro", file_uri(db_path)),
+        OpenFlags::SQLITE_OPEN_READ_WRITE | OpenFlags::SQLITE_OPEN_URI | OpenFlags::SQLITE_OPEN_NO_MUTEX,
+    )?;
+    let raw_key = sqlcipher_raw_key(db_path, key).map_err(|e| anyhow!("{:?}: {}", db_path, e))?;
+    source.pragma_update(None, "key", &raw_key)?;
+    source.execute_batch(WECHAT_CIPHER_PRAGMAS)?;
+    verify_readable(&source, "main", db_path, true)?;
+
+    let uri = format!("file:/wxdump-{}-{}?vfs=memdb", std::process::id(), NEXT_ID.fetch_add(1, Ordering::Relaxed));
+    source.execute("ATTACH DATABASE ? AS plaintext KEY ''", [&uri])?;
+    source.query_row("SELECT sqlcipher_export('plaintext')", [], |_| Ok(()))?;
+    Ok((source, uri))
+}
+
+/// `file:` URI of `path`, with the characters that URIs give a meaning escaped.
+fn file_uri(path: &Path) -> String {
+    let mut uri = path.to_string_lossy().replace('\\', "/");
+    for (c, escaped) in [("%", "%25"), ("?", "%3f"), ("#", "%23")] {
+        uri = uri.replace(c, escaped);
+    }
+    if uri.starts_with('/') {
+        format!("file://{}", uri)
+    } else if path.is_absolute() {
+        // Windows drive paths: file:///C:/...
+        format!("file:///{}", uri)
+    } else {
+        format!("file:{}", uri)
+    }
+}
+
+fn verify_readable(conn: &Connection, schema: &str, db_path: &Path, encrypted: bool) -> Result<()> {
+    let sql = format!("SELECT count(*) FROM {}.sqlite_master;", crate::core::db_browser::quote_identifier(schema));
+    match conn.query_row(&sql, [], |row| row.get::<_, i64>(0)) {
+        Ok(_) => Ok(()),
+        Err(e) if encrypted => Err(WxDumpError::KeyInvalid(format!("cannot read {:?} with the given key (wrong key or not a WeChat database): {}", db_path, e)).into()),
+        Err(e) => Err(WxDumpError::InvalidArgument(format!("{:?} is encrypted, key required (pass --key): {}", db_path, e)).into()),
     }
 }
 
@@ -89,7 +176,7 @@ pub fn connect_sqlite_db(db_path: &std::path::Path) -> std::result::Result<rusql
 /// # Arguments
 ///
 /// * `conn` - A reference to the `rusqlite::Connection`.
-/// * `table_name` - The name of the table to fetch data from. Assumed to be trusted.
+/// * `table_name` - The name of the table to fetch data from. It is quoted, so any name is safe.
 ///
 /// # Returns
 ///
@@ -99,13 +186,30 @@ pub fn get_all_rows_from_table(
     conn: &Connection,
     table_name: &str,
 ) -> RusqliteResult<Vec<HashMap<String, Value>>> {
-    // Construct the SQL query.
-    // IMPORTANT: table_name is assumed to be trusted and not from direct user input
-    // to prevent SQL injection. For this task, this assumption is acceptable.
-    let query = format!("SELECT * FROM {}", table_name);
-    let mut stmt = conn.prepare(&query)?;
+    let query = format!("SELECT * FROM {}", crate::core::db_browser::quote_identifier(table_name));
+    rows_as_maps(conn, &query, [])
+}
+
+/// Like [`get_all_rows_from_table`], but only `limit` rows starting at `offset`, in the order SQLite stores them.
+pub fn get_rows_from_table(
+    conn: &Connection,
+    table_name: &str,
+    limit: usize,
+    offset: usize,
+) -> RusqliteResult<Vec<HashMap<String, Value>>> {
+    let query = format!("SELECT * FROM {} LIMIT ? OFFSET ?", crate::core::db_browser::quote_identifier(table_name));
+    rows_as_maps(conn, &query, [limit as i64, offset as i64])
+}
+
+pub fn count_table_rows(conn: &Connection, table_name: &str) -> RusqliteResult<i64> {
+    let query = format!("SELECT COUNT(*) FROM {}", crate::core::db_browser::quote_identifier(table_name));
+    conn.query_row(&query, [], |row| row.get(0))
+}
+
+fn rows_as_maps(conn: &Connection, query: &str, params: impl rusqlite::Params) -> RusqliteResult<Vec<HashMap<String, Value>>> {
+    let mut stmt = conn.prepare(query)?;
 
-    let mut rows = stmt.query_map([], |row| {
+    let mut rows = stmt.query_map(params, |row| {
         let mut map = HashMap::new();
         let column_count = row.as_ref().column_count();
         for i in 0..column_count {
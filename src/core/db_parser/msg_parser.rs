@@ -0,0 +1,546 @@
+use anyhow::Result;
+use rusqlite::Connection;
+use serde::Serialize;
+
+use super::micro_msg_parser::format_timestamp_to_string;
+use super::openim_parser::is_chat_room_wxid;
+use crate::core::protobuf::parse_message;
+
+// Values of MSG.Type, as used by WeChat 3.x on Windows.
+pub const MSG_TYPE_TEXT: i64 = 1;
+pub const MSG_TYPE_IMAGE: i64 = 3;
+pub const MSG_TYPE_VOICE: i64 = 34;
+pub const MSG_TYPE_VIDEO: i64 = 43;
+pub const MSG_TYPE_EMOJI: i64 = 47;
+pub const MSG_TYPE_LOCATION: i64 = 48;
+pub const MSG_TYPE_APP: i64 = 49;
+pub const MSG_TYPE_SYSTEM: i64 = 10000;
+pub const MSG_TYPE_SYSMSG: i64 = 10002; // XML <sysmsg>, e.g. revoke notices
+
+/// `<appmsg><type>` of a quote reply.
+pub const APP_MSG_TYPE_QUOTE: i64 = 57;
+
+// Values of the `1` field of BytesExtra's repeated `3` entries.
+const BYTES_EXTRA_SENDER_WXID: u64 = 1;
+const BYTES_EXTRA_THUMB_PATH: u64 = 3;
+const BYTES_EXTRA_FILE_PATH: u64 = 4;
+
+/// A single row of the `MSG` table (MSG0.db, MSG1.db, ... or a merged db).
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct Message {
+    pub local_id: i64,                     // From localId
+    pub msg_svr_id: Option<i64>,           // From MsgSvrID, also the key into MediaMSG.db
+    pub msg_type: i64,                     // From Type
+    pub sub_type: i64,                     // From SubType
+    pub is_sender: bool,                   // From IsSender
+    pub create_time: i64,                  // From CreateTime (unix seconds)
+    pub time_str: String,                  // CreateTime formatted as "%Y-%m-%d %H:%M:%S"
+    pub talker: String,                    // From StrTalker (wxid or xxx@chatroom)
+    pub content: Option<String>,           // From StrContent
+    pub display_content: Option<String>,   // From DisplayContent
+    #[serde(skip_serializing)]
+    pub compress_content: Option<Vec<u8>>, // From CompressContent (lz4 block, mostly type 49)
+    #[serde(skip_serializing)]
+    pub bytes_extra: Option<Vec<u8>>,      // From BytesExtra (protobuf)
+}
+
+/// Columns read by `message_from_row`.
+const MESSAGE_COLUMNS: &str = "localId, MsgSvrID, Type, SubType, IsSender, CreateTime, StrTalker, \
+     StrContent, DisplayContent, CompressContent, BytesExtra";
+
+fn message_from_row(row: &rusqlite::Row) -> rusqlite::Result<Message> {
+    let create_time: i64 = row.get::<_, Option<i64>>("CreateTime")?.unwrap_or(0);
+    Ok(Message {
+        local_id: row.get("localId")?,
+        msg_svr_id: row.get("MsgSvrID")?,
+        msg_type: row.get::<_, Option<i64>>("Type")?.unwrap_or(0),
+        sub_type: row.get::<_, Option<i64>>("SubType")?.unwrap_or(0),
+        is_sender: row.get::<_, Option<i64>>("IsSender")?.unwrap_or(0) == 1,
+        create_time,
+        time_str: format_timestamp_to_string(create_time, "%Y-%m-%d %H:%M:%S"),
+        talker: row.get::<_, Option<String>>("StrTalker")?.unwrap_or_default(),
+        content: row.get("StrContent")?,
+        display_content: row.get("DisplayContent")?,
+        compress_content: row.get("CompressContent")?,
+        bytes_extra: row.get("BytesExtra")?,
+    })
+}
+
+/// Retrieves messages from the MSG table.
+/// Corresponds roughly to Python's `get_msg_list`.
+///
+/// All filters are optional; `start_time`/`end_time` are unix timestamps (inclusive).
+/// Results are ordered by CreateTime ascending.
+pub fn get_messages(
+    conn: &Connection,
+    filter_talker: Option<&str>,
+    filter_msg_types: Option<&[i64]>,
+    start_time: Option<i64>,
+    end_time: Option<i64>,
+    limit: Option<usize>,
+    offset: Option<usize>,
+) -> Result<Vec<Message>> {
+    let mut messages = Vec::new();
+    for_each_message(conn, filter_talker, filter_msg_types, start_time, end_time, limit, offset, |msg| {
+        messages.push(msg);
+        Ok(())
+    })?;
+    Ok(messages)
+}
+
+/// Like `get_messages`, but hands rows to `f` one at a time instead of collecting them,
+/// so whole MSG tables can be exported without holding them in memory.
+/// Returns the number of rows visited.
+#[allow(clippy::too_many_arguments)]
+pub fn for_each_message(
+    conn: &Connection,
+    filter_talker: Option<&str>,
+    filter_msg_types: Option<&[i64]>,
+    start_time: Option<i64>,
+    end_time: Option<i64>,
+    limit: Option<usize>,
+    offset: Option<usize>,
+    mut f: impl FnMut(Message) -> Result<()>,
+) -> Result<usize> {
+    let mut sql = format!("SELECT {} FROM MSG", MESSAGE_COLUMNS);
+
+    let mut conditions: Vec<String> = Vec::new();
+    let mut params_list: Vec<Box<dyn rusqlite::ToSql>> = Vec::new();
+
+    if let Some(talker) = filter_talker {
+        conditions.push("StrTalker = ?".to_string());
+        params_list.push(Box::new(talker.to_string()));
+    }
+
+    if let Some(msg_types) = filter_msg_types {
+        if !msg_types.is_empty() {
+            let placeholders = msg_types.iter().map(|_| "?").collect::<Vec<&str>>().join(",");
+            conditions.push(format!("Type IN ({})", placeholders));
+            for t in msg_types {
+                params_list.push(Box::new(*t));
+            }
+        } else {
+            conditions.push("1=0".to_string());
+        }
+    }
+
+    if let Some(start) = start_time {
+        conditions.push("CreateTime >= ?".to_string());
+        params_list.push(Box::new(start));
+    }
+    if let Some(end) = end_time {
+        conditions.push("CreateTime <= ?".to_string());
+        params_list.push(Box::new(end));
+    }
+
+    if !conditions.is_empty() {
+        sql.push_str(" WHERE ");
+        sql.push_str(&conditions.join(" AND "));
+    }
+    sql.push_str(" ORDER BY CreateTime ASC, localId ASC");
+
+    if limit.is_some() || offset.is_some() {
+        // SQLite only accepts OFFSET together with LIMIT; -1 means "no limit".
+        sql.push_str(" LIMIT ? OFFSET ?");
+        params_list.push(Box::new(limit.map_or(-1, |l| l as i64)));
+        params_list.push(Box::new(offset.unwrap_or(0) as i64));
+    }
+    sql.push(';');
+
+    let params_for_query: Vec<&dyn rusqlite::ToSql> = params_list.iter().map(|p| p.as_ref()).collect();
+    let mut stmt = conn.prepare(&sql)?;
+    let msg_iter = stmt.query_map(&*params_for_query, message_from_row)?;
+
+    let mut count = 0;
+    for msg_result in msg_iter {
+        f(msg_result?)?;
+        count += 1;
+    }
+
+    Ok(count)
+}
+
+/// Messages whose MsgSvrID is in `svr_ids`, in the order of the ids (unknown ids are skipped).
+pub fn get_messages_by_svr_ids(conn: &Connection, svr_ids: &[i64]) -> Result<Vec<Message>> {
+    get_messages_by_key(conn, "MsgSvrID", svr_ids)
+}
+
+/// Messages whose localId is in `local_ids`, in the order of the ids (unknown ids are skipped).
+pub fn get_messages_by_local_ids(conn: &Connection, local_ids: &[i64]) -> Result<Vec<Message>> {
+    get_messages_by_key(conn, "localId", local_ids)
+}
+
+fn get_messages_by_key(conn: &Connection, column: &str, ids: &[i64]) -> Result<Vec<Message>> {
+    let mut stmt = conn.prepare(&format!("SELECT {} FROM MSG WHERE {} = ? LIMIT 1", MESSAGE_COLUMNS, column))?;
+    let mut messages = Vec::with_capacity(ids.len());
+    for id in ids {
+        if let Some(msg) = stmt.query_map([id], message_from_row)?.next() {
+            messages.push(msg?);
+        }
+    }
+    Ok(messages)
+}
+
+/// Counts the messages of a conversation (all conversations if `filter_talker` is None),
+/// optionally limited to `start_time..=end_time` like `get_messages`.
+pub fn count_messages(
+    conn: &Connection,
+    filter_talker: Option<&str>,
+    start_time: Option<i64>,
+    end_time: Option<i64>,
+) -> Result<i64> {
+    let mut sql = String::from("SELECT COUNT(*) FROM MSG");
+    let mut conditions: Vec<String> = Vec::new();
+    let mut params_list: Vec<Box<dyn rusqlite::ToSql>> = Vec::new();
+
+    if let Some(talker) = filter_talker {
+        conditions.push("StrTalker = ?".to_string());
+        params_list.push(Box::new(talker.to_string()));
+    }
+    if let Some(start) = start_time {
+        conditions.push("CreateTime >= ?".to_string());
+        params_list.push(Box::new(start));
+    }
+    if let Some(end) = end_time {
+        conditions.push("CreateTime <= ?".to_string());
+        params_list.push(Box::new(end));
+    }
+    if !conditions.is_empty() {
+        sql.push_str(" WHERE ");
+        sql.push_str(&conditions.join(" AND "));
+    }
+    sql.push(';');
+
+    let params_for_query: Vec<&dyn rusqlite::ToSql> = params_list.iter().map(|p| p.as_ref()).collect();
+    let count = conn.query_row(&sql, &*params_for_query, |row| row.get(0))?;
+    Ok(count)
+}
+
+/// Distinct talkers (wxids and chat rooms) that have messages, most active first.
+pub fn list_talkers(conn: &Connection) -> Result<Vec<String>> {
+    let mut stmt = conn.prepare(
+        "SELECT StrTalker FROM MSG WHERE StrTalker IS NOT NULL AND StrTalker != '' \
+         GROUP BY StrTalker ORDER BY COUNT(*) DESC, StrTalker;",
+    )?;
+    let talkers = stmt.query_map([], |row| row.get(0))?.collect::<rusqlite::Result<Vec<String>>>()?;
+    Ok(talkers)
+}
+
+/// The wxid of whoever sent `msg`: `my_wxid` for own messages, the BytesExtra sender
+/// in chat rooms, otherwise the talker itself.
+pub fn message_sender_wxid(msg: &Message, my_wxid: &str) -> String {
+    if msg.is_sender {
+        return my_wxid.to_string();
+    }
+    if is_chat_room_wxid(&msg.talker) {
+        if let Ok(Some(extra)) = parse_bytes_extra(msg.bytes_extra.as_deref()) {
+            if let Some(sender) = extra.sender_wxid {
+                return sender;
+            }
+        }
+    }
+    msg.talker.clone()
+}
+
+/// Decoded content of MSG.BytesExtra.
+///
+/// The blob is a protobuf `{1: {...}, 3: [{1: type, 2: value}, ...]}`; only the typed
+/// `3` entries carry anything useful.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct BytesExtraInfo {
+    pub sender_wxid: Option<String>, // type 1, set for messages in group chats
+    pub thumb_path: Option<String>,  // type 3, thumbnail of an image/video (relative to "WeChat Files")
+    pub file_path: Option<String>,   // type 4, the image .dat, video or file itself
+    pub entries: Vec<(u64, String)>, // all typed entries, in order
+}
+
+/// Parses MSG.BytesExtra. Corresponds to Python's `get_BytesExtra`.
+pub fn parse_bytes_extra(bytes_extra: Option<&[u8]>) -> Result<Option<BytesExtraInfo>> {
+    let bytes = match bytes_extra {
+        Some(b) if !b.is_empty() => b,
+        _ => return Ok(None),
+    };
+
+    let mut info = BytesExtraInfo::default();
+    for (field, value) in parse_message(bytes)? {
+        if field != 3 {
+            continue;
+        }
+        let Some(entry_bytes) = value.as_bytes() else { continue };
+        let mut entry_type = None;
+        let mut entry_value = None;
+        for (entry_field, entry_field_value) in parse_message(entry_bytes)? {
+            match entry_field {
+                1 => entry_type = entry_field_value.as_u64(),
+                2 => entry_value = entry_field_value.as_string(),
+                _ => {}
+            }
+        }
+        if let (Some(t), Some(v)) = (entry_type, entry_value) {
+            match t {
+                BYTES_EXTRA_SENDER_WXID => info.sender_wxid = Some(v.clone()),
+                BYTES_EXTRA_THUMB_PATH => info.thumb_path = Some(v.clone()),
+                BYTES_EXTRA_FILE_PATH => info.file_path = Some(v.clone()),
+                _ => {}
+            }
+            info.entries.push((t, v));
+        }
+    }
+    Ok(Some(info))
+}
+
+/// Decompresses MSG.CompressContent (an lz4 block without size prefix) to the XML it holds.
+/// Corresponds to Python's `decompress_CompressContent`.
+pub fn decompress_content(data: &[u8]) -> Result<String> {
+    // The uncompressed size is not stored; grow the buffer until it fits.
+    let mut capacity = data.len().max(64) * 4;
+    loop {
+        let mut buf = vec![0u8; capacity];
+        match lz4_flex::block::decompress_into(data, &mut buf) {
+            Ok(len) => {
+                buf.truncate(len);
+                // Some rows carry a trailing NUL.
+                let text = String::from_utf8_lossy(&buf);
+                return Ok(text.trim_end_matches('\0').to_string());
+            }
+            Err(lz4_flex::block::DecompressError::OutputTooSmall { .. }) if capacity < data.len() << 10 => {
+                capacity *= 4;
+            }
+            Err(e) => return Err(anyhow::anyhow!("Failed to decompress CompressContent: {}", e)),
+        }
+    }
+}
+
+/// Fields of an `<appmsg>` (type 49 message) that are useful for display and export.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct AppMsgInfo {
+    pub app_type: Option<i64>,   // <type>, e.g. 5 link, 6 file, 19 chat record, 57 quote
+    pub title: Option<String>,
+    pub des: Option<String>,
+    pub url: Option<String>,
+    pub thumb_url: Option<String>,
+    pub file_ext: Option<String>, // <appattach><fileext>
+    pub total_len: Option<i64>,   // <appattach><totallen>
+}
+
+/// Parses the `<msg><appmsg>...</appmsg></msg>` XML of a type 49 message
+/// (from StrContent or the decompressed CompressContent).
+pub fn parse_app_msg(xml: &str) -> Option<AppMsgInfo> {
+    let doc = roxmltree::Document::parse(xml.trim()).ok()?;
+    let appmsg = doc.descendants().find(|n| n.has_tag_name("appmsg"))?;
+    let child_text = |parent: roxmltree::Node, name: &str| -> Option<String> {
+        parent
+            .children()
+            .find(|n| n.has_tag_name(name))
+            .and_then(|n| n.text())
+            .map(|t| t.trim().to_string())
+            .filter(|t| !t.is_empty())
+    };
+    let appattach = appmsg.children().find(|n| n.has_tag_name("appattach"));
+
+    Some(AppMsgInfo {
+        app_type: child_text(appmsg, "type").and_then(|t| t.parse().ok()),
+        title: child_text(appmsg, "title"),
+        des: child_text(appmsg, "des"),
+        url: child_text(appmsg, "url"),
+        thumb_url: child_text(appmsg, "thumburl"),
+        file_ext: appattach.and_then(|a| child_text(a, "fileext")),
+        total_len: appattach.and_then(|a| child_text(a, "totallen")).and_then(|t| t.parse().ok()),
+    })
+}
+
+/// The XML of a type 49 message: the decompressed CompressContent, or StrContent for older rows.
+pub fn app_msg_xml(msg: &Message) -> Option<String> {
+    match &msg.compress_content {
+        Some(data) if !data.is_empty() => decompress_content(data).ok(),
+        _ => msg.content.clone(),
+    }
+}
+
+/// The `<location>` element of a type 48 message.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct LocationInfo {
+    pub latitude: Option<f64>,  // x
+    pub longitude: Option<f64>, // y
+    pub label: Option<String>,  // address
+    pub poi_name: Option<String>,
+}
+
+/// Parses `<msg><location x=".." y=".." label=".." poiname=".." .../></msg>`.
+pub fn parse_location(content: &str) -> Option<LocationInfo> {
+    let doc = roxmltree::Document::parse(content.trim()).ok()?;
+    let node = doc.descendants().find(|n| n.has_tag_name("location"))?;
+    let attr = |name: &str| node.attribute(name).map(|v| v.trim().to_string()).filter(|v| !v.is_empty());
+    Some(LocationInfo {
+        latitude: attr("x").and_then(|v| v.parse().ok()),
+        longitude: attr("y").and_then(|v| v.parse().ok()),
+        label: attr("label"),
+        poi_name: attr("poiname"),
+    })
+}
+
+/// Extracts the `voicelength` attribute (milliseconds) from a voice message's StrContent,
+/// e.g. `<msg><voicemsg endflag="1" length="5243" voicelength="3500" ... /></msg>`.
+pub fn parse_voice_length_ms(content: &str) -> Option<i64> {
+    let doc = roxmltree::Document::parse(content.trim()).ok()?;
+    let node = doc.descendants().find(|n| n.has_tag_name("voicemsg"))?;
+    node.attribute("voicelength")?.trim().parse::<i64>().ok()
+}
+
+/// The `<refermsg>` of a quote reply (type 49, `<appmsg><type>57`): the message being replied to.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ReferMsg {
+    pub msg_type: Option<i64>,        // <type>, MSG.Type of the quoted message
+    pub svr_id: Option<i64>,          // <svrid>, its MsgSvrID
+    pub sender_wxid: Option<String>,  // <chatusr> in chat rooms, else <fromusr>
+    pub display_name: Option<String>, // <displayname>
+    pub content: Option<String>,      // <content>, text or the quoted message's XML
+}
+
+/// Parses the quoted message out of a quote reply's XML.
+pub fn parse_refer_msg(xml: &str) -> Option<ReferMsg> {
+    let doc = roxmltree::Document::parse(xml.trim()).ok()?;
+    let refer = doc.descendants().find(|n| n.has_tag_name("refermsg"))?;
+    let child_text = |name: &str| -> Option<String> {
+        refer
+            .children()
+            .find(|n| n.has_tag_name(name))
+            .and_then(|n| n.text())
+            .map(|t| t.trim().to_string())
+            .filter(|t| !t.is_empty())
+    };
+
+    Some(ReferMsg {
+        msg_type: child_text("type").and_then(|t| t.parse().ok()),
+        svr_id: child_text("svrid").and_then(|t| t.parse().ok()),
+        sender_wxid: child_text("chatusr").or_else(|| child_text("fromusr")),
+        display_name: child_text("displayname"),
+        content: child_text("content"),
+    })
+}
+
+/// Readable text of a system message (10000) or `<sysmsg>` (10002, e.g. the
+/// `<revokemsg><replacemsg>` of a recalled message).
+pub fn system_message_text(msg: &Message) -> String {
+    let content = msg.content.as_deref().unwrap_or("").trim();
+    if msg.msg_type == MSG_TYPE_SYSMSG {
+        if let Ok(doc) = roxmltree::Document::parse(content) {
+            if let Some(text) = doc
+                .descendants()
+                .find(|n| n.has_tag_name("replacemsg") || n.has_tag_name("content"))
+                .and_then(|n| n.text())
+            {
+                return text.trim().to_string();
+            }
+        }
+    }
+    content.to_string()
+}
+
+/// Whether `msg` is the notice left behind by a recalled message.
+pub fn is_revoke_notice(msg: &Message) -> bool {
+    match msg.msg_type {
+        MSG_TYPE_SYSTEM => msg.content.as_deref().is_some_and(|c| c.contains("撤回了一条消息")),
+        MSG_TYPE_SYSMSG => msg.content.as_deref().is_some_and(|c| c.contains("revokemsg")),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_voice_length_ms() {
+        let xml = r#"<msg><voicemsg endflag="1" cancelflag="0" forwardflag="0" voiceformat="4" voicelength="3500" length="5243" bufid="0" /></msg>"#;
+        assert_eq!(parse_voice_length_ms(xml), Some(3500));
+        assert_eq!(parse_voice_length_ms("not xml"), None);
+        assert_eq!(parse_voice_length_ms("<msg><voicemsg /></msg>"), None);
+    }
+
+    #[test]
+    fn test_parse_bytes_extra() {
+        fn entry(t: u8, v: &str) -> Vec<u8> {
+            let mut inner = vec![0x08, t, 0x12, v.len() as u8];
+            inner.extend_from_slice(v.as_bytes());
+            let mut out = vec![0x1A, inner.len() as u8];
+            out.extend(inner);
+            out
+        }
+        let mut bytes = vec![0x0A, 0x04, 0x08, 0x10, 0x10, 0x00]; // field 1 header, ignored
+        bytes.extend(entry(1, "wxid_sender"));
+        bytes.extend(entry(3, "wxid_me\\FileStorage\\MsgAttach\\abc\\Thumb\\2023-04\\x_t.dat"));
+        bytes.extend(entry(4, "wxid_me\\FileStorage\\MsgAttach\\abc\\Image\\2023-04\\x.dat"));
+
+        let info = parse_bytes_extra(Some(&bytes)).unwrap().unwrap();
+        assert_eq!(info.sender_wxid.as_deref(), Some("wxid_sender"));
+        assert!(info.thumb_path.unwrap().ends_with("x_t.dat"));
+        assert!(info.file_path.unwrap().ends_with("Image\\2023-04\\x.dat"));
+        assert_eq!(info.entries.len(), 3);
+        assert!(parse_bytes_extra(None).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_decompress_content_and_parse_app_msg() {
+        let xml = "<msg><appmsg appid=\"\" sdkver=\"0\"><title>report.pdf</title><type>6</type>\
+                   <appattach><totallen>1024</totallen><fileext>pdf</fileext></appattach></appmsg></msg>";
+        let compressed = lz4_flex::block::compress(xml.as_bytes());
+        let decompressed = decompress_content(&compressed).unwrap();
+        assert_eq!(decompressed, xml);
+
+        let app = parse_app_msg(&decompressed).unwrap();
+        assert_eq!(app.app_type, Some(6));
+        assert_eq!(app.title.as_deref(), Some("report.pdf"));
+        assert_eq!(app.file_ext.as_deref(), Some("pdf"));
+        assert_eq!(app.total_len, Some(1024));
+        assert!(app.url.is_none());
+    }
+
+    #[test]
+    fn test_parse_refer_msg_and_system_text() {
+        let quote = "<msg><appmsg><title>me too</title><type>57</type><refermsg><type>1</type><svrid>42</svrid>\
+                     <fromusr>123@chatroom</fromusr><chatusr>wxid_a</chatusr><displayname>A</displayname>\
+                     <content>hello</content></refermsg></appmsg></msg>";
+        let refer = parse_refer_msg(quote).unwrap();
+        assert_eq!(refer.svr_id, Some(42));
+        assert_eq!(refer.sender_wxid.as_deref(), Some("wxid_a"));
+        assert_eq!(refer.content.as_deref(), Some("hello"));
+        assert_eq!(parse_app_msg(quote).unwrap().app_type, Some(APP_MSG_TYPE_QUOTE));
+
+        let revoke = Message {
+            msg_type: MSG_TYPE_SYSMSG,
+            content: Some("<sysmsg type=\"revokemsg\"><revokemsg><replacemsg><![CDATA[\"A\" 撤回了一条消息]]></replacemsg></revokemsg></sysmsg>".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(system_message_text(&revoke), "\"A\" 撤回了一条消息");
+        assert!(is_revoke_notice(&revoke));
+        let plain = Message { msg_type: MSG_TYPE_SYSTEM, content: Some("你撤回了一条消息".to_string()), ..Default::default() };
+        assert_eq!(system_message_text(&plain), "你撤回了一条消息");
+        assert!(is_revoke_notice(&plain));
+    }
+
+    #[test]
+    fn test_list_talkers_and_parse_location() {
+        let conn = crate::core::testutil::merged_db_with_sample_data();
+        assert_eq!(list_talkers(&conn).unwrap(), vec!["123@chatroom", "wxid_alice"]);
+
+        let loc = parse_location("<msg><location x=\"39.9\" y=\"116.4\" label=\"Beijing\" poiname=\"Tiananmen\" /></msg>").unwrap();
+        assert_eq!(loc.latitude, Some(39.9));
+        assert_eq!(loc.poi_name.as_deref(), Some("Tiananmen"));
+    }
+
+    #[test]
+    fn test_msg_fixture() {
+        let conn = crate::core::testutil::msg_db();
+        let image = &get_messages(&conn, Some("wxid_carol"), Some(&[MSG_TYPE_IMAGE]), None, None, None, None).unwrap()[0];
+        let extra = parse_bytes_extra(image.bytes_extra.as_deref()).unwrap().unwrap();
+        assert_eq!(extra.file_path.as_deref(), Some("wxid_me\\FileStorage\\Image\\2023-11\\3003.dat"));
+        assert!(extra.thumb_path.unwrap().ends_with("3003_t.dat"));
+
+        let room = get_messages(&conn, Some("456@chatroom"), None, None, None, None, None).unwrap();
+        let senders: Vec<String> = room.iter().map(|m| message_sender_wxid(m, "wxid_me")).collect();
+        assert_eq!(senders, vec!["wxid_dave", "wxid_carol", "wxid_me"]);
+        let refer = parse_refer_msg(&app_msg_xml(&room[1]).unwrap()).unwrap();
+        assert_eq!((refer.svr_id, refer.sender_wxid.as_deref()), (Some(4001), Some("wxid_dave")));
+    }
+}
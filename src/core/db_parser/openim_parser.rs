@@ -0,0 +1,437 @@
+// src/core/db_parser/openim_parser.rs
+
+use anyhow::Result;
+use rusqlite::Connection;
+use std::collections::HashMap;
+use std::path::Path;
+
+use super::{attach_database, format_timestamp_to_string, ChatRoomInfo, Contact, ExtraBufInfo, SessionInfo};
+
+/// Suffix of Enterprise WeChat (企业微信) users.
+pub const OPENIM_USER_SUFFIX: &str = "@openim";
+/// Suffix of chat rooms that include Enterprise WeChat users.
+pub const OPENIM_ROOM_SUFFIX: &str = "@im.chatroom";
+
+/// True for contacts and rooms stored in OpenIMContact.db rather than MicroMsg.db.
+pub fn is_openim_wxid(wxid: &str) -> bool {
+    wxid.ends_with(OPENIM_USER_SUFFIX) || wxid.ends_with(OPENIM_ROOM_SUFFIX)
+}
+
+/// True for both regular (`@chatroom`) and Enterprise WeChat (`@im.chatroom`) chat rooms.
+pub fn is_chat_room_wxid(wxid: &str) -> bool {
+    wxid.ends_with("@chatroom") || wxid.ends_with(OPENIM_ROOM_SUFFIX)
+}
+
+/// Whether `name` is a table of the main or any attached database.
+fn has_table(conn: &Connection, name: &str) -> bool {
+    conn.query_row("SELECT COUNT(*) FROM pragma_table_list WHERE name = ?", [name], |row| row.get::<_, i64>(0))
+        .map(|count| count > 0)
+        .unwrap_or(false)
+}
+
+/// Whether the OpenIMContact table is reachable from `conn`, either merged into it or attached
+/// with [`attach_openim_db`].
+pub fn has_openim_contacts(conn: &Connection) -> bool {
+    has_table(conn, "OpenIMContact")
+}
+
+/// Attaches OpenIMContact.db to `conn` (encrypted when `key` is given, see `attach_database`),
+/// after which the contact, chat room and session lookups of the MicroMsg tables also resolve
+/// `@openim` users.
+pub fn attach_openim_db(conn: &Connection, path: &Path, key: Option<&str>) -> Result<()> {
+    attach_database(conn, path, "openim", key)
+}
+
+/// Columns shared by the contact and session queries. The corp name comes from the
+/// OpenIMWordingInfo entry of DescWordingId, written as `@Corp` by the client.
+fn contact_columns(conn: &Connection) -> String {
+    let corp_name = if has_table(conn, "OpenIMWordingInfo") {
+        "(SELECT W.Wording FROM OpenIMWordingInfo W WHERE W.WordingId = A.DescWordingId \
+         ORDER BY W.Language = 'zh_CN' DESC LIMIT 1)"
+    } else {
+        "NULL"
+    };
+    format!(
+        "A.UserName, A.NickName, A.Remark, A.Type, A.Sex, \
+         COALESCE(NULLIF(A.BigHeadImgUrl, ''), A.SmallHeadImgUrl) AS HeadImgUrl, {} AS CorpName",
+        corp_name
+    )
+}
+
+fn corp_name_from_wording(wording: Option<String>) -> Option<String> {
+    wording
+        .map(|w| w.trim().trim_start_matches('@').to_string())
+        .filter(|w| !w.is_empty())
+}
+
+/// Reads Enterprise WeChat contacts from OpenIMContact as [`Contact`]s with `is_openim` set.
+///
+/// Filters mirror [`super::get_contacts`]: `filter_word` matches names and their pinyin, and
+/// without `filter_wxids` only users are returned (no `@im.chatroom` rooms).
+/// Returns nothing when the table is not available.
+pub fn get_openim_contacts(
+    conn: &Connection,
+    filter_word: Option<&str>,
+    filter_wxids: Option<&[String]>,
+) -> Result<Vec<Contact>> {
+    if !has_openim_contacts(conn) {
+        return Ok(Vec::new());
+    }
+    let mut sql = format!("SELECT {} FROM OpenIMContact A", contact_columns(conn));
+
+    let mut conditions: Vec<String> = Vec::new();
+    let mut params_list: Vec<Box<dyn rusqlite::ToSql>> = Vec::new();
+
+    if let Some(word) = filter_word {
+        let like_pattern = format!("%{}%", word);
+        let or_conditions = [
+            "LOWER(A.UserName) LIKE LOWER(?)",
+            "LOWER(A.NickName) LIKE LOWER(?)",
+            "LOWER(A.Remark) LIKE LOWER(?)",
+            "LOWER(A.NickNameQuanPin) LIKE LOWER(?)",
+            "LOWER(A.NickNamePYInit) LIKE LOWER(?)",
+            "LOWER(A.RemarkQuanPin) LIKE LOWER(?)",
+            "LOWER(A.RemarkPYInit) LIKE LOWER(?)",
+        ];
+        conditions.push(format!("({})", or_conditions.join(" OR ")));
+        for _ in 0..or_conditions.len() {
+            params_list.push(Box::new(like_pattern.clone()));
+        }
+    }
+
+    match filter_wxids {
+        Some([]) => conditions.push("1=0".to_string()),
+        Some(wxids) => {
+            let placeholders = wxids.iter().map(|_| "?").collect::<Vec<&str>>().join(",");
+            conditions.push(format!("A.UserName IN ({})", placeholders));
+            for wxid in wxids {
+                params_list.push(Box::new(wxid.clone()));
+            }
+        }
+        None => conditions.push(format!("A.UserName NOT LIKE '%{}'", OPENIM_ROOM_SUFFIX)),
+    }
+
+    sql.push_str(" WHERE ");
+    sql.push_str(&conditions.join(" AND "));
+    sql.push_str(" ORDER BY A.RemarkPYInit, A.NickNamePYInit, A.NickName;");
+
+    let params_for_query: Vec<&dyn rusqlite::ToSql> = params_list.iter().map(|p| p.as_ref()).collect();
+    let mut stmt = conn.prepare(&sql)?;
+    let rows = stmt.query_map(&*params_for_query, |row| {
+        let wxid: String = row.get("UserName")?;
+        let gender: Option<i64> = row.get("Sex")?;
+        let corp_name = corp_name_from_wording(row.get("CorpName")?);
+        Ok(Contact {
+            account: None,
+            nickname: row.get("NickName")?,
+            remark: row.get("Remark")?,
+            head_img_url: row.get("HeadImgUrl")?,
+            label_list: Vec::new(),
+            description: None,
+            extra_buf_info: Some(ExtraBufInfo { gender, company_name: corp_name.clone(), ..Default::default() }),
+            user_type: row.get("Type")?,
+            verify_flag: None,
+            chat_room_type: None,
+            del_flag: None,
+            reserved1: gender,
+            reserved2: None,
+            reserved5: None,
+            chat_room_notify: None,
+            is_chatroom_contact: is_chat_room_wxid(&wxid),
+            is_openim: true,
+            corp_name,
+            wxid,
+        })
+    })?;
+
+    let mut contacts = Vec::new();
+    for contact in rows {
+        contacts.push(contact?);
+    }
+    Ok(contacts)
+}
+
+/// Reads the `@im.chatroom` rooms of OpenIMContact, with their members from
+/// OpenIMChatRoomMember (ChatRoomName, UserName, DisplayName) when that table exists.
+///
+/// Member details are resolved through [`super::get_contacts`], so regular and Enterprise
+/// WeChat members both carry names when the MicroMsg tables are on the same connection.
+pub fn get_openim_chat_rooms(
+    conn: &Connection,
+    filter_room_wxids: Option<&[String]>,
+) -> Result<HashMap<String, ChatRoomInfo>> {
+    let mut rooms = HashMap::new();
+    if !has_openim_contacts(conn) {
+        return Ok(rooms);
+    }
+    let room_wxids: Vec<String> = match filter_room_wxids {
+        Some(wxids) => wxids.iter().filter(|w| w.ends_with(OPENIM_ROOM_SUFFIX)).cloned().collect(),
+        None => openim_room_wxids(conn)?,
+    };
+    if room_wxids.is_empty() {
+        return Ok(rooms);
+    }
+
+    let has_members = has_table(conn, "OpenIMChatRoomMember");
+    for room in get_openim_contacts(conn, None, Some(&room_wxids))? {
+        let mut member_wxids = Vec::new();
+        let mut room_nicknames = HashMap::new();
+        if has_members {
+            let mut stmt = conn.prepare(
+                "SELECT UserName, DisplayName FROM OpenIMChatRoomMember WHERE ChatRoomName = ? ORDER BY rowid",
+            )?;
+            let rows = stmt.query_map([&room.wxid], |row| {
+                Ok((row.get::<_, String>(0)?, row.get::<_, Option<String>>(1)?))
+            })?;
+            for row in rows {
+                let (wxid, display_name) = row?;
+                if let Some(name) = display_name.filter(|n| !n.is_empty()) {
+                    room_nicknames.insert(wxid.clone(), name);
+                }
+                member_wxids.push(wxid);
+            }
+        }
+
+        let members = super::get_contacts(conn, None, Some(&member_wxids), None)?
+            .into_iter()
+            .map(|contact| super::ChatRoomMember {
+                room_nickname: room_nicknames.get(&contact.wxid).cloned(),
+                wxid: contact.wxid,
+                nickname: contact.nickname,
+                remark: contact.remark,
+                account: contact.account,
+                head_img_url: contact.head_img_url,
+            })
+            .collect();
+
+        rooms.insert(
+            room.wxid.clone(),
+            ChatRoomInfo {
+                wxid: room.wxid,
+                member_wxids,
+                members,
+                is_openim: true,
+                corp_name: room.corp_name,
+                ..Default::default()
+            },
+        );
+    }
+    Ok(rooms)
+}
+
+/// Sessions of MicroMsg's Session table whose talker is an Enterprise WeChat contact or room,
+/// newest first, with the contact columns taken from OpenIMContact.
+pub fn get_openim_sessions(conn: &Connection) -> Result<Vec<SessionInfo>> {
+    if !has_openim_contacts(conn) || !has_table(conn, "Session") {
+        return Ok(Vec::new());
+    }
+    let sql = format!(
+        "SELECT S.strUsrName, S.nOrder, S.nUnReadCount, S.strNickName, S.nStatus, S.nIsSend, S.strContent, \
+         S.nMsgLocalID, S.nMsgStatus, S.nTime, S.nMsgType, S.Reserved2, {} \
+         FROM Session S INNER JOIN OpenIMContact A ON S.strUsrName = A.UserName \
+         WHERE S.nTime = (SELECT MAX(nTime) FROM Session WHERE strUsrName = S.strUsrName) \
+         ORDER BY S.nTime DESC;",
+        contact_columns(conn)
+    );
+    let mut stmt = conn.prepare(&sql)?;
+    let rows = stmt.query_map([], |row| {
+        let timestamp: Option<i64> = row.get("nTime")?;
+        let gender: Option<i64> = row.get("Sex")?;
+        let corp_name = corp_name_from_wording(row.get("CorpName")?);
+        Ok(SessionInfo {
+            wxid: row.get("strUsrName")?,
+            order_num: row.get("nOrder")?,
+            unread_count: row.get("nUnReadCount")?,
+            session_nickname: row.get("strNickName")?,
+            session_status: row.get("nStatus")?,
+            is_send: row.get("nIsSend")?,
+            content: row.get("strContent")?,
+            msg_local_id: row.get("nMsgLocalID")?,
+            msg_status: row.get("nMsgStatus")?,
+            timestamp,
+            time_str: timestamp.map(|ts| format_timestamp_to_string(ts, "%Y-%m-%d %H:%M:%S")),
+            msg_type: row.get("nMsgType")?,
+            msg_sub_type: row.get("Reserved2")?,
+            contact_nickname: row.get("NickName")?,
+            contact_remark: row.get("Remark")?,
+            contact_head_img_url: row.get("HeadImgUrl")?,
+            contact_extra_buf_info: Some(ExtraBufInfo {
+                gender,
+                company_name: corp_name.clone(),
+                ..Default::default()
+            }),
+            contact_type: row.get("Type")?,
+            contact_is_openim: true,
+            contact_corp_name: corp_name,
+            ..Default::default()
+        })
+    })?;
+
+    let mut sessions = Vec::new();
+    for session in rows {
+        sessions.push(session?);
+    }
+    Ok(sessions)
+}
+
+/// Display names (remark, then nickname) of every OpenIMContact entry, suffixed with
+/// `@Corp` the way the client shows them.
+pub fn get_openim_display_names(conn: &Connection) -> Result<HashMap<String, String>> {
+    Ok(get_openim_all(conn)?
+        .into_iter()
+        .map(|contact| {
+            let name = [&contact.remark, &contact.nickname]
+                .into_iter()
+                .flatten()
+                .find(|n| !n.is_empty())
+                .cloned()
+                .unwrap_or_else(|| contact.wxid.clone());
+            let name = match &contact.corp_name {
+                Some(corp) => format!("{}@{}", name, corp),
+                None => name,
+            };
+            (contact.wxid, name)
+        })
+        .collect())
+}
+
+/// Users and rooms of OpenIMContact alike.
+fn get_openim_all(conn: &Connection) -> Result<Vec<Contact>> {
+    let mut contacts = get_openim_contacts(conn, None, None)?;
+    if has_openim_contacts(conn) {
+        let rooms = openim_room_wxids(conn)?;
+        if !rooms.is_empty() {
+            contacts.extend(get_openim_contacts(conn, None, Some(&rooms))?);
+        }
+    }
+    Ok(contacts)
+}
+
+fn openim_room_wxids(conn: &Connection) -> Result<Vec<String>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT UserName FROM OpenIMContact WHERE UserName LIKE '%{}'",
+        OPENIM_ROOM_SUFFIX
+    ))?;
+    let wxids = stmt.query_map([], |row| row.get::<_, String>(0))?.collect::<rusqlite::Result<Vec<_>>>()?;
+    Ok(wxids)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::testutil::{merged_db_with_sample_data, write_encrypted_copy, OPENIM_CONTACT_SCHEMA, TEST_KEY};
+    use crate::core::db_parser::{get_chat_rooms, get_contacts, get_display_names, get_sessions};
+
+    const OPENIM_ROWS: &str = "
+        INSERT INTO OpenIMWordingInfo (WordingId, Language, Wording) VALUES ('w1', 'en', '@Acme');
+        INSERT INTO OpenIMWordingInfo (WordingId, Language, Wording) VALUES ('w1', 'zh_CN', '@艾克米');
+        INSERT INTO OpenIMContact (UserName, NickName, Remark, BigHeadImgUrl, SmallHeadImgUrl, Sex, DescWordingId,
+            NickNamePYInit) VALUES ('carol@openim', 'Carol', '', '', 'http://img/carol_s.jpg', 2, 'w1', 'CAROL');
+        INSERT INTO OpenIMContact (UserName, NickName) VALUES ('456@im.chatroom', 'Partners');
+        INSERT INTO OpenIMChatRoomMember (ChatRoomName, UserName, DisplayName)
+            VALUES ('456@im.chatroom', 'carol@openim', 'Carol (sales)');
+        INSERT INTO OpenIMChatRoomMember (ChatRoomName, UserName, DisplayName)
+            VALUES ('456@im.chatroom', 'wxid_alice', '');
+        INSERT INTO Session (strUsrName, nOrder, strNickName, strContent, nMsgType, nTime)
+            VALUES ('carol@openim', 3, 'Carol', 'quote attached', 1, 1700000050);";
+
+    fn db_with_openim() -> Connection {
+        let conn = merged_db_with_sample_data();
+        conn.execute_batch(OPENIM_CONTACT_SCHEMA).unwrap();
+        conn.execute_batch(OPENIM_ROWS).unwrap();
+        conn
+    }
+
+    #[test]
+    fn test_openim_contacts() {
+        let conn = db_with_openim();
+        let contacts = get_openim_contacts(&conn, None, None).unwrap();
+        assert_eq!(contacts.len(), 1);
+        let carol = &contacts[0];
+        assert!(carol.is_openim);
+        assert_eq!(carol.corp_name.as_deref(), Some("艾克米"));
+        assert_eq!(carol.head_img_url.as_deref(), Some("http://img/carol_s.jpg"));
+        assert_eq!(carol.reserved1, Some(2));
+
+        assert_eq!(get_openim_contacts(&conn, Some("carol"), None).unwrap().len(), 1);
+        assert!(get_openim_contacts(&conn, Some("alice"), None).unwrap().is_empty());
+        let room = get_openim_contacts(&conn, None, Some(&["456@im.chatroom".to_string()])).unwrap();
+        assert!(room[0].is_chatroom_contact);
+    }
+
+    #[test]
+    fn test_merged_into_contact_resolution() {
+        let conn = db_with_openim();
+        let contacts = get_contacts(&conn, None, Some(&["wxid_alice".to_string(), "carol@openim".to_string()]), None)
+            .unwrap();
+        let wxids: Vec<_> = contacts.iter().map(|c| (c.wxid.as_str(), c.is_openim)).collect();
+        assert_eq!(wxids, vec![("wxid_alice", false), ("carol@openim", true)]);
+        // A wxid also present in OpenIMContact comes once, from Contact.
+        conn.execute("INSERT INTO OpenIMContact (UserName, NickName) VALUES ('wxid_alice', 'Alice OpenIM')", []).unwrap();
+        let contacts = get_contacts(&conn, None, Some(&["wxid_alice".to_string()]), None).unwrap();
+        assert_eq!(contacts.len(), 1);
+        assert!(!contacts[0].is_openim);
+
+        let names = get_display_names(&conn).unwrap();
+        assert_eq!(names["carol@openim"], "Carol@艾克米");
+        assert_eq!(names["wxid_alice"], "Alice (work)");
+
+        let sessions = get_sessions(&conn).unwrap();
+        let order: Vec<_> = sessions.iter().map(|s| s.wxid.as_str()).collect();
+        assert_eq!(order, vec!["wxid_alice", "carol@openim", "123@chatroom"]);
+        assert!(sessions[1].contact_is_openim);
+        assert_eq!(sessions[1].contact_corp_name.as_deref(), Some("艾克米"));
+
+        let rooms = get_chat_rooms(&conn, None).unwrap();
+        assert_eq!(rooms.len(), 2);
+        let room = &rooms["456@im.chatroom"];
+        assert!(room.is_openim);
+        assert_eq!(room.member_wxids, vec!["carol@openim", "wxid_alice"]);
+        let carol = room.members.iter().find(|m| m.wxid == "carol@openim").unwrap();
+        assert_eq!(carol.room_nickname.as_deref(), Some("Carol (sales)"));
+        assert!(room.members.iter().any(|m| m.wxid == "wxid_alice" && m.room_nickname.is_none()));
+    }
+
+    #[test]
+    fn test_attach_openim_db() {
+        let path = std::env::temp_dir().join(format!("wxdump_rs_openim_{}.db", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        {
+            let openim = Connection::open(&path).unwrap();
+            openim.execute_batch(OPENIM_CONTACT_SCHEMA).unwrap();
+            openim
+                .execute("INSERT INTO OpenIMContact (UserName, NickName) VALUES ('dave@openim', 'Dave')", [])
+                .unwrap();
+        }
+        let conn = merged_db_with_sample_data();
+        assert!(!has_openim_contacts(&conn));
+        assert!(get_openim_sessions(&conn).unwrap().is_empty());
+        attach_openim_db(&conn, &path, None).unwrap();
+        assert!(has_openim_contacts(&conn));
+        let contacts = get_contacts(&conn, Some("dave"), None, None).unwrap();
+        assert_eq!(contacts.len(), 1);
+        assert!(contacts[0].corp_name.is_none());
+        drop(conn);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_attach_encrypted_openim_db() {
+        let path = std::env::temp_dir().join(format!("wxdump_rs_openim_enc_{}.db", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let plain = Connection::open_in_memory().unwrap();
+        plain.execute_batch(OPENIM_CONTACT_SCHEMA).unwrap();
+        plain.execute("INSERT INTO OpenIMContact (UserName, NickName) VALUES ('dave@openim', 'Dave')", []).unwrap();
+        write_encrypted_copy(&plain, &path, [7; 16]);
+
+        let conn = merged_db_with_sample_data();
+        assert!(attach_openim_db(&conn, &path, Some(&"0".repeat(64))).is_err());
+        attach_openim_db(&conn, &path, Some(TEST_KEY)).unwrap();
+        assert_eq!(get_contacts(&conn, Some("dave"), None, None).unwrap().len(), 1);
+        // The process-wide SQLCipher defaults are left alone.
+        let kdf_iter: String = conn.query_row("PRAGMA cipher_default_kdf_iter", [], |row| row.get(0)).unwrap();
+        assert_eq!(kdf_iter, "256000");
+        drop(conn);
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,195 @@
+// src/core/db_parser/public_msg_parser.rs
+
+use anyhow::Result;
+use rusqlite::Connection;
+use serde::Serialize;
+
+use super::{app_msg_xml, for_each_message, format_timestamp_to_string, Message, MSG_TYPE_APP};
+
+/// `<appmsg><type>` of an article push.
+pub const APP_MSG_TYPE_ARTICLE: i64 = 5;
+
+/// One article of an official-account push. A multi-article push yields one per item.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct Article {
+    /// wxid of the account (`gh_...`), the StrTalker of the message.
+    pub account: String,
+    /// Display name from `<mmreader><category><name>` or `<publisher><nickname>`.
+    pub account_name: Option<String>,
+    pub title: String,
+    pub digest: Option<String>,
+    pub url: Option<String>,
+    pub cover: Option<String>,
+    /// Unix seconds from `<pub_time>`, the message CreateTime when missing.
+    pub pub_time: i64,
+    pub time_str: String,
+    pub msg_svr_id: Option<i64>,
+    /// Position in the push, 0 for the headline.
+    pub item_index: usize,
+}
+
+/// Articles of the type 49/5 XML of a push; `<mmreader><category><item>` entries when present,
+/// otherwise the single `<appmsg>` itself. Empty for other app messages.
+pub fn parse_articles(xml: &str) -> Vec<Article> {
+    let Ok(doc) = roxmltree::Document::parse(xml.trim()) else { return Vec::new() };
+    let Some(appmsg) = doc.descendants().find(|n| n.has_tag_name("appmsg")) else { return Vec::new() };
+    let child_text = |parent: roxmltree::Node, name: &str| -> Option<String> {
+        parent
+            .children()
+            .find(|n| n.has_tag_name(name))
+            .and_then(|n| n.text())
+            .map(|t| t.trim().to_string())
+            .filter(|t| !t.is_empty())
+    };
+    if child_text(appmsg, "type").and_then(|t| t.parse::<i64>().ok()) != Some(APP_MSG_TYPE_ARTICLE) {
+        return Vec::new();
+    }
+
+    let reader = appmsg.children().find(|n| n.has_tag_name("mmreader"));
+    let category = reader.and_then(|r| r.children().find(|n| n.has_tag_name("category")));
+    let account_name = category.and_then(|c| child_text(c, "name")).or_else(|| {
+        reader
+            .and_then(|r| r.children().find(|n| n.has_tag_name("publisher")))
+            .and_then(|p| child_text(p, "nickname"))
+    });
+
+    let items: Vec<Article> = category
+        .into_iter()
+        .flat_map(|c| c.children().filter(|n| n.has_tag_name("item")))
+        .filter_map(|item| {
+            Some(Article {
+                account_name: account_name.clone(),
+                title: child_text(item, "title")?,
+                digest: child_text(item, "digest"),
+                url: child_text(item, "url"),
+                cover: child_text(item, "cover"),
+                pub_time: child_text(item, "pub_time").and_then(|t| t.parse().ok()).unwrap_or(0),
+                ..Default::default()
+            })
+        })
+        .collect();
+    if !items.is_empty() {
+        return items;
+    }
+
+    match child_text(appmsg, "title") {
+        Some(title) => vec![Article {
+            account_name,
+            title,
+            digest: child_text(appmsg, "des"),
+            url: child_text(appmsg, "url"),
+            cover: child_text(appmsg, "thumburl"),
+            ..Default::default()
+        }],
+        None => Vec::new(),
+    }
+}
+
+/// Articles of a message, filled in with its account, time and MsgSvrID.
+pub fn message_articles(msg: &Message) -> Vec<Article> {
+    if msg.msg_type != MSG_TYPE_APP {
+        return Vec::new();
+    }
+    let Some(xml) = app_msg_xml(msg) else { return Vec::new() };
+    let mut articles = parse_articles(&xml);
+    for (index, article) in articles.iter_mut().enumerate() {
+        article.account = msg.talker.clone();
+        if article.pub_time == 0 {
+            article.pub_time = msg.create_time;
+        }
+        article.time_str = format_timestamp_to_string(article.pub_time, "%Y-%m-%d %H:%M:%S");
+        article.msg_svr_id = msg.msg_svr_id;
+        article.item_index = index;
+    }
+    articles
+}
+
+/// Streams the articles pushed by official accounts in PublicMsg.db (its MSG table has the
+/// usual schema), oldest first. `account` and the inclusive unix range filter by message.
+/// Returns the number of articles visited.
+pub fn for_each_article(
+    conn: &Connection,
+    account: Option<&str>,
+    start_time: Option<i64>,
+    end_time: Option<i64>,
+    mut f: impl FnMut(Article) -> Result<()>,
+) -> Result<usize> {
+    let mut count = 0;
+    for_each_message(conn, account, Some(&[MSG_TYPE_APP]), start_time, end_time, None, None, |msg| {
+        for article in message_articles(&msg) {
+            f(article)?;
+            count += 1;
+        }
+        Ok(())
+    })?;
+    Ok(count)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::testutil::MSG_SCHEMA;
+
+    const PUSH_XML: &str = "<msg><appmsg><title>Headline</title><des>Headline digest</des><type>5</type>\
+        <url>https://mp.weixin.qq.com/s/a</url><mmreader><category type=\"20\" count=\"2\"><name>Industry Weekly</name>\
+        <item><title>Headline</title><url>https://mp.weixin.qq.com/s/a</url><digest>Headline digest</digest>\
+        <cover>https://mmbiz.qpic.cn/a.jpg</cover><pub_time>1700000500</pub_time></item>\
+        <item><title>Second &amp; last</title><url>https://mp.weixin.qq.com/s/b</url><digest></digest>\
+        <cover>https://mmbiz.qpic.cn/b.jpg</cover></item></category>\
+        <publisher><username>gh_weekly</username><nickname>Industry Weekly</nickname></publisher></mmreader></appmsg></msg>";
+
+    #[test]
+    fn test_parse_articles() {
+        let articles = parse_articles(PUSH_XML);
+        assert_eq!(articles.len(), 2);
+        assert_eq!(articles[0].account_name.as_deref(), Some("Industry Weekly"));
+        assert_eq!(articles[0].cover.as_deref(), Some("https://mmbiz.qpic.cn/a.jpg"));
+        assert_eq!(articles[0].pub_time, 1_700_000_500);
+        assert_eq!(articles[1].title, "Second & last");
+        assert!(articles[1].digest.is_none());
+
+        let single = parse_articles(
+            "<msg><appmsg><title>Only</title><des>d</des><type>5</type><url>u</url><thumburl>t</thumburl></appmsg></msg>",
+        );
+        assert_eq!(single.len(), 1);
+        assert_eq!(single[0].cover.as_deref(), Some("t"));
+        assert!(parse_articles("<msg><appmsg><title>File</title><type>6</type></appmsg></msg>").is_empty());
+    }
+
+    #[test]
+    fn test_for_each_article() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(MSG_SCHEMA).unwrap();
+        let insert = "INSERT INTO MSG (MsgSvrID, Type, SubType, IsSender, CreateTime, StrTalker, StrContent) \
+                      VALUES (?, ?, ?, 0, ?, ?, ?)";
+        conn.execute(insert, rusqlite::params![1, 49, 5, 1_700_000_000, "gh_weekly", PUSH_XML]).unwrap();
+        conn.execute(insert, rusqlite::params![2, 1, 0, 1_700_000_100, "gh_weekly", "Thanks for following"]).unwrap();
+        let compressed = lz4_flex::block::compress(
+            b"<msg><appmsg><title>Other news</title><type>5</type><url>https://mp.weixin.qq.com/s/c</url></appmsg></msg>",
+        );
+        conn.execute(
+            "INSERT INTO MSG (MsgSvrID, Type, SubType, IsSender, CreateTime, StrTalker, CompressContent) \
+             VALUES (3, 49, 5, 0, 1700000200, 'gh_other', ?)",
+            [compressed],
+        )
+        .unwrap();
+
+        let mut articles = Vec::new();
+        let count = for_each_article(&conn, None, None, None, |a| {
+            articles.push(a);
+            Ok(())
+        })
+        .unwrap();
+        assert_eq!(count, 3);
+        assert_eq!(articles[0].account, "gh_weekly");
+        assert_eq!(articles[0].time_str, "2023-11-14 22:21:40");
+        // No <pub_time>: the push time.
+        assert_eq!(articles[1].pub_time, 1_700_000_000);
+        assert_eq!(articles[1].item_index, 1);
+        assert_eq!(articles[2].title, "Other news");
+        assert_eq!(articles[2].msg_svr_id, Some(3));
+
+        let count = for_each_article(&conn, Some("gh_other"), None, None, |_| Ok(())).unwrap();
+        assert_eq!(count, 1);
+    }
+}
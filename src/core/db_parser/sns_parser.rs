@@ -0,0 +1,436 @@
+// src/core/db_parser/sns_parser.rs
+
+use anyhow::Result;
+use md5::{Digest, Md5};
+use rusqlite::Connection;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use super::{format_timestamp_to_string, get_contacts};
+
+/// `CommentV20.Type` of a like.
+pub const SNS_COMMENT_TYPE_LIKE: i64 = 1;
+/// `CommentV20.Type` of a text comment.
+pub const SNS_COMMENT_TYPE_COMMENT: i64 = 2;
+
+/// `<mediaList><media>` of a post.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct SnsMedia {
+    pub id: Option<String>,
+    /// 2 for images, 6 for videos.
+    pub media_type: Option<i64>,
+    pub url: Option<String>,
+    pub thumb_url: Option<String>,
+    pub md5: Option<String>,
+    pub width: Option<f64>,
+    pub height: Option<f64>,
+    /// Cached copy under `FileStorage/Sns/Cache`, only set when the file exists.
+    pub local_path: Option<PathBuf>,
+    pub local_thumb_path: Option<PathBuf>,
+}
+
+/// `<location>` of a post.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct SnsLocation {
+    pub poi_name: Option<String>,
+    pub address: Option<String>,
+    pub city: Option<String>,
+    pub latitude: Option<f64>,
+    pub longitude: Option<f64>,
+}
+
+/// Shared link (`contentStyle` 3) of a post.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct SnsLink {
+    pub title: Option<String>,
+    pub description: Option<String>,
+    pub url: Option<String>,
+}
+
+/// A row of CommentV20, a like or a comment.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct SnsComment {
+    pub comment_id: Option<i64>,
+    pub from_wxid: String,
+    /// Remark or nickname of `from_wxid`, the wxid when unknown.
+    pub from_name: String,
+    pub create_time: i64,
+    pub time_str: String,
+    /// Empty for likes.
+    pub content: String,
+}
+
+/// A Moments post: a FeedsV20 row with its `TimelineObject` XML parsed.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct SnsPost {
+    pub feed_id: i64,
+    /// `StringId`, the id used in share links.
+    pub string_id: Option<String>,
+    pub author_wxid: String,
+    pub author_name: String,
+    pub create_time: i64,
+    pub time_str: String,
+    /// `<ContentObject><contentStyle>`: 1 images, 2 text only, 3 link, 15 video.
+    pub content_style: Option<i64>,
+    /// `<contentDesc>`.
+    pub text: String,
+    pub media: Vec<SnsMedia>,
+    pub link: Option<SnsLink>,
+    pub location: Option<SnsLocation>,
+    pub likes: Vec<SnsComment>,
+    pub comments: Vec<SnsComment>,
+}
+
+/// Filters of `get_sns_posts`.
+#[derive(Debug, Clone, Default)]
+pub struct SnsQuery {
+    /// Only posts of this wxid.
+    pub author: Option<String>,
+    /// Unix timestamps, inclusive.
+    pub start_time: Option<i64>,
+    pub end_time: Option<i64>,
+    pub limit: Option<usize>,
+    /// Account folder, to find cached media under `FileStorage/Sns/Cache`.
+    pub wx_path: Option<PathBuf>,
+}
+
+/// The parts of a `<TimelineObject>` that `SnsPost` carries.
+#[derive(Debug, Clone, Default)]
+pub struct TimelineObject {
+    pub username: Option<String>,
+    pub create_time: Option<i64>,
+    pub content_desc: Option<String>,
+    pub content_style: Option<i64>,
+    pub media: Vec<SnsMedia>,
+    pub link: Option<SnsLink>,
+    pub location: Option<SnsLocation>,
+}
+
+/// Parses the `Content` XML of a FeedsV20 row.
+pub fn parse_timeline_object(xml: &str) -> Option<TimelineObject> {
+    let doc = roxmltree::Document::parse(xml.trim()).ok()?;
+    let root = doc.descendants().find(|n| n.has_tag_name("TimelineObject"))?;
+    let child_text = |parent: roxmltree::Node, name: &str| -> Option<String> {
+        parent
+            .children()
+            .find(|n| n.has_tag_name(name))
+            .and_then(|n| n.text())
+            .map(|t| t.trim().to_string())
+            .filter(|t| !t.is_empty())
+    };
+    let attr = |node: roxmltree::Node, name: &str| {
+        node.attribute(name).map(|v| v.trim().to_string()).filter(|v| !v.is_empty())
+    };
+
+    let content = root.children().find(|n| n.has_tag_name("ContentObject"));
+    let content_style = content.and_then(|c| child_text(c, "contentStyle")).and_then(|s| s.parse().ok());
+
+    let media = content
+        .and_then(|c| c.children().find(|n| n.has_tag_name("mediaList")))
+        .map(|list| {
+            list.children()
+                .filter(|n| n.has_tag_name("media"))
+                .map(|m| {
+                    let url_node = m.children().find(|n| n.has_tag_name("url"));
+                    let size = m.children().find(|n| n.has_tag_name("size"));
+                    SnsMedia {
+                        id: child_text(m, "id"),
+                        media_type: child_text(m, "type").and_then(|t| t.parse().ok()),
+                        url: child_text(m, "url"),
+                        thumb_url: child_text(m, "thumb"),
+                        md5: url_node.and_then(|u| attr(u, "md5")),
+                        width: size.and_then(|s| attr(s, "width")).and_then(|v| v.parse().ok()),
+                        height: size.and_then(|s| attr(s, "height")).and_then(|v| v.parse().ok()),
+                        local_path: None,
+                        local_thumb_path: None,
+                    }
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+
+    let link = content.and_then(|c| {
+        let link = SnsLink {
+            title: child_text(c, "title"),
+            description: child_text(c, "description"),
+            url: child_text(c, "contentUrl"),
+        };
+        (link.url.is_some() && content_style == Some(3)).then_some(link)
+    });
+
+    let location = root.children().find(|n| n.has_tag_name("location")).and_then(|node| {
+        let location = SnsLocation {
+            poi_name: attr(node, "poiName"),
+            address: attr(node, "poiAddress"),
+            city: attr(node, "city"),
+            latitude: attr(node, "latitude").and_then(|v| v.parse().ok()).filter(|v: &f64| *v != 0.0),
+            longitude: attr(node, "longitude").and_then(|v| v.parse().ok()).filter(|v: &f64| *v != 0.0),
+        };
+        (location.poi_name.is_some() || location.city.is_some() || location.latitude.is_some()).then_some(location)
+    });
+
+    Some(TimelineObject {
+        username: child_text(root, "username"),
+        create_time: child_text(root, "createTime").and_then(|t| t.parse().ok()),
+        content_desc: child_text(root, "contentDesc"),
+        content_style,
+        media,
+        link,
+        location,
+    })
+}
+
+/// Moments posts of Sns.db, newest first, with likes and comments attached.
+///
+/// Names come from the MicroMsg tables of `contact_conn` (remark, then nickname); without it,
+/// or for people who are not contacts, the wxid is used.
+pub fn get_sns_posts(sns_conn: &Connection, contact_conn: Option<&Connection>, query: &SnsQuery) -> Result<Vec<SnsPost>> {
+    let mut sql = String::from("SELECT FeedId, CreateTime, UserName, StringId, Content FROM FeedsV20");
+    let mut conditions: Vec<String> = Vec::new();
+    let mut params_list: Vec<Box<dyn rusqlite::ToSql>> = Vec::new();
+
+    if let Some(author) = &query.author {
+        conditions.push("UserName = ?".to_string());
+        params_list.push(Box::new(author.clone()));
+    }
+    if let Some(start) = query.start_time {
+        conditions.push("CreateTime >= ?".to_string());
+        params_list.push(Box::new(start));
+    }
+    if let Some(end) = query.end_time {
+        conditions.push("CreateTime <= ?".to_string());
+        params_list.push(Box::new(end));
+    }
+    if !conditions.is_empty() {
+        sql.push_str(" WHERE ");
+        sql.push_str(&conditions.join(" AND "));
+    }
+    sql.push_str(" ORDER BY CreateTime DESC, FeedId DESC");
+    if let Some(limit) = query.limit {
+        sql.push_str(" LIMIT ?");
+        params_list.push(Box::new(limit as i64));
+    }
+    sql.push(';');
+
+    let params_for_query: Vec<&dyn rusqlite::ToSql> = params_list.iter().map(|p| p.as_ref()).collect();
+    let mut stmt = sns_conn.prepare(&sql)?;
+    let rows = stmt.query_map(&*params_for_query, |row| {
+        Ok((
+            row.get::<_, i64>("FeedId")?,
+            row.get::<_, Option<i64>>("CreateTime")?.unwrap_or(0),
+            row.get::<_, Option<String>>("UserName")?.unwrap_or_default(),
+            row.get::<_, Option<String>>("StringId")?,
+            row.get::<_, Option<String>>("Content")?.unwrap_or_default(),
+        ))
+    })?;
+
+    let mut posts = Vec::new();
+    for row in rows {
+        let (feed_id, create_time, user_name, string_id, content) = row?;
+        let timeline = parse_timeline_object(&content).unwrap_or_default();
+        let create_time = if create_time > 0 { create_time } else { timeline.create_time.unwrap_or(0) };
+        let author_wxid = if user_name.is_empty() { timeline.username.unwrap_or_default() } else { user_name };
+        let mut media = timeline.media;
+        if let Some(wx_path) = &query.wx_path {
+            for item in &mut media {
+                resolve_sns_cache(item, wx_path, create_time);
+            }
+        }
+        posts.push(SnsPost {
+            feed_id,
+            string_id,
+            author_name: author_wxid.clone(),
+            author_wxid,
+            create_time,
+            time_str: format_timestamp_to_string(create_time, "%Y-%m-%d %H:%M:%S"),
+            content_style: timeline.content_style,
+            text: timeline.content_desc.unwrap_or_default(),
+            media,
+            link: timeline.link,
+            location: timeline.location,
+            likes: Vec::new(),
+            comments: Vec::new(),
+        });
+    }
+
+    attach_comments(sns_conn, &mut posts)?;
+    if let Some(contact_conn) = contact_conn {
+        resolve_names(contact_conn, &mut posts)?;
+    }
+    Ok(posts)
+}
+
+fn attach_comments(sns_conn: &Connection, posts: &mut [SnsPost]) -> Result<()> {
+    let mut stmt = sns_conn.prepare(
+        "SELECT CommentId, Type, FromUserName, CreateTime, Content FROM CommentV20 \
+         WHERE FeedId = ? ORDER BY CreateTime ASC, CommentId ASC;",
+    )?;
+    for post in posts.iter_mut() {
+        let rows = stmt.query_map([post.feed_id], |row| {
+            let create_time = row.get::<_, Option<i64>>("CreateTime")?.unwrap_or(0);
+            let from_wxid = row.get::<_, Option<String>>("FromUserName")?.unwrap_or_default();
+            Ok((
+                row.get::<_, Option<i64>>("Type")?.unwrap_or(0),
+                SnsComment {
+                    comment_id: row.get("CommentId")?,
+                    from_name: from_wxid.clone(),
+                    from_wxid,
+                    create_time,
+                    time_str: format_timestamp_to_string(create_time, "%Y-%m-%d %H:%M:%S"),
+                    content: row.get::<_, Option<String>>("Content")?.unwrap_or_default(),
+                },
+            ))
+        })?;
+        for row in rows {
+            match row? {
+                (SNS_COMMENT_TYPE_LIKE, like) => post.likes.push(like),
+                (SNS_COMMENT_TYPE_COMMENT, comment) => post.comments.push(comment),
+                (other, row) => log::debug!("Skipping CommentV20 row {:?} of unknown type {}", row.comment_id, other),
+            }
+        }
+    }
+    Ok(())
+}
+
+fn resolve_names(contact_conn: &Connection, posts: &mut [SnsPost]) -> Result<()> {
+    let mut wxids: Vec<String> = posts
+        .iter()
+        .flat_map(|p| {
+            std::iter::once(p.author_wxid.clone()).chain(p.likes.iter().chain(&p.comments).map(|c| c.from_wxid.clone()))
+        })
+        .collect();
+    wxids.sort();
+    wxids.dedup();
+
+    let names: HashMap<String, String> = get_contacts(contact_conn, None, Some(&wxids), None)?
+        .into_iter()
+        .filter_map(|c| {
+            let name = [c.remark, c.nickname].into_iter().flatten().find(|n| !n.is_empty())?;
+            Some((c.wxid, name))
+        })
+        .collect();
+    let name_of = |wxid: &str| names.get(wxid).cloned().unwrap_or_else(|| wxid.to_string());
+
+    for post in posts.iter_mut() {
+        post.author_name = name_of(&post.author_wxid);
+        for comment in post.likes.iter_mut().chain(post.comments.iter_mut()) {
+            comment.from_name = name_of(&comment.from_wxid);
+        }
+    }
+    Ok(())
+}
+
+/// Looks for the cached copy of `media` in `FileStorage/Sns/Cache/<YYYY-MM>/`, where the
+/// client stores a downloaded file under the md5 of its URL (thumbnails with a `_t` suffix).
+fn resolve_sns_cache(media: &mut SnsMedia, wx_path: &Path, create_time: i64) {
+    let month_dir = wx_path
+        .join("FileStorage")
+        .join("Sns")
+        .join("Cache")
+        .join(format_timestamp_to_string(create_time, "%Y-%m"));
+    let existing = |url: &Option<String>, suffix: &str| -> Option<PathBuf> {
+        let digest = hex::encode(Md5::digest(url.as_ref()?.as_bytes()));
+        let path = month_dir.join(format!("{}{}", digest, suffix));
+        path.is_file().then_some(path)
+    };
+    media.local_path = existing(&media.url, "");
+    media.local_thumb_path = existing(&media.thumb_url, "_t");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::testutil::{merged_db_with_sample_data, SNS_SCHEMA};
+
+    const POST_XML: &str = r#"<TimelineObject><id>13900000000000000001</id><username>wxid_alice</username>
+        <createTime>1700000000</createTime><contentDesc>Sunset &amp; sea</contentDesc>
+        <location poiName="Beach" poiAddress="1 Sea Road" city="Xiamen" latitude="24.4" longitude="118.1" />
+        <ContentObject><contentStyle>1</contentStyle><title></title><description></description><contentUrl></contentUrl>
+        <mediaList><media><id>1</id><type>2</type>
+            <url type="1" md5="abc123">http://szmmsns.qpic.cn/mmsns/x/0</url>
+            <thumb type="1">http://szmmsns.qpic.cn/mmsns/x/150</thumb>
+            <size width="1080" height="720" totalSize="1" /></media></mediaList>
+        </ContentObject></TimelineObject>"#;
+
+    fn sns_db() -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(SNS_SCHEMA).unwrap();
+        conn.execute(
+            "INSERT INTO FeedsV20 (FeedId, CreateTime, UserName, StringId, Content) VALUES (1, 1700000000, 'wxid_alice', 's1', ?)",
+            [POST_XML],
+        )
+        .unwrap();
+        conn.execute_batch(
+            "INSERT INTO FeedsV20 (FeedId, CreateTime, UserName, StringId, Content) VALUES (2, 1700086400, 'wxid_bob', 's2',
+                 '<TimelineObject><username>wxid_bob</username><contentDesc>Read this</contentDesc><ContentObject>
+                  <contentStyle>3</contentStyle><title>An article</title><contentUrl>https://example.com/a</contentUrl>
+                  </ContentObject></TimelineObject>');
+             INSERT INTO CommentV20 (FeedId, CommentId, Type, FromUserName, CreateTime, Content)
+                 VALUES (1, 11, 1, 'wxid_bob', 1700000100, ''), (1, 12, 2, 'wxid_stranger', 1700000200, 'Nice!'),
+                        (1, 13, 2, 'wxid_unknown', 1700000300, 'Where?'), (1, 14, 9, 'wxid_bob', 1700000400, '?');",
+        )
+        .unwrap();
+        conn
+    }
+
+    #[test]
+    fn test_parse_timeline_object() {
+        let timeline = parse_timeline_object(POST_XML).unwrap();
+        assert_eq!(timeline.username.as_deref(), Some("wxid_alice"));
+        assert_eq!(timeline.content_desc.as_deref(), Some("Sunset & sea"));
+        assert_eq!(timeline.content_style, Some(1));
+        assert!(timeline.link.is_none());
+        let media = &timeline.media[0];
+        assert_eq!(media.url.as_deref(), Some("http://szmmsns.qpic.cn/mmsns/x/0"));
+        assert_eq!(media.thumb_url.as_deref(), Some("http://szmmsns.qpic.cn/mmsns/x/150"));
+        assert_eq!(media.md5.as_deref(), Some("abc123"));
+        assert_eq!(media.width, Some(1080.0));
+        let location = timeline.location.unwrap();
+        assert_eq!(location.poi_name.as_deref(), Some("Beach"));
+        assert_eq!(location.latitude, Some(24.4));
+        assert!(parse_timeline_object("not xml").is_none());
+    }
+
+    #[test]
+    fn test_get_sns_posts() {
+        let sns = sns_db();
+        let contacts = merged_db_with_sample_data();
+        let posts = get_sns_posts(&sns, Some(&contacts), &SnsQuery::default()).unwrap();
+        assert_eq!(posts.len(), 2);
+
+        let link_post = &posts[0];
+        assert_eq!(link_post.author_name, "Bob");
+        assert_eq!(link_post.link.as_ref().unwrap().title.as_deref(), Some("An article"));
+
+        let photo_post = &posts[1];
+        assert_eq!(photo_post.author_name, "Alice (work)");
+        assert_eq!(photo_post.time_str, "2023-11-14 22:13:20");
+        assert_eq!(photo_post.likes.len(), 1);
+        assert_eq!(photo_post.likes[0].from_name, "Bob");
+        let comments: Vec<_> = photo_post.comments.iter().map(|c| (c.from_name.as_str(), c.content.as_str())).collect();
+        assert_eq!(comments, vec![("Stranger", "Nice!"), ("wxid_unknown", "Where?")]);
+
+        let query = SnsQuery { author: Some("wxid_alice".to_string()), ..Default::default() };
+        assert_eq!(get_sns_posts(&sns, None, &query).unwrap().len(), 1);
+        let query = SnsQuery { end_time: Some(1_700_000_000), ..Default::default() };
+        let posts = get_sns_posts(&sns, None, &query).unwrap();
+        assert_eq!(posts[0].author_name, "wxid_alice");
+    }
+
+    #[test]
+    fn test_resolve_sns_cache() {
+        let wx_path = std::env::temp_dir().join(format!("wxdump_rs_sns_cache_{}", std::process::id()));
+        let url = "http://szmmsns.qpic.cn/mmsns/x/0";
+        let month_dir = wx_path.join("FileStorage/Sns/Cache/2023-11");
+        std::fs::create_dir_all(&month_dir).unwrap();
+        let cached = month_dir.join(hex::encode(Md5::digest(url.as_bytes())));
+        std::fs::write(&cached, b"jpg").unwrap();
+
+        let query = SnsQuery { wx_path: Some(wx_path.clone()), author: Some("wxid_alice".to_string()), ..Default::default() };
+        let posts = get_sns_posts(&sns_db(), None, &query).unwrap();
+        assert_eq!(posts[0].media[0].local_path.as_deref(), Some(cached.as_path()));
+        assert!(posts[0].media[0].local_thumb_path.is_none());
+        std::fs::remove_dir_all(&wx_path).unwrap();
+    }
+}
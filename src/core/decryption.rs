@@ -1,9 +1,10 @@
 // src/core/decryption.rs
 
 use std::fs::File;
-use std::io::{Read, Write};
-use std::path::Path;
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
 use anyhow::Result; 
+use log::{debug, info, warn};
 
 // Cryptography crates
 use aes::Aes256;
@@ -64,6 +65,205 @@ impl std::fmt::Display for DecryptionError {
 impl std::error::Error for DecryptionError {}
 
 
+/// PBKDF2-HMAC-SHA1 (64000 rounds) of the account key with the salt of one database file:
+/// the AES key of its pages. SQLCipher calls this the raw key.
+pub fn derive_page_key(password: &[u8], salt: &[u8]) -> [u8; KEY_SIZE] {
+    let mut page_key = [0u8; KEY_SIZE];
+    pbkdf2_hmac::<Sha1>(password, salt, 64000, &mut page_key);
+    page_key
+}
+
+/// SQLCipher `PRAGMA key` value (`x'<raw key><salt>'`) that opens the encrypted database at
+/// `db_path` in place, given the 64 hex char account key. Only the salt (first 16 bytes) is read.
+pub fn sqlcipher_raw_key(db_path: &Path, key_hex: &str) -> Result<String, DecryptionError> {
+    if key_hex.len() != 64 {
+        return Err(DecryptionError::Other("Key hex string must be 64 characters long.".to_string()));
+    }
+    let password_bytes = hex::decode(key_hex)?;
+    let mut salt = [0u8; SALT_SIZE];
+    File::open(db_path)?.read_exact(&mut salt).map_err(|_| DecryptionError::FileTooShort)?;
+    if salt.as_slice() == &SQLITE_FILE_HEADER[..SALT_SIZE] {
+        return Err(DecryptionError::Other(format!("{:?} is not encrypted", db_path)));
+    }
+    let page_key = derive_page_key(&password_bytes, &salt);
+    Ok(format!("x'{}{}'", hex::encode(page_key), hex::encode(salt)))
+}
+
+/// Decrypts one 4096 byte page, keeping the original reserved area (IV + HMAC) like Python does.
+/// The first page also gets the plain SQLite header in place of the salt, and is switched to
+/// the rollback journal so the copy opens without its (absent) WAL.
+fn decrypt_page(page_slice: &[u8], first_page: bool, aes_key: &[u8; KEY_SIZE]) -> Result<Vec<u8>, DecryptionError> {
+    const AES_BLOCK_SIZE_USIZE_CONST: usize = U16::USIZE; // Using U16::USIZE
+
+    let data_start = if first_page { SALT_SIZE } else { 0 };
+    let data_to_decrypt = &page_slice[data_start..(DEFAULT_PAGESIZE - RESERVED_SIZE)];
+    let iv_slice = &page_slice[(DEFAULT_PAGESIZE - RESERVED_SIZE)..(DEFAULT_PAGESIZE - RESERVED_SIZE + IV_SIZE)];
+    if data_to_decrypt.len() % AES_BLOCK_SIZE_USIZE_CONST != 0 {
+        return Err(DecryptionError::Other(format!("Data to decrypt is not a multiple of AES block size ({} bytes): length {}", AES_BLOCK_SIZE_USIZE_CONST, data_to_decrypt.len())));
+    }
+
+    let mut buffer = Vec::with_capacity(DEFAULT_PAGESIZE);
+    if first_page {
+        buffer.extend_from_slice(SQLITE_FILE_HEADER);
+    }
+    buffer.extend_from_slice(data_to_decrypt);
+
+    let key_ga = GenericArray::from_slice(aes_key);
+    let iv_ga = GenericArray::from_slice(iv_slice);
+    let mut cipher = cbc::Decryptor::<Aes256>::new(key_ga, iv_ga);
+    for chunk in buffer[data_start..].chunks_exact_mut(AES_BLOCK_SIZE_USIZE_CONST) {
+        let block = AesBlock::from_mut_slice(chunk); // Corrected to use AesBlock type alias
+        cipher.decrypt_block_mut(block);
+    }
+
+    // Write back the original reserved 48 bytes from the encrypted page.
+    buffer.extend_from_slice(&page_slice[(DEFAULT_PAGESIZE - RESERVED_SIZE)..]);
+
+    // File format write/read versions: 2 (WAL) -> 1 (rollback journal).
+    if first_page && buffer[18] == 2 && buffer[19] == 2 {
+        buffer[18] = 1;
+        buffer[19] = 1;
+    }
+    Ok(buffer)
+}
+
+/// `<db>-wal`, where SQLite keeps pages committed since the last checkpoint.
+pub fn wal_path_of(db_path: &Path) -> PathBuf {
+    let mut name = db_path.as_os_str().to_owned();
+    name.push("-wal");
+    PathBuf::from(name)
+}
+
+const WAL_HEADER_SIZE: usize = 32;
+const WAL_FRAME_HEADER_SIZE: usize = 24;
+
+/// Running checksum of the WAL format over `data` (a multiple of 8 bytes).
+fn wal_checksum(data: &[u8], big_endian: bool, (mut s0, mut s1): (u32, u32)) -> (u32, u32) {
+    let word = |b: &[u8]| {
+        let bytes = [b[0], b[1], b[2], b[3]];
+        if big_endian { u32::from_be_bytes(bytes) } else { u32::from_le_bytes(bytes) }
+    };
+    for chunk in data.chunks_exact(8) {
+        s0 = s0.wrapping_add(word(&chunk[0..4])).wrapping_add(s1);
+        s1 = s1.wrapping_add(word(&chunk[4..8])).wrapping_add(s0);
+    }
+    (s0, s1)
+}
+
+/// Checkpoints an encrypted WAL into a decrypted copy: every frame up to the last valid
+/// commit is decrypted with the database's page key and written over its page, and the copy
+/// is cut to the committed size. Frames are checked the way SQLite recovers a WAL (salts and
+/// cumulative checksums over the encrypted bytes), so a torn tail left by a copy taken
+/// mid-write is ignored. Returns the number of frames applied.
+fn apply_encrypted_wal(wal_path: &Path, out: &mut File, aes_key: &[u8; KEY_SIZE]) -> Result<usize, DecryptionError> {
+    let wal = std::fs::read(wal_path)?;
+    if wal.len() < WAL_HEADER_SIZE {
+        return Ok(0);
+    }
+    let be_u32 = |b: &[u8]| u32::from_be_bytes([b[0], b[1], b[2], b[3]]);
+    let magic = be_u32(&wal[0..4]);
+    if magic & !1 != 0x377f0682 {
+        return Err(DecryptionError::Other(format!("{:?} is not a WAL file", wal_path)));
+    }
+    let big_endian = magic & 1 == 1;
+    let page_size = be_u32(&wal[8..12]) as usize;
+    if page_size != DEFAULT_PAGESIZE {
+        return Err(DecryptionError::Other(format!("Unexpected WAL page size {} in {:?}", page_size, wal_path)));
+    }
+    let salts = &wal[16..24];
+    let mut checksum = wal_checksum(&wal[0..24], big_endian, (0, 0));
+    if checksum != (be_u32(&wal[24..28]), be_u32(&wal[28..32])) {
+        return Ok(0);
+    }
+
+    // Frames of committed transactions, applied in order (later frames win).
+    let mut committed: Vec<(u32, &[u8])> = Vec::new();
+    let mut pending: Vec<(u32, &[u8])> = Vec::new();
+    let mut db_pages = 0u32;
+    let frame_size = WAL_FRAME_HEADER_SIZE + page_size;
+    let mut offset = WAL_HEADER_SIZE;
+    while offset + frame_size <= wal.len() {
+        let header = &wal[offset..offset + WAL_FRAME_HEADER_SIZE];
+        let page = &wal[offset + WAL_FRAME_HEADER_SIZE..offset + frame_size];
+        // Like SQLite's recovery, stop at a frame of another generation or for page 0.
+        let page_number = be_u32(&header[0..4]);
+        if &header[8..16] != salts || page_number == 0 {
+            break;
+        }
+        checksum = wal_checksum(&header[0..8], big_endian, checksum);
+        checksum = wal_checksum(page, big_endian, checksum);
+        if checksum != (be_u32(&header[16..20]), be_u32(&header[20..24])) {
+            break;
+        }
+        pending.push((page_number, page));
+        let commit_size = be_u32(&header[4..8]);
+        if commit_size > 0 {
+            committed.append(&mut pending);
+            db_pages = commit_size;
+        }
+        offset += frame_size;
+    }
+
+    for (page_number, page) in &committed {
+        let plain = decrypt_page(page, *page_number == 1, aes_key)?;
+        out.seek(SeekFrom::Start((*page_number as u64 - 1) * page_size as u64))?;
+        out.write_all(&plain)?;
+    }
+    if db_pages > 0 {
+        out.set_len(db_pages as u64 * page_size as u64)?;
+    }
+    Ok(committed.len())
+}
+
+/// Copies a database that a running WeChat may be writing, together with its `-wal`, into
+/// `out_dir`. The copy is retried while the size or modification time of either file changes
+/// during the copy, so the result is a consistent snapshot. Returns the copied database path.
+pub fn snapshot_copy(db_path: &Path, out_dir: &Path) -> Result<PathBuf, DecryptionError> {
+    const ATTEMPTS: usize = 5;
+
+    fn state(path: &Path) -> Option<(u64, std::time::SystemTime)> {
+        let meta = std::fs::metadata(path).ok()?;
+        Some((meta.len(), meta.modified().ok()?))
+    }
+
+    let file_name = db_path
+        .file_name()
+        .ok_or_else(|| DecryptionError::Other(format!("Not a file path: {:?}", db_path)))?;
+    std::fs::create_dir_all(out_dir)?;
+    let out = out_dir.join(file_name);
+    let wal = wal_path_of(db_path);
+    let out_wal = wal_path_of(&out);
+
+    for attempt in 1..=ATTEMPTS {
+        let before = (state(db_path), state(&wal));
+        std::fs::copy(db_path, &out)?;
+        match std::fs::copy(&wal, &out_wal) {
+            Ok(_) => {}
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                let _ = std::fs::remove_file(&out_wal);
+            }
+            Err(e) => return Err(e.into()),
+        }
+        if (state(db_path), state(&wal)) == before {
+            return Ok(out);
+        }
+        warn!("{:?} changed while copying (attempt {}/{}), retrying", db_path, attempt, ATTEMPTS);
+        std::thread::sleep(std::time::Duration::from_millis(200 * attempt as u64));
+    }
+    Err(DecryptionError::Other(format!("{:?} kept changing while copying; try again once WeChat is idle", db_path)))
+}
+
+/// Decrypts a database of a running WeChat: takes a [`snapshot_copy`] (still encrypted) into a
+/// temporary folder next to `output_path`, decrypts it including its WAL, and removes the copy.
+pub fn decrypt_live_database_file(encrypted_db_path: &Path, output_path: &Path, key_hex: &str) -> Result<(), DecryptionError> {
+    let parent = output_path.parent().filter(|p| !p.as_os_str().is_empty()).unwrap_or(Path::new("."));
+    let staging = parent.join(format!(".snapshot_{}", std::process::id()));
+    let result = snapshot_copy(encrypted_db_path, &staging)
+        .and_then(|copy| decrypt_database_file(&copy, output_path, key_hex));
+    let _ = std::fs::remove_dir_all(&staging);
+    result
+}
+
 pub fn decrypt_database_file(
     encrypted_db_path: &Path,
     output_path: &Path,
@@ -87,8 +287,7 @@ pub fn decrypt_database_file(
     }
 
     let salt = &encrypted_data[0..SALT_SIZE];
-    let mut aes_key_arr = [0u8; KEY_SIZE]; // Renamed to avoid conflict if KEY_SIZE was a type
-    pbkdf2_hmac::<Sha1>(&password_bytes, salt, 64000, &mut aes_key_arr);
+    let aes_key_arr = derive_page_key(&password_bytes, salt);
     
     let mac_salt_array: [u8; SALT_SIZE] = core::array::from_fn(|i| salt[i] ^ 0x3A);
     let mut hmac_key_material = [0u8; KEY_SIZE]; 
@@ -104,60 +303,104 @@ pub fn decrypt_database_file(
 
     let calculated_hmac_bytes = mac.finalize_fixed();
     if calculated_hmac_bytes.as_slice() != stored_hmac {
-         println!("[Decryption] Calculated HMAC: {:02x?}", calculated_hmac_bytes.as_slice());
-         println!("[Decryption] Stored HMAC: {:02x?}", stored_hmac);
+         debug!("Calculated HMAC: {:02x?}", calculated_hmac_bytes.as_slice());
+         debug!("Stored HMAC: {:02x?}", stored_hmac);
         return Err(DecryptionError::HmacVerificationFailed);
     }
-    println!("[Decryption] HMAC for the first page verified successfully.");
+    debug!("HMAC for the first page verified successfully.");
 
     let mut decrypted_writer = File::create(output_path)?;
-    decrypted_writer.write_all(SQLITE_FILE_HEADER)?; // Using SQLITE_FILE_HEADER const
 
     let num_pages = encrypted_data.len() / DEFAULT_PAGESIZE; // Using DEFAULT_PAGESIZE const
-    const AES_BLOCK_SIZE_USIZE_CONST: usize = U16::USIZE; // Using U16::USIZE
-
     for i in 0..num_pages {
         let page_offset = i * DEFAULT_PAGESIZE;
-        let page_end = page_offset + DEFAULT_PAGESIZE;
-        if page_end > encrypted_data.len() { break; } 
-        let page_slice = &encrypted_data[page_offset..page_end];
-
-        let data_to_decrypt: &[u8];
-        let iv_slice: &[u8]; 
-
-        if i == 0 { 
-            data_to_decrypt = &page_slice[SALT_SIZE..(DEFAULT_PAGESIZE - RESERVED_SIZE)]; 
-            iv_slice = &page_slice[(DEFAULT_PAGESIZE - RESERVED_SIZE)..(DEFAULT_PAGESIZE - RESERVED_SIZE + IV_SIZE)];
-        } else { 
-            data_to_decrypt = &page_slice[0..(DEFAULT_PAGESIZE - RESERVED_SIZE)]; 
-            iv_slice = &page_slice[(DEFAULT_PAGESIZE - RESERVED_SIZE)..(DEFAULT_PAGESIZE - RESERVED_SIZE + IV_SIZE)];
-        }
-        
-        if data_to_decrypt.is_empty() { continue; }
-        
-        if data_to_decrypt.len() % AES_BLOCK_SIZE_USIZE_CONST != 0 { 
-            return Err(DecryptionError::Other(format!("Data to decrypt for page {} is not a multiple of AES block size ({} bytes): length {}", i, AES_BLOCK_SIZE_USIZE_CONST, data_to_decrypt.len())));
-        }
-        
-        let mut buffer = data_to_decrypt.to_vec(); 
-        
-        let key_ga = GenericArray::from_slice(&aes_key_arr);
-        let iv_ga = GenericArray::from_slice(iv_slice);
-        let mut cipher = cbc::Decryptor::<Aes256>::new(key_ga, iv_ga);
-        
-        for chunk in buffer.chunks_exact_mut(AES_BLOCK_SIZE_USIZE_CONST) {
-            let block = AesBlock::from_mut_slice(chunk); // Corrected to use AesBlock type alias
-            cipher.decrypt_block_mut(block);
-        }
-        
-        decrypted_writer.write_all(&buffer)?;
+        let page_slice = &encrypted_data[page_offset..page_offset + DEFAULT_PAGESIZE];
+        decrypted_writer.write_all(&decrypt_page(page_slice, i == 0, &aes_key_arr)?)?;
+    }
 
-        // Write back the original reserved 48 bytes from the encrypted page, like Python does
-        // page_slice is the full current encrypted page.
-        // The last 48 bytes are page_slice[(DEFAULT_PAGESIZE - RESERVED_SIZE)..]
-        decrypted_writer.write_all(&page_slice[(DEFAULT_PAGESIZE - RESERVED_SIZE)..])?;
+    // Pages committed since the last checkpoint only exist in the -wal file.
+    let wal_path = wal_path_of(encrypted_db_path);
+    if wal_path.is_file() {
+        let applied = apply_encrypted_wal(&wal_path, &mut decrypted_writer, &aes_key_arr)?;
+        info!("Applied {} WAL frame(s) from {:?}", applied, wal_path);
     }
     
-    println!("[Decryption] Database (with original reserved areas) decrypted successfully to {:?}", output_path);
+    info!("Database (with original reserved areas) decrypted successfully to {:?}", output_path);
     Ok(())
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::testutil::{write_encrypted_copy, TEST_KEY};
+    use rusqlite::Connection;
+
+    #[test]
+    fn test_decrypt_live_database_with_wal() {
+        let dir = std::env::temp_dir().join(format!("wxdump_rs_live_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let db_path = dir.join("MSG0.db");
+        let seed = Connection::open_in_memory().unwrap();
+        seed.execute_batch("CREATE TABLE MSG (localId INTEGER PRIMARY KEY, StrContent TEXT); INSERT INTO MSG (StrContent) VALUES ('checkpointed');").unwrap();
+        write_encrypted_copy(&seed, &db_path, [7; SALT_SIZE]);
+
+        // Keep a writer open with checkpoints disabled, as WeChat does: new rows stay in the WAL.
+        let salt_key = sqlcipher_raw_key(&db_path, TEST_KEY).unwrap();
+        let live = Connection::open(&db_path).unwrap();
+        live.execute_batch(&format!(
+            "PRAGMA key = \"{}\"; PRAGMA cipher_page_size = 4096; PRAGMA kdf_iter = 64000;
+             PRAGMA cipher_hmac_algorithm = HMAC_SHA1; PRAGMA cipher_kdf_algorithm = PBKDF2_HMAC_SHA1;",
+            salt_key
+        ))
+        .unwrap();
+        live.query_row("PRAGMA journal_mode = WAL", [], |_| Ok(())).unwrap();
+        live.execute_batch("PRAGMA wal_autocheckpoint = 0;").unwrap();
+        for i in 0..200 {
+            live.execute("INSERT INTO MSG (StrContent) VALUES (?)", [format!("live message {} {}", i, "x".repeat(100))]).unwrap();
+        }
+        assert!(std::fs::metadata(wal_path_of(&db_path)).unwrap().len() > 0);
+
+        let out = dir.join("MSG0.decrypted.db");
+        decrypt_live_database_file(&db_path, &out, TEST_KEY).unwrap();
+        assert!(!dir.join(format!(".snapshot_{}", std::process::id())).exists());
+
+        let plain = Connection::open(&out).unwrap();
+        let count: i64 = plain.query_row("SELECT COUNT(*) FROM MSG", [], |r| r.get(0)).unwrap();
+        assert_eq!(count, 201);
+        let journal: String = plain.query_row("PRAGMA journal_mode", [], |r| r.get(0)).unwrap();
+        assert_eq!(journal, "delete");
+        drop(live);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_wal_checksum() {
+        let dir = std::env::temp_dir().join(format!("wxdump_rs_wal_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let db_path = dir.join("plain.db");
+        let conn = Connection::open(&db_path).unwrap();
+        conn.query_row("PRAGMA journal_mode = WAL", [], |_| Ok(())).unwrap();
+        conn.execute_batch("PRAGMA wal_autocheckpoint = 0; CREATE TABLE t (x TEXT); INSERT INTO t VALUES ('a');").unwrap();
+        let mut wal = std::fs::read(wal_path_of(&db_path)).unwrap();
+        let be_u32 = |b: &[u8]| u32::from_be_bytes([b[0], b[1], b[2], b[3]]);
+
+        // The header and the first frame checksums as written by SQLite.
+        let big_endian = be_u32(&wal[0..4]) & 1 == 1;
+        let header = wal_checksum(&wal[0..24], big_endian, (0, 0));
+        assert_eq!(header, (be_u32(&wal[24..28]), be_u32(&wal[28..32])));
+        let page_size = be_u32(&wal[8..12]) as usize;
+        let frame = &wal[WAL_HEADER_SIZE..WAL_HEADER_SIZE + WAL_FRAME_HEADER_SIZE + page_size];
+        let checksum = wal_checksum(&frame[WAL_FRAME_HEADER_SIZE..], big_endian, wal_checksum(&frame[0..8], big_endian, header));
+        assert_eq!(checksum, (be_u32(&frame[16..20]), be_u32(&frame[20..24])));
+
+        // A frame for page 0 ends the log instead of underflowing the page offset.
+        wal[WAL_HEADER_SIZE..WAL_HEADER_SIZE + 4].copy_from_slice(&[0; 4]);
+        let wal_path = dir.join("page0.db-wal");
+        std::fs::write(&wal_path, &wal).unwrap();
+        let mut out = File::create(dir.join("out.db")).unwrap();
+        assert_eq!(apply_encrypted_wal(&wal_path, &mut out, &[0; KEY_SIZE]).unwrap(), 0);
+        drop(conn);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,176 @@
+// src/core/error.rs
+
+use std::path::PathBuf;
+
+use crate::core::decryption::DecryptionError;
+
+/// Failures of the public APIs. Most functions still return `anyhow::Result`, carrying one of
+/// these inside so callers can `downcast_ref` (or use [`WxDumpError::exit_code_of`]) to tell
+/// what went wrong; an empty result is always `Ok`, never an error.
+#[derive(Debug)]
+pub enum WxDumpError {
+    WeChatNotRunning,
+    ProcessAccessDenied { pid: u32 },
+    ModuleNotFound { pid: u32, module: String },
+    OffsetsMissing { version: String },
+    KeyNotFound { pid: u32 },
+    KeyInvalid(String),
+    DatabaseNotFound(PathBuf),
+    SchemaUnexpected(String),
+    Parse { field: String, message: String },
+    InvalidArgument(String),
+    Io(std::io::Error),
+    Sqlite(rusqlite::Error),
+    Other(String),
+}
+
+impl WxDumpError {
+    pub fn parse(field: impl Into<String>, message: impl std::fmt::Display) -> WxDumpError {
+        WxDumpError::Parse { field: field.into(), message: message.to_string() }
+    }
+
+    /// Process exit code of the CLI for this error. Invalid arguments deliberately share 2 with
+    /// clap's usage errors: both mean the command line has to be fixed.
+    pub fn exit_code(&self) -> u8 {
+        match self {
+            WxDumpError::Other(_) => 1,
+            WxDumpError::InvalidArgument(_) => 2,
+            WxDumpError::DatabaseNotFound(_) => 3,
+            WxDumpError::WeChatNotRunning => 4,
+            WxDumpError::ProcessAccessDenied { .. } => 5,
+            WxDumpError::ModuleNotFound { .. } => 6,
+            WxDumpError::OffsetsMissing { .. } => 7,
+            WxDumpError::KeyNotFound { .. } | WxDumpError::KeyInvalid(_) => 8,
+            WxDumpError::SchemaUnexpected(_) => 9,
+            WxDumpError::Parse { .. } => 10,
+            WxDumpError::Io(_) => 11,
+            WxDumpError::Sqlite(_) => 12,
+        }
+    }
+
+    /// Exit code for an `anyhow` error: the first cause in its chain we know how to classify.
+    pub fn exit_code_of(err: &anyhow::Error) -> u8 {
+        // Row mappers wrap ours inside rusqlite errors, so look for it through the whole chain first.
+        if let Some(e) = err.chain().find_map(|cause| cause.downcast_ref::<WxDumpError>()) {
+            return e.exit_code();
+        }
+        for cause in err.chain() {
+            if let Some(e) = cause.downcast_ref::<DecryptionError>() {
+                return classify_decryption(e).exit_code();
+            }
+            if let Some(e) = cause.downcast_ref::<rusqlite::Error>() {
+                return classify_sqlite(e).exit_code();
+            }
+            if cause.is::<std::io::Error>() {
+                return 11;
+            }
+        }
+        1
+    }
+}
+
+fn classify_decryption(err: &DecryptionError) -> WxDumpError {
+    match err {
+        DecryptionError::HmacVerificationFailed | DecryptionError::HexDecodingFailed(_) => WxDumpError::KeyInvalid(err.to_string()),
+        _ => WxDumpError::Other(err.to_string()),
+    }
+}
+
+/// "no such table/column" means the file is not the database (or WeChat version) we expected.
+fn classify_sqlite(err: &rusqlite::Error) -> WxDumpError {
+    let message = err.to_string();
+    if message.starts_with("no such table") || message.starts_with("no such column") {
+        WxDumpError::SchemaUnexpected(message)
+    } else {
+        WxDumpError::Other(message)
+    }
+}
+
+impl std::fmt::Display for WxDumpError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            WxDumpError::WeChatNotRunning => write!(f, "No running WeChat.exe found"),
+            WxDumpError::ProcessAccessDenied { pid } => write!(f, "Access denied to process {} (run as administrator?)", pid),
+            WxDumpError::ModuleNotFound { pid, module } => write!(f, "Module '{}' not found in PID {}", module, pid),
+            WxDumpError::OffsetsMissing { version } => write!(f, "No offsets for WeChat version {} in WX_OFFS.json", version),
+            WxDumpError::KeyNotFound { pid } => write!(f, "Database key not found in the memory of PID {}", pid),
+            WxDumpError::KeyInvalid(s) => write!(f, "Invalid key: {}", s),
+            WxDumpError::DatabaseNotFound(p) => write!(f, "Database file not found: {:?}", p),
+            WxDumpError::SchemaUnexpected(s) => write!(f, "Unexpected database schema: {}", s),
+            WxDumpError::Parse { field, message } => write!(f, "Failed to parse {}: {}", field, message),
+            WxDumpError::InvalidArgument(s) => write!(f, "{}", s),
+            WxDumpError::Io(e) => write!(f, "IO error: {}", e),
+            WxDumpError::Sqlite(e) => write!(f, "SQLite error: {}", e),
+            WxDumpError::Other(s) => write!(f, "{}", s),
+        }
+    }
+}
+
+impl std::error::Error for WxDumpError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            WxDumpError::Io(e) => Some(e),
+            WxDumpError::Sqlite(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for WxDumpError {
+    fn from(err: std::io::Error) -> WxDumpError {
+        WxDumpError::Io(err)
+    }
+}
+
+impl From<rusqlite::Error> for WxDumpError {
+    fn from(err: rusqlite::Error) -> WxDumpError {
+        match classify_sqlite(&err) {
+            WxDumpError::SchemaUnexpected(s) => WxDumpError::SchemaUnexpected(s),
+            _ => WxDumpError::Sqlite(err),
+        }
+    }
+}
+
+impl From<DecryptionError> for WxDumpError {
+    fn from(err: DecryptionError) -> WxDumpError {
+        match err {
+            DecryptionError::Io(e) => WxDumpError::Io(e),
+            other => classify_decryption(&other),
+        }
+    }
+}
+
+/// Keeps a [`WxDumpError`] carried by an `anyhow` error (e.g. from `win_api`), otherwise
+/// falls back to `Other` with the full message.
+impl From<anyhow::Error> for WxDumpError {
+    fn from(err: anyhow::Error) -> WxDumpError {
+        match err.downcast::<WxDumpError>() {
+            Ok(e) => e,
+            Err(err) => match err.downcast::<rusqlite::Error>() {
+                Ok(e) => WxDumpError::from(e),
+                Err(err) => WxDumpError::Other(format!("{:#}", err)),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::Context;
+
+    #[test]
+    fn test_exit_codes() {
+        let err = anyhow::Error::from(WxDumpError::OffsetsMissing { version: "3.9.0.28".into() }).context("Bias failed");
+        assert_eq!(WxDumpError::exit_code_of(&err), 7);
+
+        let conn = rusqlite::Connection::open_in_memory().unwrap();
+        let err = conn.prepare("SELECT * FROM Contact").map_err(anyhow::Error::from).context("contacts").unwrap_err();
+        assert_eq!(WxDumpError::exit_code_of(&err), 9);
+        assert!(matches!(WxDumpError::from(err), WxDumpError::SchemaUnexpected(_)));
+
+        let err = Err::<(), _>(DecryptionError::HmacVerificationFailed).context("MSG0.db").unwrap_err();
+        assert_eq!(WxDumpError::exit_code_of(&err), 8);
+        assert_eq!(WxDumpError::exit_code_of(&anyhow::anyhow!("plain")), 1);
+    }
+}
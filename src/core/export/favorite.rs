@@ -0,0 +1,172 @@
+// src/core/export/favorite.rs
+
+use anyhow::Result;
+use std::io::Write;
+
+use crate::core::db_parser::{FavData, FavoriteContent, FavoriteItem, FAV_TYPE_TEXT};
+
+/// What `favorites` writes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum FavoriteFormat {
+    Json,
+    #[value(alias = "markdown")]
+    Md,
+}
+
+/// Writes `items` as a pretty-printed JSON array.
+pub fn write_favorites_json(items: &[FavoriteItem], mut out: impl Write) -> Result<()> {
+    serde_json::to_writer_pretty(&mut out, items)?;
+    out.write_all(b"\n")?;
+    out.flush()?;
+    Ok(())
+}
+
+/// Writes `items` as one Markdown document, a section per favorite.
+pub fn write_favorites_markdown(items: &[FavoriteItem], mut out: impl Write) -> Result<()> {
+    writeln!(out, "# 收藏\n\n{} items", items.len())?;
+    for item in items {
+        writeln!(out, "\n## {}\n", item_title(item))?;
+        let mut meta = vec![item.time_str.clone(), item.content.kind().to_string()];
+        if let Some(source) = item.source_name.as_ref().or(item.source_talker.as_ref()) {
+            meta.push(format!("from {}", source));
+        }
+        if !item.tags.is_empty() {
+            meta.push(item.tags.iter().map(|t| format!("#{}", t)).collect::<Vec<_>>().join(" "));
+        }
+        writeln!(out, "_{}_\n", meta.join(" · "))?;
+        write_content(&mut out, &item.content)?;
+    }
+    out.flush()?;
+    Ok(())
+}
+
+fn item_title(item: &FavoriteItem) -> String {
+    let title = match &item.content {
+        FavoriteContent::Text { text } | FavoriteContent::Note { text, .. } => text.lines().next().map(str::to_string),
+        FavoriteContent::Link { title, .. } | FavoriteContent::File { title, .. } => title.clone(),
+        FavoriteContent::ChatRecord { title, .. } => title.clone(),
+        FavoriteContent::Location { poi_name, label, .. } => poi_name.clone().or_else(|| label.clone()),
+        _ => None,
+    };
+    let title: String = title.unwrap_or_else(|| format!("#{}", item.local_id)).chars().take(60).collect();
+    title.replace('#', "\\#")
+}
+
+fn write_content(out: &mut impl Write, content: &FavoriteContent) -> Result<()> {
+    match content {
+        FavoriteContent::Text { text } => writeln!(out, "{}", quote(text))?,
+        FavoriteContent::Note { text, items } => {
+            writeln!(out, "{}", quote(text))?;
+            write_items(out, items)?;
+        }
+        FavoriteContent::Link { title, description, url } => {
+            let label = title.as_deref().or(url.as_deref()).unwrap_or_default();
+            match url {
+                Some(url) => writeln!(out, "[{}]({})", label, url)?,
+                None => writeln!(out, "{}", label)?,
+            }
+            if let Some(description) = description {
+                writeln!(out, "\n{}", quote(description))?;
+            }
+        }
+        FavoriteContent::Location { label, poi_name, latitude, longitude } => {
+            let place = [poi_name, label].into_iter().flatten().cloned().collect::<Vec<_>>().join(", ");
+            match (latitude, longitude) {
+                (Some(lat), Some(lng)) => writeln!(out, "📍 {} ({}, {})", place, lat, lng)?,
+                _ => writeln!(out, "📍 {}", place)?,
+            }
+        }
+        FavoriteContent::ChatRecord { records, .. } => {
+            for record in records {
+                let who = record.source_name.as_deref().unwrap_or("?");
+                let when = record.source_time.as_deref().map(|t| format!("[{}] ", t)).unwrap_or_default();
+                writeln!(out, "- {}**{}**: {}", when, who, data_summary(record))?;
+            }
+        }
+        FavoriteContent::Image { items }
+        | FavoriteContent::Voice { items }
+        | FavoriteContent::Video { items }
+        | FavoriteContent::File { items, .. }
+        | FavoriteContent::Other { items, .. } => write_items(out, items)?,
+    }
+    Ok(())
+}
+
+fn write_items(out: &mut impl Write, items: &[FavData]) -> Result<()> {
+    for item in items.iter().filter(|i| i.data_type != Some(FAV_TYPE_TEXT)) {
+        writeln!(out, "- {}", data_summary(item))?;
+    }
+    Ok(())
+}
+
+/// Text of a text item, otherwise `[fmt: title, size]` style placeholder.
+fn data_summary(data: &FavData) -> String {
+    if let Some(desc) = data.desc.as_ref().filter(|_| data.data_type == Some(FAV_TYPE_TEXT)) {
+        return desc.replace('\n', " ");
+    }
+    let mut parts: Vec<String> = Vec::new();
+    parts.extend(data.title.clone());
+    if let Some(size) = data.size {
+        parts.push(format!("{} bytes", size));
+    }
+    let format = data.format.as_deref().unwrap_or("attachment");
+    if parts.is_empty() { format!("[{}]", format) } else { format!("[{}: {}]", format, parts.join(", ")) }
+}
+
+fn quote(text: &str) -> String {
+    text.lines().map(|l| format!("> {}", l)).collect::<Vec<_>>().join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn item(local_id: i64, content: FavoriteContent, tags: &[&str]) -> FavoriteItem {
+        FavoriteItem {
+            local_id,
+            server_id: None,
+            fav_type: 0,
+            from_wxid: None,
+            source_talker: Some("wxid_alice".to_string()),
+            source_name: Some("Alice".to_string()),
+            update_time: 1_700_000_000,
+            time_str: "2023-11-14 22:13:20".to_string(),
+            tags: tags.iter().map(|t| t.to_string()).collect(),
+            content,
+        }
+    }
+
+    #[test]
+    fn test_markdown() {
+        let items = vec![
+            item(1, FavoriteContent::Text { text: "remember\nthe milk".to_string() }, &["todo"]),
+            item(
+                2,
+                FavoriteContent::ChatRecord {
+                    title: Some("Group chat".to_string()),
+                    records: vec![
+                        FavData {
+                            data_type: Some(1),
+                            desc: Some("hello all".to_string()),
+                            source_name: Some("Stranger".to_string()),
+                            ..Default::default()
+                        },
+                        FavData { data_type: Some(2), format: Some("jpg".to_string()), size: Some(10), ..Default::default() },
+                    ],
+                },
+                &[],
+            ),
+        ];
+        let mut out = Vec::new();
+        write_favorites_markdown(&items, &mut out).unwrap();
+        let md = String::from_utf8(out).unwrap();
+        assert!(md.contains("## remember\n\n_2023-11-14 22:13:20 · text · from Alice · #todo_\n\n> remember\n> the milk\n"));
+        assert!(md.contains("## Group chat\n"));
+        assert!(md.contains("- **Stranger**: hello all\n- **?**: [jpg: 10 bytes]\n"));
+
+        let mut out = Vec::new();
+        write_favorites_json(&items, &mut out).unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&out).unwrap();
+        assert_eq!(json[1]["content"]["kind"], "chat_record");
+    }
+}
@@ -0,0 +1,189 @@
+// src/core/export/graph.rs
+
+use anyhow::Result;
+use serde_json::json;
+use std::io::Write;
+
+use crate::core::analytics::graph::{ContactGraph, GraphNode};
+use crate::core::html::escape_html;
+
+/// What `graph` writes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum GraphFormat {
+    Graphml,
+    Gexf,
+    /// Node-link JSON.
+    Json,
+}
+
+/// Node attributes of the XML formats: (name, GraphML type, GEXF type).
+const NODE_ATTRIBUTES: [(&str, &str, &str); 5] = [
+    ("kind", "string", "string"),
+    ("is_friend", "boolean", "boolean"),
+    ("deleted", "boolean", "boolean"),
+    ("official", "boolean", "boolean"),
+    ("messages", "int", "integer"),
+];
+
+/// Writes `graph` in the node-link JSON of networkx and d3 (`nodes`/`links`). Two contacts
+/// can be linked by both a `shared_rooms` and a `messages` edge, hence `multigraph`.
+pub fn write_graph_json(graph: &ContactGraph, mut out: impl Write) -> Result<()> {
+    let value = json!({
+        "directed": false,
+        "multigraph": true,
+        "graph": {},
+        "nodes": graph.nodes,
+        "links": graph.edges,
+    });
+    serde_json::to_writer_pretty(&mut out, &value)?;
+    out.write_all(b"\n")?;
+    out.flush()?;
+    Ok(())
+}
+
+/// Writes `graph` as GraphML (Gephi, yEd, Cytoscape, networkx), with the node attributes and
+/// the edge `kind` and `weight` as data keys.
+pub fn write_graphml(graph: &ContactGraph, mut out: impl Write) -> Result<()> {
+    writeln!(out, "<?xml version=\"1.0\" encoding=\"UTF-8\"?>")?;
+    writeln!(out, "<graphml xmlns=\"http://graphml.graphdrawing.org/xmlns\">")?;
+    writeln!(out, "  <key id=\"label\" for=\"node\" attr.name=\"label\" attr.type=\"string\"/>")?;
+    for (name, graphml_type, _) in NODE_ATTRIBUTES {
+        writeln!(out, "  <key id=\"{name}\" for=\"node\" attr.name=\"{name}\" attr.type=\"{graphml_type}\"/>")?;
+    }
+    writeln!(out, "  <key id=\"edge_kind\" for=\"edge\" attr.name=\"kind\" attr.type=\"string\"/>")?;
+    writeln!(out, "  <key id=\"weight\" for=\"edge\" attr.name=\"weight\" attr.type=\"double\"/>")?;
+    writeln!(out, "  <graph id=\"G\" edgedefault=\"undirected\">")?;
+    for node in &graph.nodes {
+        writeln!(out, "    <node id=\"{}\">", xml_text(&node.id))?;
+        writeln!(out, "      <data key=\"label\">{}</data>", xml_text(&node.label))?;
+        for (name, value) in node_values(node) {
+            writeln!(out, "      <data key=\"{}\">{}</data>", name, value)?;
+        }
+        writeln!(out, "    </node>")?;
+    }
+    for (i, edge) in graph.edges.iter().enumerate() {
+        writeln!(
+            out,
+            "    <edge id=\"e{}\" source=\"{}\" target=\"{}\"><data key=\"edge_kind\">{}</data><data key=\"weight\">{}</data></edge>",
+            i,
+            xml_text(&edge.source),
+            xml_text(&edge.target),
+            edge.kind.as_str(),
+            edge.weight
+        )?;
+    }
+    writeln!(out, "  </graph>")?;
+    writeln!(out, "</graphml>")?;
+    out.flush()?;
+    Ok(())
+}
+
+/// Writes `graph` as GEXF 1.3 (Gephi). Edges carry their kind in the `kind` attribute, which
+/// keeps parallel edges of different kinds apart.
+pub fn write_gexf(graph: &ContactGraph, mut out: impl Write) -> Result<()> {
+    writeln!(out, "<?xml version=\"1.0\" encoding=\"UTF-8\"?>")?;
+    writeln!(out, "<gexf xmlns=\"http://gexf.net/1.3\" version=\"1.3\">")?;
+    writeln!(out, "  <meta><creator>{}</creator></meta>", env!("CARGO_PKG_NAME"))?;
+    writeln!(out, "  <graph defaultedgetype=\"undirected\" mode=\"static\">")?;
+    writeln!(out, "    <attributes class=\"node\">")?;
+    for (name, _, gexf_type) in NODE_ATTRIBUTES {
+        writeln!(out, "      <attribute id=\"{name}\" title=\"{name}\" type=\"{gexf_type}\"/>")?;
+    }
+    writeln!(out, "    </attributes>")?;
+    writeln!(out, "    <nodes>")?;
+    for node in &graph.nodes {
+        writeln!(out, "      <node id=\"{}\" label=\"{}\">", xml_text(&node.id), xml_text(&node.label))?;
+        writeln!(out, "        <attvalues>")?;
+        for (name, value) in node_values(node) {
+            writeln!(out, "          <attvalue for=\"{}\" value=\"{}\"/>", name, value)?;
+        }
+        writeln!(out, "        </attvalues>")?;
+        writeln!(out, "      </node>")?;
+    }
+    writeln!(out, "    </nodes>")?;
+    writeln!(out, "    <edges>")?;
+    for (i, edge) in graph.edges.iter().enumerate() {
+        writeln!(
+            out,
+            "      <edge id=\"{}\" source=\"{}\" target=\"{}\" kind=\"{}\" weight=\"{}\"/>",
+            i,
+            xml_text(&edge.source),
+            xml_text(&edge.target),
+            edge.kind.as_str(),
+            edge.weight
+        )?;
+    }
+    writeln!(out, "    </edges>")?;
+    writeln!(out, "  </graph>")?;
+    writeln!(out, "</gexf>")?;
+    out.flush()?;
+    Ok(())
+}
+
+/// Escapes `text` for XML content and attributes, dropping the control characters XML 1.0
+/// does not allow (all below U+0020 except tab, line feed and carriage return), which
+/// nicknames do contain now and then.
+fn xml_text(text: &str) -> String {
+    let allowed = |c: &char| *c >= '\u{20}' || matches!(c, '\t' | '\n' | '\r');
+    escape_html(&text.chars().filter(allowed).collect::<String>())
+}
+
+/// Values of [`NODE_ATTRIBUTES`] for `node`, in the same order.
+fn node_values(node: &GraphNode) -> [(&'static str, String); 5] {
+    [
+        ("kind", node.kind.as_str().to_string()),
+        ("is_friend", node.is_friend.to_string()),
+        ("deleted", node.deleted.to_string()),
+        ("official", node.official.to_string()),
+        ("messages", node.messages.to_string()),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::analytics::graph::{EdgeKind, GraphEdge, NodeKind};
+
+    fn sample() -> ContactGraph {
+        let node = |id: &str, label: &str, kind| GraphNode {
+            id: id.into(),
+            label: label.into(),
+            kind,
+            is_friend: kind == NodeKind::Contact,
+            deleted: false,
+            official: false,
+            messages: 3,
+        };
+        ContactGraph {
+            nodes: vec![node("1@chatroom", "A & B\u{1}", NodeKind::Room), node("wxid_a", "\"A\"\u{8}", NodeKind::Contact)],
+            edges: vec![GraphEdge { source: "wxid_a".into(), target: "1@chatroom".into(), kind: EdgeKind::Member, weight: 2 }],
+        }
+    }
+
+    #[test]
+    fn test_graph_formats() {
+        let mut graphml = Vec::new();
+        write_graphml(&sample(), &mut graphml).unwrap();
+        let graphml = String::from_utf8(graphml).unwrap();
+        let doc = roxmltree::Document::parse(&graphml).unwrap();
+        assert_eq!(doc.descendants().filter(|n| n.has_tag_name("node")).count(), 2);
+        assert!(graphml.contains("<data key=\"label\">A &amp; B</data>"));
+        assert!(graphml.contains("source=\"wxid_a\" target=\"1@chatroom\"><data key=\"edge_kind\">member</data><data key=\"weight\">2</data>"));
+
+        let mut gexf = Vec::new();
+        write_gexf(&sample(), &mut gexf).unwrap();
+        let gexf = String::from_utf8(gexf).unwrap();
+        let doc = roxmltree::Document::parse(&gexf).unwrap();
+        let node = doc.descendants().find(|n| n.attribute("id") == Some("wxid_a")).unwrap();
+        assert_eq!(node.attribute("label"), Some("\"A\""));
+        assert!(gexf.contains("<attvalue for=\"is_friend\" value=\"true\"/>"));
+        assert!(gexf.contains("kind=\"member\" weight=\"2\""));
+
+        let mut json = Vec::new();
+        write_graph_json(&sample(), &mut json).unwrap();
+        let parsed: serde_json::Value = serde_json::from_slice(&json).unwrap();
+        assert_eq!(parsed["nodes"][0]["kind"], "room");
+        assert_eq!(parsed["links"][0]["source"], "wxid_a");
+        assert_eq!(parsed["links"][0]["kind"], "member");
+    }
+}
@@ -0,0 +1,437 @@
+// src/core/export/html.rs
+
+use anyhow::{Result, anyhow};
+use base64::Engine;
+use rusqlite::Connection;
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+
+use super::Participants;
+use crate::core::db_parser::{
+    find_voice_blob, get_messages, message_sender_wxid, read_cached_emoji, resolve_emoji, strip_wechat_silk_prefix,
+    EmojiInfo, EmojiSource, Message, MSG_TYPE_APP, MSG_TYPE_IMAGE, MSG_TYPE_VIDEO, MSG_TYPE_VOICE,
+};
+use crate::core::html::{escape_html, render_messages, MediaLink, MessageContext, MESSAGE_STYLE};
+use crate::core::image_decode::{decode_dat_file, detect_image_format, DatKeys};
+use crate::core::media_resolver::{MediaKind, MediaResolver};
+use crate::core::silk;
+
+/// Name of the assets folder next to `index.html` in folder exports.
+const ASSETS_DIR: &str = "assets";
+
+const STYLE: &str = "
+body { margin: 0; font-family: -apple-system, 'Microsoft YaHei', sans-serif; background: #ededed; }
+header { background: #2e2e2e; color: #eee; padding: 12px 16px; }
+header .sub { color: #aaa; font-size: 12px; }
+main { max-width: 860px; margin: 0 auto; padding: 16px; }
+";
+
+/// Options of `export_html`.
+#[derive(Debug, Clone, Default)]
+pub struct HtmlExportOptions {
+    /// wxid of the account owner, used for own messages in chat rooms.
+    pub my_wxid: String,
+    /// Account folder (`WeChat Files/<wxid>`) holding images, videos, files and stickers.
+    pub wx_path: Option<PathBuf>,
+    /// Keys for V1/V2 `.dat` images.
+    pub dat_keys: DatKeys,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct HtmlExportReport {
+    pub messages: usize,
+    pub assets: usize,
+    /// Media messages whose file could not be found or decoded (rendered as placeholders).
+    pub missing_media: usize,
+    /// The written HTML file.
+    pub html_path: PathBuf,
+}
+
+/// Where media referenced by the page ends up.
+enum AssetSink {
+    /// Everything inside the single HTML file as `data:` urls. Videos and attachments are left out.
+    Inline,
+    /// Files under `<dir>/assets`, referenced by relative urls.
+    Folder(PathBuf),
+}
+
+impl AssetSink {
+    fn embeds_large_files(&self) -> bool {
+        matches!(self, AssetSink::Folder(_))
+    }
+
+    fn store(&self, name: &str, mime: &str, data: &[u8]) -> Result<String> {
+        match self {
+            AssetSink::Inline => {
+                Ok(format!("data:{};base64,{}", mime, base64::engine::general_purpose::STANDARD.encode(data)))
+            }
+            AssetSink::Folder(dir) => {
+                let assets = dir.join(ASSETS_DIR);
+                std::fs::create_dir_all(&assets)?;
+                std::fs::write(assets.join(name), data)?;
+                Ok(format!("{}/{}", ASSETS_DIR, encode_path_segment(name)))
+            }
+        }
+    }
+}
+
+/// Renders the conversation with `talker` as HTML.
+///
+/// If `out` ends in `.html` a single self-contained file is written (images, stickers and
+/// decodable voice embedded). Otherwise `out` is a folder receiving `index.html` and an
+/// `assets` folder that also holds videos, raw voice and attached files.
+///
+/// `msg_conn` holds MSG, `contact_conn` the MicroMsg tables (the same connection for a merged
+/// db) and `media_conns` the MediaMSG databases used for voice.
+pub fn export_html(
+    msg_conn: &Connection,
+    contact_conn: &Connection,
+    media_conns: &[Connection],
+    talker: &str,
+    options: &HtmlExportOptions,
+    out: &Path,
+) -> Result<HtmlExportReport> {
+    let single_file = out.extension().is_some_and(|e| e.eq_ignore_ascii_case("html") || e.eq_ignore_ascii_case("htm"));
+    let (sink, html_path) = if single_file {
+        if let Some(parent) = out.parent().filter(|p| !p.as_os_str().is_empty()) {
+            std::fs::create_dir_all(parent)?;
+        }
+        (AssetSink::Inline, out.to_path_buf())
+    } else {
+        std::fs::create_dir_all(out)?;
+        (AssetSink::Folder(out.to_path_buf()), out.join("index.html"))
+    };
+
+    let messages = get_messages(msg_conn, Some(talker), None, None, None, None, None)?;
+    let mut senders: Vec<String> = messages.iter().map(|m| message_sender_wxid(m, &options.my_wxid)).collect();
+    senders.push(options.my_wxid.clone());
+    let participants = Participants::load(contact_conn, talker, &senders)?;
+
+    let mut renderer = Renderer {
+        participants: &participants,
+        resolver: options.wx_path.as_ref().map(MediaResolver::new),
+        media_conns,
+        options,
+        sink: &sink,
+        assets: 0,
+        missing_media: 0,
+    };
+
+    let body = render_messages(&mut renderer, &messages)?;
+
+    let title = participants.name(talker);
+    let range = match (messages.first(), messages.last()) {
+        (Some(first), Some(last)) => format!("{} ~ {}", first.time_str, last.time_str),
+        _ => String::new(),
+    };
+    let html = format!(
+        "<!DOCTYPE html><html><head><meta charset=\"utf-8\"><title>{title}</title><style>{STYLE}{MESSAGE_STYLE}</style></head>\
+         <body><header>{title}<div class=\"sub\">{count} 条消息 {range}</div></header><main>{body}</main></body></html>",
+        title = escape_html(&title),
+        count = messages.len(),
+        range = escape_html(&range),
+        body = body
+    );
+    std::fs::write(&html_path, html).map_err(|e| anyhow!("Failed to write {:?}: {}", html_path, e))?;
+
+    Ok(HtmlExportReport {
+        messages: messages.len(),
+        assets: renderer.assets,
+        missing_media: renderer.missing_media,
+        html_path,
+    })
+}
+
+struct Renderer<'a> {
+    participants: &'a Participants,
+    resolver: Option<MediaResolver>,
+    media_conns: &'a [Connection],
+    options: &'a HtmlExportOptions,
+    sink: &'a AssetSink,
+    assets: usize,
+    missing_media: usize,
+}
+
+impl MessageContext for Renderer<'_> {
+    fn my_wxid(&self) -> &str {
+        &self.options.my_wxid
+    }
+
+    fn display_name(&self, wxid: &str) -> String {
+        self.participants.name(wxid)
+    }
+
+    fn media(&mut self, msg: &Message) -> Result<Option<MediaLink>> {
+        let link = match msg.msg_type {
+            MSG_TYPE_IMAGE => self.image(msg)?,
+            MSG_TYPE_VIDEO => self.video(msg)?,
+            MSG_TYPE_VOICE => self.voice(msg)?,
+            MSG_TYPE_APP => return self.file(msg),
+            _ => return Ok(None),
+        };
+        if link.is_none() {
+            self.missing_media += 1;
+        }
+        Ok(link)
+    }
+
+    fn emoji(&mut self, emoji: &EmojiInfo) -> Result<Option<String>> {
+        let root = self.resolver.as_ref().map(|r| r.account_root().to_path_buf());
+        match resolve_emoji(None, root.as_deref(), emoji)? {
+            EmojiSource::LocalFile { path, encrypted } => {
+                if let Ok(data) = read_cached_emoji(&path, encrypted, emoji.aes_key.as_deref()) {
+                    if let Some(format) = detect_image_format(&data) {
+                        let name = format!("emoji_{}.{}", emoji.md5, format.extension());
+                        return self.store(&name, format.mime_type(), &data).map(Some);
+                    }
+                }
+                Ok(None)
+            }
+            // Stickers only on the CDN stay placeholders: opening an export makes no remote requests.
+            _ => Ok(None),
+        }
+    }
+}
+
+impl Renderer<'_> {
+    fn store(&mut self, name: &str, mime: &str, data: &[u8]) -> Result<String> {
+        self.assets += 1;
+        self.sink.store(name, mime, data)
+    }
+
+    fn image(&mut self, msg: &Message) -> Result<Option<MediaLink>> {
+        let Some(media) = self.resolver.as_ref().and_then(|r| r.resolve(msg)) else { return Ok(None) };
+        let candidates = [media.path.filter(|_| media.exists), media.thumb_path];
+        for path in candidates.iter().flatten().filter_map(|p| self.account_file(p)) {
+            if let Ok(image) = decode_dat_file(&path, &self.options.dat_keys) {
+                let name = format!("img_{}.{}", msg.local_id, image.format.extension());
+                return self.store(&name, image.format.mime_type(), &image.data).map(|url| Some(MediaLink::Image(url)));
+            }
+        }
+        Ok(None)
+    }
+
+    fn video(&mut self, msg: &Message) -> Result<Option<MediaLink>> {
+        let Some(media) = self.resolver.as_ref().and_then(|r| r.resolve(msg)) else { return Ok(None) };
+        if self.sink.embeds_large_files() && media.exists {
+            let data = media.path.as_deref().and_then(|p| self.account_file(p)).and_then(|p| std::fs::read(p).ok());
+            if let Some(data) = data {
+                let url = self.store(&format!("video_{}.mp4", msg.local_id), "video/mp4", &data)?;
+                return Ok(Some(MediaLink::Video(url)));
+            }
+        }
+        // Single-file exports (or missing videos) fall back to the thumbnail.
+        let thumb = media.thumb_path.as_deref().and_then(|t| self.account_file(t));
+        if let Some(data) = thumb.and_then(|t| std::fs::read(t).ok()) {
+            if let Some(format) = detect_image_format(&data) {
+                let url = self.store(&format!("video_{}.{}", msg.local_id, format.extension()), format.mime_type(), &data)?;
+                return Ok(Some(MediaLink::VideoThumbnail(url)));
+            }
+        }
+        Ok(None)
+    }
+
+    fn voice(&mut self, msg: &Message) -> Result<Option<MediaLink>> {
+        let Some(blob) = msg.msg_svr_id.map(|id| find_voice_blob(self.media_conns, id)).transpose()?.flatten() else {
+            return Ok(None);
+        };
+        let silk_data = strip_wechat_silk_prefix(&blob);
+
+        if silk::silk_decoding_available() {
+            if let Ok(pcm) = silk::decode_silk_to_pcm(silk_data, silk::DEFAULT_SAMPLE_RATE) {
+                let wav = silk::encode_wav(&pcm, silk::DEFAULT_SAMPLE_RATE);
+                let url = self.store(&format!("voice_{}.wav", msg.local_id), "audio/wav", &wav)?;
+                return Ok(Some(MediaLink::Audio(url)));
+            }
+        }
+        if self.sink.embeds_large_files() {
+            let url = self.store(&format!("voice_{}.silk", msg.local_id), "audio/silk", silk_data)?;
+            return Ok(Some(MediaLink::VoiceDownload(url)));
+        }
+        Ok(None)
+    }
+
+    /// Attached files are copied into folder exports only; a missing file, or one that cannot be
+    /// copied, counts as missing media.
+    fn file(&mut self, msg: &Message) -> Result<Option<MediaLink>> {
+        let Some(media) = self.resolver.as_ref().and_then(|r| r.resolve(msg)).filter(|m| m.kind == MediaKind::File) else {
+            return Ok(None);
+        };
+        if !self.sink.embeds_large_files() {
+            if !media.exists {
+                self.missing_media += 1;
+            }
+            return Ok(Some(MediaLink::File(None)));
+        }
+        let path = media.path.as_deref().filter(|_| media.exists).and_then(|p| self.account_file(p));
+        if let Some((path, data)) = path.and_then(|p| std::fs::read(&p).ok().map(|data| (p, data))) {
+            let fallback = format!("file_{}", msg.local_id);
+            let name = format!("{}_{}", msg.local_id, file_name_or(&path, &fallback));
+            let url = self.store(&name, "application/octet-stream", &data)?;
+            return Ok(Some(MediaLink::File(Some(url))));
+        }
+        self.missing_media += 1;
+        Ok(Some(MediaLink::File(None)))
+    }
+
+    /// `path` if it is a regular file inside the account folder once symlinks are followed. Only
+    /// such files may end up in an export that is handed to others.
+    fn account_file(&self, path: &Path) -> Option<PathBuf> {
+        let root = self.resolver.as_ref()?.account_root().canonicalize().ok()?;
+        path.canonicalize().ok().filter(|p| p.starts_with(&root) && p.is_file())
+    }
+}
+
+fn file_name_or(path: &Path, fallback: &str) -> String {
+    path.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_else(|| fallback.to_string())
+}
+
+/// Percent-encodes a file name for use in a relative url.
+fn encode_path_segment(name: &str) -> String {
+    crate::core::html::encode_query_value(name).replace('+', "%20")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::testutil::merged_db_with_sample_data;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("wxdump_rs_export_html_{}_{}", name, std::process::id()))
+    }
+
+    #[test]
+    fn test_export_single_file_chat_room() {
+        let conn = merged_db_with_sample_data();
+        let dir = temp_dir("single");
+        let out = dir.join("room.html");
+        let options = HtmlExportOptions { my_wxid: "wxid_me".to_string(), ..Default::default() };
+        let report = export_html(&conn, &conn, &[], "123@chatroom", &options, &out).unwrap();
+        assert_eq!(report.messages, 3);
+        assert_eq!(report.html_path, out);
+
+        let html = std::fs::read_to_string(&out).unwrap();
+        assert!(html.contains("<title>Project &lt;Team&gt;</title>"));
+        assert!(html.contains("Stranger"));
+        // Quote reply shows the quoted message below the reply.
+        assert!(html.contains("welcome!<div class=\"quote\">Stranger: hello all</div>"));
+        assert!(html.contains("<div class=\"sys revoked\">"));
+        assert!(!dir.join(ASSETS_DIR).exists());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_export_leaves_remote_stickers_as_placeholders() {
+        let conn = merged_db_with_sample_data();
+        conn.execute(
+            "INSERT INTO MSG (MsgSvrID, Type, SubType, IsSender, CreateTime, StrTalker, StrContent)
+             VALUES (5001, 47, 0, 0, 1700000400, 'wxid_bob', ?)",
+            [format!("<msg><emoji md5=\"{}\" cdnurl=\"https://cdn.example/e.gif\" /></msg>", "ab".repeat(16))],
+        )
+        .unwrap();
+        let dir = temp_dir("stickers");
+        let out = dir.join("bob.html");
+        let options = HtmlExportOptions { my_wxid: "wxid_me".to_string(), ..Default::default() };
+        export_html(&conn, &conn, &[], "wxid_bob", &options, &out).unwrap();
+
+        let html = std::fs::read_to_string(&out).unwrap();
+        assert!(!html.contains("cdn.example"));
+        assert!(html.contains("[表情]"));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_export_folder_with_voice_asset() {
+        let conn = merged_db_with_sample_data();
+        let media = merged_db_with_sample_data();
+        let dir = temp_dir("folder");
+        let options = HtmlExportOptions { my_wxid: "wxid_me".to_string(), ..Default::default() };
+        let report = export_html(&conn, &conn, &[media], "wxid_alice", &options, &dir).unwrap();
+        assert_eq!(report.messages, 3);
+        assert_eq!(report.assets, 1);
+
+        let html = std::fs::read_to_string(dir.join("index.html")).unwrap();
+        // Avatars are letters, not the remote head images.
+        assert!(!html.contains("http://img/alice.jpg"));
+        assert!(html.contains("hello &lt;b&gt;Alice&lt;/b&gt;"));
+        let voice = if silk::silk_decoding_available() { "voice_3.wav" } else { "voice_3.silk" };
+        assert!(dir.join(ASSETS_DIR).join(voice).is_file());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_export_folder_copies_only_files_of_the_account() {
+        use crate::core::testutil::bytes_extra;
+
+        let root = temp_dir("files");
+        let account = root.join("wxid_me");
+        let files = account.join("FileStorage").join("File").join("2023-11");
+        std::fs::create_dir_all(&files).unwrap();
+        std::fs::write(files.join("report.pdf"), b"%PDF").unwrap();
+        std::fs::write(root.join("secret.txt"), b"outside").unwrap();
+        std::os::unix::fs::symlink(root.join("secret.txt"), files.join("link.txt")).unwrap();
+
+        let conn = merged_db_with_sample_data();
+        for (svr_id, title) in [(3001, "report.pdf"), (3002, "link.txt")] {
+            conn.execute(
+                "INSERT INTO MSG (MsgSvrID, Type, SubType, IsSender, CreateTime, StrTalker, StrContent, BytesExtra)
+                 VALUES (?, 49, 6, 0, 1700000400, 'wxid_bob', ?, ?)",
+                rusqlite::params![
+                    svr_id,
+                    format!("<msg><appmsg><title>{}</title><type>6</type></appmsg></msg>", title),
+                    bytes_extra(&[(4, &format!("wxid_me\\FileStorage\\File\\2023-11\\{}", title))]),
+                ],
+            )
+            .unwrap();
+        }
+
+        let out = root.join("out");
+        let options =
+            HtmlExportOptions { my_wxid: "wxid_me".to_string(), wx_path: Some(account.clone()), ..Default::default() };
+        let report = export_html(&conn, &conn, &[], "wxid_bob", &options, &out).unwrap();
+        assert_eq!(report.assets, 1);
+        let assets: Vec<_> = std::fs::read_dir(out.join(ASSETS_DIR)).unwrap().map(|e| e.unwrap().file_name()).collect();
+        assert_eq!(assets.len(), 1);
+        assert!(assets[0].to_string_lossy().ends_with("_report.pdf"));
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_export_folder_copies_only_videos_of_the_account() {
+        use crate::core::testutil::bytes_extra;
+
+        let root = temp_dir("videos");
+        let account = root.join("wxid_me");
+        let videos = account.join("FileStorage").join("Video").join("2023-11");
+        std::fs::create_dir_all(videos.join("dir.mp4")).unwrap();
+        std::fs::write(videos.join("clip.mp4"), b"mp4").unwrap();
+        std::fs::write(root.join("secret"), b"outside").unwrap();
+        std::os::unix::fs::symlink(root.join("secret"), videos.join("link.mp4")).unwrap();
+
+        let conn = merged_db_with_sample_data();
+        let stored =
+            ["clip.mp4", "link.mp4", "dir.mp4"].map(|name| format!("wxid_me\\FileStorage\\Video\\2023-11\\{}", name));
+        let escaping = "wxid_me\\FileStorage\\..\\..\\secret".to_string();
+        for (svr_id, path) in (4001..).zip(stored.iter().chain([&escaping])) {
+            conn.execute(
+                "INSERT INTO MSG (MsgSvrID, Type, SubType, IsSender, CreateTime, StrTalker, StrContent, BytesExtra)
+                 VALUES (?, 43, 0, 0, 1700000400, 'wxid_bob', '<msg><videomsg /></msg>', ?)",
+                rusqlite::params![svr_id, bytes_extra(&[(4, path)])],
+            )
+            .unwrap();
+        }
+
+        let out = root.join("out");
+        let options =
+            HtmlExportOptions { my_wxid: "wxid_me".to_string(), wx_path: Some(account.clone()), ..Default::default() };
+        let report = export_html(&conn, &conn, &[], "wxid_bob", &options, &out).unwrap();
+        assert_eq!(report.assets, 1);
+        assert_eq!(report.missing_media, 3);
+        let assets: Vec<_> = std::fs::read_dir(out.join(ASSETS_DIR)).unwrap().map(|e| e.unwrap().file_name()).collect();
+        assert_eq!(assets.len(), 1);
+        assert_eq!(std::fs::read(out.join(ASSETS_DIR).join(&assets[0])).unwrap(), b"mp4");
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
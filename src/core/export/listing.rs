@@ -0,0 +1,134 @@
+// src/core/export/listing.rs
+
+use anyhow::Result;
+use colored::Colorize;
+use serde::Serialize;
+use std::io::Write;
+
+use crate::core::db_browser::{clip_cell, write_text_table};
+
+/// Cells longer than this are cut in the table formats (long enough for a 64 char key).
+const MAX_CELL_CHARS: usize = 80;
+
+/// Output of the CLI commands (`--output`): what goes to stdout. Diagnostics always go to the
+/// log on stderr, so `json`/`jsonl` output can be piped into `jq`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, clap::ValueEnum)]
+pub enum ListFormat {
+    /// Aligned columns with a highlighted header (colors are dropped when stdout is not a terminal).
+    #[default]
+    Text,
+    /// All records as one pretty-printed JSON array (or object for summaries).
+    Json,
+    /// One JSON record per line.
+    #[value(alias = "ndjson")]
+    Jsonl,
+    /// The aligned columns of `Text`, never colored.
+    Table,
+}
+
+impl ListFormat {
+    pub fn is_json(&self) -> bool {
+        matches!(self, ListFormat::Json | ListFormat::Jsonl)
+    }
+}
+
+/// Writes `records`: serialized in full for the JSON formats, otherwise as a table of `columns`
+/// with the cells produced by `cells` (clipped to one short line each).
+pub fn write_listing<T: Serialize>(
+    out: &mut impl Write,
+    format: ListFormat,
+    records: &[T],
+    columns: &[&str],
+    cells: impl Fn(&T) -> Vec<String>,
+) -> Result<()> {
+    match format {
+        ListFormat::Json => {
+            serde_json::to_writer_pretty(&mut *out, records)?;
+            writeln!(out)?;
+        }
+        ListFormat::Jsonl => {
+            for record in records {
+                serde_json::to_writer(&mut *out, record)?;
+                writeln!(out)?;
+            }
+        }
+        ListFormat::Text | ListFormat::Table => {
+            let header: Vec<String> = columns.iter().map(|c| c.to_string()).collect();
+            let rows: Vec<Vec<String>> =
+                records.iter().map(|r| cells(r).iter().map(|c| clip_cell(c, MAX_CELL_CHARS)).collect()).collect();
+            let mut table = Vec::new();
+            write_text_table(&mut table, &header, &rows)?;
+            let table = String::from_utf8(table)?;
+            let footer = format!("({} rows)", rows.len());
+            if format == ListFormat::Table {
+                write!(out, "{}", table)?;
+                writeln!(out, "{}", footer)?;
+            } else {
+                for (i, line) in table.lines().enumerate() {
+                    match i {
+                        0 => writeln!(out, "{}", line.bold())?,
+                        1 => writeln!(out, "{}", line.dimmed())?,
+                        _ => writeln!(out, "{}", line)?,
+                    }
+                }
+                writeln!(out, "{}", footer.dimmed())?;
+            }
+        }
+    }
+    out.flush()?;
+    Ok(())
+}
+
+/// Result of a command that writes files: `text` for the table formats, `summary` as JSON
+/// (pretty for `json`, one line for `jsonl`).
+pub fn write_summary(out: &mut impl Write, format: ListFormat, text: &str, summary: &serde_json::Value) -> Result<()> {
+    match format {
+        ListFormat::Json => writeln!(out, "{}", serde_json::to_string_pretty(summary)?)?,
+        ListFormat::Jsonl => writeln!(out, "{}", serde_json::to_string(summary)?)?,
+        ListFormat::Text | ListFormat::Table => writeln!(out, "{}", text)?,
+    }
+    out.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::ValueEnum;
+    use serde_json::json;
+
+    #[derive(Serialize)]
+    struct Row {
+        wxid: &'static str,
+        names: Vec<&'static str>,
+    }
+
+    fn render(format: ListFormat) -> String {
+        let rows = [Row { wxid: "wxid_a", names: vec!["A", "Alice"] }, Row { wxid: "wxid_bob", names: vec![] }];
+        let mut out = Vec::new();
+        write_listing(&mut out, format, &rows, &["wxid", "names"], |r| vec![r.wxid.to_string(), r.names.join(", ")])
+            .unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn test_listing_formats() {
+        assert_eq!(
+            render(ListFormat::Table),
+            "wxid      names\n\
+             --------  --------\n\
+             wxid_a    A, Alice\n\
+             wxid_bob\n\
+             (2 rows)\n"
+        );
+        assert_eq!(render(ListFormat::Jsonl), "{\"wxid\":\"wxid_a\",\"names\":[\"A\",\"Alice\"]}\n{\"wxid\":\"wxid_bob\",\"names\":[]}\n");
+        let parsed: serde_json::Value = serde_json::from_str(&render(ListFormat::Json)).unwrap();
+        assert_eq!(parsed[1]["wxid"], "wxid_bob");
+        assert_eq!(ListFormat::from_str("NDJSON", true).unwrap(), ListFormat::Jsonl);
+        assert!(ListFormat::from_str("csv", true).is_err());
+
+        let mut out = Vec::new();
+        write_summary(&mut out, ListFormat::Jsonl, "Exported 3 row(s).", &json!({ "rows": 3 })).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), "{\"rows\":3}\n");
+    }
+}
@@ -0,0 +1,191 @@
+// src/core/export/mod.rs
+
+pub mod favorite;
+pub mod graph;
+pub mod html;
+pub mod listing;
+pub mod sns;
+pub mod stats;
+pub mod table;
+pub mod text;
+
+use anyhow::Result;
+use rusqlite::Connection;
+use std::collections::HashMap;
+
+use super::db_parser::{
+    app_msg_xml, get_chat_rooms, get_contacts, is_chat_room_wxid, parse_app_msg, parse_bytes_extra, parse_location, parse_refer_msg,
+    parse_voice_length_ms, system_message_text, Message, APP_MSG_TYPE_QUOTE, MSG_TYPE_APP, MSG_TYPE_EMOJI,
+    MSG_TYPE_IMAGE, MSG_TYPE_LOCATION, MSG_TYPE_SYSMSG, MSG_TYPE_SYSTEM, MSG_TYPE_TEXT, MSG_TYPE_VIDEO, MSG_TYPE_VOICE,
+};
+use super::media_resolver::{MediaKind, MediaResolver};
+
+/// Display names and avatars of the people appearing in one conversation.
+///
+/// Names follow WeChat: the group nickname (chat rooms only), then the remark, then the nickname.
+#[derive(Debug, Clone, Default)]
+pub struct Participants {
+    names: HashMap<String, String>,
+    avatars: HashMap<String, String>,
+}
+
+impl Participants {
+    /// Loads `wxids` (and the members of `talker` when it is a chat room) from the MicroMsg tables.
+    pub fn load(conn: &Connection, talker: &str, wxids: &[String]) -> Result<Self> {
+        let mut participants = Participants::default();
+
+        let mut all: Vec<String> = wxids.to_vec();
+        all.push(talker.to_string());
+        all.sort();
+        all.dedup();
+        for contact in get_contacts(conn, None, Some(&all), None)? {
+            let name = [&contact.remark, &contact.nickname]
+                .into_iter()
+                .flatten()
+                .find(|n| !n.is_empty())
+                .cloned()
+                .unwrap_or_else(|| contact.wxid.clone());
+            participants.names.insert(contact.wxid.clone(), name);
+            if let Some(url) = contact.head_img_url.filter(|u| !u.is_empty()) {
+                participants.avatars.insert(contact.wxid, url);
+            }
+        }
+
+        if is_chat_room_wxid(talker) {
+            let rooms = get_chat_rooms(conn, Some(&[talker.to_string()]))?;
+            for member in rooms.into_values().flat_map(|room| room.members) {
+                let name = [&member.room_nickname, &member.remark, &member.nickname]
+                    .into_iter()
+                    .flatten()
+                    .find(|n| !n.is_empty())
+                    .cloned();
+                if let Some(name) = name {
+                    participants.names.insert(member.wxid.clone(), name);
+                }
+                if let Some(url) = member.head_img_url.filter(|u| !u.is_empty()) {
+                    participants.avatars.entry(member.wxid).or_insert(url);
+                }
+            }
+        }
+        Ok(participants)
+    }
+
+    pub fn name(&self, wxid: &str) -> String {
+        self.names.get(wxid).cloned().unwrap_or_else(|| wxid.to_string())
+    }
+
+    pub fn avatar(&self, wxid: &str) -> Option<&str> {
+        self.avatars.get(wxid).map(String::as_str)
+    }
+}
+
+/// One-line description of a message for transcripts and tabular exports.
+///
+/// Text is returned unchanged; other kinds become short placeholders such as `[Image: path]`,
+/// `[Voice 12s]` or `[Link: title url]`. Media paths are relative to the account folder when a
+/// resolver is given, otherwise as stored in BytesExtra.
+pub fn message_text(msg: &Message, resolver: Option<&MediaResolver>) -> String {
+    let content = msg.content.as_deref().unwrap_or("");
+    let with_path = |label: &str| match media_path(msg, resolver) {
+        Some(path) => format!("[{}: {}]", label, path),
+        None => format!("[{}]", label),
+    };
+
+    match msg.msg_type {
+        MSG_TYPE_TEXT => content.to_string(),
+        MSG_TYPE_IMAGE => with_path("Image"),
+        MSG_TYPE_VIDEO => with_path("Video"),
+        MSG_TYPE_VOICE => match parse_voice_length_ms(content) {
+            Some(ms) => format!("[Voice {}s]", (ms + 500) / 1000),
+            None => "[Voice]".to_string(),
+        },
+        MSG_TYPE_EMOJI => "[Sticker]".to_string(),
+        MSG_TYPE_LOCATION => {
+            let place = parse_location(content).map(|loc| {
+                [loc.poi_name, loc.label].into_iter().flatten().collect::<Vec<_>>().join(" ")
+            });
+            match place.filter(|p| !p.is_empty()) {
+                Some(place) => format!("[Location: {}]", place),
+                None => "[Location]".to_string(),
+            }
+        }
+        MSG_TYPE_APP => app_msg_text(msg, resolver),
+        MSG_TYPE_SYSTEM | MSG_TYPE_SYSMSG => format!("[System] {}", system_message_text(msg)),
+        other => format!("[Message type {}]", other),
+    }
+}
+
+fn app_msg_text(msg: &Message, resolver: Option<&MediaResolver>) -> String {
+    let xml = app_msg_xml(msg);
+    let Some(app) = xml.as_deref().and_then(parse_app_msg) else { return "[Link]".to_string() };
+    let title = app.title.clone().unwrap_or_default();
+
+    if app.app_type == Some(APP_MSG_TYPE_QUOTE) {
+        let quoted = xml.as_deref().and_then(parse_refer_msg).map(|refer| {
+            let who = refer.display_name.or(refer.sender_wxid).unwrap_or_default();
+            let what = match refer.msg_type {
+                Some(MSG_TYPE_TEXT) | None => refer.content.unwrap_or_default(),
+                Some(t) => message_text(&Message { msg_type: t, content: refer.content, ..Default::default() }, None),
+            };
+            format!(" [Quote {}: {}]", who, what.replace('\n', " "))
+        });
+        return format!("{}{}", title, quoted.unwrap_or_default());
+    }
+    if resolver.and_then(|r| r.resolve(msg)).is_some_and(|m| m.kind == MediaKind::File) {
+        return format!("[File: {}]", title);
+    }
+    match app.url.as_deref().filter(|u| !u.is_empty()) {
+        Some(url) => format!("[Link: {} {}]", title, url),
+        None => format!("[Link: {}]", title),
+    }
+}
+
+fn media_path(msg: &Message, resolver: Option<&MediaResolver>) -> Option<String> {
+    if let Some(resolver) = resolver {
+        let media = resolver.resolve(msg)?;
+        let path = media.path.or(media.thumb_path)?;
+        let rel = path.strip_prefix(resolver.account_root()).unwrap_or(&path);
+        return Some(rel.to_string_lossy().replace('\\', "/"));
+    }
+    let extra = parse_bytes_extra(msg.bytes_extra.as_deref()).ok().flatten()?;
+    extra.file_path.or(extra.thumb_path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_message_text_placeholders() {
+        let voice = Message { msg_type: MSG_TYPE_VOICE, content: Some("<msg><voicemsg voicelength=\"11600\"/></msg>".into()), ..Default::default() };
+        assert_eq!(message_text(&voice, None), "[Voice 12s]");
+
+        let link = Message {
+            msg_type: MSG_TYPE_APP,
+            content: Some("<msg><appmsg><title>News</title><type>5</type><url>http://x/y</url></appmsg></msg>".into()),
+            ..Default::default()
+        };
+        assert_eq!(message_text(&link, None), "[Link: News http://x/y]");
+
+        let image = Message { msg_type: MSG_TYPE_IMAGE, ..Default::default() };
+        assert_eq!(message_text(&image, None), "[Image]");
+
+        let quote = Message {
+            msg_type: MSG_TYPE_APP,
+            content: Some("<msg><appmsg><title>ok</title><type>57</type><refermsg><type>3</type>\
+                           <displayname>A</displayname><content>&lt;msg/&gt;</content></refermsg></appmsg></msg>".into()),
+            ..Default::default()
+        };
+        assert_eq!(message_text(&quote, None), "ok [Quote A: [Image]]");
+    }
+
+    #[test]
+    fn test_participants_prefer_remark() {
+        let conn = crate::core::testutil::merged_db_with_sample_data();
+        let people = Participants::load(&conn, "123@chatroom", &["wxid_stranger".to_string()]).unwrap();
+        assert_eq!(people.name("wxid_alice"), "Alice (work)");
+        assert_eq!(people.name("wxid_stranger"), "Stranger");
+        assert_eq!(people.name("wxid_unknown"), "wxid_unknown");
+        assert_eq!(people.avatar("wxid_alice"), Some("http://img/alice.jpg"));
+    }
+}
@@ -0,0 +1,236 @@
+// src/core/export/sns.rs
+
+use anyhow::{Result, anyhow};
+use base64::Engine;
+use std::fmt::Write as _;
+use std::io::Write;
+use std::path::Path;
+
+use crate::core::db_parser::SnsPost;
+use crate::core::html::{escape_html, is_web_url};
+use crate::core::image_decode::detect_image_format;
+
+const STYLE: &str = "
+body { margin: 0; font-family: -apple-system, 'Microsoft YaHei', sans-serif; background: #ededed; }
+header { background: #2e2e2e; color: #eee; padding: 12px 16px; }
+header .sub { color: #aaa; font-size: 12px; }
+main { max-width: 720px; margin: 0 auto; padding: 16px; }
+.post { background: #fff; border-radius: 4px; padding: 12px 16px; margin: 12px 0; }
+.author { color: #576b95; font-weight: bold; }
+.text { margin: 6px 0; white-space: pre-wrap; word-break: break-all; }
+.media img { width: 120px; height: 120px; object-fit: cover; margin: 2px; }
+.link { display: block; background: #f3f3f3; padding: 8px; color: #333; text-decoration: none; }
+.meta { color: #999; font-size: 12px; margin-top: 6px; }
+.feedback { background: #f3f3f3; margin-top: 8px; padding: 6px 8px; font-size: 13px; }
+.feedback .who { color: #576b95; }
+";
+
+/// What `sns` writes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum SnsFormat {
+    Json,
+    Html,
+}
+
+/// Writes `posts` as a pretty-printed JSON array.
+pub fn write_sns_json(posts: &[SnsPost], mut out: impl Write) -> Result<()> {
+    serde_json::to_writer_pretty(&mut out, posts)?;
+    out.write_all(b"\n")?;
+    out.flush()?;
+    Ok(())
+}
+
+/// Writes `posts` as a single HTML page.
+///
+/// Media found in the local cache is embedded when it is a plain image; everything else is
+/// shown from its (thumbnail) URL, which only loads while the CDN link is still valid.
+pub fn export_sns_html(posts: &[SnsPost], title: &str, out: &Path) -> Result<()> {
+    if let Some(parent) = out.parent().filter(|p| !p.as_os_str().is_empty()) {
+        std::fs::create_dir_all(parent)?;
+    }
+
+    let mut body = String::new();
+    for post in posts {
+        body.push_str(&render_post(post));
+    }
+    let range = match (posts.last(), posts.first()) {
+        (Some(first), Some(last)) => format!("{} ~ {}", first.time_str, last.time_str),
+        _ => String::new(),
+    };
+    let html = format!(
+        "<!DOCTYPE html><html><head><meta charset=\"utf-8\"><meta name=\"referrer\" content=\"no-referrer\">\
+         <title>{title}</title><style>{STYLE}</style></head>\
+         <body><header>{title}<div class=\"sub\">{count} 条朋友圈 {range}</div></header><main>{body}</main></body></html>",
+        title = escape_html(title),
+        count = posts.len(),
+        range = escape_html(&range),
+        body = body
+    );
+    std::fs::write(out, html).map_err(|e| anyhow!("Failed to write {:?}: {}", out, e))
+}
+
+fn render_post(post: &SnsPost) -> String {
+    let mut html = format!("<div class=\"post\"><div class=\"author\">{}</div>", escape_html(&post.author_name));
+    if !post.text.is_empty() {
+        let _ = write!(html, "<div class=\"text\">{}</div>", escape_html(&post.text));
+    }
+
+    if !post.media.is_empty() {
+        html.push_str("<div class=\"media\">");
+        for media in &post.media {
+            let src = [&media.local_thumb_path, &media.local_path]
+                .into_iter()
+                .flatten()
+                .find_map(|path| embedded_image(path))
+                .or_else(|| media.thumb_url.clone())
+                .or_else(|| media.url.clone());
+            let Some(src) = src else { continue };
+            let img = format!("<img src=\"{}\" loading=\"lazy\">", escape_html(&src));
+            match media.url.as_ref().filter(|u| is_web_url(u)) {
+                Some(url) => {
+                    let _ = write!(html, "<a href=\"{}\" target=\"_blank\">{}</a>", escape_html(url), img);
+                }
+                None => html.push_str(&img),
+            }
+        }
+        html.push_str("</div>");
+    }
+
+    if let Some(link) = &post.link {
+        let label = link.title.as_deref().or(link.url.as_deref()).unwrap_or_default();
+        match link.url.as_deref().filter(|u| is_web_url(u)) {
+            Some(url) => {
+                let _ = write!(
+                    html,
+                    "<a class=\"link\" href=\"{}\" target=\"_blank\">{}</a>",
+                    escape_html(url),
+                    escape_html(label)
+                );
+            }
+            // Other schemes would run in the page; show them as text.
+            None => {
+                let _ = write!(html, "<div class=\"link\">{}</div>", escape_html(label));
+            }
+        }
+    }
+
+    let _ = write!(html, "<div class=\"meta\">{}", escape_html(&post.time_str));
+    if let Some(location) = &post.location {
+        let place = [&location.poi_name, &location.city].into_iter().flatten().next();
+        if let Some(place) = place {
+            let _ = write!(html, " · {}", escape_html(place));
+        }
+    }
+    html.push_str("</div>");
+
+    if !post.likes.is_empty() || !post.comments.is_empty() {
+        html.push_str("<div class=\"feedback\">");
+        if !post.likes.is_empty() {
+            let names: Vec<String> = post.likes.iter().map(|l| escape_html(&l.from_name)).collect();
+            let _ = write!(html, "<div>♡ <span class=\"who\">{}</span></div>", names.join(", "));
+        }
+        for comment in &post.comments {
+            let _ = write!(
+                html,
+                "<div><span class=\"who\">{}</span>: {}</div>",
+                escape_html(&comment.from_name),
+                escape_html(&comment.content)
+            );
+        }
+        html.push_str("</div>");
+    }
+    html.push_str("</div>");
+    html
+}
+
+/// `data:` url of a cached file that is a plain image (some cache entries are encrypted).
+fn embedded_image(path: &Path) -> Option<String> {
+    let data = std::fs::read(path).ok()?;
+    let format = detect_image_format(&data)?;
+    Some(format!(
+        "data:{};base64,{}",
+        format.mime_type(),
+        base64::engine::general_purpose::STANDARD.encode(&data)
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::db_parser::{SnsComment, SnsLink, SnsMedia};
+
+    fn sample_posts() -> Vec<SnsPost> {
+        vec![
+            SnsPost {
+                feed_id: 2,
+                author_name: "Bob".to_string(),
+                time_str: "2023-11-15 22:13:20".to_string(),
+                content_style: Some(3),
+                link: Some(SnsLink {
+                    title: Some("A <b>title</b>".to_string()),
+                    url: Some("https://example.com/a".to_string()),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            },
+            SnsPost {
+                feed_id: 1,
+                author_name: "Alice".to_string(),
+                time_str: "2023-11-14 22:13:20".to_string(),
+                text: "Sunset & sea".to_string(),
+                media: vec![SnsMedia {
+                    url: Some("http://img/0".to_string()),
+                    thumb_url: Some("http://img/150".to_string()),
+                    ..Default::default()
+                }],
+                likes: vec![SnsComment { from_name: "Bob".to_string(), ..Default::default() }],
+                comments: vec![SnsComment {
+                    from_name: "Stranger".to_string(),
+                    content: "Nice!".to_string(),
+                    ..Default::default()
+                }],
+                ..Default::default()
+            },
+        ]
+    }
+
+    #[test]
+    fn test_sns_html() {
+        let out = std::env::temp_dir().join(format!("wxdump_rs_sns_{}.html", std::process::id()));
+        export_sns_html(&sample_posts(), "Moments", &out).unwrap();
+        let html = std::fs::read_to_string(&out).unwrap();
+        assert!(html.contains("2 条朋友圈 2023-11-14 22:13:20 ~ 2023-11-15 22:13:20"));
+        assert!(html.contains("<div class=\"text\">Sunset &amp; sea</div>"));
+        assert!(html.contains("<a href=\"http://img/0\" target=\"_blank\"><img src=\"http://img/150\""));
+        assert!(html.contains("A &lt;b&gt;title&lt;/b&gt;"));
+        assert!(html.contains("<span class=\"who\">Stranger</span>: Nice!"));
+        std::fs::remove_file(&out).unwrap();
+    }
+
+    #[test]
+    fn test_sns_html_does_not_link_other_schemes() {
+        let post = SnsPost {
+            author_name: "Mallory".to_string(),
+            link: Some(SnsLink {
+                title: Some("Click".to_string()),
+                url: Some("javascript:alert(document.domain)".to_string()),
+                ..Default::default()
+            }),
+            media: vec![SnsMedia { url: Some("javascript:alert(1)".to_string()), ..Default::default() }],
+            ..Default::default()
+        };
+        let html = render_post(&post);
+        assert!(!html.contains("href"));
+        assert!(html.contains("<div class=\"link\">Click</div>"));
+    }
+
+    #[test]
+    fn test_sns_json() {
+        let mut out = Vec::new();
+        write_sns_json(&sample_posts(), &mut out).unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&out).unwrap();
+        assert_eq!(json[1]["text"], "Sunset & sea");
+        assert_eq!(json[1]["comments"][0]["content"], "Nice!");
+        assert_eq!(json[0]["link"]["url"], "https://example.com/a");
+    }
+}
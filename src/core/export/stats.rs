@@ -0,0 +1,234 @@
+// src/core/export/stats.rs
+
+use anyhow::{Result, anyhow};
+use std::fmt::Write as _;
+use std::io::Write;
+use std::path::Path;
+
+use crate::core::analytics::ConversationStats;
+use crate::core::html::escape_html;
+
+const STYLE: &str = "
+body { margin: 0; font-family: -apple-system, 'Microsoft YaHei', sans-serif; background: #ededed; color: #333; }
+header { background: #2e2e2e; color: #eee; padding: 12px 16px; }
+header .sub { color: #aaa; font-size: 12px; }
+main { max-width: 960px; margin: 0 auto; padding: 16px; }
+section { background: #fff; border-radius: 4px; padding: 12px 16px; margin: 12px 0; }
+h2 { font-size: 18px; margin: 4px 0 8px; }
+h3 { font-size: 14px; color: #576b95; margin: 12px 0 4px; }
+table { border-collapse: collapse; font-size: 13px; }
+td, th { padding: 3px 10px 3px 0; text-align: left; }
+td.num { text-align: right; }
+.figures span { display: inline-block; margin-right: 18px; font-size: 13px; }
+.figures b { font-size: 16px; }
+.charts { display: flex; flex-wrap: wrap; gap: 8px 24px; }
+svg text { font-size: 10px; fill: #555; }
+svg rect { fill: #07c160; }
+";
+
+/// What `stats` writes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum StatsFormat {
+    Json,
+    /// A page with SVG charts.
+    Html,
+}
+
+const WEEKDAYS: [&str; 7] = ["一", "二", "三", "四", "五", "六", "日"];
+
+/// Rows of the horizontal bar charts (senders, kinds, words).
+const MAX_BARS: usize = 20;
+
+/// Writes `stats` as a pretty-printed JSON array.
+pub fn write_stats_json(stats: &[ConversationStats], mut out: impl Write) -> Result<()> {
+    serde_json::to_writer_pretty(&mut out, stats)?;
+    out.write_all(b"\n")?;
+    out.flush()?;
+    Ok(())
+}
+
+/// Writes `stats` as a single HTML page: an overview table, then one section per conversation
+/// with its figures and inline SVG charts (no scripts or external resources).
+pub fn export_stats_html(stats: &[ConversationStats], title: &str, out: &Path) -> Result<()> {
+    if let Some(parent) = out.parent().filter(|p| !p.as_os_str().is_empty()) {
+        std::fs::create_dir_all(parent)?;
+    }
+
+    let mut body = String::from("<section><h2>概览</h2><table><tr><th>会话</th><th>消息</th><th>首次</th><th>最近</th><th>活跃天数</th></tr>");
+    for s in stats {
+        let _ = write!(
+            body,
+            "<tr><td><a href=\"#{id}\">{name}</a></td><td class=\"num\">{total}</td><td>{first}</td><td>{last}</td><td class=\"num\">{days}</td></tr>",
+            id = escape_html(&s.talker),
+            name = escape_html(&s.name),
+            total = s.total_messages,
+            first = s.first_date,
+            last = s.last_date,
+            days = s.active_days
+        );
+    }
+    body.push_str("</table></section>");
+    for s in stats {
+        body.push_str(&render_conversation(s));
+    }
+
+    let total: usize = stats.iter().map(|s| s.total_messages).sum();
+    let html = format!(
+        "<!DOCTYPE html><html><head><meta charset=\"utf-8\"><title>{title}</title><style>{STYLE}</style></head>\
+         <body><header>{title}<div class=\"sub\">{count} 个会话, {total} 条消息</div></header><main>{body}</main></body></html>",
+        title = escape_html(title),
+        count = stats.len(),
+        total = total,
+        body = body
+    );
+    std::fs::write(out, html).map_err(|e| anyhow!("Failed to write {:?}: {}", out, e))
+}
+
+fn render_conversation(s: &ConversationStats) -> String {
+    let mut html = format!(
+        "<section id=\"{}\"><h2>{} <small>{}</small></h2><div class=\"figures\">",
+        escape_html(&s.talker),
+        escape_html(&s.name),
+        escape_html(&s.talker)
+    );
+    let _ = write!(
+        html,
+        "<span><b>{}</b> 条消息</span><span>{} ~ {}</span><span><b>{}</b> 天有聊天</span><span>最长连续 <b>{}</b> 天 ({} ~ {})</span></div>",
+        s.total_messages, s.first_date, s.last_date, s.active_days, s.longest_streak.days, s.longest_streak.start, s.longest_streak.end
+    );
+
+    html.push_str("<div class=\"charts\">");
+    let hours: Vec<(String, usize)> = s.by_hour.iter().enumerate().map(|(h, &c)| (h.to_string(), c)).collect();
+    chart(&mut html, "每小时", &column_chart(&hours));
+    let weekdays: Vec<(String, usize)> = WEEKDAYS.iter().zip(s.by_weekday).map(|(d, c)| (d.to_string(), c)).collect();
+    chart(&mut html, "星期", &column_chart(&weekdays));
+    let months: Vec<(String, usize)> = s.by_month.iter().map(|m| (m.label.clone(), m.count)).collect();
+    chart(&mut html, "每月", &column_chart(&months));
+    html.push_str("</div><div class=\"charts\">");
+    let senders: Vec<(String, usize)> = s.by_sender.iter().map(|c| (c.name.clone(), c.messages)).collect();
+    chart(&mut html, "发送者", &bar_chart(&senders));
+    let kinds: Vec<(String, usize)> = s.by_kind.iter().map(|k| (k.label.clone(), k.count)).collect();
+    chart(&mut html, "消息类型", &bar_chart(&kinds));
+    let words: Vec<(String, usize)> = s.top_words.iter().map(|w| (w.label.clone(), w.count)).collect();
+    chart(&mut html, "高频词", &bar_chart(&words));
+    html.push_str("</div>");
+
+    if !s.response_times.is_empty() {
+        html.push_str("<h3>平均回复时间</h3><table>");
+        for r in &s.response_times {
+            let _ = write!(
+                html,
+                "<tr><td>{}</td><td class=\"num\">{}</td><td class=\"num\">{} 次</td></tr>",
+                escape_html(&r.name),
+                format_duration(r.average_seconds),
+                r.replies
+            );
+        }
+        html.push_str("</table>");
+    }
+    html.push_str("</section>");
+    html
+}
+
+fn chart(html: &mut String, title: &str, svg: &str) {
+    let _ = write!(html, "<div><h3>{}</h3>{}</div>", escape_html(title), svg);
+}
+
+/// `95` -> `1m 35s`, `7300` -> `2h 1m`.
+fn format_duration(seconds: i64) -> String {
+    match seconds {
+        s if s < 60 => format!("{}s", s),
+        s if s < 3600 => format!("{}m {}s", s / 60, s % 60),
+        s => format!("{}h {}m", s / 3600, s % 3600 / 60),
+    }
+}
+
+/// Vertical bars with the labels below (labels are thinned out when there are many bars).
+fn column_chart(values: &[(String, usize)]) -> String {
+    const HEIGHT: usize = 120;
+    let bar = if values.len() > 24 { 14 } else { 22 };
+    let width = values.len().max(1) * bar;
+    let max = values.iter().map(|(_, c)| *c).max().unwrap_or(0).max(1);
+    let label_every = values.len().div_ceil(24).max(1);
+    let mut svg = format!("<svg width=\"{}\" height=\"{}\" role=\"img\">", width, HEIGHT + 16);
+    for (i, (label, count)) in values.iter().enumerate() {
+        let h = count * HEIGHT / max;
+        let _ = write!(
+            svg,
+            "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\"><title>{}: {}</title></rect>",
+            i * bar + 1,
+            HEIGHT - h,
+            bar - 2,
+            h,
+            escape_html(label),
+            count
+        );
+        if i % label_every == 0 {
+            let _ = write!(svg, "<text x=\"{}\" y=\"{}\">{}</text>", i * bar + 1, HEIGHT + 12, escape_html(label));
+        }
+    }
+    svg.push_str("</svg>");
+    svg
+}
+
+/// Horizontal bars, label on the left and count on the right, for the first [`MAX_BARS`] values.
+fn bar_chart(values: &[(String, usize)]) -> String {
+    const LABEL: usize = 110;
+    const BAR: usize = 180;
+    const ROW: usize = 16;
+    let values = &values[..values.len().min(MAX_BARS)];
+    let max = values.iter().map(|(_, c)| *c).max().unwrap_or(0).max(1);
+    let mut svg = format!("<svg width=\"{}\" height=\"{}\" role=\"img\">", LABEL + BAR + 50, values.len().max(1) * ROW);
+    for (i, (label, count)) in values.iter().enumerate() {
+        let y = i * ROW;
+        let w = (count * BAR / max).max(1);
+        let short: String = label.chars().take(12).collect();
+        let _ = write!(
+            svg,
+            "<text x=\"0\" y=\"{ty}\">{short}</text><rect x=\"{LABEL}\" y=\"{ry}\" width=\"{w}\" height=\"{h}\"><title>{label}: {count}</title></rect>\
+             <text x=\"{cx}\" y=\"{ty}\">{count}</text>",
+            ty = y + 12,
+            short = escape_html(&short),
+            ry = y + 2,
+            w = w,
+            h = ROW - 4,
+            label = escape_html(label),
+            count = count,
+            cx = LABEL + w + 4
+        );
+    }
+    svg.push_str("</svg>");
+    svg
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::analytics::{collect_stats, StatsOptions};
+    use crate::core::testutil::{micro_msg_db, msg_db};
+
+    #[test]
+    fn test_export_stats_html() {
+        let options = StatsOptions { my_wxid: "wxid_me".into(), ..Default::default() };
+        let talkers = vec!["wxid_carol".to_string(), "456@chatroom".to_string(), "wxid_nobody".to_string()];
+        let stats = collect_stats(&msg_db(), &micro_msg_db(), &talkers, &options).unwrap();
+        assert_eq!(stats.len(), 2);
+
+        let out = std::env::temp_dir().join(format!("wxdump_rs_stats_{}.html", std::process::id()));
+        export_stats_html(&stats, "2023 <report>", &out).unwrap();
+        let html = std::fs::read_to_string(&out).unwrap();
+        std::fs::remove_file(&out).unwrap();
+        assert!(html.contains("<title>2023 &lt;report&gt;</title>"));
+        assert!(html.contains("<section id=\"456@chatroom\">"));
+        assert_eq!(html.matches("<svg").count(), 12);
+        assert!(html.contains("<title>Carol 🏔: 1</title>"));
+        assert!(!html.contains("<script"));
+
+        let mut json = Vec::new();
+        write_stats_json(&stats, &mut json).unwrap();
+        let parsed: serde_json::Value = serde_json::from_slice(&json).unwrap();
+        assert_eq!(parsed[0]["by_kind"][0]["label"], "text");
+        assert_eq!(format_duration(95), "1m 35s");
+        assert_eq!(format_duration(7300), "2h 1m");
+    }
+}
@@ -0,0 +1,349 @@
+// src/core/export/table.rs
+
+use anyhow::{Result, anyhow};
+use rusqlite::Connection;
+use serde_json::{Map, Value};
+use std::io::Write;
+use std::path::PathBuf;
+
+use super::message_text;
+use crate::core::db_parser::{for_each_article, for_each_message, get_chat_rooms, get_contacts, get_sessions, message_sender_wxid};
+use crate::core::media_resolver::MediaResolver;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum TableFormat {
+    Csv,
+    /// One JSON object per line.
+    #[value(alias = "ndjson")]
+    Jsonl,
+    /// A single JSON array (still written row by row).
+    Json,
+}
+
+/// What `export_dataset` writes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Dataset {
+    Contacts,
+    /// One row per chat room member.
+    #[value(name = "chatrooms", alias = "rooms")]
+    ChatRooms,
+    Sessions,
+    #[value(alias = "msg")]
+    Messages,
+    /// Official-account articles (PublicMsg.db), one row per article of a push.
+    Articles,
+}
+
+const CONTACT_COLUMNS: &[&str] = &[
+    "wxid", "account", "nickname", "remark", "label_list", "description", "head_img_url", "user_type",
+    "verify_flag", "chat_room_type", "del_flag", "reserved1", "reserved2", "reserved5", "chat_room_notify",
+    "is_chatroom_contact", "is_openim", "corp_name", "extra_buf_info",
+];
+
+const CHAT_ROOM_COLUMNS: &[&str] = &[
+    "room_wxid", "owner_wxid", "self_display_name", "announcement", "member_wxid", "member_nickname",
+    "member_remark", "member_account", "member_room_nickname", "is_owner",
+];
+
+const SESSION_COLUMNS: &[&str] = &[
+    "wxid", "order_num", "unread_count", "session_nickname", "session_status", "is_send", "content",
+    "msg_local_id", "msg_status", "timestamp", "time_str", "msg_type", "msg_sub_type", "contact_nickname",
+    "contact_remark", "contact_account", "contact_description", "contact_head_img_url", "contact_label_list",
+    "contact_del_flag", "contact_type", "contact_verify_flag", "contact_chat_room_type", "contact_chat_room_notify",
+    "contact_is_openim", "contact_corp_name", "contact_extra_buf_info",
+];
+
+/// `sender` and `text` (see `message_text`) are computed, the rest are MSG columns.
+const MESSAGE_COLUMNS: &[&str] = &[
+    "local_id", "msg_svr_id", "msg_type", "sub_type", "is_sender", "create_time", "time_str", "talker", "sender",
+    "content", "display_content", "text",
+];
+
+const ARTICLE_COLUMNS: &[&str] = &[
+    "account", "account_name", "title", "digest", "url", "cover", "pub_time", "time_str", "msg_svr_id", "item_index",
+];
+
+impl Dataset {
+    /// All columns, in output order.
+    pub fn columns(&self) -> &'static [&'static str] {
+        match self {
+            Dataset::Contacts => CONTACT_COLUMNS,
+            Dataset::ChatRooms => CHAT_ROOM_COLUMNS,
+            Dataset::Sessions => SESSION_COLUMNS,
+            Dataset::Messages => MESSAGE_COLUMNS,
+            Dataset::Articles => ARTICLE_COLUMNS,
+        }
+    }
+}
+
+/// Options of `export_dataset`.
+#[derive(Debug, Clone, Default)]
+pub struct DatasetOptions {
+    /// Columns to write, in this order (all columns when empty).
+    pub columns: Vec<String>,
+    /// Messages and articles: conversation (official account) and inclusive unix time range.
+    pub talker: Option<String>,
+    pub start_time: Option<i64>,
+    pub end_time: Option<i64>,
+    /// Messages only: wxid used as `sender` of own messages.
+    pub my_wxid: String,
+    /// Messages only: account folder for media paths in `text`.
+    pub wx_path: Option<PathBuf>,
+}
+
+/// Writes rows in one of the `TableFormat`s, one at a time.
+pub struct RowWriter<W: Write> {
+    format: TableFormat,
+    columns: Vec<String>,
+    csv: Option<csv::Writer<W>>,
+    out: Option<W>,
+    rows: usize,
+}
+
+impl<W: Write> RowWriter<W> {
+    /// Starts the output (CSV header, or `[` for JSON).
+    pub fn new(out: W, format: TableFormat, columns: Vec<String>) -> Result<Self> {
+        let mut writer = RowWriter { format, columns, csv: None, out: None, rows: 0 };
+        match format {
+            TableFormat::Csv => {
+                let mut csv = csv::Writer::from_writer(out);
+                csv.write_record(&writer.columns)?;
+                writer.csv = Some(csv);
+            }
+            TableFormat::Json => {
+                let mut out = out;
+                out.write_all(b"[")?;
+                writer.out = Some(out);
+            }
+            TableFormat::Jsonl => writer.out = Some(out),
+        }
+        Ok(writer)
+    }
+
+    /// Writes the selected columns of `row`; missing keys become null / empty.
+    pub fn write_row(&mut self, row: &Map<String, Value>) -> Result<()> {
+        let values = self.columns.iter().map(|c| row.get(c).cloned().unwrap_or(Value::Null));
+        if let Some(csv) = self.csv.as_mut() {
+            csv.write_record(values.map(|v| csv_field(&v)).collect::<Vec<_>>())?;
+        } else if let Some(out) = self.out.as_mut() {
+            let object: Map<String, Value> = self.columns.iter().cloned().zip(values).collect();
+            match self.format {
+                TableFormat::Json => {
+                    out.write_all(if self.rows == 0 { b"\n" } else { b",\n" })?;
+                    serde_json::to_writer(&mut *out, &object)?;
+                }
+                _ => {
+                    serde_json::to_writer(&mut *out, &object)?;
+                    out.write_all(b"\n")?;
+                }
+            }
+        }
+        self.rows += 1;
+        Ok(())
+    }
+
+    /// Ends the output and flushes it. Returns the number of rows written.
+    pub fn finish(self) -> Result<usize> {
+        if let Some(mut csv) = self.csv {
+            csv.flush()?;
+        } else if let Some(mut out) = self.out {
+            if self.format == TableFormat::Json {
+                out.write_all(if self.rows == 0 { b"]\n" } else { b"\n]\n" })?;
+            }
+            out.flush()?;
+        }
+        Ok(self.rows)
+    }
+}
+
+/// CSV cell for a JSON value: strings as is, null empty, lists joined with `;`, objects as JSON.
+fn csv_field(value: &Value) -> String {
+    match value {
+        Value::Null => String::new(),
+        Value::String(s) => s.clone(),
+        Value::Array(items) => items.iter().map(csv_field).collect::<Vec<_>>().join(";"),
+        Value::Object(_) => value.to_string(),
+        other => other.to_string(),
+    }
+}
+
+/// Checks `requested` against the dataset's columns (all of them when empty).
+pub fn select_columns(dataset: Dataset, requested: &[String]) -> Result<Vec<String>> {
+    let available = dataset.columns();
+    if requested.is_empty() {
+        return Ok(available.iter().map(|c| c.to_string()).collect());
+    }
+    for column in requested {
+        if !available.contains(&column.as_str()) {
+            return Err(anyhow!("Unknown column {:?}, available: {}", column, available.join(", ")));
+        }
+    }
+    Ok(requested.to_vec())
+}
+
+fn to_row(value: impl serde::Serialize) -> Result<Map<String, Value>> {
+    match serde_json::to_value(value)? {
+        Value::Object(map) => Ok(map),
+        _ => Err(anyhow!("Expected a JSON object")),
+    }
+}
+
+/// Writes `dataset` to `out`. Messages are streamed straight from the MSG table.
+///
+/// `conn` must hold the tables the dataset comes from: MicroMsg for contacts, chat rooms and
+/// sessions, MSG for messages (a merged db has both), PublicMsg.db for articles.
+/// Returns the number of rows written.
+pub fn export_dataset(
+    conn: &Connection,
+    dataset: Dataset,
+    format: TableFormat,
+    options: &DatasetOptions,
+    out: impl Write,
+) -> Result<usize> {
+    let columns = select_columns(dataset, &options.columns)?;
+    let mut writer = RowWriter::new(out, format, columns)?;
+
+    match dataset {
+        Dataset::Contacts => {
+            for contact in get_contacts(conn, None, None, None)? {
+                writer.write_row(&to_row(&contact)?)?;
+            }
+        }
+        Dataset::Sessions => {
+            for session in get_sessions(conn)? {
+                writer.write_row(&to_row(&session)?)?;
+            }
+        }
+        Dataset::ChatRooms => {
+            let mut rooms: Vec<_> = get_chat_rooms(conn, None)?.into_values().collect();
+            rooms.sort_by(|a, b| a.wxid.cmp(&b.wxid));
+            for room in rooms {
+                for member_wxid in &room.member_wxids {
+                    let member = room.members.iter().find(|m| &m.wxid == member_wxid);
+                    let mut row = Map::new();
+                    row.insert("room_wxid".into(), room.wxid.clone().into());
+                    row.insert("owner_wxid".into(), room.owner_wxid.clone().into());
+                    row.insert("self_display_name".into(), room.self_display_name.clone().into());
+                    row.insert("announcement".into(), room.announcement.clone().into());
+                    row.insert("member_wxid".into(), member_wxid.clone().into());
+                    row.insert("member_nickname".into(), member.and_then(|m| m.nickname.clone()).into());
+                    row.insert("member_remark".into(), member.and_then(|m| m.remark.clone()).into());
+                    row.insert("member_account".into(), member.and_then(|m| m.account.clone()).into());
+                    row.insert("member_room_nickname".into(), member.and_then(|m| m.room_nickname.clone()).into());
+                    row.insert("is_owner".into(), (room.owner_wxid.as_ref() == Some(member_wxid)).into());
+                    writer.write_row(&row)?;
+                }
+            }
+        }
+        Dataset::Messages => {
+            let resolver = options.wx_path.as_ref().map(MediaResolver::new);
+            let wants_text = writer.columns.iter().any(|c| c == "text");
+            for_each_message(
+                conn,
+                options.talker.as_deref(),
+                None,
+                options.start_time,
+                options.end_time,
+                None,
+                None,
+                |msg| {
+                    let mut row = to_row(&msg)?;
+                    row.insert("sender".into(), message_sender_wxid(&msg, &options.my_wxid).into());
+                    if wants_text {
+                        row.insert("text".into(), message_text(&msg, resolver.as_ref()).into());
+                    }
+                    writer.write_row(&row)
+                },
+            )?;
+        }
+        Dataset::Articles => {
+            for_each_article(conn, options.talker.as_deref(), options.start_time, options.end_time, |article| {
+                writer.write_row(&to_row(&article)?)
+            })?;
+        }
+    }
+    writer.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::testutil::merged_db_with_sample_data;
+    use clap::ValueEnum;
+
+    fn export(dataset: Dataset, format: TableFormat, options: &DatasetOptions) -> (usize, String) {
+        let conn = merged_db_with_sample_data();
+        let mut out = Vec::new();
+        let rows = export_dataset(&conn, dataset, format, options, &mut out).unwrap();
+        (rows, String::from_utf8(out).unwrap())
+    }
+
+    #[test]
+    fn test_messages_csv_with_columns() {
+        let options = DatasetOptions {
+            columns: vec!["talker".into(), "sender".into(), "text".into()],
+            talker: Some("123@chatroom".into()),
+            my_wxid: "wxid_me".into(),
+            ..Default::default()
+        };
+        let (rows, csv) = export(Dataset::Messages, TableFormat::Csv, &options);
+        assert_eq!(rows, 3);
+        assert_eq!(
+            csv,
+            "talker,sender,text\n\
+             123@chatroom,wxid_stranger,hello all\n\
+             123@chatroom,wxid_alice,welcome! [Quote Stranger: hello all]\n\
+             123@chatroom,123@chatroom,\"[System] \"\"Alice\"\" 撤回了一条消息\"\n"
+        );
+    }
+
+    #[test]
+    fn test_jsonl_and_json_arrays() {
+        let options = DatasetOptions { columns: vec!["wxid".into(), "label_list".into()], ..Default::default() };
+        let (rows, jsonl) = export(Dataset::Contacts, TableFormat::Jsonl, &options);
+        assert_eq!(rows, 2);
+        let first: Value = serde_json::from_str(jsonl.lines().next().unwrap()).unwrap();
+        assert_eq!(first, serde_json::json!({"wxid": "wxid_alice", "label_list": ["Work"]}));
+
+        let (_, json) = export(Dataset::Sessions, TableFormat::Json, &DatasetOptions::default());
+        let sessions: Vec<Value> = serde_json::from_str(&json).unwrap();
+        assert_eq!(sessions.len(), 2);
+        assert_eq!(sessions[0]["wxid"], "wxid_alice");
+    }
+
+    #[test]
+    fn test_articles() {
+        let conn = merged_db_with_sample_data();
+        conn.execute(
+            "INSERT INTO MSG (MsgSvrID, Type, SubType, IsSender, CreateTime, StrTalker, StrContent) \
+             VALUES (3001, 49, 5, 0, 1700000000, 'gh_news', ?)",
+            ["<msg><appmsg><title>Weekly, issue 1</title><type>5</type><url>https://mp.weixin.qq.com/s/a</url></appmsg></msg>"],
+        )
+        .unwrap();
+        let options = DatasetOptions { columns: vec!["account".into(), "title".into(), "url".into()], ..Default::default() };
+        let mut out = Vec::new();
+        let rows = export_dataset(&conn, Dataset::Articles, TableFormat::Csv, &options, &mut out).unwrap();
+        assert_eq!(rows, 1);
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "account,title,url\ngh_news,\"Weekly, issue 1\",https://mp.weixin.qq.com/s/a\n"
+        );
+    }
+
+    #[test]
+    fn test_chat_rooms_one_row_per_member() {
+        let options = DatasetOptions { columns: vec!["member_wxid".into(), "is_owner".into()], ..Default::default() };
+        let (rows, csv) = export(Dataset::ChatRooms, TableFormat::Csv, &options);
+        assert_eq!(rows, 3);
+        assert!(csv.contains("wxid_alice,true\n"));
+        assert!(csv.contains("wxid_me,false\n"));
+    }
+
+    #[test]
+    fn test_unknown_column_and_format() {
+        assert!(select_columns(Dataset::Contacts, &["nope".to_string()]).is_err());
+        assert!(TableFormat::from_str("xml", true).is_err());
+        assert_eq!(TableFormat::from_str("NDJSON", true).unwrap(), TableFormat::Jsonl);
+        assert_eq!(Dataset::from_str("rooms", true).unwrap(), Dataset::ChatRooms);
+    }
+}
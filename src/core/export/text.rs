@@ -0,0 +1,252 @@
+// src/core/export/text.rs
+
+use anyhow::{Result, anyhow};
+use rusqlite::Connection;
+use serde::Serialize;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use super::{message_text, Participants};
+use crate::core::db_parser::{format_timestamp_to_string, get_messages, message_sender_wxid, Message};
+use crate::core::media_resolver::MediaResolver;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TranscriptFormat {
+    /// `[YYYY-MM-DD HH:MM:SS] Name: content` lines.
+    #[default]
+    Text,
+    /// The same lines as a Markdown list, with a heading per conversation and per day.
+    Markdown,
+}
+
+impl TranscriptFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            TranscriptFormat::Text => "txt",
+            TranscriptFormat::Markdown => "md",
+        }
+    }
+}
+
+/// Options of `export_transcripts`.
+#[derive(Debug, Clone, Default)]
+pub struct TranscriptOptions {
+    pub format: TranscriptFormat,
+    /// wxid of the account owner, used as the sender of own messages.
+    pub my_wxid: String,
+    /// Account folder; media placeholders then carry paths relative to it.
+    pub wx_path: Option<PathBuf>,
+    /// Unix timestamps, inclusive.
+    pub start_time: Option<i64>,
+    pub end_time: Option<i64>,
+    /// Keep only messages sent by these wxids (all senders when empty).
+    pub senders: Vec<String>,
+    /// Write every conversation into the single file `out` instead of one file per talker in `out/`.
+    pub combined: bool,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct TranscriptReport {
+    pub files: Vec<PathBuf>,
+    /// Conversations with at least one message after filtering.
+    pub conversations: usize,
+    pub messages: usize,
+}
+
+/// Writes transcripts of `talkers`. Conversations left empty by the filters are skipped.
+///
+/// `msg_conn` holds MSG and `contact_conn` the MicroMsg tables (the same connection for a merged db).
+pub fn export_transcripts(
+    msg_conn: &Connection,
+    contact_conn: &Connection,
+    talkers: &[String],
+    options: &TranscriptOptions,
+    out: &Path,
+) -> Result<TranscriptReport> {
+    let resolver = options.wx_path.as_ref().map(MediaResolver::new);
+    let mut report = TranscriptReport::default();
+
+    let mut combined_writer = if options.combined {
+        if let Some(parent) = out.parent().filter(|p| !p.as_os_str().is_empty()) {
+            std::fs::create_dir_all(parent)?;
+        }
+        report.files.push(out.to_path_buf());
+        Some(create_writer(out)?)
+    } else {
+        std::fs::create_dir_all(out)?;
+        None
+    };
+
+    for talker in talkers {
+        let messages: Vec<Message> =
+            get_messages(msg_conn, Some(talker), None, options.start_time, options.end_time, None, None)?
+                .into_iter()
+                .filter(|m| {
+                    options.senders.is_empty() || options.senders.contains(&message_sender_wxid(m, &options.my_wxid))
+                })
+                .collect();
+        if messages.is_empty() {
+            continue;
+        }
+
+        let senders: Vec<String> = messages.iter().map(|m| message_sender_wxid(m, &options.my_wxid)).collect();
+        let participants = Participants::load(contact_conn, talker, &senders)?;
+        let transcript = Transcript {
+            talker,
+            messages: &messages,
+            participants: &participants,
+            resolver: resolver.as_ref(),
+            options,
+        };
+
+        match combined_writer.as_mut() {
+            Some(writer) => {
+                if report.conversations > 0 {
+                    writeln!(writer)?;
+                }
+                transcript.write(writer)?;
+            }
+            None => {
+                let path = out.join(format!("{}.{}", safe_file_name(talker), options.format.extension()));
+                let mut writer = create_writer(&path)?;
+                transcript.write(&mut writer)?;
+                writer.flush()?;
+                report.files.push(path);
+            }
+        }
+        report.conversations += 1;
+        report.messages += messages.len();
+    }
+
+    if let Some(mut writer) = combined_writer {
+        writer.flush()?;
+    }
+    Ok(report)
+}
+
+struct Transcript<'a> {
+    talker: &'a str,
+    messages: &'a [Message],
+    participants: &'a Participants,
+    resolver: Option<&'a MediaResolver>,
+    options: &'a TranscriptOptions,
+}
+
+impl Transcript<'_> {
+    fn write(&self, w: &mut impl Write) -> Result<()> {
+        let title = self.participants.name(self.talker);
+        match self.options.format {
+            TranscriptFormat::Text => writeln!(w, "===== {} ({}) =====", title, self.talker)?,
+            TranscriptFormat::Markdown => {
+                writeln!(w, "# {}\n\n`{}`, {} messages", title, self.talker, self.messages.len())?;
+            }
+        }
+
+        let mut last_day = String::new();
+        for msg in self.messages {
+            let sender = self.participants.name(&message_sender_wxid(msg, &self.options.my_wxid));
+            let text = message_text(msg, self.resolver);
+            let mut lines = text.lines();
+            let first = lines.next().unwrap_or("");
+
+            match self.options.format {
+                TranscriptFormat::Text => {
+                    writeln!(w, "[{}] {}: {}", msg.time_str, sender, first)?;
+                    for line in lines {
+                        writeln!(w, "    {}", line)?;
+                    }
+                }
+                TranscriptFormat::Markdown => {
+                    let day = format_timestamp_to_string(msg.create_time, "%Y-%m-%d");
+                    if day != last_day {
+                        writeln!(w, "\n## {}\n", day)?;
+                        last_day = day;
+                    }
+                    writeln!(w, "- [{}] **{}**: {}", msg.time_str, escape_markdown(&sender), first)?;
+                    for line in lines {
+                        writeln!(w, "  {}", line)?;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+fn create_writer(path: &Path) -> Result<BufWriter<File>> {
+    let file = File::create(path).map_err(|e| anyhow!("Failed to create {:?}: {}", path, e))?;
+    Ok(BufWriter::new(file))
+}
+
+/// Keeps a talker usable as a file name on Windows (`xxx@chatroom` and wxids are fine as is).
+pub fn safe_file_name(name: &str) -> String {
+    name.chars()
+        .map(|c| if matches!(c, '<' | '>' | ':' | '"' | '/' | '\\' | '|' | '?' | '*') || c.is_control() { '_' } else { c })
+        .collect()
+}
+
+fn escape_markdown(text: &str) -> String {
+    text.replace('*', "\\*").replace('_', "\\_")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::testutil::merged_db_with_sample_data;
+
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("wxdump_rs_export_text_{}_{}", name, std::process::id()))
+    }
+
+    #[test]
+    fn test_text_per_talker_with_filters() {
+        let conn = merged_db_with_sample_data();
+        let out = temp_path("per_talker");
+        let options = TranscriptOptions {
+            my_wxid: "wxid_me".to_string(),
+            start_time: Some(1_700_000_050),
+            ..Default::default()
+        };
+        let talkers = vec!["wxid_alice".to_string(), "123@chatroom".to_string(), "wxid_bob".to_string()];
+        let report = export_transcripts(&conn, &conn, &talkers, &options, &out).unwrap();
+        assert_eq!(report.conversations, 2);
+        assert_eq!(report.files.len(), 2);
+
+        let alice = std::fs::read_to_string(out.join("wxid_alice.txt")).unwrap();
+        assert_eq!(
+            alice,
+            "===== Alice (work) (wxid_alice) =====\n\
+             [2023-11-14 22:14:10] Me: hello <b>Alice</b>\n\
+             [2023-11-14 22:15:00] Alice (work): [Voice 3s]\n"
+        );
+        let room = std::fs::read_to_string(out.join("123@chatroom.txt")).unwrap();
+        assert!(room.contains("Alice (work): welcome! [Quote Stranger: hello all]"));
+        assert!(room.contains("[System] \"Alice\" 撤回了一条消息"));
+        std::fs::remove_dir_all(&out).unwrap();
+    }
+
+    #[test]
+    fn test_markdown_combined_with_sender_filter() {
+        let conn = merged_db_with_sample_data();
+        let out = temp_path("combined.md");
+        let options = TranscriptOptions {
+            format: TranscriptFormat::Markdown,
+            my_wxid: "wxid_me".to_string(),
+            senders: vec!["wxid_alice".to_string()],
+            combined: true,
+            ..Default::default()
+        };
+        let talkers = vec!["wxid_alice".to_string(), "123@chatroom".to_string()];
+        let report = export_transcripts(&conn, &conn, &talkers, &options, &out).unwrap();
+        assert_eq!(report.files, vec![out.clone()]);
+        assert_eq!(report.messages, 3);
+
+        let md = std::fs::read_to_string(&out).unwrap();
+        assert!(md.starts_with("# Alice (work)\n"));
+        assert!(md.contains("\n## 2023-11-14\n\n- [2023-11-14 22:13:20] **Alice (work)**: hi there\n"));
+        assert!(md.contains("# Project <Team>"));
+        assert!(!md.contains("Stranger: hello all\n"));
+        std::fs::remove_file(&out).unwrap();
+    }
+}
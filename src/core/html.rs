@@ -0,0 +1,296 @@
+// src/core/html.rs
+
+use anyhow::Result;
+use std::fmt::Write as _;
+
+use crate::core::db_parser::{
+    app_msg_xml, format_timestamp_to_string, is_revoke_notice, message_sender_wxid, parse_app_msg, parse_emoji_xml,
+    parse_refer_msg, parse_voice_length_ms, system_message_text, EmojiInfo, Message, APP_MSG_TYPE_QUOTE, MSG_TYPE_APP,
+    MSG_TYPE_EMOJI, MSG_TYPE_IMAGE, MSG_TYPE_LOCATION, MSG_TYPE_SYSMSG, MSG_TYPE_SYSTEM, MSG_TYPE_TEXT, MSG_TYPE_VIDEO,
+    MSG_TYPE_VOICE,
+};
+
+/// Styles of the markup written by [`render_messages`], shared by the web viewer and the HTML export.
+pub const MESSAGE_STYLE: &str = "
+.day { text-align: center; color: #999; font-size: 12px; margin: 16px 0 8px; }
+.msg { display: flex; margin: 10px 0; }
+.msg.me { flex-direction: row-reverse; }
+.avatar { width: 36px; height: 36px; border-radius: 4px; flex: none; background: #bbb; color: #fff;
+          text-align: center; line-height: 36px; }
+.body { margin: 0 10px; max-width: 70%; }
+.msg.me .body { text-align: right; }
+.who { color: #999; font-size: 12px; margin-bottom: 2px; }
+.bubble { display: inline-block; text-align: left; background: #fff; padding: 8px 12px; border-radius: 4px; word-break: break-all; }
+.msg.me .bubble { background: #95ec69; }
+.bubble img, .bubble video { max-width: 240px; max-height: 240px; display: block; }
+.quote { margin-top: 4px; padding: 4px 8px; background: #e3e3e3; color: #666; font-size: 12px; border-radius: 4px; }
+.sys { text-align: center; color: #999; font-size: 12px; margin: 8px 0; }
+.revoked { font-style: italic; }
+";
+
+/// Media of a message as the page can reference it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MediaLink {
+    Image(String),
+    Video(String),
+    /// The thumbnail of a video whose file is not included.
+    VideoThumbnail(String),
+    Audio(String),
+    /// Voice that browsers cannot play (raw SILK), offered as a download.
+    VoiceDownload(String),
+    /// An attached file (app message), with its url when the file is included.
+    File(Option<String>),
+}
+
+/// What [`render_messages`] needs from the page it renders for: names and media urls.
+pub trait MessageContext {
+    /// wxid of the account owner, the sender of own messages in chat rooms.
+    fn my_wxid(&self) -> &str;
+    fn display_name(&self, wxid: &str) -> String;
+    /// Media of an image, video, voice or file message; None renders a placeholder.
+    fn media(&mut self, msg: &Message) -> Result<Option<MediaLink>>;
+    /// Url of a sticker image.
+    fn emoji(&mut self, emoji: &EmojiInfo) -> Result<Option<String>>;
+}
+
+/// Renders `messages` (in order) as chat bubbles, with a separator whenever the day changes.
+pub fn render_messages(ctx: &mut impl MessageContext, messages: &[Message]) -> Result<String> {
+    let mut html = String::new();
+    let mut last_day = String::new();
+    for msg in messages {
+        let day = format_timestamp_to_string(msg.create_time, "%Y-%m-%d");
+        if day != last_day {
+            let _ = write!(html, "<div class=\"day\">{}</div>", escape_html(&day));
+            last_day = day;
+        }
+        html.push_str(&render_message(ctx, msg)?);
+    }
+    Ok(html)
+}
+
+/// Renders one message. Avatars show the first letter of the sender's name: the head image
+/// urls of the contacts point to WeChat's servers, which a saved page should not contact.
+pub fn render_message(ctx: &mut impl MessageContext, msg: &Message) -> Result<String> {
+    let time = escape_html(&format_timestamp_to_string(msg.create_time, "%H:%M:%S"));
+    if msg.msg_type == MSG_TYPE_SYSTEM || msg.msg_type == MSG_TYPE_SYSMSG {
+        let class = if is_revoke_notice(msg) { "sys revoked" } else { "sys" };
+        return Ok(format!("<div class=\"{}\">{} {}</div>", class, time, escape_html(&system_message_text(msg))));
+    }
+
+    let name = ctx.display_name(&message_sender_wxid(msg, ctx.my_wxid()));
+    Ok(format!(
+        "<div class=\"msg{}\"><div class=\"avatar\">{}</div><div class=\"body\"><div class=\"who\">{} {}</div><div class=\"bubble\">{}</div></div></div>",
+        if msg.is_sender { " me" } else { "" },
+        escape_html(&name.chars().take(1).collect::<String>()),
+        escape_html(&name),
+        time,
+        render_content(ctx, msg)?
+    ))
+}
+
+fn render_content(ctx: &mut impl MessageContext, msg: &Message) -> Result<String> {
+    let content = msg.content.as_deref().unwrap_or("");
+    let html = match msg.msg_type {
+        MSG_TYPE_TEXT => text_to_html(content),
+        MSG_TYPE_IMAGE => match ctx.media(msg)? {
+            Some(MediaLink::Image(url)) => format!("<img src=\"{}\" loading=\"lazy\">", url),
+            _ => "[图片]".to_string(),
+        },
+        MSG_TYPE_VIDEO => match ctx.media(msg)? {
+            Some(MediaLink::Video(url)) => format!("<video controls preload=\"none\" src=\"{}\"></video>", url),
+            Some(MediaLink::VideoThumbnail(url)) => format!("<img src=\"{}\" loading=\"lazy\">[视频]", url),
+            _ => "[视频]".to_string(),
+        },
+        MSG_TYPE_VOICE => {
+            let label = match parse_voice_length_ms(content) {
+                Some(ms) => format!("[语音 {}\"]", (ms + 500) / 1000),
+                None => "[语音]".to_string(),
+            };
+            match ctx.media(msg)? {
+                Some(MediaLink::Audio(url)) => format!("<audio controls preload=\"none\" src=\"{}\"></audio>", url),
+                Some(MediaLink::VoiceDownload(url)) => format!("<a href=\"{}\">{}</a>", url, label),
+                _ => label,
+            }
+        }
+        MSG_TYPE_EMOJI => match parse_emoji_xml(content) {
+            Some(emoji) => match ctx.emoji(&emoji)? {
+                Some(url) => format!("<img src=\"{}\" loading=\"lazy\" alt=\"[表情]\">", url),
+                None => "[表情]".to_string(),
+            },
+            None => "[表情]".to_string(),
+        },
+        MSG_TYPE_LOCATION => "[位置]".to_string(),
+        MSG_TYPE_APP => render_app_msg(ctx, msg)?,
+        other => format!("[消息类型 {}]", other),
+    };
+    Ok(html)
+}
+
+fn render_app_msg(ctx: &mut impl MessageContext, msg: &Message) -> Result<String> {
+    let xml = app_msg_xml(msg);
+    let Some(app) = xml.as_deref().and_then(parse_app_msg) else { return Ok("[链接]".to_string()) };
+    let title = app.title.clone().unwrap_or_default();
+
+    if app.app_type == Some(APP_MSG_TYPE_QUOTE) {
+        let mut html = text_to_html(&title);
+        if let Some(refer) = xml.as_deref().and_then(parse_refer_msg) {
+            let who = refer
+                .display_name
+                .clone()
+                .or_else(|| refer.sender_wxid.as_deref().map(|w| ctx.display_name(w)))
+                .unwrap_or_default();
+            let quoted = match refer.msg_type {
+                Some(MSG_TYPE_TEXT) | None => refer.content.clone().unwrap_or_default(),
+                Some(MSG_TYPE_IMAGE) => "[图片]".to_string(),
+                Some(MSG_TYPE_VOICE) => "[语音]".to_string(),
+                Some(MSG_TYPE_VIDEO) => "[视频]".to_string(),
+                Some(MSG_TYPE_EMOJI) => "[表情]".to_string(),
+                Some(MSG_TYPE_APP) => refer
+                    .content
+                    .as_deref()
+                    .and_then(parse_app_msg)
+                    .and_then(|a| a.title)
+                    .unwrap_or_else(|| "[链接]".to_string()),
+                Some(other) => format!("[消息类型 {}]", other),
+            };
+            let _ = write!(html, "<div class=\"quote\">{}: {}</div>", escape_html(&who), text_to_html(&quoted));
+        }
+        return Ok(html);
+    }
+
+    if let Some(MediaLink::File(url)) = ctx.media(msg)? {
+        let size = app.total_len.map(|n| format!(" ({})", human_size(n))).unwrap_or_default();
+        return Ok(match url {
+            Some(url) => format!("[文件] <a href=\"{}\" download>{}</a>{}", url, escape_html(&title), size),
+            None => format!("[文件] {}{}", escape_html(&title), size),
+        });
+    }
+    Ok(match app.url.as_deref() {
+        Some(url) if is_web_url(url) => {
+            format!("<a href=\"{}\" target=\"_blank\">{}</a>", escape_html(url), escape_html(&title))
+        }
+        // Other schemes (javascript:, file:, ...) would run or open in the page's origin.
+        Some(url) if title.is_empty() => escape_html(url),
+        _ if !title.is_empty() => escape_html(&title),
+        _ => "[链接]".to_string(),
+    })
+}
+
+/// Whether a url taken from a message is safe to put in an `href`: only http and https are.
+pub fn is_web_url(url: &str) -> bool {
+    let url = url.trim_start().to_ascii_lowercase();
+    url.starts_with("http://") || url.starts_with("https://")
+}
+
+/// Escapes text and keeps its line breaks.
+pub fn text_to_html(text: &str) -> String {
+    escape_html(text).replace('\n', "<br>")
+}
+
+/// Escapes text for use in HTML element content and double-quoted attributes.
+pub fn escape_html(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&#39;"),
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+/// Percent-encodes a value for use inside a URL query string.
+pub fn encode_query_value(value: &str) -> String {
+    form_urlencoded::byte_serialize(value.as_bytes()).collect()
+}
+
+/// File size with a binary unit, e.g. `1.5 KB`.
+pub fn human_size(bytes: i64) -> String {
+    const UNITS: [&str; 4] = ["B", "KB", "MB", "GB"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 { format!("{} B", bytes) } else { format!("{:.1} {}", size, UNITS[unit]) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_escape_html() {
+        assert_eq!(escape_html("<a href=\"x\">Tom & 'Jerry'</a>"), "&lt;a href=&quot;x&quot;&gt;Tom &amp; &#39;Jerry&#39;&lt;/a&gt;");
+        assert_eq!(encode_query_value("a b&c@chatroom"), "a+b%26c%40chatroom");
+    }
+
+    struct Stub;
+
+    impl MessageContext for Stub {
+        fn my_wxid(&self) -> &str {
+            "wxid_me"
+        }
+
+        fn display_name(&self, wxid: &str) -> String {
+            if wxid == "wxid_me" { "Me".to_string() } else { "<Alice>".to_string() }
+        }
+
+        fn media(&mut self, msg: &Message) -> Result<Option<MediaLink>> {
+            Ok((msg.msg_type == MSG_TYPE_VOICE).then(|| MediaLink::VoiceDownload("v.silk".to_string())))
+        }
+
+        fn emoji(&mut self, _: &EmojiInfo) -> Result<Option<String>> {
+            Ok(None)
+        }
+    }
+
+    #[test]
+    fn test_render_messages() {
+        let text = Message { msg_type: MSG_TYPE_TEXT, talker: "wxid_alice".into(), content: Some("a\nb".into()), ..Default::default() };
+        let voice = Message {
+            msg_type: MSG_TYPE_VOICE,
+            is_sender: true,
+            content: Some("<msg><voicemsg voicelength=\"2600\"/></msg>".into()),
+            ..Default::default()
+        };
+        let image = Message { msg_type: MSG_TYPE_IMAGE, ..Default::default() };
+        let html = render_messages(&mut Stub, &[text, voice, image]).unwrap();
+        assert_eq!(html.matches("<div class=\"day\">").count(), 1);
+        assert!(html.contains("<div class=\"avatar\">&lt;</div>"));
+        assert!(html.contains("<div class=\"bubble\">a<br>b</div>"));
+        assert!(html.contains("<div class=\"msg me\"><div class=\"avatar\">M</div>"));
+        assert!(html.contains("<a href=\"v.silk\">[语音 3\"]</a>"));
+        assert!(html.contains("<div class=\"bubble\">[图片]</div>"));
+    }
+
+    #[test]
+    fn test_app_msg_links_only_web_urls() {
+        let link = |url: &str| Message {
+            msg_type: MSG_TYPE_APP,
+            content: Some(format!("<msg><appmsg><title>Read me</title><type>5</type><url>{}</url></appmsg></msg>", url)),
+            ..Default::default()
+        };
+        let html = render_message(&mut Stub, &link("https://mp.weixin.qq.com/s/x")).unwrap();
+        assert!(html.contains("<a href=\"https://mp.weixin.qq.com/s/x\" target=\"_blank\">Read me</a>"));
+        for url in ["javascript:alert(1)", " JavaScript:alert(1)", "file:///etc/passwd"] {
+            let html = render_message(&mut Stub, &link(url)).unwrap();
+            assert!(!html.contains("href"), "{}", url);
+            assert!(html.contains("Read me"));
+        }
+        assert!(is_web_url("HTTP://example.com"));
+        assert!(!is_web_url("data:text/html,x"));
+    }
+
+    #[test]
+    fn test_human_size() {
+        assert_eq!(human_size(512), "512 B");
+        assert_eq!(human_size(1536), "1.5 KB");
+        assert_eq!(human_size(5 * 1024 * 1024), "5.0 MB");
+    }
+}
@@ -0,0 +1,306 @@
+// src/core/image_decode.rs
+
+use anyhow::{Result, anyhow};
+use serde::Serialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+use walkdir::WalkDir;
+
+use aes::Aes128;
+use aes::cipher::generic_array::GenericArray;
+use aes::cipher::{BlockDecrypt, KeyInit};
+
+/// Signature of the newer .dat layout with an AES-encrypted head.
+const DAT_V1_SIGNATURE: &[u8] = b"\x07\x08V1\x08\x07";
+const DAT_V2_SIGNATURE: &[u8] = b"\x07\x08V2\x08\x07";
+/// 6 bytes signature, u32 aes_size, u32 xor_size, 1 byte padding.
+const DAT_V_HEADER_SIZE: usize = 15;
+/// V1 files use a fixed AES key (the first 16 hex chars of md5("0")).
+const DAT_V1_AES_KEY: &[u8; 16] = b"cfcd208495d565ef";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImageFormat {
+    Jpeg,
+    Png,
+    Gif,
+    Bmp,
+    Webp,
+}
+
+impl ImageFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ImageFormat::Jpeg => "jpg",
+            ImageFormat::Png => "png",
+            ImageFormat::Gif => "gif",
+            ImageFormat::Bmp => "bmp",
+            ImageFormat::Webp => "webp",
+        }
+    }
+
+    pub fn mime_type(&self) -> &'static str {
+        match self {
+            ImageFormat::Jpeg => "image/jpeg",
+            ImageFormat::Png => "image/png",
+            ImageFormat::Gif => "image/gif",
+            ImageFormat::Bmp => "image/bmp",
+            ImageFormat::Webp => "image/webp",
+        }
+    }
+
+    /// Magic bytes at the start of the file. WebP additionally has "WEBP" at offset 8.
+    fn magic(&self) -> &'static [u8] {
+        match self {
+            ImageFormat::Jpeg => &[0xFF, 0xD8, 0xFF],
+            ImageFormat::Png => &[0x89, 0x50, 0x4E, 0x47],
+            ImageFormat::Gif => b"GIF8",
+            ImageFormat::Bmp => b"BM",
+            ImageFormat::Webp => b"RIFF",
+        }
+    }
+
+    /// Bytes every well-formed file of this format ends with, if any.
+    fn trailer(&self) -> Option<&'static [u8]> {
+        match self {
+            ImageFormat::Jpeg => Some(&[0xFF, 0xD9]),
+            ImageFormat::Png => Some(&[0xAE, 0x42, 0x60, 0x82]), // CRC of the IEND chunk
+            ImageFormat::Gif => Some(&[0x3B]),
+            ImageFormat::Bmp | ImageFormat::Webp => None,
+        }
+    }
+}
+
+// Checked in this order; BMP's two-byte magic is the weakest, so it goes last.
+const KNOWN_FORMATS: [ImageFormat; 5] =
+    [ImageFormat::Jpeg, ImageFormat::Png, ImageFormat::Gif, ImageFormat::Webp, ImageFormat::Bmp];
+
+/// How a .dat file was obfuscated.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DatVersion {
+    /// Whole file XORed with a single byte.
+    Xor { key: u8 },
+    /// `\x07\x08V1\x08\x07` header, AES head with the fixed V1 key.
+    V1,
+    /// `\x07\x08V2\x08\x07` header, AES head with a per-account key.
+    V2,
+}
+
+/// Keys for the V1/V2 layouts. Plain XOR files need neither.
+#[derive(Debug, Clone, Default)]
+pub struct DatKeys {
+    /// 16-byte AES key for V2 files (found in WeChat's memory).
+    pub aes_key: Option<[u8; 16]>,
+    /// XOR key for the tail of V1/V2 files. Inferred from the image trailer if not given.
+    pub xor_key: Option<u8>,
+}
+
+#[derive(Debug, Clone)]
+pub struct DecodedImage {
+    pub format: ImageFormat,
+    pub version: DatVersion,
+    pub data: Vec<u8>,
+}
+
+/// Detects a (decoded) image format from its magic bytes.
+pub fn detect_image_format(data: &[u8]) -> Option<ImageFormat> {
+    KNOWN_FORMATS.iter().copied().find(|f| {
+        data.starts_with(f.magic()) && (*f != ImageFormat::Webp || data.get(8..12) == Some(b"WEBP".as_slice()))
+    })
+}
+
+/// Finds the single-byte XOR key of an old-style .dat file by XORing its first bytes
+/// against the known image magics.
+pub fn detect_xor_key(data: &[u8]) -> Option<(u8, ImageFormat)> {
+    let first = *data.first()?;
+    KNOWN_FORMATS.iter().copied().find_map(|f| {
+        let key = first ^ f.magic()[0];
+        let head: Vec<u8> = data.iter().take(12).map(|b| b ^ key).collect();
+        (detect_image_format(&head) == Some(f)).then_some((key, f))
+    })
+}
+
+/// Decodes the content of a WeChat .dat image, detecting the layout automatically.
+pub fn decode_dat(data: &[u8], keys: &DatKeys) -> Result<DecodedImage> {
+    if data.starts_with(DAT_V1_SIGNATURE) {
+        return decode_dat_v(data, DAT_V1_AES_KEY, keys.xor_key, DatVersion::V1);
+    }
+    if data.starts_with(DAT_V2_SIGNATURE) {
+        let aes_key = keys
+            .aes_key
+            .ok_or_else(|| anyhow!("V2 .dat file requires an AES key"))?;
+        return decode_dat_v(data, &aes_key, keys.xor_key, DatVersion::V2);
+    }
+
+    let (key, format) = detect_xor_key(data).ok_or_else(|| anyhow!("Unknown .dat format (no image magic matched)"))?;
+    Ok(DecodedImage { format, version: DatVersion::Xor { key }, data: data.iter().map(|b| b ^ key).collect() })
+}
+
+/// Reads and decodes a .dat file.
+pub fn decode_dat_file(path: &Path, keys: &DatKeys) -> Result<DecodedImage> {
+    let data = fs::read(path).map_err(|e| anyhow!("Failed to read {:?}: {}", path, e))?;
+    decode_dat(&data, keys)
+}
+
+fn decode_dat_v(data: &[u8], aes_key: &[u8; 16], xor_key: Option<u8>, version: DatVersion) -> Result<DecodedImage> {
+    if data.len() < DAT_V_HEADER_SIZE {
+        return Err(anyhow!("File is too short for a V1/V2 .dat header"));
+    }
+    let aes_size = u32::from_le_bytes(data[6..10].try_into().unwrap()) as usize;
+    let xor_size = u32::from_le_bytes(data[10..14].try_into().unwrap()) as usize;
+    let body = &data[DAT_V_HEADER_SIZE..];
+
+    // The AES part is PKCS#7 padded, which always adds at least one byte.
+    let aligned_aes_size = aes_size - aes_size % 16 + 16;
+    if body.len() < aligned_aes_size || body.len() - aligned_aes_size < xor_size {
+        return Err(anyhow!("V1/V2 .dat sizes (aes {}, xor {}) exceed file length {}", aes_size, xor_size, body.len()));
+    }
+
+    let cipher = Aes128::new(GenericArray::from_slice(aes_key));
+    let mut head = body[..aligned_aes_size].to_vec();
+    for chunk in head.chunks_exact_mut(16) {
+        cipher.decrypt_block(GenericArray::from_mut_slice(chunk));
+    }
+    head.truncate(aes_size);
+
+    let format = detect_image_format(&head).ok_or_else(|| anyhow!("AES head did not decrypt to a known image (wrong key?)"))?;
+
+    let rest = &body[aligned_aes_size..];
+    let (raw, xored) = rest.split_at(rest.len() - xor_size);
+    let xor_key = match xor_key {
+        Some(k) => k,
+        None if xored.is_empty() => 0,
+        None => infer_tail_xor_key(xored, format)
+            .ok_or_else(|| anyhow!("Cannot infer the XOR key for the file tail, please provide it"))?,
+    };
+
+    let mut out = head;
+    out.extend_from_slice(raw);
+    out.extend(xored.iter().map(|b| b ^ xor_key));
+    Ok(DecodedImage { format, version, data: out })
+}
+
+/// The XORed tail of a V1/V2 file ends with the format trailer (e.g. FF D9 for JPEG),
+/// which gives away the key.
+fn infer_tail_xor_key(xored_tail: &[u8], format: ImageFormat) -> Option<u8> {
+    let trailer = format.trailer()?;
+    if xored_tail.len() < trailer.len() {
+        return None;
+    }
+    let tail = &xored_tail[xored_tail.len() - trailer.len()..];
+    let key = tail[0] ^ trailer[0];
+    tail.iter().zip(trailer).all(|(b, t)| b ^ key == *t).then_some(key)
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct DecryptImagesReport {
+    pub decoded: Vec<PathBuf>,
+    pub failed: Vec<(PathBuf, String)>,
+}
+
+/// Walks `root` (an account folder such as `WeChat Files/wxid_xxx`) for .dat files in
+/// `Image`/`Thumb` directories and writes the decoded images under `out_dir`,
+/// preserving the relative layout and replacing `.dat` with the real extension.
+pub fn decrypt_images_in_dir(root: &Path, out_dir: &Path, keys: &DatKeys) -> Result<DecryptImagesReport> {
+    if !root.is_dir() {
+        return Err(anyhow!("Not a directory: {:?}", root));
+    }
+    let mut report = DecryptImagesReport::default();
+
+    for entry in WalkDir::new(root).into_iter().filter_map(Result::ok) {
+        let path = entry.path();
+        if !path.is_file() || !path.extension().is_some_and(|e| e.eq_ignore_ascii_case("dat")) {
+            continue;
+        }
+        let relative = match path.strip_prefix(root) {
+            Ok(r) => r,
+            Err(_) => continue,
+        };
+        let in_image_dir = relative
+            .parent()
+            .is_some_and(|p| p.components().any(|c| c.as_os_str() == "Image" || c.as_os_str() == "Thumb"));
+        if !in_image_dir {
+            continue;
+        }
+
+        match decode_dat_file(path, keys) {
+            Ok(image) => {
+                let target = out_dir.join(relative).with_extension(image.format.extension());
+                if let Some(parent) = target.parent() {
+                    fs::create_dir_all(parent)?;
+                }
+                fs::write(&target, &image.data).map_err(|e| anyhow!("Failed to write {:?}: {}", target, e))?;
+                report.decoded.push(target);
+            }
+            Err(e) => report.failed.push((path.to_path_buf(), e.to_string())),
+        }
+    }
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use aes::cipher::BlockEncrypt;
+
+    const JPEG: &[u8] = &[0xFF, 0xD8, 0xFF, 0xE0, 0x00, 0x10, b'J', b'F', b'I', b'F', 0x00, 0x01, 0x02, 0x03, 0xFF, 0xD9];
+
+    #[test]
+    fn test_detect_xor_key() {
+        let xored: Vec<u8> = JPEG.iter().map(|b| b ^ 0x5A).collect();
+        assert_eq!(detect_xor_key(&xored), Some((0x5A, ImageFormat::Jpeg)));
+
+        let png = [0x89, 0x50, 0x4E, 0x47, 0x0D, 0x0A, 0x1A, 0x0A];
+        let xored: Vec<u8> = png.iter().map(|b| b ^ 0x13).collect();
+        assert_eq!(detect_xor_key(&xored), Some((0x13, ImageFormat::Png)));
+
+        let decoded = decode_dat(&JPEG.iter().map(|b| b ^ 0x77).collect::<Vec<_>>(), &DatKeys::default()).unwrap();
+        assert_eq!(decoded.version, DatVersion::Xor { key: 0x77 });
+        assert_eq!(decoded.data, JPEG);
+    }
+
+    #[test]
+    fn test_detect_webp_requires_webp_tag() {
+        assert_eq!(detect_image_format(b"RIFF\x00\x00\x00\x00WEBPVP8 "), Some(ImageFormat::Webp));
+        assert_eq!(detect_image_format(b"RIFF\x00\x00\x00\x00WAVEfmt "), None);
+    }
+
+    fn encode_v(signature: &[u8], aes_key: &[u8; 16], aes_size: usize, xor_size: usize, xor_key: u8) -> Vec<u8> {
+        let mut head = JPEG[..aes_size].to_vec();
+        let pad = 16 - aes_size % 16;
+        head.extend(std::iter::repeat_n(pad as u8, pad));
+        let cipher = Aes128::new(GenericArray::from_slice(aes_key));
+        for chunk in head.chunks_exact_mut(16) {
+            cipher.encrypt_block(GenericArray::from_mut_slice(chunk));
+        }
+
+        let mut out = signature.to_vec();
+        out.extend_from_slice(&(aes_size as u32).to_le_bytes());
+        out.extend_from_slice(&(xor_size as u32).to_le_bytes());
+        out.push(0);
+        out.extend_from_slice(&head);
+        out.extend_from_slice(&JPEG[aes_size..JPEG.len() - xor_size]);
+        out.extend(JPEG[JPEG.len() - xor_size..].iter().map(|b| b ^ xor_key));
+        out
+    }
+
+    #[test]
+    fn test_decode_v1_infers_xor_key() {
+        let data = encode_v(DAT_V1_SIGNATURE, DAT_V1_AES_KEY, 10, 4, 0x88);
+        let decoded = decode_dat(&data, &DatKeys::default()).unwrap();
+        assert_eq!(decoded.version, DatVersion::V1);
+        assert_eq!(decoded.format, ImageFormat::Jpeg);
+        assert_eq!(decoded.data, JPEG);
+    }
+
+    #[test]
+    fn test_decode_v2_needs_key() {
+        let key = *b"0123456789abcdef";
+        let data = encode_v(DAT_V2_SIGNATURE, &key, 12, 2, 0x31);
+        assert!(decode_dat(&data, &DatKeys::default()).is_err());
+
+        let keys = DatKeys { aes_key: Some(key), xor_key: Some(0x31) };
+        let decoded = decode_dat(&data, &keys).unwrap();
+        assert_eq!(decoded.version, DatVersion::V2);
+        assert_eq!(decoded.data, JPEG);
+    }
+}
@@ -4,8 +4,12 @@ use anyhow::{Result, anyhow};
 use std::path::PathBuf;
 use super::win_api::{self}; // Removed unused ProcessInfo
 use super::offsets::WxOffsets;
+use super::error::WxDumpError;
+use super::logging::redact_key;
+use log::{debug, info, warn};
+use serde::Serialize;
 
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, Serialize)]
 pub struct WeChatUserInfo {
     pub pid: u32,
     pub version: String,
@@ -34,36 +38,36 @@ fn get_wechat_files_path_from_registry() -> Result<Option<PathBuf>> {
                     let docs_path = PathBuf::from(user_profile).join("Documents");
                     let wechat_files_path = docs_path.join("WeChat Files");
                     if wechat_files_path.exists() && wechat_files_path.is_dir(){
-                        println!("[InfoExtractor] Resolved 'MyDocument:' to WeChat Files path: {:?}", wechat_files_path);
+                        debug!("Resolved 'MyDocument:' to WeChat Files path: {:?}", wechat_files_path);
                         return Ok(Some(wechat_files_path));
                     } else {
-                         println!("[InfoExtractor] 'MyDocument:' resolved path does not exist or not a dir: {:?}", wechat_files_path);
+                         debug!("'MyDocument:' resolved path does not exist or not a dir: {:?}", wechat_files_path);
                         return Ok(None);
                     }
                 } else {
-                     println!("[InfoExtractor] Could not resolve 'MyDocument:' due to missing USERPROFILE.");
+                     debug!("Could not resolve 'MyDocument:' due to missing USERPROFILE.");
                     return Ok(None);
                 }
             } else if !path_str.is_empty() {
                 let path_str_clone_for_join = path_str.clone(); // Clone for the first PathBuf creation
                 let wechat_files_path = PathBuf::from(path_str_clone_for_join).join("WeChat Files"); 
                  if wechat_files_path.exists() && wechat_files_path.is_dir(){
-                    println!("[InfoExtractor] Found WeChat Files path from registry (joined): {:?}", wechat_files_path);
+                    debug!("Found WeChat Files path from registry (joined): {:?}", wechat_files_path);
                     return Ok(Some(wechat_files_path));
                 } else {
                     let original_path_buf = PathBuf::from(&path_str); // Borrow original path_str
                     if original_path_buf.exists() && original_path_buf.is_dir() && original_path_buf.file_name().map_or(false, |name| name == "WeChat Files") {
-                        println!("[InfoExtractor] Found WeChat Files path from registry (original path): {:?}", original_path_buf);
+                        debug!("Found WeChat Files path from registry (original path): {:?}", original_path_buf);
                         return Ok(Some(original_path_buf));
                     }
-                    println!("[InfoExtractor] Registry path for WeChat Files does not exist or not a dir: {:?} (and original path {:?} also invalid)", wechat_files_path, path_str);
+                    debug!("Registry path for WeChat Files does not exist or not a dir: {:?} (and original path {:?} also invalid)", wechat_files_path, path_str);
                     return Ok(None);
                 }
             }
             Ok(None)
         }
         Err(e) => {
-            println!("[InfoExtractor] Failed to read WeChat FileSavePath from registry: {}. This might be normal.", e);
+            debug!("Failed to read WeChat FileSavePath from registry: {}. This might be normal.", e);
             Ok(None)
         }
     }
@@ -80,7 +84,7 @@ fn get_wechat_files_path_from_memory(pid: u32, wxid: &str) -> Result<Option<Path
     match win_api::search_memory_for_pattern(pid, wxid_bytes, search_start_address, search_end_address, 5) { 
         Ok(addresses) => {
             if addresses.is_empty() {
-                println!("[InfoExtractor] WxID pattern for path search not found in memory for PID {}.", pid);
+                debug!("WxID pattern for path search not found in memory for PID {}.", pid);
                 return Ok(None);
             }
             for &addr in &addresses {
@@ -97,7 +101,7 @@ fn get_wechat_files_path_from_memory(pid: u32, wxid: &str) -> Result<Option<Path
                                         let root_path_str = &path_str[..(wc_files_end_idx + "WeChat Files".len())];
                                         let path_buf = PathBuf::from(root_path_str);
                                         if path_buf.exists() && path_buf.is_dir() {
-                                            println!("[InfoExtractor] Found potential WeChat Files path via memory search: {:?}", path_buf);
+                                            debug!("Found potential WeChat Files path via memory search: {:?}", path_buf);
                                             return Ok(Some(path_buf));
                                         }
                                     }
@@ -110,17 +114,17 @@ fn get_wechat_files_path_from_memory(pid: u32, wxid: &str) -> Result<Option<Path
             Ok(None) 
         }
         Err(e) => {
-            eprintln!("[InfoExtractor] Error searching memory for WxID pattern (for path): {}", e);
+            warn!("Error searching memory for WxID pattern (for path): {}", e);
             Ok(None)
         }
     }
 }
 
 fn get_key_from_memory_search(pid: u32, pointer_size: usize) -> Result<Option<String>> {
-    println!("[InfoExtractor DEBUG] Attempting memory search for key using anchor strings (Python-like).");
+    debug!("Attempting memory search for key using anchor strings (Python-like).");
     let wechat_win_dll_base = match win_api::get_module_base_address(pid, "WeChatWin.dll") {
         Ok(addr) => addr,
-        Err(e) => { eprintln!("[InfoExtractor DEBUG] WeChatWin.dll not found for key search: {}", e); return Ok(None); }
+        Err(e) => { warn!("WeChatWin.dll not found for key search: {}", e); return Ok(None); }
     };
     let search_start_address = wechat_win_dll_base;
     let search_end_address = wechat_win_dll_base.saturating_add(100 * 1024 * 1024); 
@@ -132,15 +136,15 @@ fn get_key_from_memory_search(pid: u32, pointer_size: usize) -> Result<Option<St
         match win_api::search_memory_for_pattern(pid, anchor, search_start_address, search_end_address, 5) {
             Ok(addrs) => {
                 if !addrs.is_empty() {
-                    println!("[InfoExtractor DEBUG] Found anchor {:?} at addresses: {:?}", String::from_utf8_lossy(anchor), addrs.iter().map(|a| format!("0x{:X}", a)).collect::<Vec<_>>());
+                    debug!("Found anchor {:?} at addresses: {:?}", String::from_utf8_lossy(anchor), addrs.iter().map(|a| format!("0x{:X}", a)).collect::<Vec<_>>());
                     found_anchor_addrs.extend_from_slice(&addrs);
                 }
             }
-            Err(e) => { println!("[InfoExtractor DEBUG] Error searching for anchor {:?}: {}", String::from_utf8_lossy(anchor), e); }
+            Err(e) => warn!("Error searching for anchor {:?}: {}", String::from_utf8_lossy(anchor), e),
         }
     }
     if found_anchor_addrs.is_empty() {
-        println!("[InfoExtractor DEBUG] No anchor strings found for Python-like key search in WeChatWin.dll range.");
+        debug!("No anchor strings found for Python-like key search in WeChatWin.dll range.");
         return Ok(None);
     }
     found_anchor_addrs.sort_unstable();
@@ -160,7 +164,7 @@ fn get_key_from_memory_search(pid: u32, pointer_size: usize) -> Result<Option<St
                         if let Ok(key_bytes) = win_api::read_process_memory(pid, key_address, KEY_LEN) {
                             if key_bytes.len() == KEY_LEN && !key_bytes.iter().all(|&b| b == 0) {
                                 let key_hex = hex::encode(&key_bytes);
-                                println!("[InfoExtractor DEBUG] Python-like memory search found potential key at 0x{:X} (ptr at 0x{:X}): {}", key_address, ptr_addr_to_check, key_hex);
+                                debug!("Python-like memory search found potential key at 0x{:X} (ptr at 0x{:X}): {}", key_address, ptr_addr_to_check, redact_key(&key_hex));
                                 return Ok(Some(key_hex));
                             }
                         }
@@ -170,162 +174,175 @@ fn get_key_from_memory_search(pid: u32, pointer_size: usize) -> Result<Option<St
             }
         }
     }
-    println!("[InfoExtractor DEBUG] No key found via Python-like memory search after checking all anchors.");
+    debug!("No key found via Python-like memory search after checking all anchors.");
     Ok(None)
 }
 
-pub fn extract_all_wechat_info(loaded_offsets: &WxOffsets) -> Result<Vec<WeChatUserInfo>> {
+/// Reads the account info and key of every running WeChat.exe. Fails with
+/// [`WxDumpError::WeChatNotRunning`] when there is none, and with the error of the first
+/// process when no process could be read at all.
+pub fn extract_all_wechat_info(loaded_offsets: &WxOffsets) -> std::result::Result<Vec<WeChatUserInfo>, WxDumpError> {
     let mut all_user_info = Vec::new();
+    let mut first_error: Option<WxDumpError> = None;
     let processes = win_api::list_processes()?;
 
-    for process in processes {
-        if process.name == "WeChat.exe" {
-            println!("[InfoExtractor] Found WeChat.exe with PID: {}", process.pid);
-            let exe_path = match win_api::get_process_exe_path(process.pid) {
-                Ok(p) => p,
-                Err(e) => { eprintln!("[InfoExtractor] Failed to get exe path for PID {}: {}", process.pid, e); continue; }
-            };
-            let version = match win_api::get_file_version_info(&exe_path) {
-                Ok(v) => v,
-                Err(e) => { eprintln!("[InfoExtractor] Failed to get version for PID {} (path: {}): {}", process.pid, exe_path, e); "unknown".to_string() }
-            };
-            println!("[InfoExtractor] PID: {}, Path: {}, Version: {}", process.pid, exe_path, version);
-
-            let mut user_info = WeChatUserInfo { pid: process.pid, version: version.clone(), ..Default::default() };
-            let mut dll_base_address_opt: Option<usize> = None;
-            let mut pointer_size_opt: Option<usize> = None;
-
-            if let Some(v_offsets) = loaded_offsets.get(&version) {
-                println!("[InfoExtractor] Found offsets for version {}: {:?}", version, v_offsets);
-                if let Ok(arch_size) = win_api::get_process_architecture(process.pid) {
-                    pointer_size_opt = Some(arch_size);
-                    if let Ok(base_addr) = win_api::get_module_base_address(process.pid, "WeChatWin.dll") {
-                        dll_base_address_opt = Some(base_addr);
-                        println!("[InfoExtractor] WeChatWin.dll base: 0x{:X}, ArchSize: {}", base_addr, arch_size);
-
-                        // Nickname, Account, Mobile, Mail
-                        if v_offsets.len() > 0 && v_offsets[0] != 0 {
-                            match read_string_via_pointer_offset(process.pid, base_addr, v_offsets[0], arch_size, 64) {
-                                Ok(name) => { println!("[InfoExtractor] Nickname (ptr): {}", name); user_info.nickname = Some(name); },
-                                Err(_e_ptr) => match read_direct_string_from_offset(process.pid, base_addr, v_offsets[0], 64) {
-                                    Ok(name_direct) => { println!("[InfoExtractor] Nickname (direct): {}", name_direct); user_info.nickname = Some(name_direct); },
-                                    Err(_e_direct) => eprintln!("[InfoExtractor] Failed to read nickname (ptr/direct)."),
-                                }
-                            }
-                        }
-                        if v_offsets.len() > 1 && v_offsets[1] != 0 {
-                            match read_direct_string_from_offset(process.pid, base_addr, v_offsets[1], 32) {
-                                Ok(acc) => { println!("[InfoExtractor] Account: {}", acc); user_info.account = Some(acc); },
-                                Err(e) => eprintln!("[InfoExtractor] Failed to read account: {}", e),
-                            }
-                        }
-                        if v_offsets.len() > 2 && v_offsets[2] != 0 {
-                            match read_direct_string_from_offset(process.pid, base_addr, v_offsets[2], 64) {
-                                Ok(mob) => { println!("[InfoExtractor] Mobile: {}", mob); user_info.mobile = Some(mob); },
-                                Err(e) => eprintln!("[InfoExtractor] Failed to read mobile: {}", e),
-                            }
-                        }
-                        if v_offsets.len() > 3 && v_offsets[3] != 0 {
-                            match read_direct_string_from_offset(process.pid, base_addr, v_offsets[3], 64) {
-                                Ok(em) => { println!("[InfoExtractor] Mail: {}", em); user_info.mail = Some(em); },
-                                Err(e) => eprintln!("[InfoExtractor] Failed to read mail: {}", e),
-                            }
-                        }
-                    } else { eprintln!("[InfoExtractor] Failed to get WeChatWin.dll base for PID {}.", process.pid); }
-                } else { eprintln!("[InfoExtractor] Failed to get arch size for PID {}.", process.pid); }
-            } else { println!("[InfoExtractor] No offsets for version {}.", version); }
+    for process in processes.iter().filter(|p| p.name == "WeChat.exe") {
+        info!("Found WeChat.exe with PID: {}", process.pid);
+        match extract_wechat_info(process.pid, loaded_offsets) {
+            Ok(user_info) => all_user_info.push(user_info),
+            Err(e) => {
+                warn!("PID {}: {}", process.pid, e);
+                first_error.get_or_insert(e);
+            }
+        }
+    }
+    if all_user_info.is_empty() {
+        return Err(first_error.unwrap_or(WxDumpError::WeChatNotRunning));
+    }
+    Ok(all_user_info)
+}
+
+/// Reads the account info and key of one WeChat process. Profile fields that cannot be read
+/// stay `None` and failed memory searches are logged and skipped; failing to open the process,
+/// to find WeChatWin.dll or to find the key by any method is an error.
+pub fn extract_wechat_info(pid: u32, loaded_offsets: &WxOffsets) -> std::result::Result<WeChatUserInfo, WxDumpError> {
+    let exe_path = win_api::get_process_exe_path(pid)?;
+    let version = match win_api::get_file_version_info(&exe_path) {
+        Ok(v) => v,
+        Err(e) => { warn!("Failed to get version for PID {} (path: {}): {}", pid, exe_path, e); "unknown".to_string() }
+    };
+    debug!("PID: {}, Path: {}, Version: {}", pid, exe_path, version);
+
+    let mut user_info = WeChatUserInfo { pid, version: version.clone(), ..Default::default() };
+    let arch_size = win_api::get_process_architecture(pid)?;
+    let base_addr = win_api::get_module_base_address(pid, "WeChatWin.dll")?;
+    debug!("WeChatWin.dll base: 0x{:X}, ArchSize: {}", base_addr, arch_size);
 
-            match get_wxid_from_memory(process.pid) {
-                Ok(Some(wxid_val)) => { println!("[InfoExtractor] WxID (mem): {}", wxid_val); user_info.wxid = Some(wxid_val); },
-                _ => println!("[InfoExtractor] WxID not found via memory search."),
+    let version_offsets = loaded_offsets.get(&version);
+    if let Some(v_offsets) = version_offsets {
+        debug!("Found offsets for version {}: {:?}", version, v_offsets);
+
+        // Nickname, Account, Mobile, Mail
+        if v_offsets.len() > 0 && v_offsets[0] != 0 {
+            match read_string_via_pointer_offset(pid, base_addr, v_offsets[0], arch_size, 64) {
+                Ok(name) => { debug!("Nickname (ptr): {}", name); user_info.nickname = Some(name); },
+                Err(_e_ptr) => match read_direct_string_from_offset(pid, base_addr, v_offsets[0], 64) {
+                    Ok(name_direct) => { debug!("Nickname (direct): {}", name_direct); user_info.nickname = Some(name_direct); },
+                    Err(_e_direct) => warn!("Failed to read nickname (ptr/direct)."),
+                }
             }
+        }
+        if v_offsets.len() > 1 && v_offsets[1] != 0 {
+            match read_direct_string_from_offset(pid, base_addr, v_offsets[1], 32) {
+                Ok(acc) => { debug!("Account: {}", acc); user_info.account = Some(acc); },
+                Err(e) => warn!("Failed to read account: {}", e),
+            }
+        }
+        if v_offsets.len() > 2 && v_offsets[2] != 0 {
+            match read_direct_string_from_offset(pid, base_addr, v_offsets[2], 64) {
+                Ok(mob) => { debug!("Mobile: {}", mob); user_info.mobile = Some(mob); },
+                Err(e) => warn!("Failed to read mobile: {}", e),
+            }
+        }
+        if v_offsets.len() > 3 && v_offsets[3] != 0 {
+            match read_direct_string_from_offset(pid, base_addr, v_offsets[3], 64) {
+                Ok(em) => { debug!("Mail: {}", em); user_info.mail = Some(em); },
+                Err(e) => warn!("Failed to read mail: {}", e),
+            }
+        }
+    } else { debug!("No offsets for version {}.", version); }
 
-            let mut memory_search_attempted_for_path = false;
-            match get_wechat_files_path_from_registry() {
-                Ok(Some(reg_path)) => {
-                    println!("[InfoExtractor] Path (reg): {:?}", reg_path);
-                    user_info.wx_files_path = Some(reg_path.clone());
-                    if let Some(id) = &user_info.wxid { user_info.wx_user_db_path = Some(reg_path.join(id)); }
+    match get_wxid_from_memory(pid) {
+        Ok(Some(wxid_val)) => { debug!("WxID (mem): {}", wxid_val); user_info.wxid = Some(wxid_val); },
+        _ => debug!("WxID not found via memory search."),
+    }
+
+    let mut memory_search_attempted_for_path = false;
+    match get_wechat_files_path_from_registry()? {
+        Some(reg_path) => {
+            debug!("Path (reg): {:?}", reg_path);
+            user_info.wx_files_path = Some(reg_path.clone());
+            if let Some(id) = &user_info.wxid { user_info.wx_user_db_path = Some(reg_path.join(id)); }
+        }
+        None => {
+            debug!("Path not in registry. Trying memory.");
+            memory_search_attempted_for_path = true;
+            if let Some(id) = &user_info.wxid {
+                match get_wechat_files_path_from_memory(pid, id)? {
+                    Some(mem_path) => {
+                        debug!("Path (mem): {:?}", mem_path);
+                        user_info.wx_files_path = Some(mem_path.clone());
+                        user_info.wx_user_db_path = Some(mem_path.join(id));
+                    }
+                    None => debug!("Path not found via memory search."),
                 }
-                _ => { // Ok(None) or Err
-                    println!("[InfoExtractor] Path not in registry or error. Trying memory.");
-                    memory_search_attempted_for_path = true;
-                    if let Some(id) = &user_info.wxid {
-                        match get_wechat_files_path_from_memory(process.pid, id) {
-                            Ok(Some(mem_path)) => {
-                                println!("[InfoExtractor] Path (mem): {:?}", mem_path);
-                                user_info.wx_files_path = Some(mem_path.clone());
-                                user_info.wx_user_db_path = Some(mem_path.join(id));
-                            }
-                            _ => println!("[InfoExtractor] Path not found via memory search."),
-                        }
-                    } else { println!("[InfoExtractor] No WxID to search path in memory."); }
+            } else { debug!("No WxID to search path in memory."); }
+        }
+    }
+    if !memory_search_attempted_for_path && user_info.wx_user_db_path.as_ref().map_or(true, |p| !p.exists()) {
+        debug!("Registry path for user DB invalid or not found. Trying memory for path.");
+        if let Some(id) = &user_info.wxid {
+            match get_wechat_files_path_from_memory(pid, id)? {
+                Some(mem_path) => {
+                    debug!("Path (mem fallback): {:?}", mem_path);
+                    user_info.wx_files_path = Some(mem_path.clone());
+                    user_info.wx_user_db_path = Some(mem_path.join(id));
                 }
+                None => debug!("Path not found via memory search (fallback)."),
             }
-            if !memory_search_attempted_for_path && user_info.wx_user_db_path.as_ref().map_or(true, |p| !p.exists()) {
-                 println!("[InfoExtractor] Registry path for user DB invalid or not found. Trying memory for path.");
-                 if let Some(id) = &user_info.wxid {
-                        match get_wechat_files_path_from_memory(process.pid, id) {
-                            Ok(Some(mem_path)) => {
-                                println!("[InfoExtractor] Path (mem fallback): {:?}", mem_path);
-                                user_info.wx_files_path = Some(mem_path.clone());
-                                user_info.wx_user_db_path = Some(mem_path.join(id));
-                            }
-                            _ => println!("[InfoExtractor] Path not found via memory search (fallback)."),
-                        }
-                    } else { println!("[InfoExtractor] No WxID to search path in memory (fallback)."); }
-            }
+        } else { debug!("No WxID to search path in memory (fallback)."); }
+    }
+
+    if user_info.wx_user_db_path.is_some() {
+        debug!("User DB Path: {:?}", user_info.wx_user_db_path.as_ref().unwrap());
+    } else {
+        debug!("User DB Path could not be determined.");
+    }
 
+    let mut key_from_offset_method: Option<String> = None;
+    let expected_key_str = "ef135b887201452c9301f7ff774d83ce34852ab7f68844bfaae485b233626fe6";
 
-            if user_info.wx_user_db_path.is_some() {
-                 println!("[InfoExtractor] User DB Path: {:?}", user_info.wx_user_db_path.as_ref().unwrap());
-            } else {
-                 println!("[InfoExtractor] User DB Path could not be determined.");
+    if let Some(v_offsets) = version_offsets {
+        if v_offsets.len() > 4 && v_offsets[4] != 0 {
+            match read_key_via_pointer_offset(pid, base_addr, v_offsets[4], arch_size) {
+                Ok(k) => { debug!("Key (offset): {}", redact_key(&k)); key_from_offset_method = Some(k); },
+                Err(e) => warn!("Failed key (offset): {}", e),
             }
-            
-            let mut key_from_offset_method: Option<String> = None;
-            let mut key_from_memory_search_method: Option<String> = None;
-            let expected_key_str = "ef135b887201452c9301f7ff774d83ce34852ab7f68844bfaae485b233626fe6";
-
-            if let (Some(base_addr), Some(ptr_size)) = (dll_base_address_opt, pointer_size_opt) {
-                if let Some(v_offsets) = loaded_offsets.get(&version) {
-                    if v_offsets.len() > 4 && v_offsets[4] != 0 {
-                        match read_key_via_pointer_offset(process.pid, base_addr, v_offsets[4], ptr_size) {
-                            Ok(k) => { println!("[InfoExtractor] Key (offset): {}", k); key_from_offset_method = Some(k); },
-                            Err(e) => eprintln!("[InfoExtractor] Failed key (offset): {}", e),
-                        }
-                    } else { println!("[InfoExtractor] Key offset invalid or 0."); }
-                } else { println!("[InfoExtractor] No offsets for key.");}
-                
-                match get_key_from_memory_search(process.pid, ptr_size) {
-                    Ok(Some(mk)) => { println!("[InfoExtractor] Key (mem): {}", mk); key_from_memory_search_method = Some(mk); },
-                    _ => println!("[InfoExtractor] Key not found (mem)."),
-                }
-            } else { println!("[InfoExtractor] No DLL base/ptr size for key methods."); }
+        } else { debug!("Key offset invalid or 0."); }
+    }
 
-            let mut final_key_source = "None";
-            if let Some(mem_k) = &key_from_memory_search_method {
-                if mem_k == expected_key_str {
-                    user_info.key = Some(mem_k.clone()); final_key_source = "Memory (Matches Expected)";
-                } else {
-                    if let Some(offset_k) = &key_from_offset_method {
-                        if offset_k == expected_key_str {
-                            user_info.key = Some(offset_k.clone()); final_key_source = "Offset (Matches Expected)";
-                        } else { user_info.key = Some(mem_k.clone()); final_key_source = "Memory (No Match, Fallback)"; }
-                    } else { user_info.key = Some(mem_k.clone()); final_key_source = "Memory (No Match, Offset Missing)"; }
-                }
-            } else if let Some(offset_k) = &key_from_offset_method {
+    let key_from_memory_search_method = match get_key_from_memory_search(pid, arch_size) {
+        Ok(Some(mk)) => { debug!("Key (mem): {}", redact_key(&mk)); Some(mk) },
+        Ok(None) => { debug!("Key not found (mem)."); None },
+        Err(e) => { warn!("Key memory search failed: {}", e); None },
+    };
+
+    let mut final_key_source = "None";
+    if let Some(mem_k) = &key_from_memory_search_method {
+        if mem_k == expected_key_str {
+            user_info.key = Some(mem_k.clone()); final_key_source = "Memory (Matches Expected)";
+        } else {
+            if let Some(offset_k) = &key_from_offset_method {
                 if offset_k == expected_key_str {
-                    user_info.key = Some(offset_k.clone()); final_key_source = "Offset (Matches Expected, Mem Failed)";
-                } else { user_info.key = Some(offset_k.clone()); final_key_source = "Offset (No Match, Mem Failed)"; }
-            }
-            println!("[InfoExtractor] Final key for PID {}: {:?} (Source: {})", process.pid, user_info.key, final_key_source);
-            
-            all_user_info.push(user_info);
-        } 
-    } 
-    if all_user_info.is_empty() { println!("[InfoExtractor] No WeChat.exe processes found."); }
-    Ok(all_user_info)
+                    user_info.key = Some(offset_k.clone()); final_key_source = "Offset (Matches Expected)";
+                } else { user_info.key = Some(mem_k.clone()); final_key_source = "Memory (No Match, Fallback)"; }
+            } else { user_info.key = Some(mem_k.clone()); final_key_source = "Memory (No Match, Offset Missing)"; }
+        }
+    } else if let Some(offset_k) = &key_from_offset_method {
+        if offset_k == expected_key_str {
+            user_info.key = Some(offset_k.clone()); final_key_source = "Offset (Matches Expected, Mem Failed)";
+        } else { user_info.key = Some(offset_k.clone()); final_key_source = "Offset (No Match, Mem Failed)"; }
+    }
+    info!("Final key for PID {}: {:?} (Source: {})", pid, user_info.key.as_deref().map(redact_key), final_key_source);
+
+    if user_info.key.is_none() {
+        // Without offsets the memory search was the only way; say so rather than "not found".
+        return Err(match version_offsets {
+            None => WxDumpError::OffsetsMissing { version },
+            Some(_) => WxDumpError::KeyNotFound { pid },
+        });
+    }
+    Ok(user_info)
 }
 
 fn read_direct_string_from_offset(pid: u32, dll_base_address: usize, offset: isize, max_len: usize) -> Result<String> {
@@ -406,6 +423,6 @@ fn get_wxid_from_memory(pid: u32) -> Result<Option<String>> {
             }
             Ok(None)
         }
-        Err(e) => { eprintln!("[InfoExtractor:get_wxid] Error: {}", e); Ok(None) }
+        Err(e) => { warn!("Error searching memory for wxid: {}", e); Ok(None) }
     }
 }
\ No newline at end of file
@@ -0,0 +1,106 @@
+// src/core/logging.rs
+
+use anyhow::{Result, anyhow};
+use log::{LevelFilter, Log, Metadata, Record};
+use std::fs::File;
+use std::io::Write;
+use std::path::Path;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static REVEAL_KEYS: AtomicBool = AtomicBool::new(false);
+
+/// Lets [`redact_key`] return keys unchanged (CLI `--log-keys`).
+pub fn set_reveal_keys(reveal: bool) {
+    REVEAL_KEYS.store(reveal, Ordering::Relaxed);
+}
+
+/// How keys appear in diagnostics: the first 4 characters and the length, unless revealing
+/// was switched on with [`set_reveal_keys`].
+pub fn redact_key(key: &str) -> String {
+    if REVEAL_KEYS.load(Ordering::Relaxed) || key.is_empty() {
+        return key.to_string();
+    }
+    let head: String = key.chars().take(4).collect();
+    format!("{}…({} chars)", head, key.chars().count())
+}
+
+/// Level for `-v`/`-q` counts: info by default, each `-v` one step more verbose (debug, trace),
+/// each `-q` one step quieter (warn, error, off).
+pub fn level_from_verbosity(verbose: u8, quiet: u8) -> LevelFilter {
+    const LEVELS: [LevelFilter; 6] =
+        [LevelFilter::Off, LevelFilter::Error, LevelFilter::Warn, LevelFilter::Info, LevelFilter::Debug, LevelFilter::Trace];
+    let index = (3 + verbose as i32 - quiet as i32).clamp(0, LEVELS.len() as i32 - 1);
+    LEVELS[index as usize]
+}
+
+/// Writes log records to stderr (never stdout, which carries command output) and, when
+/// given, appends them to a log file with a timestamp.
+struct CliLogger {
+    level: LevelFilter,
+    file: Option<Mutex<File>>,
+}
+
+impl Log for CliLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= self.level
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        let target = record.target().strip_prefix("wxdump_rs::").unwrap_or(record.target());
+        let line = format!("[{} {}] {}", record.level(), target, record.args());
+        eprintln!("{}", line);
+        if let Some(file) = &self.file {
+            if let Ok(mut file) = file.lock() {
+                let _ = writeln!(file, "{} {}", chrono::Local::now().format("%Y-%m-%d %H:%M:%S"), line);
+            }
+        }
+    }
+
+    fn flush(&self) {
+        if let Some(file) = &self.file {
+            if let Ok(mut file) = file.lock() {
+                let _ = file.flush();
+            }
+        }
+    }
+}
+
+/// Installs the CLI logger. Library code only uses the `log` macros, so embedders can install
+/// any other logger instead.
+pub fn init(level: LevelFilter, log_file: Option<&Path>) -> Result<()> {
+    let file = log_file
+        .map(|path| {
+            std::fs::OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(path)
+                .map_err(|e| anyhow!("Failed to open log file {:?}: {}", path, e))
+        })
+        .transpose()?;
+    log::set_boxed_logger(Box::new(CliLogger { level, file: file.map(Mutex::new) }))
+        .map_err(|e| anyhow!("Logger already installed: {}", e))?;
+    log::set_max_level(level);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_redact_and_levels() {
+        let key = "ef135b887201452c9301f7ff774d83ce34852ab7f68844bfaae485b233626fe6";
+        assert_eq!(redact_key(key), "ef13…(64 chars)");
+        assert_eq!(redact_key(""), "");
+
+        assert_eq!(level_from_verbosity(0, 0), LevelFilter::Info);
+        assert_eq!(level_from_verbosity(1, 0), LevelFilter::Debug);
+        assert_eq!(level_from_verbosity(5, 0), LevelFilter::Trace);
+        assert_eq!(level_from_verbosity(0, 1), LevelFilter::Warn);
+        assert_eq!(level_from_verbosity(0, 9), LevelFilter::Off);
+    }
+}
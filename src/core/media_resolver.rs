@@ -0,0 +1,310 @@
+// src/core/media_resolver.rs
+
+use chrono::{DateTime, Local};
+use md5::{Digest, Md5};
+use std::path::{Component, Path, PathBuf};
+
+use super::db_parser::msg_parser::{
+    app_msg_xml, parse_app_msg, parse_bytes_extra, Message, MSG_TYPE_APP, MSG_TYPE_IMAGE, MSG_TYPE_VIDEO,
+};
+
+/// `<appmsg><type>` of a file attachment.
+const APP_MSG_TYPE_FILE: i64 = 6;
+
+/// Largest distance of a time zone from UTC (UTC+14), for the month folders of [`month_dirs`].
+const MAX_UTC_OFFSET_SECS: i64 = 14 * 3600;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MediaKind {
+    Image,
+    Video,
+    File,
+}
+
+/// Where the media of a message lives on disk.
+#[derive(Debug, Clone)]
+pub struct MediaRef {
+    pub kind: MediaKind,
+    pub path: Option<PathBuf>,       // The .dat image, .mp4 or attached file
+    pub thumb_path: Option<PathBuf>, // Thumbnail (.dat for images, .jpg for videos)
+    pub exists: bool,                // Whether `path` exists under the account root
+}
+
+/// Maps messages to files under an account folder (`WeChat Files/<wxid>`, the `wx_path` of `DbShow`).
+///
+/// Layout used by WeChat 3.x:
+/// - images: `FileStorage/MsgAttach/<md5(talker)>/Image/YYYY-MM/<name>.dat` (+ `Thumb/...`)
+/// - videos: `FileStorage/Video/YYYY-MM/<name>.mp4` (+ `<name>.jpg` thumbnail)
+/// - files:  `FileStorage/File/YYYY-MM/<title>`
+///
+/// `YYYY-MM` is the month in the local time of the machine WeChat ran on.
+#[derive(Debug, Clone)]
+pub struct MediaResolver {
+    account_root: PathBuf,
+}
+
+impl MediaResolver {
+    pub fn new(account_root: impl Into<PathBuf>) -> Self {
+        MediaResolver { account_root: account_root.into() }
+    }
+
+    pub fn account_root(&self) -> &Path {
+        &self.account_root
+    }
+
+    /// `FileStorage/MsgAttach/<md5(talker)>`, the attachment folder of one conversation.
+    pub fn msg_attach_dir(&self, talker: &str) -> PathBuf {
+        let digest = hex::encode(Md5::digest(talker.as_bytes()));
+        self.account_root.join("FileStorage").join("MsgAttach").join(digest)
+    }
+
+    /// Turns a path as stored in BytesExtra (`wxid_xxx\FileStorage\...`, relative to
+    /// "WeChat Files", backslash separated) into a path under the account root.
+    ///
+    /// Returns `None` for paths that could leave the account root: `.`/`..` segments and drive
+    /// letters (absolute Windows paths, e.g. files saved outside "WeChat Files").
+    pub fn resolve_stored_path(&self, stored: &str) -> Option<PathBuf> {
+        let parts: Vec<&str> = stored.split(['\\', '/']).filter(|p| !p.is_empty()).collect();
+        if parts.iter().any(|p| *p == "." || *p == ".." || p.contains(':')) {
+            return None;
+        }
+
+        let root_name = self.account_root.file_name().map(|n| n.to_string_lossy().into_owned());
+        let start = parts
+            .iter()
+            .position(|p| *p == "FileStorage")
+            .unwrap_or_else(|| usize::from(parts.first().map(|p| p.to_string()) == root_name));
+
+        let mut path = self.account_root.clone();
+        for part in &parts[start..] {
+            path.push(part);
+        }
+        Some(path)
+    }
+
+    /// Resolves image (3), video (43) and file (49/6) messages. Returns `None` for other kinds.
+    pub fn resolve(&self, msg: &Message) -> Option<MediaRef> {
+        let kind = match msg.msg_type {
+            MSG_TYPE_IMAGE => MediaKind::Image,
+            MSG_TYPE_VIDEO => MediaKind::Video,
+            MSG_TYPE_APP if self.app_msg_type(msg) == Some(APP_MSG_TYPE_FILE) => MediaKind::File,
+            _ => return None,
+        };
+
+        let extra = parse_bytes_extra(msg.bytes_extra.as_deref()).ok().flatten().unwrap_or_default();
+        let mut path = extra.file_path.as_deref().and_then(|p| self.resolve_stored_path(p));
+        let thumb_path = extra.thumb_path.as_deref().and_then(|p| self.resolve_stored_path(p));
+
+        if path.is_none() {
+            path = match kind {
+                // Videos sit next to their .jpg thumbnail.
+                MediaKind::Video => thumb_path.as_ref().map(|t| t.with_extension("mp4")),
+                MediaKind::File => self.app_msg_title(msg).filter(|t| is_plain_file_name(t)).and_then(|title| {
+                    let dir = self.account_root.join("FileStorage").join("File");
+                    first_existing(month_dirs(msg.create_time).iter().map(|month| dir.join(month).join(&title)))
+                }),
+                MediaKind::Image => self.find_image_dat(msg, thumb_path.as_deref()),
+            };
+        }
+
+        let exists = path.as_ref().is_some_and(|p| p.exists());
+        Some(MediaRef { kind, path, thumb_path, exists })
+    }
+
+    /// The `.dat` of an image message without a BytesExtra path, in
+    /// `MsgAttach/<md5(talker)>/Image/YYYY-MM/`: named like its thumbnail (without `_t`), by the
+    /// `md5` of the `<img>` element, or by MsgSvrID. The first name is returned when none exists.
+    fn find_image_dat(&self, msg: &Message, thumb_path: Option<&Path>) -> Option<PathBuf> {
+        let image_dir = &self.msg_attach_dir(&msg.talker).join("Image");
+        let mut names: Vec<String> = Vec::new();
+        if let Some(stem) = thumb_path.and_then(|t| t.file_stem()).map(|s| s.to_string_lossy().into_owned()) {
+            names.push(stem.strip_suffix("_t").unwrap_or(&stem).to_string());
+        }
+        if let Some(md5) = msg.content.as_deref().and_then(image_md5) {
+            names.push(md5);
+        }
+        if let Some(svr_id) = msg.msg_svr_id {
+            names.push(svr_id.to_string());
+        }
+        let months = month_dirs(msg.create_time);
+        first_existing(
+            months.iter().flat_map(|month| names.iter().map(move |name| image_dir.join(month).join(format!("{}.dat", name)))),
+        )
+    }
+
+    fn app_msg_type(&self, msg: &Message) -> Option<i64> {
+        app_msg_xml(msg).and_then(|xml| parse_app_msg(&xml)).and_then(|a| a.app_type)
+    }
+
+    fn app_msg_title(&self, msg: &Message) -> Option<String> {
+        app_msg_xml(msg).and_then(|xml| parse_app_msg(&xml)).and_then(|a| a.title)
+    }
+}
+
+/// `YYYY-MM` folders that may hold the media of a message sent at `create_time`: the month in
+/// local time first, then the months it falls in elsewhere (near a month boundary the machine
+/// WeChat ran on may have been in another time zone).
+fn month_dirs(create_time: i64) -> Vec<String> {
+    let Some(utc) = DateTime::from_timestamp(create_time, 0) else { return Vec::new() };
+    let mut months = vec![utc.with_timezone(&Local).format("%Y-%m").to_string()];
+    for secs in [-MAX_UTC_OFFSET_SECS, 0, MAX_UTC_OFFSET_SECS] {
+        if let Some(month) = DateTime::from_timestamp(create_time + secs, 0).map(|t| t.format("%Y-%m").to_string()) {
+            if !months.contains(&month) {
+                months.push(month);
+            }
+        }
+    }
+    months
+}
+
+/// The first of `candidates` that exists, otherwise the first one.
+fn first_existing(candidates: impl IntoIterator<Item = PathBuf>) -> Option<PathBuf> {
+    let mut first = None;
+    for path in candidates {
+        if path.exists() {
+            return Some(path);
+        }
+        first.get_or_insert(path);
+    }
+    first
+}
+
+/// Whether a name taken from message XML is a bare file name: one normal path component, with
+/// no separator of either platform.
+fn is_plain_file_name(name: &str) -> bool {
+    let mut components = Path::new(name).components();
+    !name.contains(['/', '\\'])
+        && matches!(components.next(), Some(Component::Normal(_)))
+        && components.next().is_none()
+}
+
+/// The `md5` attribute of `<msg><img md5="..."/></msg>`, if it is hex.
+fn image_md5(content: &str) -> Option<String> {
+    let doc = roxmltree::Document::parse(content.trim()).ok()?;
+    let md5 = doc.descendants().find(|n| n.has_tag_name("img"))?.attribute("md5")?;
+    (!md5.is_empty() && md5.bytes().all(|b| b.is_ascii_hexdigit())).then(|| md5.to_lowercase())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resolve_stored_path() {
+        let resolver = MediaResolver::new("/data/WeChat Files/wxid_me");
+        let expected = Path::new("/data/WeChat Files/wxid_me/FileStorage/MsgAttach/abc/Image/2023-04/x.dat");
+        let resolve = |stored: &str| resolver.resolve_stored_path(stored);
+        assert_eq!(resolve("wxid_me\\FileStorage\\MsgAttach\\abc\\Image\\2023-04\\x.dat").as_deref(), Some(expected));
+        // Account folder renamed after copying: anchor on FileStorage instead.
+        assert_eq!(resolve("wxid_other\\FileStorage\\MsgAttach\\abc\\Image\\2023-04\\x.dat").as_deref(), Some(expected));
+        assert_eq!(resolve("/FileStorage/File/a.txt").unwrap(), Path::new("/data/WeChat Files/wxid_me/FileStorage/File/a.txt"));
+
+        for stored in [
+            "C:\\Users\\me\\a.txt",
+            "wxid_me\\FileStorage\\..\\..\\..\\secret",
+            "wxid_me\\FileStorage\\File\\2023-04\\..\\..\\..\\..\\secret",
+            "..\\wxid_other\\config.data",
+            "wxid_me\\FileStorage\\.\\File\\a.txt",
+            "wxid_me\\FileStorage\\File\\D:a.txt",
+        ] {
+            assert!(resolve(stored).is_none(), "{}", stored);
+        }
+    }
+
+    #[test]
+    fn test_msg_attach_dir_uses_md5_of_talker() {
+        let resolver = MediaResolver::new("/root");
+        let dir = resolver.msg_attach_dir("wxid_test");
+        let name = dir.file_name().unwrap().to_string_lossy().into_owned();
+        assert_eq!(name.len(), 32);
+        assert_eq!(name, hex::encode(Md5::digest(b"wxid_test")));
+        assert!(dir.ends_with(Path::new("FileStorage/MsgAttach").join(&name)));
+    }
+
+    #[test]
+    fn test_resolve_file_message_falls_back_to_title() {
+        let resolver = MediaResolver::new("/nonexistent/wxid_me");
+        let msg = Message {
+            msg_type: MSG_TYPE_APP,
+            create_time: 1_681_560_000, // 2023-04-15 12:00 UTC
+            content: Some("<msg><appmsg><title>report.pdf</title><type>6</type></appmsg></msg>".to_string()),
+            ..Default::default()
+        };
+        let media = resolver.resolve(&msg).unwrap();
+        assert_eq!(media.kind, MediaKind::File);
+        assert_eq!(media.path.unwrap(), Path::new("/nonexistent/wxid_me/FileStorage/File/2023-04/report.pdf"));
+        assert!(!media.exists);
+
+        for title in ["../../../../.ssh/id_rsa", "/etc/passwd", "..", "a\\..\\..\\b.txt"] {
+            let content = format!("<msg><appmsg><title>{}</title><type>6</type></appmsg></msg>", title);
+            let crafted = Message { content: Some(content), ..msg.clone() };
+            assert!(resolver.resolve(&crafted).unwrap().path.is_none(), "{}", title);
+        }
+
+        // A BytesExtra path leaving the account folder is dropped in favour of the title.
+        for stored in ["wxid_me\\FileStorage\\..\\..\\..\\secret", "C:\\Users\\me\\secret"] {
+            let extra = crate::core::testutil::bytes_extra(&[(3, stored), (4, stored)]);
+            let crafted = Message { bytes_extra: Some(extra), ..msg.clone() };
+            let media = resolver.resolve(&crafted).unwrap();
+            assert_eq!(media.path.unwrap(), Path::new("/nonexistent/wxid_me/FileStorage/File/2023-04/report.pdf"));
+            assert!(media.thumb_path.is_none(), "{}", stored);
+        }
+
+        let text = Message { msg_type: 1, ..Default::default() };
+        assert!(resolver.resolve(&text).is_none());
+    }
+
+    #[test]
+    fn test_resolve_file_in_neighbouring_month() {
+        // 2023-04-30 20:00 UTC is already May for a WeChat running in UTC+8.
+        let root = std::env::temp_dir().join(format!("wxdump_rs_file_month_{}", std::process::id()));
+        let dir = root.join("FileStorage").join("File").join("2023-05");
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("report.pdf"), b"%PDF").unwrap();
+
+        let msg = Message {
+            msg_type: MSG_TYPE_APP,
+            create_time: 1_682_884_800,
+            content: Some("<msg><appmsg><title>report.pdf</title><type>6</type></appmsg></msg>".to_string()),
+            ..Default::default()
+        };
+        let media = MediaResolver::new(&root).resolve(&msg).unwrap();
+        assert_eq!(media.path.as_deref(), Some(dir.join("report.pdf").as_path()));
+        assert!(media.exists);
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn test_month_dirs() {
+        let months = month_dirs(1_682_884_800);
+        assert!(months.contains(&"2023-04".to_string()) && months.contains(&"2023-05".to_string()));
+        assert_eq!(month_dirs(1_681_560_000), vec!["2023-04".to_string()]);
+    }
+
+    #[test]
+    fn test_resolve_image_in_msg_attach() {
+        let root = std::env::temp_dir().join(format!("wxdump_rs_msg_attach_{}", std::process::id()));
+        let resolver = MediaResolver::new(&root);
+        let dir = resolver.msg_attach_dir("wxid_carol").join("Image").join("2023-04");
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("3003.dat"), b"dat").unwrap();
+
+        let msg = Message {
+            msg_type: MSG_TYPE_IMAGE,
+            msg_svr_id: Some(3003),
+            create_time: 1_681_560_000,
+            talker: "wxid_carol".into(),
+            content: Some("<msg><img length=\"1024\" md5=\"ABC\" /></msg>".into()),
+            ..Default::default()
+        };
+        let media = resolver.resolve(&msg).unwrap();
+        assert_eq!(media.path.as_deref(), Some(dir.join("3003.dat").as_path()));
+        assert!(media.exists);
+
+        let missing = Message { msg_svr_id: Some(9), ..msg };
+        let media = resolver.resolve(&missing).unwrap();
+        assert_eq!(media.path.as_deref(), Some(dir.join("abc.dat").as_path()));
+        assert!(!media.exists);
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
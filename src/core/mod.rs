@@ -1,7 +1,23 @@
 // src/core/mod.rs
 
 pub mod offsets;
+#[cfg(windows)]
 pub mod win_api;
+#[cfg(windows)]
 pub mod info_extractor;
 pub mod db_parser;
-pub mod decryption; // Added this line
\ No newline at end of file
+pub mod decryption; // Added this line
+pub mod silk;
+pub mod image_decode;
+pub mod protobuf;
+pub mod media_resolver;
+pub mod html;
+pub mod export;
+pub mod db_browser;
+pub mod search;
+pub mod account;
+pub mod analytics;
+pub mod error;
+pub mod logging;
+#[cfg(any(test, feature = "testutil"))]
+pub mod testutil;
\ No newline at end of file
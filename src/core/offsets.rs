@@ -73,7 +73,7 @@ pub fn load_wx_offsets() -> Result<WxOffsets> {
     let wx_offs_path_str = find_wx_offs_json()
         .ok_or_else(|| anyhow!("{} not found in standard locations.", WX_OFFS_FILE_NAME))?;
     
-    println!("[Offsets] Found {} at: {}", WX_OFFS_FILE_NAME, wx_offs_path_str);
+    log::debug!("Found {} at: {}", WX_OFFS_FILE_NAME, wx_offs_path_str);
 
     let file_content = fs::read_to_string(&wx_offs_path_str)
         .map_err(|e| anyhow!("Failed to read {}: {}", wx_offs_path_str, e))?;
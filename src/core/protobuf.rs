@@ -0,0 +1,106 @@
+// src/core/protobuf.rs
+
+// Minimal schema-less protobuf wire-format reader, standing in for Python's
+// blackboxprotobuf when decoding BytesExtra, RoomData and similar blobs.
+
+use anyhow::{Result, anyhow};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProtoValue<'a> {
+    Varint(u64),
+    Fixed64(u64),
+    Bytes(&'a [u8]),
+    Fixed32(u32),
+}
+
+impl<'a> ProtoValue<'a> {
+    pub fn as_u64(&self) -> Option<u64> {
+        match *self {
+            ProtoValue::Varint(v) | ProtoValue::Fixed64(v) => Some(v),
+            ProtoValue::Fixed32(v) => Some(v as u64),
+            ProtoValue::Bytes(_) => None,
+        }
+    }
+
+    pub fn as_bytes(&self) -> Option<&'a [u8]> {
+        match *self {
+            ProtoValue::Bytes(b) => Some(b),
+            _ => None,
+        }
+    }
+
+    /// Length-delimited value interpreted as UTF-8 (lossy).
+    pub fn as_string(&self) -> Option<String> {
+        self.as_bytes().map(|b| String::from_utf8_lossy(b).into_owned())
+    }
+}
+
+fn read_varint(data: &[u8], pos: &mut usize) -> Result<u64> {
+    let mut result: u64 = 0;
+    let mut shift = 0;
+    loop {
+        let byte = *data.get(*pos).ok_or_else(|| anyhow!("Truncated varint at offset {}", *pos))?;
+        *pos += 1;
+        if shift >= 64 {
+            return Err(anyhow!("Varint too long at offset {}", *pos));
+        }
+        result |= ((byte & 0x7F) as u64) << shift;
+        if byte & 0x80 == 0 {
+            return Ok(result);
+        }
+        shift += 7;
+    }
+}
+
+/// Parses one message level into `(field_number, value)` pairs, in wire order.
+/// Nested messages are left as `Bytes`; call `parse_message` again on them.
+pub fn parse_message(data: &[u8]) -> Result<Vec<(u32, ProtoValue<'_>)>> {
+    let mut fields = Vec::new();
+    let mut pos = 0;
+    while pos < data.len() {
+        let key = read_varint(data, &mut pos)?;
+        let field_number = (key >> 3) as u32;
+        let value = match key & 0x07 {
+            0 => ProtoValue::Varint(read_varint(data, &mut pos)?),
+            1 => {
+                let bytes = data.get(pos..pos + 8).ok_or_else(|| anyhow!("Truncated fixed64 field {}", field_number))?;
+                pos += 8;
+                ProtoValue::Fixed64(u64::from_le_bytes(bytes.try_into().unwrap()))
+            }
+            2 => {
+                let len = read_varint(data, &mut pos)? as usize;
+                let end = pos.checked_add(len).filter(|&e| e <= data.len())
+                    .ok_or_else(|| anyhow!("Truncated length-delimited field {}", field_number))?;
+                let bytes = &data[pos..end];
+                pos = end;
+                ProtoValue::Bytes(bytes)
+            }
+            5 => {
+                let bytes = data.get(pos..pos + 4).ok_or_else(|| anyhow!("Truncated fixed32 field {}", field_number))?;
+                pos += 4;
+                ProtoValue::Fixed32(u32::from_le_bytes(bytes.try_into().unwrap()))
+            }
+            wire_type => return Err(anyhow!("Unsupported wire type {} for field {}", wire_type, field_number)),
+        };
+        fields.push((field_number, value));
+    }
+    Ok(fields)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_message() {
+        // field 1 varint 150, field 2 string "hi", field 3 nested { field 1 varint 1 }
+        let data = [0x08, 0x96, 0x01, 0x12, 0x02, b'h', b'i', 0x1A, 0x02, 0x08, 0x01];
+        let fields = parse_message(&data).unwrap();
+        assert_eq!(fields[0], (1, ProtoValue::Varint(150)));
+        assert_eq!(fields[1].1.as_string().as_deref(), Some("hi"));
+        let nested = parse_message(fields[2].1.as_bytes().unwrap()).unwrap();
+        assert_eq!(nested, vec![(1, ProtoValue::Varint(1))]);
+
+        assert!(parse_message(&[0x12, 0x05, b'a']).is_err());
+    }
+}
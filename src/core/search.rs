@@ -0,0 +1,264 @@
+// src/core/search.rs
+
+use anyhow::Result;
+use rusqlite::{params, Connection, OptionalExtension};
+use std::path::Path;
+
+use crate::core::db_parser::{
+    fts_index_names, for_each_message, get_messages_by_local_ids, get_messages_by_svr_ids,
+    like_pattern, search_fts, Message,
+};
+use crate::core::export::message_text;
+
+/// FTS5 terms shorter than this cannot use the trigram index and are matched with LIKE.
+const TRIGRAM_LEN: usize = 3;
+
+/// Number of messages, newest localId and newest CreateTime of the MSG table.
+fn msg_state(msg_conn: &Connection) -> Result<(i64, i64, i64)> {
+    Ok(msg_conn.query_row(
+        "SELECT COUNT(*), COALESCE(MAX(localId), 0), COALESCE(MAX(CreateTime), 0) FROM MSG",
+        [],
+        |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+    )?)
+}
+
+/// Where `search_messages` found its results.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SearchSource {
+    /// WeChat's FTSMSG.db.
+    WeChatFts,
+    /// Our own `SearchIndex`.
+    LocalIndex,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct SearchOptions {
+    pub talker: Option<String>,
+    /// Inclusive unix timestamp.
+    pub since: Option<i64>,
+    pub limit: usize,
+}
+
+/// Full-text index over decoded message text (see `message_text`), for when FTSMSG.db
+/// is missing or cannot be read.
+///
+/// It lives in its own SQLite file next to the MSG database and uses the FTS5 trigram
+/// tokenizer, which also finds Chinese text that has no word boundaries.
+pub struct SearchIndex {
+    conn: Connection,
+}
+
+impl SearchIndex {
+    /// Opens (creating it if needed) the index file at `path`.
+    pub fn open(path: &Path) -> Result<Self> {
+        Self::from_connection(Connection::open(path)?)
+    }
+
+    /// An index that only lives as long as it is used, for databases read with a key: the
+    /// plaintext of their messages must not end up on disk.
+    pub fn open_in_memory() -> Result<Self> {
+        Self::from_connection(Connection::open_in_memory()?)
+    }
+
+    pub fn from_connection(conn: Connection) -> Result<Self> {
+        conn.execute_batch(
+            "CREATE VIRTUAL TABLE IF NOT EXISTS msg_fts USING fts5(
+                 text, talker UNINDEXED, create_time UNINDEXED, local_id UNINDEXED, tokenize = 'trigram');
+             CREATE TABLE IF NOT EXISTS index_meta (key TEXT PRIMARY KEY, value INTEGER);",
+        )?;
+        Ok(SearchIndex { conn })
+    }
+
+    /// Number of messages the index was last built from, None if it never was.
+    pub fn indexed_count(&self) -> Result<Option<i64>> {
+        Ok(self
+            .conn
+            .query_row("SELECT value FROM index_meta WHERE key = 'messages'", [], |row| row.get(0))
+            .optional()?)
+    }
+
+    /// True when the index is missing or MSG changed since it was built: another number of
+    /// messages, or another newest localId or CreateTime (messages deleted and others added).
+    pub fn is_stale(&self, msg_conn: &Connection) -> Result<bool> {
+        // Values are NULL when the index was never built (or by a version not recording them).
+        let indexed: (Option<i64>, Option<i64>, Option<i64>) = self.conn.query_row(
+            "SELECT (SELECT value FROM index_meta WHERE key = 'messages'),
+                    (SELECT value FROM index_meta WHERE key = 'max_local_id'),
+                    (SELECT value FROM index_meta WHERE key = 'max_create_time')",
+            [],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+        )?;
+        let (count, max_local_id, max_create_time) = msg_state(msg_conn)?;
+        Ok(indexed != (Some(count), Some(max_local_id), Some(max_create_time)))
+    }
+
+    /// Replaces the index content with every message of `msg_conn`. Returns the number indexed.
+    pub fn rebuild(&mut self, msg_conn: &Connection) -> Result<usize> {
+        let (_, max_local_id, max_create_time) = msg_state(msg_conn)?;
+        let tx = self.conn.transaction()?;
+        tx.execute("DELETE FROM msg_fts", [])?;
+        let count = {
+            let mut insert =
+                tx.prepare("INSERT INTO msg_fts (text, talker, create_time, local_id) VALUES (?, ?, ?, ?)")?;
+            for_each_message(msg_conn, None, None, None, None, None, None, |msg| {
+                insert.execute(params![message_text(&msg, None), msg.talker, msg.create_time, msg.local_id])?;
+                Ok(())
+            })?
+        };
+        tx.execute(
+            "INSERT OR REPLACE INTO index_meta (key, value) VALUES ('messages', ?), ('max_local_id', ?), ('max_create_time', ?)",
+            [count as i64, max_local_id, max_create_time],
+        )?;
+        tx.commit()?;
+        Ok(count)
+    }
+
+    /// localIds of the messages containing every whitespace separated term of `query`, newest first.
+    pub fn search(&self, query: &str, options: &SearchOptions) -> Result<Vec<i64>> {
+        let terms: Vec<&str> = query.split_whitespace().collect();
+        if terms.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let mut conditions: Vec<String> = Vec::new();
+        let mut params_list: Vec<Box<dyn rusqlite::ToSql>> = Vec::new();
+        let (long, short): (Vec<&str>, Vec<&str>) = terms.iter().partition(|t| t.chars().count() >= TRIGRAM_LEN);
+        if !long.is_empty() {
+            conditions.push("msg_fts MATCH ?".to_string());
+            let phrases: Vec<String> = long.iter().map(|t| format!("\"{}\"", t.replace('"', "\"\""))).collect();
+            params_list.push(Box::new(phrases.join(" AND ")));
+        }
+        for term in short {
+            conditions.push("text LIKE ? ESCAPE '\\'".to_string());
+            params_list.push(Box::new(like_pattern(term)));
+        }
+        if let Some(talker) = &options.talker {
+            conditions.push("talker = ?".to_string());
+            params_list.push(Box::new(talker.clone()));
+        }
+        if let Some(since) = options.since {
+            conditions.push("create_time >= ?".to_string());
+            params_list.push(Box::new(since));
+        }
+
+        let sql = format!(
+            "SELECT local_id FROM msg_fts WHERE {} ORDER BY create_time DESC, local_id DESC LIMIT ?;",
+            conditions.join(" AND ")
+        );
+        params_list.push(Box::new(options.limit as i64));
+        let params_for_query: Vec<&dyn rusqlite::ToSql> = params_list.iter().map(|p| p.as_ref()).collect();
+        let mut stmt = self.conn.prepare(&sql)?;
+        let ids = stmt.query_map(&*params_for_query, |row| row.get(0))?.collect::<std::result::Result<Vec<i64>, _>>()?;
+        Ok(ids)
+    }
+}
+
+/// Searches message history, newest first.
+///
+/// Uses `fts_conn` (FTSMSG.db) when it has FTSChatMsg tables and falls back to `index`
+/// otherwise. Hits are joined back to full `Message` rows of `msg_conn`; hits whose
+/// message is not in `msg_conn` are dropped.
+pub fn search_messages(
+    msg_conn: &Connection,
+    fts_conn: Option<&Connection>,
+    index: Option<&SearchIndex>,
+    query: &str,
+    options: &SearchOptions,
+) -> Result<(SearchSource, Vec<Message>)> {
+    if let Some(fts_conn) = fts_conn {
+        if !fts_index_names(fts_conn)?.is_empty() {
+            let hits = search_fts(fts_conn, query, options.talker.as_deref(), options.since, options.limit)?;
+            let svr_ids: Vec<i64> = hits.iter().map(|h| h.msg_svr_id).collect();
+            return Ok((SearchSource::WeChatFts, get_messages_by_svr_ids(msg_conn, &svr_ids)?));
+        }
+    }
+    match index {
+        Some(index) => {
+            let local_ids = index.search(query, options)?;
+            Ok((SearchSource::LocalIndex, get_messages_by_local_ids(msg_conn, &local_ids)?))
+        }
+        None => Err(anyhow::anyhow!("No FTS tables in FTSMSG.db and no local search index")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::testutil::merged_db_with_sample_data;
+
+    fn built_index(msg_conn: &Connection) -> SearchIndex {
+        let mut index = SearchIndex::from_connection(Connection::open_in_memory().unwrap()).unwrap();
+        assert!(index.is_stale(msg_conn).unwrap());
+        assert_eq!(index.rebuild(msg_conn).unwrap(), 6);
+        assert!(!index.is_stale(msg_conn).unwrap());
+        index
+    }
+
+    #[test]
+    fn test_index_stale_after_delete_and_insert() {
+        let conn = merged_db_with_sample_data();
+        let index = built_index(&conn);
+        // Same number of messages, but not the same ones.
+        conn.execute_batch(
+            "DELETE FROM MSG WHERE localId = (SELECT MIN(localId) FROM MSG);
+             INSERT INTO MSG (localId, TalkerId, MsgSvrID, Type, SubType, IsSender, CreateTime, StrTalker, StrContent)
+             SELECT MAX(localId) + 1, 1, 9001, 1, 0, 0, MAX(CreateTime) + 1, 'wxid_alice', 'new' FROM MSG;",
+        )
+        .unwrap();
+        assert!(index.is_stale(&conn).unwrap());
+    }
+
+    fn options(limit: usize) -> SearchOptions {
+        SearchOptions { limit, ..Default::default() }
+    }
+
+    #[test]
+    fn test_local_index_search() {
+        let conn = merged_db_with_sample_data();
+        let index = built_index(&conn);
+
+        let (source, messages) = search_messages(&conn, None, Some(&index), "hello", &options(10)).unwrap();
+        assert_eq!(source, SearchSource::LocalIndex);
+        let ids: Vec<_> = messages.iter().map(|m| m.msg_svr_id.unwrap()).collect();
+        assert_eq!(ids, vec![2002, 1002, 2001]);
+
+        // Decoded text is indexed, so quotes and placeholders are searchable; short terms use LIKE.
+        let (_, messages) = search_messages(&conn, None, Some(&index), "Quote", &options(10)).unwrap();
+        assert_eq!(messages[0].msg_svr_id, Some(2002));
+        let (_, messages) = search_messages(&conn, None, Some(&index), "撤回", &options(10)).unwrap();
+        assert_eq!(messages[0].msg_svr_id, Some(2003));
+
+        let filtered = SearchOptions { talker: Some("wxid_alice".to_string()), since: Some(1_700_000_010), limit: 10 };
+        let (_, messages) = search_messages(&conn, None, Some(&index), "hello", &filtered).unwrap();
+        assert_eq!(messages.len(), 1);
+        assert_eq!(messages[0].msg_svr_id, Some(1002));
+
+        let (_, messages) = search_messages(&conn, None, Some(&index), "hello", &options(1)).unwrap();
+        assert_eq!(messages.len(), 1);
+        assert!(search_messages(&conn, None, None, "hello", &options(1)).is_err());
+    }
+
+    #[test]
+    fn test_prefers_wechat_fts() {
+        let conn = merged_db_with_sample_data();
+        let fts = Connection::open_in_memory().unwrap();
+        fts.execute_batch(
+            "CREATE TABLE FTSChatMsg2_content (docid INTEGER PRIMARY KEY, c0content TEXT, c1entityId TEXT);
+             CREATE TABLE FTSChatMsg2_MetaData (docid INTEGER PRIMARY KEY, msgId INTEGER, entityId INTEGER,
+                 type INTEGER, subType INTEGER, CreateTime INTEGER);
+             INSERT INTO FTSChatMsg2_content VALUES (1, 'hi there', '1'), (2, 'hi ghost', '1');
+             INSERT INTO FTSChatMsg2_MetaData VALUES (1, 1001, 1, 1, 0, 1700000000), (2, 9999, 1, 1, 0, 1700000001);",
+        )
+        .unwrap();
+        let (source, messages) = search_messages(&conn, Some(&fts), None, "hi", &options(10)).unwrap();
+        assert_eq!(source, SearchSource::WeChatFts);
+        assert_eq!(messages.len(), 1);
+        assert_eq!(messages[0].content.as_deref(), Some("hi there"));
+
+        // An FTSMSG.db without index tables falls back to the local index.
+        let empty = Connection::open_in_memory().unwrap();
+        let index = built_index(&conn);
+        let (source, _) = search_messages(&conn, Some(&empty), Some(&index), "hi", &options(10)).unwrap();
+        assert_eq!(source, SearchSource::LocalIndex);
+    }
+}
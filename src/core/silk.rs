@@ -0,0 +1,209 @@
+// src/core/silk.rs
+
+// SILK v3 voice handling: splitting the container into packets, decoding to PCM
+// (behind the `silk` cargo feature, via the Skype SILK SDK) and writing PCM as WAV.
+
+use anyhow::{Result, anyhow};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+use super::db_parser::media_parser::{SILK_V3_HEADER, strip_wechat_silk_prefix};
+
+/// Sample rate WeChat voice messages are recorded at.
+pub const DEFAULT_SAMPLE_RATE: u32 = 24000;
+
+/// Splits a SILK v3 stream ("#!SILK_V3" followed by `[i16 LE length][payload]` packets)
+/// into its packet payloads. The WeChat `0x02` prefix is accepted.
+/// Parsing stops at a negative length (end marker) or at a truncated packet.
+pub fn split_silk_packets(data: &[u8]) -> Result<Vec<&[u8]>> {
+    let data = strip_wechat_silk_prefix(data);
+    if !data.starts_with(SILK_V3_HEADER) {
+        return Err(anyhow!("Not a SILK v3 stream (missing #!SILK_V3 header)"));
+    }
+
+    let mut packets = Vec::new();
+    let mut pos = SILK_V3_HEADER.len();
+    while pos + 2 <= data.len() {
+        let len = i16::from_le_bytes([data[pos], data[pos + 1]]);
+        if len < 0 {
+            break;
+        }
+        let start = pos + 2;
+        let end = start + len as usize;
+        if end > data.len() {
+            break;
+        }
+        packets.push(&data[start..end]);
+        pos = end;
+    }
+    Ok(packets)
+}
+
+/// Writes 16-bit mono PCM samples as a WAV file.
+pub fn write_wav(path: &Path, samples: &[i16], sample_rate: u32) -> Result<()> {
+    let file = File::create(path).map_err(|e| anyhow!("Failed to create {:?}: {}", path, e))?;
+    let mut writer = BufWriter::new(file);
+    write_wav_to(&mut writer, samples, sample_rate)?;
+    writer.flush()?;
+    Ok(())
+}
+
+/// Encodes 16-bit mono PCM samples as an in-memory WAV file.
+pub fn encode_wav(samples: &[i16], sample_rate: u32) -> Vec<u8> {
+    let mut out = Vec::with_capacity(44 + samples.len() * 2);
+    write_wav_to(&mut out, samples, sample_rate).expect("writing to a Vec cannot fail");
+    out
+}
+
+fn write_wav_to(writer: &mut impl Write, samples: &[i16], sample_rate: u32) -> std::io::Result<()> {
+    let data_len = (samples.len() * 2) as u32;
+    let channels: u16 = 1;
+    let bits_per_sample: u16 = 16;
+    let block_align = channels * bits_per_sample / 8;
+    let byte_rate = sample_rate * block_align as u32;
+
+    writer.write_all(b"RIFF")?;
+    writer.write_all(&(36 + data_len).to_le_bytes())?;
+    writer.write_all(b"WAVE")?;
+    writer.write_all(b"fmt ")?;
+    writer.write_all(&16u32.to_le_bytes())?; // fmt chunk size
+    writer.write_all(&1u16.to_le_bytes())?; // PCM
+    writer.write_all(&channels.to_le_bytes())?;
+    writer.write_all(&sample_rate.to_le_bytes())?;
+    writer.write_all(&byte_rate.to_le_bytes())?;
+    writer.write_all(&block_align.to_le_bytes())?;
+    writer.write_all(&bits_per_sample.to_le_bytes())?;
+    writer.write_all(b"data")?;
+    writer.write_all(&data_len.to_le_bytes())?;
+    for s in samples {
+        writer.write_all(&s.to_le_bytes())?;
+    }
+    Ok(())
+}
+
+/// Whether this build can decode SILK to PCM (i.e. was built with `--features silk`).
+pub fn silk_decoding_available() -> bool {
+    cfg!(feature = "silk")
+}
+
+/// Decodes a SILK v3 stream to 16-bit mono PCM at `sample_rate`.
+#[cfg(feature = "silk")]
+pub fn decode_silk_to_pcm(data: &[u8], sample_rate: u32) -> Result<Vec<i16>> {
+    ffi::decode(&split_silk_packets(data)?, sample_rate)
+}
+
+/// Decodes a SILK v3 stream to 16-bit mono PCM at `sample_rate`.
+#[cfg(not(feature = "silk"))]
+pub fn decode_silk_to_pcm(_data: &[u8], _sample_rate: u32) -> Result<Vec<i16>> {
+    Err(anyhow!("SILK decoding is not available, rebuild with `--features silk`"))
+}
+
+/// Bindings to the Skype SILK SDK (libSKP_SILK_SDK), the same decoder pysilk wraps.
+/// The library has to be available to the linker when the `silk` feature is enabled.
+#[cfg(feature = "silk")]
+mod ffi {
+    use anyhow::{Result, anyhow};
+    use std::os::raw::{c_int, c_void};
+
+    // 20 ms frames at up to 48 kHz.
+    const MAX_FRAME_SAMPLES: usize = 20 * 48;
+
+    #[repr(C)]
+    #[derive(Default)]
+    struct DecControl {
+        api_sample_rate: i32,
+        frame_size: c_int,
+        frames_per_packet: c_int,
+        more_internal_decoder_frames: c_int,
+        in_band_fec_offset: c_int,
+    }
+
+    #[link(name = "SKP_SILK_SDK")]
+    extern "C" {
+        fn SKP_Silk_SDK_Get_Decoder_Size(dec_size_bytes: *mut i32) -> c_int;
+        fn SKP_Silk_SDK_InitDecoder(dec_state: *mut c_void) -> c_int;
+        fn SKP_Silk_SDK_Decode(
+            dec_state: *mut c_void,
+            dec_control: *mut DecControl,
+            lost_flag: c_int,
+            in_data: *const u8,
+            n_bytes_in: c_int,
+            samples_out: *mut i16,
+            n_samples_out: *mut i16,
+        ) -> c_int;
+    }
+
+    pub(super) fn decode(packets: &[&[u8]], sample_rate: u32) -> Result<Vec<i16>> {
+        let mut size: i32 = 0;
+        if unsafe { SKP_Silk_SDK_Get_Decoder_Size(&mut size) } != 0 || size <= 0 {
+            return Err(anyhow!("SKP_Silk_SDK_Get_Decoder_Size failed"));
+        }
+        // u64 backing storage keeps the decoder state suitably aligned.
+        let mut state = vec![0u64; (size as usize).div_ceil(8)];
+        let state_ptr = state.as_mut_ptr() as *mut c_void;
+        let ret = unsafe { SKP_Silk_SDK_InitDecoder(state_ptr) };
+        if ret != 0 {
+            return Err(anyhow!("SKP_Silk_SDK_InitDecoder failed with code {}", ret));
+        }
+
+        let mut control = DecControl { api_sample_rate: sample_rate as i32, frames_per_packet: 1, ..Default::default() };
+        let mut pcm = Vec::new();
+        let mut frame = [0i16; MAX_FRAME_SAMPLES];
+
+        for packet in packets {
+            loop {
+                let mut n_samples: i16 = 0;
+                let ret = unsafe {
+                    SKP_Silk_SDK_Decode(
+                        state_ptr,
+                        &mut control,
+                        0,
+                        packet.as_ptr(),
+                        packet.len() as c_int,
+                        frame.as_mut_ptr(),
+                        &mut n_samples,
+                    )
+                };
+                if ret != 0 {
+                    return Err(anyhow!("SKP_Silk_SDK_Decode failed with code {}", ret));
+                }
+                pcm.extend_from_slice(&frame[..(n_samples.max(0) as usize).min(MAX_FRAME_SAMPLES)]);
+                if control.more_internal_decoder_frames == 0 {
+                    break;
+                }
+            }
+        }
+        Ok(pcm)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_silk_packets() {
+        let mut data = b"\x02#!SILK_V3".to_vec();
+        data.extend_from_slice(&[3, 0, 0xAA, 0xBB, 0xCC]);
+        data.extend_from_slice(&[1, 0, 0xDD]);
+        data.extend_from_slice(&[0xFF, 0xFF]); // end marker
+        let packets = split_silk_packets(&data).unwrap();
+        assert_eq!(packets, vec![&[0xAA, 0xBB, 0xCC][..], &[0xDD][..]]);
+
+        assert!(split_silk_packets(b"RIFF....").is_err());
+    }
+
+    #[test]
+    fn test_write_wav_header() {
+        let path = std::env::temp_dir().join(format!("wxdump_rs_silk_test_{}.wav", std::process::id()));
+        write_wav(&path, &[0, 1, -1], DEFAULT_SAMPLE_RATE).unwrap();
+        let bytes = std::fs::read(&path).unwrap();
+        let _ = std::fs::remove_file(&path);
+        assert_eq!(bytes.len(), 44 + 6);
+        assert_eq!(&bytes[0..4], b"RIFF");
+        assert_eq!(&bytes[8..12], b"WAVE");
+        assert_eq!(u32::from_le_bytes(bytes[24..28].try_into().unwrap()), DEFAULT_SAMPLE_RATE);
+        assert_eq!(u32::from_le_bytes(bytes[40..44].try_into().unwrap()), 6);
+    }
+}
@@ -0,0 +1,380 @@
+// src/core/testutil.rs
+
+// Synthetic WeChat 3.x databases for tests: the schemas of the decrypted databases, builders
+// for the blobs WeChat stores in them (ExtraBuf, RoomData, BytesExtra, CompressContent) and
+// sample accounts, plain or encrypted with [`TEST_KEY`]. Built for the crate's own tests and,
+// with the `testutil` feature, for those of crates using it. Everything here panics on failure.
+
+use rusqlite::Connection;
+use std::path::Path;
+
+use crate::core::db_parser::ExtraBufInfo;
+use crate::core::decryption::derive_page_key;
+
+pub const MICRO_MSG_SCHEMA: &str = "
+CREATE TABLE Contact (UserName TEXT PRIMARY KEY, Alias TEXT, EncryptUserName TEXT, DelFlag INTEGER DEFAULT 0,
+    Type INTEGER DEFAULT 0, VerifyFlag INTEGER DEFAULT 0, Reserved1 INTEGER DEFAULT 0, Reserved2 INTEGER DEFAULT 0,
+    Reserved3 TEXT, Reserved4 TEXT, Remark TEXT, NickName TEXT, LabelIDList TEXT, DomainList TEXT,
+    ChatRoomType INT, PYInitial TEXT, QuanPin TEXT, RemarkPYInitial TEXT, RemarkQuanPin TEXT,
+    BigHeadImgUrl TEXT, SmallHeadImgUrl TEXT, HeadImgMd5 TEXT, ChatRoomNotify INTEGER DEFAULT 0,
+    Reserved5 INTEGER DEFAULT 0, Reserved6 TEXT, Reserved7 TEXT, ExtraBuf BLOB, Reserved8 INTEGER DEFAULT 0,
+    Reserved9 INTEGER DEFAULT 0, Reserved10 TEXT, Reserved11 TEXT);
+CREATE TABLE ContactHeadImgUrl (usrName TEXT PRIMARY KEY, smallHeadImgUrl TEXT, bigHeadImgUrl TEXT, headImgMd5 TEXT,
+    reverse0 INT, reverse1 TEXT);
+CREATE TABLE ContactLabel (LabelId INTEGER PRIMARY KEY, LabelName TEXT, Reserved1 INT, Reserved2 INT,
+    Reserved3 TEXT, Reserved4 TEXT);
+CREATE TABLE Session (strUsrName TEXT PRIMARY KEY, nOrder INT, nUnReadCount INTEGER DEFAULT 0, parentRef TEXT,
+    Reserved0 INTEGER DEFAULT 0, Reserved1 TEXT, strNickName TEXT, nStatus INTEGER, nIsSend INTEGER,
+    strContent TEXT, nMsgType INTEGER, nMsgLocalID INTEGER, nMsgStatus INTEGER, nTime INTEGER, editContent TEXT,
+    othersAtMe INT, Reserved2 INTEGER DEFAULT 0, Reserved3 TEXT, Reserved4 INTEGER DEFAULT 0, Reserved5 TEXT,
+    bytesXml BLOB);
+CREATE TABLE ChatRoom (ChatRoomName TEXT PRIMARY KEY, UserNameList TEXT, DisplayNameList TEXT,
+    ChatRoomFlag int Default 0, Owner INTEGER DEFAULT 0, IsShowName INTEGER DEFAULT 0, SelfDisplayName TEXT,
+    Reserved1 INTEGER DEFAULT 0, Reserved2 TEXT, Reserved3 INTEGER DEFAULT 0, Reserved4 TEXT,
+    Reserved5 INTEGER DEFAULT 0, Reserved6 TEXT, RoomData BLOB, Reserved7 INTEGER DEFAULT 0, Reserved8 TEXT);
+CREATE TABLE ChatRoomInfo (ChatRoomName TEXT PRIMARY KEY, Announcement TEXT, InfoVersion INTEGER DEFAULT 0,
+    AnnouncementEditor TEXT, AnnouncementPublishTime INTEGER DEFAULT 0, ChatRoomStatus INTEGER DEFAULT 0,
+    Reserved1 INTEGER DEFAULT 0, Reserved2 TEXT, Reserved3 INTEGER DEFAULT 0, Reserved4 TEXT,
+    Reserved5 INTEGER DEFAULT 0, Reserved6 TEXT, Reserved7 INTEGER DEFAULT 0, Reserved8 TEXT);
+";
+
+pub const MSG_SCHEMA: &str = "
+CREATE TABLE MSG (localId INTEGER PRIMARY KEY AUTOINCREMENT, TalkerId INT DEFAULT 0, MsgSvrID INT, Type INT,
+    SubType INT, IsSender INT, CreateTime INT, Sequence INT DEFAULT 0, StatusEx INT DEFAULT 0, FlagEx INT,
+    Status INT, MsgServerSeq INT, MsgSequence INT, StrTalker TEXT, StrContent TEXT, DisplayContent TEXT,
+    Reserved0 INT DEFAULT 0, Reserved1 INT DEFAULT 0, Reserved2 INT DEFAULT 0, Reserved3 INT DEFAULT 0,
+    Reserved4 TEXT, Reserved5 TEXT, Reserved6 TEXT, CompressContent BLOB, BytesExtra BLOB, BytesTrans BLOB);
+";
+
+pub const MEDIA_MSG_SCHEMA: &str = "
+CREATE TABLE Media (Key TEXT, Reserved0 INT, Buf BLOB, Reserved1 INT, Reserved2 TEXT);
+";
+
+pub const SNS_SCHEMA: &str = "
+CREATE TABLE FeedsV20 (FeedId INTEGER PRIMARY KEY, CreateTime INTEGER, FaultId INTEGER, Type INTEGER,
+    UserName TEXT, Status INTEGER, ExtFlag INTEGER, PrivFlag INTEGER, StringId TEXT, Content TEXT,
+    Reserved1 INTEGER, Reserved2 INTEGER, Reserved3 INTEGER, Reserved4 INTEGER, Reserved5 TEXT, Reserved6 TEXT,
+    ExtraBuf BLOB, Reserved7 BLOB);
+CREATE TABLE CommentV20 (FeedId INTEGER, CommentId INTEGER, Type INTEGER, FromUserName TEXT, CreateTime INTEGER,
+    CommentFlag INTEGER, Content TEXT, RefCommentId INTEGER, Reserved1 INTEGER, Reserved2 INTEGER,
+    Reserved3 TEXT, Reserved4 TEXT, Reserved5 INTEGER, Reserved6 INTEGER, Reserved7 TEXT, Reserved8 BLOB);
+";
+
+pub const FAVORITE_SCHEMA: &str = "
+CREATE TABLE FavItems (FavLocalID INTEGER PRIMARY KEY, SvrID INTEGER, SourceType INTEGER, Type INTEGER,
+    SourceId TEXT, FromUser TEXT, RealChatName TEXT, SearchKey TEXT, UpdateTime INTEGER, UpdateSeq INTEGER,
+    LocalStatus INTEGER, Flag INTEGER, XmlBuf TEXT, Reserved1 INTEGER, Reserved2 TEXT, Reserved3 INTEGER);
+CREATE TABLE FavDataItem (FavLocalID INTEGER, Type INTEGER, DataId TEXT, Reserved1 INTEGER, Reserved2 TEXT);
+CREATE TABLE FavTags (LocalID INTEGER PRIMARY KEY, ServerSeq INTEGER, TagName TEXT, Reserved1 INTEGER, Reserved2 TEXT);
+CREATE TABLE FavBindTagDatas (TagLocalID INTEGER, FavLocalID INTEGER, Reserved1 INTEGER, Reserved2 TEXT);
+";
+
+pub const OPENIM_CONTACT_SCHEMA: &str = "
+CREATE TABLE OpenIMContact (UserName TEXT PRIMARY KEY, NickName TEXT, Type INTEGER DEFAULT 0, Remark TEXT,
+    BigHeadImgUrl TEXT, SmallHeadImgUrl TEXT, Source INTEGER DEFAULT 0, NickNamePYInit TEXT, NickNameQuanPin TEXT,
+    RemarkPYInit TEXT, RemarkQuanPin TEXT, CustomInfoDetail TEXT, CustomInfoDetailVisible INTEGER DEFAULT 0,
+    AntiSpamTicket TEXT, AppId TEXT, Sex INTEGER DEFAULT 0, DescWordingId TEXT, ExtraBuf BLOB);
+CREATE TABLE OpenIMWordingInfo (WordingId TEXT, Language TEXT, Wording TEXT, Pinyin TEXT, Quanpin TEXT,
+    UpdateTime INTEGER DEFAULT 0, PRIMARY KEY (WordingId, Language));
+CREATE TABLE OpenIMChatRoomMember (ChatRoomName TEXT, UserName TEXT, DisplayName TEXT, Flag INTEGER DEFAULT 0,
+    PRIMARY KEY (ChatRoomName, UserName));
+";
+
+/// Account key of the encrypted fixtures.
+pub const TEST_KEY: &str = "000102030405060708090a0b0c0d0e0f101112131415161718191a1b1c1d1e1f";
+
+/// Writes the contents of `conn` to `path` encrypted the way WeChat 3.x does (SQLCipher 3
+/// settings, page key derived from [`TEST_KEY`] and `salt`).
+pub fn write_encrypted_copy(conn: &Connection, path: &Path, salt: [u8; 16]) {
+    let page_key = derive_page_key(&hex::decode(TEST_KEY).unwrap(), &salt);
+    let raw_key = format!("x'{}{}'", hex::encode(page_key), hex::encode(salt));
+    conn.execute("ATTACH DATABASE ? AS enc KEY ?", [path.to_string_lossy().as_ref(), raw_key.as_str()]).unwrap();
+    conn.execute_batch(
+        "PRAGMA enc.cipher_page_size = 4096; PRAGMA enc.kdf_iter = 64000;
+         PRAGMA enc.cipher_hmac_algorithm = HMAC_SHA1; PRAGMA enc.cipher_kdf_algorithm = PBKDF2_HMAC_SHA1;",
+    )
+    .unwrap();
+    conn.query_row("SELECT sqlcipher_export('enc')", [], |_| Ok(())).unwrap();
+    conn.execute("DETACH DATABASE enc", []).unwrap();
+}
+
+fn push_varint(out: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        out.push((value as u8) | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+fn push_proto_varint(out: &mut Vec<u8>, field: u32, value: u64) {
+    push_varint(out, (field as u64) << 3);
+    push_varint(out, value);
+}
+
+fn push_proto_bytes(out: &mut Vec<u8>, field: u32, value: &[u8]) {
+    push_varint(out, ((field as u64) << 3) | 2);
+    push_varint(out, value.len() as u64);
+    out.extend_from_slice(value);
+}
+
+/// Contact.ExtraBuf holding every field set in `info`: a 4 byte key per field, then a type byte
+/// and the value (1 = little-endian integer with a 1 byte length, 2 = UTF-16LE string with a
+/// 2 byte length), separated by filler bytes as in real blobs.
+pub fn extra_buf(info: &ExtraBufInfo) -> Vec<u8> {
+    let strings = [
+        ("46CF10C4", &info.signature),
+        ("A4D9024A", &info.country),
+        ("E2EAA8D1", &info.province),
+        ("1D025BBF", &info.city),
+        ("F917BCC0", &info.company_name),
+        ("759378AD", &info.mobile_phone),
+        ("4EB96D85", &info.enterprise_wechat_attr),
+        ("81AE19B4", &info.moments_background_img),
+        ("0E719F13", &info.remark_img_url1),
+        ("945F3190", &info.remark_img_url2),
+    ];
+    let mut out = vec![0x08, 0x00];
+    if let Some(gender) = info.gender {
+        out.extend(hex::decode("74752C06").unwrap());
+        out.extend([1, 1, gender as u8, 0x00]);
+    }
+    for (key, value) in strings {
+        let Some(value) = value else { continue };
+        let utf16: Vec<u8> = value.encode_utf16().flat_map(u16::to_le_bytes).collect();
+        out.extend(hex::decode(key).unwrap());
+        out.push(2);
+        out.extend((utf16.len() as u16).to_le_bytes());
+        out.extend(utf16);
+        out.push(0x00);
+    }
+    out
+}
+
+/// ChatRoom.RoomData listing `members` as `(wxid, display name in the room)`; an empty name
+/// is left out, as WeChat does for members who never set one.
+pub fn room_data(members: &[(&str, &str)]) -> Vec<u8> {
+    let mut out = Vec::new();
+    for (wxid, display_name) in members {
+        let mut member = Vec::new();
+        push_proto_bytes(&mut member, 1, wxid.as_bytes());
+        if !display_name.is_empty() {
+            push_proto_bytes(&mut member, 2, display_name.as_bytes());
+        }
+        push_proto_varint(&mut member, 3, 0);
+        push_proto_bytes(&mut out, 1, &member);
+    }
+    push_proto_varint(&mut out, 2, members.len() as u64);
+    out
+}
+
+/// MSG.BytesExtra with the typed `(type, value)` entries (1 = sender wxid, 3 = thumbnail path,
+/// 4 = file path).
+pub fn bytes_extra(entries: &[(u64, &str)]) -> Vec<u8> {
+    let mut header = Vec::new();
+    push_proto_varint(&mut header, 1, 0);
+    let mut out = Vec::new();
+    push_proto_bytes(&mut out, 1, &header);
+    for (entry_type, value) in entries {
+        let mut entry = Vec::new();
+        push_proto_varint(&mut entry, 1, *entry_type);
+        push_proto_bytes(&mut entry, 2, value.as_bytes());
+        push_proto_bytes(&mut out, 3, &entry);
+    }
+    out
+}
+
+/// BytesExtra blob carrying the sender of a chat room message (type 1 entry).
+pub fn sender_bytes_extra(wxid: &str) -> Vec<u8> {
+    let mut inner = vec![0x08, 0x01, 0x12, wxid.len() as u8];
+    inner.extend_from_slice(wxid.as_bytes());
+    let mut out = vec![0x1A, inner.len() as u8];
+    out.extend(inner);
+    out
+}
+
+/// MSG.CompressContent for `xml`: an lz4 block without size prefix.
+pub fn compress_content(xml: &str) -> Vec<u8> {
+    lz4_flex::block::compress(xml.as_bytes())
+}
+
+/// An in-memory "merge_all.db": MicroMsg tables plus MSG/Media, with a friend, a stranger,
+/// one chat room and a handful of messages.
+pub fn merged_db_with_sample_data() -> Connection {
+    let conn = Connection::open_in_memory().unwrap();
+    conn.execute_batch(MICRO_MSG_SCHEMA).unwrap();
+    conn.execute_batch(MSG_SCHEMA).unwrap();
+    conn.execute_batch(MEDIA_MSG_SCHEMA).unwrap();
+    conn.execute_batch(
+        "INSERT INTO ContactLabel (LabelId, LabelName) VALUES (1, 'Work');
+         INSERT INTO Contact (UserName, Alias, Type, Remark, NickName, LabelIDList, PYInitial)
+             VALUES ('wxid_alice', 'alice01', 3, 'Alice (work)', 'Alice', '1', 'ALICE');
+         INSERT INTO Contact (UserName, Type, NickName, PYInitial) VALUES ('wxid_bob', 3, 'Bob', 'BOB');
+         INSERT INTO Contact (UserName, Type, NickName) VALUES ('wxid_stranger', 4, 'Stranger');
+         INSERT INTO Contact (UserName, Type, NickName) VALUES ('wxid_me', 0, 'Me');
+         INSERT INTO Contact (UserName, Type, NickName, ChatRoomType) VALUES ('123@chatroom', 2, 'Project <Team>', 1);
+         INSERT INTO ContactHeadImgUrl (usrName, bigHeadImgUrl) VALUES ('wxid_alice', 'http://img/alice.jpg');
+         INSERT INTO ChatRoom (ChatRoomName, UserNameList, SelfDisplayName, Reserved2)
+             VALUES ('123@chatroom', 'wxid_alice' || char(7) || 'wxid_stranger' || char(7) || 'wxid_me', 'me in room',
+                     'wxid_alice');
+         INSERT INTO ChatRoomInfo (ChatRoomName, Announcement) VALUES ('123@chatroom', 'Be nice');
+         INSERT INTO Session (strUsrName, nOrder, strNickName, strContent, nMsgType, nTime)
+             VALUES ('wxid_alice', 2, 'Alice', 'see you', 1, 1700000100);
+         INSERT INTO Session (strUsrName, nOrder, strNickName, strContent, nMsgType, nTime)
+             VALUES ('123@chatroom', 1, 'Project <Team>', 'hello all', 1, 1700000000);
+         INSERT INTO MSG (MsgSvrID, Type, SubType, IsSender, CreateTime, StrTalker, StrContent)
+             VALUES (1001, 1, 0, 0, 1700000000, 'wxid_alice', 'hi there');
+         INSERT INTO MSG (MsgSvrID, Type, SubType, IsSender, CreateTime, StrTalker, StrContent)
+             VALUES (1002, 1, 0, 1, 1700000050, 'wxid_alice', 'hello <b>Alice</b>');
+         INSERT INTO MSG (MsgSvrID, Type, SubType, IsSender, CreateTime, StrTalker, StrContent)
+             VALUES (1003, 34, 0, 0, 1700000100, 'wxid_alice', '<msg><voicemsg voicelength=\"3000\" /></msg>');
+         INSERT INTO Media (Key, Reserved0, Buf) VALUES ('k', 1003, x'02232153494C4B5F5633FFFF');",
+    )
+    .unwrap();
+    conn.execute(
+        "INSERT INTO MSG (MsgSvrID, Type, SubType, IsSender, CreateTime, StrTalker, StrContent, BytesExtra)
+         VALUES (2001, 1, 0, 0, 1700000000, '123@chatroom', 'hello all', ?)",
+        [sender_bytes_extra("wxid_stranger")],
+    )
+    .unwrap();
+    conn.execute(
+        "INSERT INTO MSG (MsgSvrID, Type, SubType, IsSender, CreateTime, StrTalker, StrContent, BytesExtra)
+         VALUES (2002, 49, 57, 0, 1700000200, '123@chatroom', ?, ?)",
+        rusqlite::params![
+            "<msg><appmsg><title>welcome!</title><type>57</type><refermsg><type>1</type><svrid>2001</svrid>\
+             <fromusr>123@chatroom</fromusr><chatusr>wxid_stranger</chatusr><displayname>Stranger</displayname>\
+             <content>hello all</content></refermsg></appmsg></msg>",
+            sender_bytes_extra("wxid_alice"),
+        ],
+    )
+    .unwrap();
+    conn.execute(
+        "INSERT INTO MSG (MsgSvrID, Type, SubType, IsSender, CreateTime, StrTalker, StrContent)
+         VALUES (2003, 10000, 0, 0, 1700000300, '123@chatroom', '\"Alice\" 撤回了一条消息')",
+        [],
+    )
+    .unwrap();
+    conn
+}
+
+/// MicroMsg.db of the sample account ([`write_sample_account`]): the account `wxid_me`, two
+/// friends with ExtraBuf profiles and a chat room with RoomData display names.
+pub fn micro_msg_db() -> Connection {
+    let conn = Connection::open_in_memory().unwrap();
+    conn.execute_batch(MICRO_MSG_SCHEMA).unwrap();
+    conn.execute_batch(
+        "INSERT INTO ContactLabel (LabelId, LabelName) VALUES (1, 'Family'), (2, 'Hiking');
+         INSERT INTO Contact (UserName, Type, NickName) VALUES ('wxid_me', 0, 'Me');
+         INSERT INTO Contact (UserName, Type, NickName, ChatRoomType) VALUES ('456@chatroom', 2, '周末爬山', 1);
+         INSERT INTO ContactHeadImgUrl (usrName, bigHeadImgUrl) VALUES ('wxid_carol', 'http://img/carol.jpg');
+         INSERT INTO ChatRoomInfo (ChatRoomName, Announcement, AnnouncementEditor, AnnouncementPublishTime)
+             VALUES ('456@chatroom', 'Saturday 8:00', 'wxid_me', 1700100000);
+         INSERT INTO Session (strUsrName, nOrder, nUnReadCount, strNickName, strContent, nMsgType, nTime)
+             VALUES ('456@chatroom', 2, 3, '周末爬山', 'see you there', 1, 1700100300);
+         INSERT INTO Session (strUsrName, nOrder, strNickName, strContent, nMsgType, nTime)
+             VALUES ('wxid_carol', 1, 'Carol', '[语音]', 34, 1700100200);",
+    )
+    .unwrap();
+    let carol = ExtraBufInfo {
+        gender: Some(2),
+        signature: Some("山不在高".into()),
+        country: Some("CN".into()),
+        province: Some("Guangdong".into()),
+        city: Some("Shenzhen".into()),
+        ..Default::default()
+    };
+    let dave = ExtraBufInfo { gender: Some(1), mobile_phone: Some("13800000000".into()), ..Default::default() };
+    let mut insert = conn
+        .prepare("INSERT INTO Contact (UserName, Alias, Type, Remark, NickName, LabelIDList, ExtraBuf) VALUES (?, ?, 3, ?, ?, ?, ?)")
+        .unwrap();
+    insert.execute(rusqlite::params!["wxid_carol", "carol88", "Carol (cousin)", "Carol", "1,2", extra_buf(&carol)]).unwrap();
+    insert.execute(rusqlite::params!["wxid_dave", None::<String>, None::<String>, "Dave", "2", extra_buf(&dave)]).unwrap();
+    conn.execute(
+        "INSERT INTO ChatRoom (ChatRoomName, UserNameList, SelfDisplayName, Reserved2, RoomData) VALUES (?, ?, ?, ?, ?)",
+        rusqlite::params![
+            "456@chatroom",
+            "wxid_me\x07wxid_carol\x07wxid_dave",
+            "领队",
+            "wxid_me",
+            room_data(&[("wxid_me", "领队"), ("wxid_carol", "Carol 🏔"), ("wxid_dave", "")]),
+        ],
+    )
+    .unwrap();
+    drop(insert);
+    conn
+}
+
+/// MSG0.db of the sample account: a conversation with wxid_carol (text, image, voice) and
+/// chat room messages whose senders are in BytesExtra, one of them a quote reply stored in
+/// CompressContent.
+pub fn msg_db() -> Connection {
+    let conn = Connection::open_in_memory().unwrap();
+    conn.execute_batch(MSG_SCHEMA).unwrap();
+    let mut insert = conn
+        .prepare(
+            "INSERT INTO MSG (MsgSvrID, Type, SubType, IsSender, CreateTime, StrTalker, StrContent, CompressContent, BytesExtra)
+             VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)",
+        )
+        .unwrap();
+    let no_blob = None::<Vec<u8>>;
+    insert.execute(rusqlite::params![3001, 1, 0, 0, 1700100000, "wxid_carol", "早上好", no_blob, no_blob]).unwrap();
+    insert.execute(rusqlite::params![3002, 1, 0, 1, 1700100060, "wxid_carol", "Morning!", no_blob, no_blob]).unwrap();
+    let image = bytes_extra(&[
+        (3, "wxid_me\\FileStorage\\Image\\Thumb\\2023-11\\3003_t.dat"),
+        (4, "wxid_me\\FileStorage\\Image\\2023-11\\3003.dat"),
+    ]);
+    insert
+        .execute(rusqlite::params![3003, 3, 0, 0, 1700100120, "wxid_carol", "<msg><img length=\"1024\" /></msg>", no_blob, image])
+        .unwrap();
+    insert
+        .execute(rusqlite::params![
+            3004, 34, 0, 0, 1700100200, "wxid_carol", "<msg><voicemsg voicelength=\"2000\" /></msg>", no_blob, no_blob
+        ])
+        .unwrap();
+    insert
+        .execute(rusqlite::params![
+            4001, 1, 0, 0, 1700100240, "456@chatroom", "明天几点集合?", no_blob, bytes_extra(&[(1, "wxid_dave")])
+        ])
+        .unwrap();
+    let quote = compress_content(
+        "<msg><appmsg><title>see you there</title><type>57</type><refermsg><type>1</type><svrid>4001</svrid>\
+         <fromusr>456@chatroom</fromusr><chatusr>wxid_dave</chatusr><displayname>Dave</displayname>\
+         <content>明天几点集合?</content></refermsg></appmsg></msg>",
+    );
+    insert
+        .execute(rusqlite::params![4002, 49, 57, 0, 1700100300, "456@chatroom", "", quote, bytes_extra(&[(1, "wxid_carol")])])
+        .unwrap();
+    insert.execute(rusqlite::params![4003, 1, 0, 1, 1700100360, "456@chatroom", "👍", no_blob, no_blob]).unwrap();
+    drop(insert);
+    conn
+}
+
+/// MediaMSG0.db of the sample account: the SILK stream of voice message 3004.
+pub fn media_msg_db() -> Connection {
+    let conn = Connection::open_in_memory().unwrap();
+    conn.execute_batch(MEDIA_MSG_SCHEMA).unwrap();
+    conn.execute("INSERT INTO Media (Key, Reserved0, Buf) VALUES ('k3004', 3004, x'02232153494C4B5F5633FFFF')", [])
+        .unwrap();
+    conn
+}
+
+/// Writes the sample account as WeChat lays it out under `WeChat Files/<wxid>`:
+/// `Msg/MicroMsg.db`, `Msg/Multi/MSG0.db` and `Msg/Multi/MediaMSG0.db`, encrypted with
+/// [`TEST_KEY`] when `encrypted` is set. Returns the `Msg` directory.
+pub fn write_sample_account(wx_path: &Path, encrypted: bool) -> std::path::PathBuf {
+    let msg_dir = wx_path.join("Msg");
+    std::fs::create_dir_all(msg_dir.join("Multi")).unwrap();
+    let databases = [
+        (micro_msg_db(), msg_dir.join("MicroMsg.db")),
+        (msg_db(), msg_dir.join("Multi/MSG0.db")),
+        (media_msg_db(), msg_dir.join("Multi/MediaMSG0.db")),
+    ];
+    for (i, (conn, path)) in databases.iter().enumerate() {
+        let _ = std::fs::remove_file(path);
+        if encrypted {
+            write_encrypted_copy(conn, path, [i as u8 + 1; 16]);
+        } else {
+            conn.execute("VACUUM INTO ?", [path.to_string_lossy()]).unwrap();
+        }
+    }
+    msg_dir
+}
@@ -8,6 +8,19 @@ use windows_sys::Win32::{
     },
 };
 
+use super::error::WxDumpError;
+
+/// Error for a failed OpenProcess. Access denied (not elevated, or a protected process) gets
+/// its own variant so the CLI can tell it apart.
+fn open_process_error(pid: u32, purpose: &str) -> anyhow::Error {
+    let os_error = std::io::Error::last_os_error();
+    if os_error.kind() == std::io::ErrorKind::PermissionDenied {
+        WxDumpError::ProcessAccessDenied { pid }.into()
+    } else {
+        anyhow!("Failed to open process {}{}. Error: {}", pid, purpose, os_error)
+    }
+}
+
 #[derive(Debug)]
 pub struct ProcessInfo {
     pub pid: u32,
@@ -64,7 +77,7 @@ pub fn get_process_exe_path(pid: u32) -> Result<String> {
     };
 
     if process_handle == std::ptr::null_mut() || process_handle == INVALID_HANDLE_VALUE {
-        return Err(anyhow!("Failed to open process {}. Error: {}", pid, std::io::Error::last_os_error()));
+        return Err(open_process_error(pid, ""));
     }
 
     let buffer_size = MAX_PATH_LEN as u32;
@@ -181,7 +194,7 @@ pub fn read_process_memory(pid: u32, address: usize, size: usize) -> Result<Vec<
     };
 
     if process_handle == std::ptr::null_mut() || process_handle == INVALID_HANDLE_VALUE {
-        return Err(anyhow!("Failed to open process {} for reading memory. Error: {}", pid, std::io::Error::last_os_error()));
+        return Err(open_process_error(pid, " for reading memory"));
     }
 
     let mut buffer: Vec<u8> = vec![0; size];
@@ -262,7 +275,7 @@ pub fn get_module_base_address(pid: u32, module_name: &str) -> Result<usize> {
 
     match found_base_address {
         Some(addr) => Ok(addr),
-        None => Err(anyhow!("Module '{}' not found in PID {}", module_name, pid)),
+        None => Err(WxDumpError::ModuleNotFound { pid, module: module_name.to_string() }.into()),
     }
 }
 
@@ -276,7 +289,7 @@ pub fn get_process_architecture(pid: u32) -> Result<usize> {
         )
     };
     if process_handle == std::ptr::null_mut() || process_handle == INVALID_HANDLE_VALUE {
-        return Err(anyhow!("Failed to open process {} to determine architecture. Error: {}", pid, std::io::Error::last_os_error()));
+        return Err(open_process_error(pid, " to determine architecture"));
     }
 
     let mut is_wow64: windows_sys::Win32::Foundation::BOOL = 0;
@@ -335,7 +348,7 @@ pub fn search_memory_for_pattern(
         )
     };
     if process_handle == std::ptr::null_mut() || process_handle == INVALID_HANDLE_VALUE {
-        return Err(anyhow!("Failed to open process {} for memory search. Error: {}", pid, std::io::Error::last_os_error()));
+        return Err(open_process_error(pid, " for memory search"));
     }
 
     let mut found_addresses = Vec::new();
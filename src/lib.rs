@@ -1,4 +1,5 @@
 // src/lib.rs
 
 pub mod cli;
-pub mod core; // Assuming your existing core logic might also be part of the library
\ No newline at end of file
+pub mod core; // Assuming your existing core logic might also be part of the library
+pub mod server;
\ No newline at end of file
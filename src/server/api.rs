@@ -0,0 +1,263 @@
+// src/server/api.rs
+
+use anyhow::{Result, anyhow};
+use rusqlite::{Connection, OpenFlags};
+use serde_json::json;
+use std::collections::HashMap;
+use std::path::Path;
+
+use super::{Handler, HttpResponse, parse_url_multi};
+use crate::core::db_browser::sql_value_to_json;
+use crate::core::error::WxDumpError;
+use crate::core::db_parser::{
+    count_messages, count_table_rows, get_chat_rooms, get_contacts, get_messages,
+    get_recent_chat_wxids, get_rows_from_table, get_sessions, list_tables,
+};
+
+/// Messages per page in `/messages`, rows per page in `/tables/<name>`.
+const PAGE_SIZE: usize = 500;
+
+/// Default `limit` of `/recent`.
+const DEFAULT_RECENT_LIMIT: usize = 20;
+
+/// JSON API over a merged database (`merge_all.db`), for scripts.
+///
+/// Routes (all GET, query parameters optional unless noted):
+/// - `/contacts?word=&wxids=&label_ids=`: `wxids`/`label_ids` may repeat or be comma separated
+/// - `/chatrooms?wxids=`
+/// - `/sessions`
+/// - `/recent?limit=`: wxids of recent one-to-one chats
+/// - `/messages?talker=&start=&end=&page=`: `start`/`end` are unix timestamps, pages start at 1
+/// - `/tables` and `/tables/<name>?page=`: raw rows, BLOBs as hex, paged like `/messages`
+///
+/// Errors are returned as `{"error": "..."}` with a 4xx/5xx status.
+pub struct ApiServer {
+    conn: Connection,
+}
+
+impl ApiServer {
+    /// Opens `merge_path` read-only.
+    pub fn open(merge_path: &Path) -> Result<Self> {
+        if !merge_path.exists() {
+            return Err(WxDumpError::DatabaseNotFound(merge_path.to_path_buf()).into());
+        }
+        let conn = Connection::open_with_flags(merge_path, OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+        Ok(Self::from_connection(conn))
+    }
+
+    pub fn from_connection(conn: Connection) -> Self {
+        ApiServer { conn }
+    }
+
+    fn route(&self, path: &str, params: &HashMap<String, Vec<String>>) -> Result<HttpResponse> {
+        let first = |name: &str| params.get(name).and_then(|v| v.first()).map(String::as_str).filter(|v| !v.is_empty());
+        let list = |name: &str| -> Vec<String> {
+            params
+                .get(name)
+                .into_iter()
+                .flatten()
+                .flat_map(|v| v.split(','))
+                .map(|v| v.trim().to_string())
+                .filter(|v| !v.is_empty())
+                .collect()
+        };
+
+        match path.trim_end_matches('/') {
+            "/contacts" => {
+                let wxids = list("wxids");
+                let label_ids = list("label_ids")
+                    .iter()
+                    .map(|id| id.parse::<i64>().map_err(|_| anyhow!("Invalid label id: {}", id)))
+                    .collect::<Result<Vec<_>>>();
+                let label_ids = match label_ids {
+                    Ok(ids) => ids,
+                    Err(e) => return Ok(error(400, &e.to_string())),
+                };
+                let contacts = get_contacts(
+                    &self.conn,
+                    first("word"),
+                    (!wxids.is_empty()).then_some(wxids.as_slice()),
+                    (!label_ids.is_empty()).then_some(label_ids.as_slice()),
+                )?;
+                Ok(HttpResponse::json(200, &contacts))
+            }
+            "/chatrooms" => {
+                let wxids = list("wxids");
+                let mut rooms: Vec<_> =
+                    get_chat_rooms(&self.conn, (!wxids.is_empty()).then_some(wxids.as_slice()))?.into_values().collect();
+                rooms.sort_by(|a, b| a.wxid.cmp(&b.wxid));
+                Ok(HttpResponse::json(200, &rooms))
+            }
+            "/sessions" => Ok(HttpResponse::json(200, &get_sessions(&self.conn)?)),
+            "/recent" => {
+                let limit = match first("limit").map(str::parse::<usize>) {
+                    None => DEFAULT_RECENT_LIMIT,
+                    Some(Ok(limit)) => limit,
+                    Some(Err(_)) => return Ok(error(400, "Invalid limit")),
+                };
+                Ok(HttpResponse::json(200, &get_recent_chat_wxids(&self.conn, limit)?))
+            }
+            "/messages" => {
+                let Some(talker) = first("talker") else { return Ok(error(400, "Missing talker")) };
+                let (start, end, page) = match (parse_opt(first("start")), parse_opt(first("end")), parse_opt(first("page"))) {
+                    (Ok(start), Ok(end), Ok(page)) => (start, end, page.unwrap_or(1).max(1)),
+                    _ => return Ok(error(400, "start, end and page must be integers")),
+                };
+                let Some(offset) = page_offset(page) else { return Ok(error(400, "page out of range")) };
+                let total = count_messages(&self.conn, Some(talker), start, end)?;
+                let messages =
+                    get_messages(&self.conn, Some(talker), None, start, end, Some(PAGE_SIZE), Some(offset))?;
+                Ok(HttpResponse::json(
+                    200,
+                    &json!({ "total": total, "page": page, "page_size": PAGE_SIZE, "messages": messages }),
+                ))
+            }
+            "/tables" => Ok(HttpResponse::json(200, &list_tables(&self.conn)?)),
+            _ => match path.strip_prefix("/tables/") {
+                Some(name) => {
+                    let page = match parse_opt(first("page")) {
+                        Ok(page) => page.unwrap_or(1).max(1),
+                        Err(_) => return Ok(error(400, "page must be an integer")),
+                    };
+                    self.table(name, page)
+                }
+                None => Ok(error(404, "Not Found")),
+            },
+        }
+    }
+
+    fn table(&self, name: &str, page: i64) -> Result<HttpResponse> {
+        if !list_tables(&self.conn)?.iter().any(|t| t == name) {
+            return Ok(error(404, &format!("No such table: {}", name)));
+        }
+        let Some(offset) = page_offset(page) else { return Ok(error(400, "page out of range")) };
+        let total = count_table_rows(&self.conn, name)?;
+        let rows = get_rows_from_table(&self.conn, name, PAGE_SIZE, offset)?;
+        let rows: Vec<serde_json::Map<String, serde_json::Value>> = rows
+            .into_iter()
+            .map(|row| row.into_iter().map(|(k, v)| (k, sql_value_to_json(v))).collect())
+            .collect();
+        Ok(HttpResponse::json(200, &json!({ "total": total, "page": page, "page_size": PAGE_SIZE, "rows": rows })))
+    }
+}
+
+impl Handler for ApiServer {
+    fn handle(&self, method: &str, url: &str) -> HttpResponse {
+        if method != "GET" {
+            return error(405, "Method Not Allowed");
+        }
+        let (path, params) = parse_url_multi(url);
+        self.route(&path, &params).unwrap_or_else(|e| error(500, &e.to_string()))
+    }
+}
+
+fn error(status: u16, message: &str) -> HttpResponse {
+    HttpResponse::json(status, &json!({ "error": message }))
+}
+
+/// Row offset of a 1-based `page`, or None when it does not fit an SQLite OFFSET.
+fn page_offset(page: i64) -> Option<usize> {
+    (page.max(1) - 1).checked_mul(PAGE_SIZE as i64).map(|offset| offset as usize)
+}
+
+fn parse_opt(value: Option<&str>) -> std::result::Result<Option<i64>, std::num::ParseIntError> {
+    value.map(str::parse).transpose()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::testutil::merged_db_with_sample_data;
+    use std::io::{Read, Write};
+
+    fn get(api: &ApiServer, url: &str) -> (u16, serde_json::Value) {
+        let response = api.handle("GET", url);
+        (response.status, serde_json::from_slice(&response.body).unwrap())
+    }
+
+    #[test]
+    fn test_contacts_filters() {
+        let api = ApiServer::from_connection(merged_db_with_sample_data());
+
+        let (status, all) = get(&api, "/contacts");
+        assert_eq!(status, 200);
+        // Chat rooms and type 0/4 accounts are left out unless asked for by wxid.
+        let wxids: Vec<_> = all.as_array().unwrap().iter().map(|c| c["wxid"].as_str().unwrap()).collect();
+        assert_eq!(wxids, vec!["wxid_alice", "wxid_bob"]);
+
+        let (_, by_word) = get(&api, "/contacts?word=alice");
+        assert_eq!(by_word[0]["wxid"], "wxid_alice");
+        assert_eq!(by_word[0]["remark"], "Alice (work)");
+
+        let (_, by_wxids) = get(&api, "/contacts?wxids=wxid_bob,wxid_stranger");
+        assert_eq!(by_wxids.as_array().unwrap().len(), 2);
+
+        let (_, by_label) = get(&api, "/contacts?label_ids=1");
+        assert_eq!(by_label.as_array().unwrap().len(), 1);
+        assert_eq!(by_label[0]["label_list"][0], "Work");
+
+        assert_eq!(get(&api, "/contacts?label_ids=x").0, 400);
+    }
+
+    #[test]
+    fn test_sessions_rooms_and_messages() {
+        let api = ApiServer::from_connection(merged_db_with_sample_data());
+
+        let (_, sessions) = get(&api, "/sessions");
+        assert_eq!(sessions.as_array().unwrap().len(), 2);
+        let (_, recent) = get(&api, "/recent");
+        assert_eq!(recent, json!(["wxid_alice"]));
+
+        let (_, rooms) = get(&api, "/chatrooms");
+        assert_eq!(rooms[0]["wxid"], "123@chatroom");
+        assert_eq!(rooms[0]["owner_wxid"], "wxid_alice");
+        assert_eq!(rooms[0]["member_wxids"].as_array().unwrap().len(), 3);
+
+        let (_, page) = get(&api, "/messages?talker=wxid_alice&start=1700000050");
+        assert_eq!(page["total"], 2);
+        assert_eq!(page["messages"][0]["content"], "hello <b>Alice</b>");
+        assert!(page["messages"][0].get("bytes_extra").is_none());
+
+        assert_eq!(get(&api, "/messages").0, 400);
+        assert_eq!(get(&api, "/messages?talker=wxid_alice&page=x").0, 400);
+        assert_eq!(get(&api, "/messages?talker=wxid_alice&page=9223372036854775807").0, 400);
+    }
+
+    #[test]
+    fn test_tables() {
+        let api = ApiServer::from_connection(merged_db_with_sample_data());
+        let (_, tables) = get(&api, "/tables");
+        assert!(tables.as_array().unwrap().contains(&json!("MSG")));
+
+        let (status, media) = get(&api, "/tables/Media");
+        assert_eq!(status, 200);
+        assert_eq!(media["total"], 1);
+        assert_eq!(media["rows"][0]["Buf"], "02232153494c4b5f5633ffff");
+        assert_eq!(get(&api, "/tables/Media?page=2").1["rows"], json!([]));
+        assert_eq!(get(&api, "/tables/Media?page=9223372036854775807").0, 400);
+        assert_eq!(get(&api, "/tables/Nope").0, 404);
+        assert_eq!(get(&api, "/tables/MSG;DROP%20TABLE%20MSG").0, 404);
+    }
+
+    #[test]
+    fn test_served_over_http() {
+        let server = std::sync::Arc::new(super::super::bind("127.0.0.1", 0).unwrap());
+        let port = server.server_addr().to_ip().unwrap().port();
+        let serving = std::sync::Arc::clone(&server);
+        let thread = std::thread::spawn(move || {
+            let api = ApiServer::from_connection(merged_db_with_sample_data());
+            super::super::serve(&serving, &api);
+        });
+
+        let mut stream = std::net::TcpStream::connect(("127.0.0.1", port)).unwrap();
+        stream.write_all(b"GET /recent HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n").unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        assert!(response.starts_with("HTTP/1.1 200"));
+        assert!(response.contains("application/json"));
+        assert!(response.ends_with("[\"wxid_alice\"]"));
+
+        server.unblock();
+        thread.join().unwrap();
+    }
+}
@@ -0,0 +1,133 @@
+// src/server/mod.rs
+
+pub mod api;
+pub mod viewer;
+
+use anyhow::{Result, anyhow};
+use std::collections::HashMap;
+use tiny_http::{Header, Response, Server};
+
+/// A fully buffered HTTP response produced by a `Handler`.
+#[derive(Debug, Clone)]
+pub struct HttpResponse {
+    pub status: u16,
+    pub content_type: String,
+    pub body: Vec<u8>,
+}
+
+impl HttpResponse {
+    pub fn new(status: u16, content_type: &str, body: impl Into<Vec<u8>>) -> Self {
+        HttpResponse { status, content_type: content_type.to_string(), body: body.into() }
+    }
+
+    pub fn html(body: impl Into<String>) -> Self {
+        HttpResponse::new(200, "text/html; charset=utf-8", body.into())
+    }
+
+    pub fn text(status: u16, body: impl Into<String>) -> Self {
+        HttpResponse::new(status, "text/plain; charset=utf-8", body.into())
+    }
+
+    /// Serializes `value` as the JSON body.
+    pub fn json<T: serde::Serialize + ?Sized>(status: u16, value: &T) -> Self {
+        match serde_json::to_vec(value) {
+            Ok(body) => HttpResponse::new(status, "application/json; charset=utf-8", body),
+            Err(e) => HttpResponse::text(500, format!("Failed to serialize response: {}", e)),
+        }
+    }
+
+    pub fn not_found() -> Self {
+        HttpResponse::text(404, "Not Found")
+    }
+}
+
+/// Something that turns a request (method and raw url, e.g. `/chat?talker=x`) into a response.
+/// Handlers are called directly in tests, without going through a socket.
+pub trait Handler {
+    fn handle(&self, method: &str, url: &str) -> HttpResponse;
+}
+
+/// Wraps a handler and logs one line per request (the `--debug` flag of `Api`).
+pub struct RequestLog<H: Handler>(pub H);
+
+impl<H: Handler> Handler for RequestLog<H> {
+    fn handle(&self, method: &str, url: &str) -> HttpResponse {
+        let response = self.0.handle(method, url);
+        log::info!("{} {} -> {} ({} bytes)", method, url, response.status, response.body.len());
+        response
+    }
+}
+
+/// Splits a raw url into its path and decoded query parameters.
+pub fn parse_url(url: &str) -> (String, HashMap<String, String>) {
+    let (path, query) = url.split_once('?').unwrap_or((url, ""));
+    let params = form_urlencoded::parse(query.as_bytes()).into_owned().collect();
+    (path.to_string(), params)
+}
+
+/// Like `parse_url`, but keeps repeated parameters (`?wxids=a&wxids=b`).
+pub fn parse_url_multi(url: &str) -> (String, HashMap<String, Vec<String>>) {
+    let (path, query) = url.split_once('?').unwrap_or((url, ""));
+    let mut params: HashMap<String, Vec<String>> = HashMap::new();
+    for (k, v) in form_urlencoded::parse(query.as_bytes()).into_owned() {
+        params.entry(k).or_default().push(v);
+    }
+    (path.to_string(), params)
+}
+
+/// Host to bind: loopback only, or every interface when `online` (LAN viewing) is requested.
+pub fn bind_host(online: bool) -> &'static str {
+    if online { "0.0.0.0" } else { "127.0.0.1" }
+}
+
+/// Creates the listening socket. Port 0 picks a free port (see `Server::server_addr`).
+pub fn bind(host: &str, port: u16) -> Result<Server> {
+    Server::http((host, port)).map_err(|e| anyhow!("Failed to bind {}:{}: {}", host, port, e))
+}
+
+/// Url of the address `server` actually listens on, e.g. `http://0.0.0.0:5000/` with `--online`.
+pub fn listen_url(server: &Server) -> String {
+    format!("http://{}/", server.server_addr())
+}
+
+/// Serves requests one at a time until the server is shut down.
+/// Single-threaded on purpose: the handlers hold a rusqlite `Connection`, which is not `Sync`.
+pub fn serve(server: &Server, handler: &dyn Handler) {
+    for request in server.incoming_requests() {
+        let method = request.method().as_str().to_string();
+        let response = handler.handle(&method, request.url());
+        let content_type = Header::from_bytes(&b"Content-Type"[..], response.content_type.as_bytes())
+            .expect("content type is a valid header value");
+        let http_response = Response::from_data(response.body)
+            .with_status_code(response.status)
+            .with_header(content_type);
+        if let Err(e) = request.respond(http_response) {
+            log::warn!("Failed to send response: {}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_url() {
+        let (path, params) = parse_url("/chat?talker=123%40chatroom&page=2");
+        assert_eq!(path, "/chat");
+        assert_eq!(params.get("talker").map(String::as_str), Some("123@chatroom"));
+        assert_eq!(params.get("page").map(String::as_str), Some("2"));
+
+        let (_, multi) = parse_url_multi("/contacts?wxids=a&wxids=b");
+        assert_eq!(multi["wxids"], vec!["a", "b"]);
+    }
+
+    #[test]
+    fn test_listen_url() {
+        let local = bind(bind_host(false), 0).unwrap();
+        let port = local.server_addr().to_ip().unwrap().port();
+        assert_eq!(listen_url(&local), format!("http://127.0.0.1:{}/", port));
+        let online = bind(bind_host(true), 0).unwrap();
+        assert!(listen_url(&online).starts_with("http://0.0.0.0:"));
+    }
+}
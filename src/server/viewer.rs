@@ -0,0 +1,451 @@
+// src/server/viewer.rs
+
+use anyhow::Result;
+use rusqlite::{Connection, OpenFlags};
+use std::collections::HashMap;
+use std::fmt::Write as _;
+use std::path::{Component, Path, PathBuf};
+
+use super::{Handler, HttpResponse, parse_url};
+use crate::core::db_parser::{
+    count_messages, get_chat_rooms, get_contacts, get_display_names, get_messages, get_sessions, get_voice_blob,
+    is_emoji_md5, read_cached_emoji, resolve_emoji, EmojiInfo, EmojiSource, Message, MSG_TYPE_APP, MSG_TYPE_IMAGE, MSG_TYPE_VIDEO,
+    MSG_TYPE_VOICE,
+};
+use crate::core::error::WxDumpError;
+use crate::core::html::{
+    encode_query_value, escape_html, is_web_url, render_messages, MediaLink, MessageContext, MESSAGE_STYLE,
+};
+use crate::core::image_decode::{decode_dat_file, DatKeys};
+use crate::core::media_resolver::{MediaKind, MediaResolver};
+use crate::core::silk;
+
+/// Messages per page in `/chat`.
+const PAGE_SIZE: usize = 100;
+
+const STYLE: &str = "
+body { margin: 0; font-family: -apple-system, 'Microsoft YaHei', sans-serif; background: #f5f5f5; }
+nav { background: #2e2e2e; padding: 10px 16px; }
+nav a { color: #eee; margin-right: 16px; text-decoration: none; }
+main { max-width: 960px; margin: 0 auto; padding: 16px; }
+.list a { display: block; padding: 10px; background: #fff; border-bottom: 1px solid #eee; color: #222; text-decoration: none; }
+.list .sub { color: #999; font-size: 12px; }
+.pager a { margin: 0 4px; }
+";
+
+/// The `DbShow` web viewer: server-rendered pages over a merged database
+/// (`merge_all.db`, holding the MicroMsg tables next to MSG), plus media from the account folder.
+pub struct Viewer {
+    conn: Connection,
+    names: HashMap<String, String>,
+    resolver: Option<MediaResolver>,
+    my_wxid: String,
+}
+
+impl Viewer {
+    /// Opens `merge_path` read-only. `wx_path` is the account folder used for images, videos and files.
+    pub fn open(merge_path: &Path, wx_path: Option<&Path>, my_wxid: &str) -> Result<Self> {
+        if !merge_path.exists() {
+            return Err(WxDumpError::DatabaseNotFound(merge_path.to_path_buf()).into());
+        }
+        let conn = Connection::open_with_flags(merge_path, OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+        Self::from_connection(conn, wx_path.map(Path::to_path_buf), my_wxid)
+    }
+
+    pub fn from_connection(conn: Connection, wx_path: Option<PathBuf>, my_wxid: &str) -> Result<Self> {
+        let names = get_display_names(&conn)?;
+        Ok(Viewer { conn, names, resolver: wx_path.map(MediaResolver::new), my_wxid: my_wxid.to_string() })
+    }
+
+    fn display_name(&self, wxid: &str) -> String {
+        self.names.get(wxid).cloned().unwrap_or_else(|| wxid.to_string())
+    }
+
+    fn page(&self, title: &str, body: &str) -> HttpResponse {
+        HttpResponse::html(format!(
+            "<!DOCTYPE html><html><head><meta charset=\"utf-8\"><title>{}</title><style>{}{}</style></head>\
+             <body><nav><a href=\"/\">会话</a><a href=\"/contacts\">联系人</a><a href=\"/rooms\">群聊</a></nav>\
+             <main>{}</main></body></html>",
+            escape_html(title),
+            STYLE,
+            MESSAGE_STYLE,
+            body
+        ))
+    }
+
+    fn error_page(&self, e: anyhow::Error) -> HttpResponse {
+        let mut response = self.page("Error", &format!("<p>{}</p>", escape_html(&e.to_string())));
+        response.status = 500;
+        response
+    }
+
+    fn sessions_page(&self) -> Result<HttpResponse> {
+        let mut body = String::from("<div class=\"list\">");
+        for session in get_sessions(&self.conn)? {
+            let _ = write!(
+                body,
+                "<a href=\"/chat?talker={}\">{}<div class=\"sub\">{} {}</div></a>",
+                encode_query_value(&session.wxid),
+                escape_html(&self.display_name(&session.wxid)),
+                escape_html(session.time_str.as_deref().unwrap_or("")),
+                escape_html(session.content.as_deref().unwrap_or("")),
+            );
+        }
+        body.push_str("</div>");
+        Ok(self.page("会话", &body))
+    }
+
+    /// One page of a conversation. Without `page` the last (most recent) page is shown.
+    fn chat_page(&self, talker: &str, page: Option<usize>) -> Result<HttpResponse> {
+        let total = count_messages(&self.conn, Some(talker), None, None)? as usize;
+        let page_count = total.div_ceil(PAGE_SIZE).max(1);
+        let page = page.unwrap_or(page_count).clamp(1, page_count);
+        let messages =
+            get_messages(&self.conn, Some(talker), None, None, None, Some(PAGE_SIZE), Some((page - 1) * PAGE_SIZE))?;
+
+        let mut body = format!("<h3>{}</h3>", escape_html(&self.display_name(talker)));
+        let pager = self.pager(talker, page, page_count);
+        body.push_str(&pager);
+        body.push_str(&render_messages(&mut ViewerPage(self), &messages)?);
+        body.push_str(&pager);
+        Ok(self.page(&self.display_name(talker), &body))
+    }
+
+    fn pager(&self, talker: &str, page: usize, page_count: usize) -> String {
+        let link = |p: usize, label: &str| {
+            format!("<a href=\"/chat?talker={}&page={}\">{}</a>", encode_query_value(talker), p, label)
+        };
+        let mut out = String::from("<div class=\"pager\">");
+        if page > 1 {
+            out.push_str(&link(1, "首页"));
+            out.push_str(&link(page - 1, "上一页"));
+        }
+        let _ = write!(out, "<span>{}/{}</span>", page, page_count);
+        if page < page_count {
+            out.push_str(&link(page + 1, "下一页"));
+            out.push_str(&link(page_count, "末页"));
+        }
+        out.push_str("</div>");
+        out
+    }
+
+    fn contacts_page(&self, word: Option<&str>) -> Result<HttpResponse> {
+        let mut body = format!(
+            "<form action=\"/contacts\"><input name=\"word\" value=\"{}\" placeholder=\"搜索\"></form><div class=\"list\">",
+            escape_html(word.unwrap_or(""))
+        );
+        for contact in get_contacts(&self.conn, word.filter(|w| !w.is_empty()), None, None)? {
+            let _ = write!(
+                body,
+                "<a href=\"/chat?talker={}\">{}<div class=\"sub\">{} {}</div></a>",
+                encode_query_value(&contact.wxid),
+                escape_html(&self.display_name(&contact.wxid)),
+                escape_html(&contact.wxid),
+                escape_html(contact.account.as_deref().unwrap_or("")),
+            );
+        }
+        body.push_str("</div>");
+        Ok(self.page("联系人", &body))
+    }
+
+    fn rooms_page(&self) -> Result<HttpResponse> {
+        let mut rooms: Vec<_> = get_chat_rooms(&self.conn, None)?.into_values().collect();
+        rooms.sort_by(|a, b| a.wxid.cmp(&b.wxid));
+        let mut body = String::from("<div class=\"list\">");
+        for room in rooms {
+            let _ = write!(
+                body,
+                "<a href=\"/room?id={}\">{}<div class=\"sub\">{} 人</div></a>",
+                encode_query_value(&room.wxid),
+                escape_html(&self.display_name(&room.wxid)),
+                room.member_wxids.len(),
+            );
+        }
+        body.push_str("</div>");
+        Ok(self.page("群聊", &body))
+    }
+
+    fn room_page(&self, id: &str) -> Result<HttpResponse> {
+        let rooms = get_chat_rooms(&self.conn, Some(&[id.to_string()]))?;
+        let Some(room) = rooms.get(id) else { return Ok(HttpResponse::not_found()) };
+
+        let mut body = format!(
+            "<h3>{}</h3><p><a href=\"/chat?talker={}\">聊天记录</a></p>",
+            escape_html(&self.display_name(id)),
+            encode_query_value(id)
+        );
+        if let Some(announcement) = room.announcement.as_deref().filter(|a| !a.is_empty()) {
+            let _ = write!(body, "<p>群公告: {}</p>", escape_html(announcement));
+        }
+        body.push_str("<div class=\"list\">");
+        for wxid in &room.member_wxids {
+            let owner = if room.owner_wxid.as_deref() == Some(wxid.as_str()) { " (群主)" } else { "" };
+            let _ = write!(
+                body,
+                "<a href=\"/chat?talker={}\">{}{}<div class=\"sub\">{}</div></a>",
+                encode_query_value(wxid),
+                escape_html(&self.display_name(wxid)),
+                owner,
+                escape_html(wxid),
+            );
+        }
+        body.push_str("</div>");
+        Ok(self.page(&self.display_name(id), &body))
+    }
+
+    /// `/media?path=...` for the message's file, if it exists under the account folder.
+    fn media_url(&self, msg: &Message) -> Option<String> {
+        let resolver = self.resolver.as_ref()?;
+        let media = resolver.resolve(msg)?;
+        let path = if media.exists { media.path? } else { media.thumb_path.filter(|t| t.exists())? };
+        let rel = path.strip_prefix(resolver.account_root()).ok()?;
+        Some(format!("/media?path={}", encode_query_value(&rel.to_string_lossy())))
+    }
+
+    /// Serves a file below the account folder. `.dat` images are decoded on the fly.
+    fn media(&self, rel: &str) -> Result<HttpResponse> {
+        let Some(resolver) = &self.resolver else { return Ok(HttpResponse::not_found()) };
+        let rel = Path::new(rel);
+        if !rel.components().all(|c| matches!(c, Component::Normal(_))) {
+            return Ok(HttpResponse::text(403, "Forbidden"));
+        }
+        let root = resolver.account_root().canonicalize()?;
+        let Ok(path) = root.join(rel).canonicalize() else { return Ok(HttpResponse::not_found()) };
+        if !path.starts_with(&root) || !path.is_file() {
+            return Ok(HttpResponse::not_found());
+        }
+
+        let ext = path.extension().map(|e| e.to_string_lossy().to_lowercase()).unwrap_or_default();
+        if ext == "dat" {
+            let image = decode_dat_file(&path, &DatKeys::default())?;
+            return Ok(HttpResponse::new(200, image.format.mime_type(), image.data));
+        }
+        let data = std::fs::read(&path)?;
+        Ok(HttpResponse::new(200, mime_type_for_extension(&ext), data))
+    }
+
+    /// Serves a cached sticker of `FileStorage/CustomEmotion`, decrypted with `aes_key` if needed.
+    fn emoji(&self, md5: &str, aes_key: Option<&str>) -> Result<HttpResponse> {
+        if !is_emoji_md5(md5) {
+            return Ok(HttpResponse::text(400, "Invalid md5"));
+        }
+        let Some(resolver) = &self.resolver else { return Ok(HttpResponse::not_found()) };
+        let emoji = EmojiInfo { md5: md5.to_string(), ..Default::default() };
+        let EmojiSource::LocalFile { path, encrypted } = resolve_emoji(None, Some(resolver.account_root()), &emoji)? else {
+            return Ok(HttpResponse::not_found());
+        };
+        let root = resolver.account_root().canonicalize()?;
+        if !path.canonicalize().is_ok_and(|p| p.starts_with(&root)) {
+            return Ok(HttpResponse::not_found());
+        }
+        let data = read_cached_emoji(&path, encrypted, aes_key.filter(|k| !k.is_empty()))?;
+        let mime = crate::core::image_decode::detect_image_format(&data)
+            .map(|f| f.mime_type())
+            .unwrap_or("application/octet-stream");
+        Ok(HttpResponse::new(200, mime, data))
+    }
+
+    /// Voice messages come from the Media table of the merged MediaMSG databases.
+    fn voice(&self, msg_svr_id: i64) -> Result<HttpResponse> {
+        let Some(blob) = get_voice_blob(&self.conn, msg_svr_id)? else { return Ok(HttpResponse::not_found()) };
+        if silk::silk_decoding_available() {
+            let pcm = silk::decode_silk_to_pcm(&blob, silk::DEFAULT_SAMPLE_RATE)?;
+            return Ok(HttpResponse::new(200, "audio/wav", silk::encode_wav(&pcm, silk::DEFAULT_SAMPLE_RATE)));
+        }
+        Ok(HttpResponse::new(200, "audio/silk", blob))
+    }
+
+    fn route(&self, path: &str, params: &HashMap<String, String>) -> Result<HttpResponse> {
+        let param = |name: &str| params.get(name).map(String::as_str);
+        match path {
+            "/" | "/sessions" => self.sessions_page(),
+            "/chat" => match param("talker") {
+                Some(talker) => self.chat_page(talker, param("page").and_then(|p| p.parse().ok())),
+                None => Ok(HttpResponse::text(400, "Missing talker")),
+            },
+            "/contacts" => self.contacts_page(param("word")),
+            "/rooms" => self.rooms_page(),
+            "/room" => match param("id") {
+                Some(id) => self.room_page(id),
+                None => Ok(HttpResponse::text(400, "Missing id")),
+            },
+            "/media" => match param("path") {
+                Some(rel) => self.media(rel),
+                None => Ok(HttpResponse::text(400, "Missing path")),
+            },
+            "/emoji" => match param("md5") {
+                Some(md5) => self.emoji(md5, param("aeskey")),
+                None => Ok(HttpResponse::text(400, "Missing md5")),
+            },
+            "/voice" => match param("id").and_then(|id| id.parse().ok()) {
+                Some(id) => self.voice(id),
+                None => Ok(HttpResponse::text(400, "Missing id")),
+            },
+            _ => Ok(HttpResponse::not_found()),
+        }
+    }
+}
+
+/// Renders messages for [`Viewer`] pages, with media served by the viewer's own routes.
+struct ViewerPage<'a>(&'a Viewer);
+
+impl MessageContext for ViewerPage<'_> {
+    fn my_wxid(&self) -> &str {
+        &self.0.my_wxid
+    }
+
+    fn display_name(&self, wxid: &str) -> String {
+        self.0.display_name(wxid)
+    }
+
+    fn media(&mut self, msg: &Message) -> Result<Option<MediaLink>> {
+        let viewer = self.0;
+        Ok(match msg.msg_type {
+            MSG_TYPE_IMAGE => viewer.media_url(msg).map(MediaLink::Image),
+            MSG_TYPE_VIDEO => viewer.media_url(msg).map(MediaLink::Video),
+            MSG_TYPE_VOICE => msg.msg_svr_id.map(|id| {
+                let url = format!("/voice?id={}", id);
+                if silk::silk_decoding_available() { MediaLink::Audio(url) } else { MediaLink::VoiceDownload(url) }
+            }),
+            MSG_TYPE_APP => match viewer.resolver.as_ref().and_then(|r| r.resolve(msg)) {
+                Some(media) if media.kind == MediaKind::File => Some(MediaLink::File(viewer.media_url(msg))),
+                _ => None,
+            },
+            _ => None,
+        })
+    }
+
+    fn emoji(&mut self, emoji: &EmojiInfo) -> Result<Option<String>> {
+        let root = self.0.resolver.as_ref().map(|r| r.account_root());
+        Ok(match resolve_emoji(None, root, emoji) {
+            Ok(EmojiSource::LocalFile { .. }) => Some(format!(
+                "/emoji?md5={}&aeskey={}",
+                encode_query_value(&emoji.md5),
+                encode_query_value(emoji.aes_key.as_deref().unwrap_or(""))
+            )),
+            Ok(EmojiSource::Remote { cdn_url: Some(url) }) if is_web_url(&url) => Some(escape_html(&url)),
+            _ => None,
+        })
+    }
+}
+
+impl Handler for Viewer {
+    fn handle(&self, method: &str, url: &str) -> HttpResponse {
+        if method != "GET" {
+            return HttpResponse::text(405, "Method Not Allowed");
+        }
+        let (path, params) = parse_url(url);
+        self.route(&path, &params).unwrap_or_else(|e| self.error_page(e))
+    }
+}
+
+fn mime_type_for_extension(ext: &str) -> &'static str {
+    match ext {
+        "jpg" | "jpeg" => "image/jpeg",
+        "png" => "image/png",
+        "gif" => "image/gif",
+        "bmp" => "image/bmp",
+        "webp" => "image/webp",
+        "mp4" => "video/mp4",
+        "mp3" => "audio/mpeg",
+        "wav" => "audio/wav",
+        "pdf" => "application/pdf",
+        "txt" => "text/plain; charset=utf-8",
+        _ => "application/octet-stream",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::testutil::merged_db_with_sample_data;
+
+    fn body(response: &HttpResponse) -> String {
+        String::from_utf8(response.body.clone()).unwrap()
+    }
+
+    #[test]
+    fn test_sessions_and_chat_pages() {
+        let viewer = Viewer::from_connection(merged_db_with_sample_data(), None, "wxid_me").unwrap();
+
+        let index = viewer.handle("GET", "/");
+        assert_eq!(index.status, 200);
+        let html = body(&index);
+        assert!(html.contains("Alice (work)"));
+        assert!(html.contains("Project &lt;Team&gt;"));
+        assert!(html.contains("/chat?talker=123%40chatroom"));
+
+        let chat = body(&viewer.handle("GET", "/chat?talker=wxid_alice"));
+        assert!(chat.contains("hi there"));
+        assert!(chat.contains("hello &lt;b&gt;Alice&lt;/b&gt;"));
+        if silk::silk_decoding_available() {
+            assert!(chat.contains("<audio controls preload=\"none\" src=\"/voice?id=1003\"></audio>"));
+        } else {
+            assert!(chat.contains("<a href=\"/voice?id=1003\">[语音 3\"]</a>"));
+        }
+
+        let room_chat = body(&viewer.handle("GET", "/chat?talker=123%40chatroom"));
+        assert!(room_chat.contains("Stranger"));
+
+        let room = body(&viewer.handle("GET", "/room?id=123%40chatroom"));
+        assert!(room.contains("Be nice"));
+        assert!(room.contains("(群主)"));
+
+        assert_eq!(viewer.handle("GET", "/nope").status, 404);
+        assert_eq!(viewer.handle("POST", "/").status, 405);
+    }
+
+    #[test]
+    fn test_chat_page_shows_only_web_sticker_urls() {
+        let conn = merged_db_with_sample_data();
+        for (svr_id, url) in [(5001, "https://cdn.example/e.gif"), (5002, "javascript:alert(1)")] {
+            conn.execute(
+                "INSERT INTO MSG (MsgSvrID, Type, SubType, IsSender, CreateTime, StrTalker, StrContent)
+                 VALUES (?, 47, 0, 0, 1700000400, 'wxid_bob', ?)",
+                rusqlite::params![svr_id, format!("<msg><emoji md5=\"{}\" cdnurl=\"{}\" /></msg>", "ab".repeat(16), url)],
+            )
+            .unwrap();
+        }
+        let viewer = Viewer::from_connection(conn, None, "wxid_me").unwrap();
+        let chat = body(&viewer.handle("GET", "/chat?talker=wxid_bob"));
+        assert!(chat.contains("<img src=\"https://cdn.example/e.gif\""));
+        assert!(!chat.contains("javascript:"));
+        assert!(chat.contains("[表情]"));
+    }
+
+    #[test]
+    fn test_voice_route() {
+        let viewer = Viewer::from_connection(merged_db_with_sample_data(), None, "wxid_me").unwrap();
+        assert_eq!(viewer.handle("GET", "/voice?id=9999").status, 404);
+        if !silk::silk_decoding_available() {
+            let voice = viewer.handle("GET", "/voice?id=1003");
+            assert_eq!(voice.status, 200);
+            assert!(voice.body.starts_with(b"\x02#!SILK_V3"));
+        }
+    }
+
+    #[test]
+    fn test_media_rejects_path_traversal() {
+        let root = std::env::temp_dir().join(format!("wxdump_rs_viewer_test_{}", std::process::id()));
+        let account = root.join("wxid_me");
+        std::fs::create_dir_all(account.join("FileStorage")).unwrap();
+        std::fs::write(account.join("FileStorage").join("a.txt"), b"inside").unwrap();
+        std::fs::write(root.join("secret.txt"), b"outside").unwrap();
+
+        let viewer = Viewer::from_connection(merged_db_with_sample_data(), Some(account.clone()), "wxid_me").unwrap();
+        let ok = viewer.handle("GET", "/media?path=FileStorage%2Fa.txt");
+        assert_eq!(ok.status, 200);
+        assert_eq!(ok.body, b"inside");
+        assert_eq!(viewer.handle("GET", "/media?path=..%2Fsecret.txt").status, 403);
+        assert_eq!(viewer.handle("GET", "/media?path=%2Fetc%2Fpasswd").status, 403);
+
+        let secret_png = root.join("secret.png");
+        std::fs::write(&secret_png, b"\x89PNG\r\n\x1a\n").unwrap();
+        let absolute = format!("/emoji?md5={}", encode_query_value(&secret_png.to_string_lossy()));
+        assert_eq!(viewer.handle("GET", &absolute).status, 400);
+        assert_eq!(viewer.handle("GET", "/emoji?md5=..%2F..%2Fsecret.png").status, 400);
+        assert_eq!(viewer.handle("GET", &format!("/emoji?md5={}", "ab".repeat(16))).status, 404);
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -0,0 +1,53 @@
+// tests/sample_account.rs
+//
+// The decrypt -> parse -> export pipeline of the CLI, run against the synthetic account of
+// `core::testutil` (needs `--features testutil`).
+
+use std::path::PathBuf;
+
+use wxdump_rs::core::db_parser::{get_chat_rooms, get_contacts, get_messages, open_encrypted_database};
+use wxdump_rs::core::decryption::decrypt_database_file;
+use wxdump_rs::core::export::text::{export_transcripts, TranscriptFormat, TranscriptOptions};
+use wxdump_rs::core::testutil::{write_sample_account, TEST_KEY};
+
+fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("wxdump_rs_it_{}_{}", name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+#[test]
+fn decrypt_parse_and_export_sample_account() {
+    let dir = temp_dir("pipeline");
+    let msg_dir = write_sample_account(&dir.join("wxid_me"), true);
+
+    // Encrypted files are unreadable without the key, readable in place with it.
+    assert!(open_encrypted_database(&msg_dir.join("MicroMsg.db"), None).is_err());
+    let in_place = open_encrypted_database(&msg_dir.join("MicroMsg.db"), Some(TEST_KEY)).unwrap();
+    assert_eq!(get_contacts(&in_place, None, None, None).unwrap().len(), 2);
+
+    let micro = dir.join("de_MicroMsg.db");
+    let msg = dir.join("de_MSG0.db");
+    decrypt_database_file(&msg_dir.join("MicroMsg.db"), &micro, TEST_KEY).unwrap();
+    decrypt_database_file(&msg_dir.join("Multi/MSG0.db"), &msg, TEST_KEY).unwrap();
+    let contact_conn = open_encrypted_database(&micro, None).unwrap();
+    let msg_conn = open_encrypted_database(&msg, None).unwrap();
+
+    let rooms = get_chat_rooms(&contact_conn, None).unwrap();
+    let carol = rooms["456@chatroom"].members.iter().find(|m| m.wxid == "wxid_carol").unwrap();
+    assert_eq!(carol.room_nickname.as_deref(), Some("Carol 🏔"));
+    let messages = get_messages(&msg_conn, Some("wxid_carol"), None, None, None, None, None).unwrap();
+    assert_eq!(messages.len(), 4);
+
+    let options = TranscriptOptions { format: TranscriptFormat::Text, my_wxid: "wxid_me".into(), ..Default::default() };
+    let talkers = vec!["wxid_carol".to_string(), "456@chatroom".to_string()];
+    let report = export_transcripts(&msg_conn, &contact_conn, &talkers, &options, &dir.join("out")).unwrap();
+    assert_eq!((report.conversations, report.messages), (2, 7));
+    let carol_txt = std::fs::read_to_string(report.files.iter().find(|f| f.to_string_lossy().contains("wxid_carol")).unwrap()).unwrap();
+    assert!(carol_txt.contains("早上好"));
+    assert!(carol_txt.contains("Morning!"));
+
+    drop((in_place, contact_conn, msg_conn));
+    std::fs::remove_dir_all(&dir).unwrap();
+}